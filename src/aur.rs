@@ -1,23 +1,285 @@
+use crate::core::ReapError;
 use crate::utils;
 use anyhow::Result;
+use colored::Colorize;
 use futures::future::join_all;
-use owo_colors::OwoColorize;
 use reqwest::Client; // Use async client for parallel downloads
 use serde::{Deserialize, Serialize};
 use std::error::Error;
+use std::path::PathBuf;
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant};
 use tokio::time::timeout;
 
 // Keep blocking client for synchronous functions
 use reqwest::blocking::Client as BlockingClient;
 
+/// AUR endpoints and request timeout from the global config, read once per process
+struct AurSettings {
+    url: String,
+    rpc_url: String,
+    timeout: Duration,
+    offline: bool,
+    rpc_rate: f64,
+}
+
+static AUR_SETTINGS: once_cell::sync::Lazy<AurSettings> = once_cell::sync::Lazy::new(|| {
+    let global = crate::config::GlobalConfig::load();
+    AurSettings {
+        url: global.aur_url(),
+        rpc_url: global.aur_rpc_url(),
+        timeout: Duration::from_secs(global.network_timeout_secs.unwrap_or(30).max(1)),
+        offline: global.offline.unwrap_or(false),
+        rpc_rate: global
+            .aur_rpc_rate
+            .filter(|rate| rate.is_finite() && *rate > 0.0)
+            .unwrap_or(crate::config::DEFAULT_AUR_RPC_RATE),
+    }
+});
+
+/// Token bucket allowing `rate` requests per second with bursts of up to
+/// `burst`. Reservations may drive the balance negative, so concurrent
+/// callers queue up behind each other instead of all waking at once.
+#[derive(Debug)]
+pub struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(rate: f64, burst: f64, now: Instant) -> Self {
+        Self {
+            rate,
+            burst,
+            tokens: burst,
+            updated: now,
+        }
+    }
+
+    /// Take a token at `now`, returning how long to wait before using it
+    pub fn reserve(&mut self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.updated = now;
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+/// The one limiter every AUR RPC request goes through, async or blocking
+static RPC_LIMITER: once_cell::sync::Lazy<std::sync::Mutex<TokenBucket>> =
+    once_cell::sync::Lazy::new(|| {
+        let rate = AUR_SETTINGS.rpc_rate;
+        std::sync::Mutex::new(TokenBucket::new(rate, rate.ceil().max(1.0), Instant::now()))
+    });
+
+fn rpc_slot() -> Duration {
+    RPC_LIMITER
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .reserve(Instant::now())
+}
+
+/// Attempts at an RPC request the AUR answers with 429 Too Many Requests
+const RPC_ATTEMPTS: u32 = 5;
+
+/// Wait before retry `attempt` (from 0) after a 429: the server's
+/// `Retry-After` if it sent one, else exponential backoff from one second,
+/// plus up to as much again scaled by `jitter` in `0..1` so parallel tasks
+/// don't retry in lockstep
+pub fn backoff_delay(attempt: u32, retry_after: Option<u64>, jitter: f64) -> Duration {
+    let base = retry_after.map_or_else(
+        || Duration::from_secs(1 << attempt.min(6)),
+        Duration::from_secs,
+    );
+    base + base.mul_f64(jitter.clamp(0.0, 1.0))
+}
+
+/// Cheap jitter in `0..1`; only has to differ between tasks
+fn jitter() -> f64 {
+    use std::hash::{BuildHasher, RandomState};
+    (RandomState::new().hash_one(Instant::now()) % 1000) as f64 / 1000.0
+}
+
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<u64> {
+    headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Shared, connection-pooled client for async AUR requests
+static CLIENT: once_cell::sync::Lazy<Client> = once_cell::sync::Lazy::new(|| {
+    Client::builder()
+        .timeout(AUR_SETTINGS.timeout)
+        .build()
+        .expect("Failed to build HTTP client")
+});
+
+/// Shared client for blocking AUR requests. Must first be used off the
+/// async runtime threads (e.g. under `spawn_blocking`).
+static BLOCKING_CLIENT: once_cell::sync::Lazy<BlockingClient> = once_cell::sync::Lazy::new(|| {
+    BlockingClient::builder()
+        .timeout(AUR_SETTINGS.timeout)
+        .build()
+        .expect("Failed to build HTTP client")
+});
+
+fn network_error(e: reqwest::Error) -> ReapError {
+    if e.is_timeout() {
+        ReapError::NetworkError(format!(
+            "AUR request timed out after {}s",
+            AUR_SETTINGS.timeout.as_secs()
+        ))
+    } else {
+        ReapError::NetworkError(e.to_string())
+    }
+}
+
+/// Base URL of the configured AUR, without a trailing slash
+pub fn aur_url() -> &'static str {
+    &AUR_SETTINGS.url
+}
+
+/// RPC v5 request URL for `query`, e.g. `type=info&arg[]=foo`
+pub fn rpc_url(query: &str) -> String {
+    format!("{}/?v=5&{}", AUR_SETTINGS.rpc_url, query)
+}
+
+/// Raw PKGBUILD URL for `pkg` on the configured AUR
+pub fn pkgbuild_url(pkg: &str) -> String {
+    format!("{}/cgit/aur.git/plain/PKGBUILD?h={}", aur_url(), pkg)
+}
+
+/// Git clone URL for `pkg` on the configured AUR
+pub fn git_url(pkg: &str) -> String {
+    format!("{}/{}.git", aur_url(), pkg)
+}
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Serve AUR data from the local cache only, never touching the network
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::Relaxed);
+}
+
+/// Whether `--offline` or `offline = true` in reap.toml is in effect
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed) || AUR_SETTINGS.offline
+}
+
+fn rpc_cache_path(query: &str) -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(|| PathBuf::from("/tmp"))
+        .join("reap/rpc")
+        .join(format!("{}.json", urlencoding::encode(query)))
+}
+
+fn cached_rpc(query: &str) -> Result<String, ReapError> {
+    std::fs::read_to_string(rpc_cache_path(query)).map_err(|_| {
+        ReapError::NetworkError(format!("offline and no cached AUR data for '{}'", query))
+    })
+}
+
+fn cache_rpc(query: &str, body: &str) {
+    let path = rpc_cache_path(query);
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let _ = std::fs::write(path, body);
+}
+
+fn parse_rpc(body: &str) -> Result<AurResponse, ReapError> {
+    serde_json::from_str(body)
+        .map_err(|e| ReapError::NetworkError(format!("invalid AUR response: {}", e)))
+}
+
+/// Raw AUR RPC response for `query`. Every response is cached; in offline
+/// mode only that cache is read. Requests are paced by the shared
+/// `aur_rpc_rate` limiter and retried with backoff on 429.
+pub async fn rpc(query: &str) -> Result<String, ReapError> {
+    if is_offline() {
+        return cached_rpc(query);
+    }
+    let mut attempt = 0;
+    let resp = loop {
+        tokio::time::sleep(rpc_slot()).await;
+        let resp = CLIENT
+            .get(rpc_url(query))
+            .send()
+            .await
+            .map_err(network_error)?;
+        if resp.status() != reqwest::StatusCode::TOO_MANY_REQUESTS || attempt + 1 >= RPC_ATTEMPTS {
+            break resp;
+        }
+        let delay = backoff_delay(attempt, retry_after(resp.headers()), jitter());
+        eprintln!(
+            "[reap] AUR rate limit hit, retrying in {:.1}s",
+            delay.as_secs_f64()
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    };
+    let body = resp
+        .error_for_status()
+        .map_err(network_error)?
+        .text()
+        .await
+        .map_err(network_error)?;
+    cache_rpc(query, &body);
+    Ok(body)
+}
+
+/// Blocking version of [`rpc`]
+pub fn rpc_blocking(query: &str) -> Result<String, ReapError> {
+    if is_offline() {
+        return cached_rpc(query);
+    }
+    let mut attempt = 0;
+    let resp = loop {
+        std::thread::sleep(rpc_slot());
+        let resp = BLOCKING_CLIENT
+            .get(rpc_url(query))
+            .send()
+            .map_err(network_error)?;
+        if resp.status() != reqwest::StatusCode::TOO_MANY_REQUESTS || attempt + 1 >= RPC_ATTEMPTS {
+            break resp;
+        }
+        let delay = backoff_delay(attempt, retry_after(resp.headers()), jitter());
+        eprintln!(
+            "[reap] AUR rate limit hit, retrying in {:.1}s",
+            delay.as_secs_f64()
+        );
+        std::thread::sleep(delay);
+        attempt += 1;
+    };
+    let body = resp
+        .error_for_status()
+        .and_then(|resp| resp.text())
+        .map_err(network_error)?;
+    cache_rpc(query, &body);
+    Ok(body)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchResult {
     pub name: String,
     pub version: String,
     pub description: String,
     pub source: crate::core::Source,
+    /// AUR votes; other sources have none
+    #[serde(default)]
+    pub votes: Option<u32>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -28,6 +290,23 @@ pub struct AurResult {
     pub version: String,
     #[serde(rename = "Description")]
     pub description: Option<String>,
+    /// The git repo the package is built from; differs from the name for
+    /// split packages
+    #[serde(rename = "PackageBase", default)]
+    pub package_base: Option<String>,
+    /// When the package was flagged out-of-date (Unix time), if it is
+    #[serde(rename = "OutOfDate", default)]
+    pub out_of_date: Option<i64>,
+    #[serde(rename = "NumVotes", default)]
+    pub num_votes: Option<u32>,
+    /// Only `type=info` responses list dependencies
+    #[serde(rename = "Depends", default)]
+    pub depends: Vec<String>,
+    #[serde(rename = "MakeDepends", default)]
+    pub make_depends: Vec<String>,
+    /// `name: description` entries
+    #[serde(rename = "OptDepends", default)]
+    pub opt_depends: Vec<String>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -37,22 +316,90 @@ pub struct AurResponse {
 
 pub struct AurInfo {
     pub version: String,
+    /// pkgbase, whose git repo builds this package
+    pub base: String,
+    /// When the package was flagged out-of-date (Unix time), if it is
+    pub out_of_date: Option<i64>,
+}
+
+/// Most package names sent in one multi-arg `type=info` request
+pub const INFO_BATCH_SIZE: usize = 150;
+
+/// `type=info` RPC queries for `pkgs`, [`INFO_BATCH_SIZE`] names each
+pub fn info_queries(pkgs: &[String]) -> Vec<String> {
+    pkgs.chunks(INFO_BATCH_SIZE)
+        .map(|chunk| {
+            chunk.iter().fold("type=info".to_string(), |q, pkg| {
+                format!("{}&arg[]={}", q, urlencoding::encode(pkg))
+            })
+        })
+        .collect()
+}
+
+/// AUR versions of `pkgs`, batching many names per RPC request. Packages
+/// not in the AUR are left out.
+pub fn fetch_versions(
+    pkgs: &[String],
+) -> Result<std::collections::HashMap<String, String>, ReapError> {
+    let mut versions = std::collections::HashMap::new();
+    for query in info_queries(pkgs) {
+        for r in parse_rpc(&rpc_blocking(&query)?)?.results {
+            versions.insert(r.name, r.version);
+        }
+    }
+    Ok(versions)
+}
+
+/// AUR info (with dependencies) of `pkgs`, sending the batched requests
+/// concurrently through `rpc_fn`. Packages not in the AUR are left out.
+pub async fn fetch_info_batch_with<F, Fut>(
+    pkgs: &[String],
+    rpc_fn: F,
+) -> Result<Vec<AurResult>, ReapError>
+where
+    F: Fn(String) -> Fut,
+    Fut: std::future::Future<Output = Result<String, ReapError>>,
+{
+    let bodies = futures::future::join_all(info_queries(pkgs).into_iter().map(rpc_fn)).await;
+    let mut results = Vec::new();
+    for body in bodies {
+        results.extend(parse_rpc(&body?)?.results);
+    }
+    Ok(results)
+}
+
+/// AUR info (with dependencies) of `pkgs` in as few requests as possible,
+/// paced by the RPC rate limiter. Offline, each package's own cached
+/// `info` response is used instead.
+pub async fn fetch_info_batch(pkgs: &[String]) -> Result<Vec<AurResult>, ReapError> {
+    if !is_offline() {
+        return fetch_info_batch_with(pkgs, |query| async move { rpc(&query).await }).await;
+    }
+    let mut results = Vec::new();
+    for pkg in pkgs {
+        if let Ok(body) = cached_rpc(&format!("type=info&arg[]={}", pkg)) {
+            results.extend(parse_rpc(&body)?.results);
+        }
+    }
+    Ok(results)
 }
 
 /// Fetch package info from AUR
 ///
 /// # Errors
 ///
-/// Returns an error if the request to the AUR fails or if the package is not found.
-pub fn fetch_package_info(pkg: &str) -> Result<AurInfo, Box<dyn Error + Send + Sync>> {
-    let url = format!("https://aur.archlinux.org/rpc/?v=5&type=info&arg[]={}", pkg);
-    let client = BlockingClient::new();
-    let resp = client.get(&url).send()?;
-    let aur_resp: AurResponse = resp.json()?;
+/// Returns `NetworkError` if the request to the AUR fails or times out and
+/// `SourceNotFound` if the package is not found.
+pub fn fetch_package_info(pkg: &str) -> Result<AurInfo, ReapError> {
+    let aur_resp = parse_rpc(&rpc_blocking(&format!("type=info&arg[]={}", pkg))?)?;
     if let Some(r) = aur_resp.results.into_iter().next() {
-        Ok(AurInfo { version: r.version })
+        Ok(AurInfo {
+            base: r.package_base.unwrap_or_else(|| r.name.clone()),
+            version: r.version,
+            out_of_date: r.out_of_date,
+        })
     } else {
-        Err("Package not found".into())
+        Err(ReapError::SourceNotFound(pkg.to_string()))
     }
 }
 
@@ -60,19 +407,13 @@ pub fn fetch_package_info(pkg: &str) -> Result<AurInfo, Box<dyn Error + Send + S
 ///
 /// # Errors
 ///
-/// Returns an error if the request to the AUR fails.
-pub async fn search(query: &str) -> Result<Vec<SearchResult>, Box<dyn Error + Send + Sync>> {
+/// Returns `NetworkError` if the request to the AUR fails or times out.
+pub async fn search(query: &str) -> Result<Vec<SearchResult>, ReapError> {
     #[cfg(feature = "cache")]
     if let Some(cached) = crate::utils::get_cached_search(query) {
         return Ok(cached);
     }
-    let url = format!(
-        "https://aur.archlinux.org/rpc/?v=5&type=search&arg={}",
-        query
-    );
-    let client = reqwest::Client::new();
-    let resp = client.get(&url).send().await?;
-    let aur_resp: AurResponse = resp.json().await?;
+    let aur_resp = parse_rpc(&rpc(&format!("type=search&arg={}", query)).await?)?;
     let results: Vec<SearchResult> = aur_resp
         .results
         .into_iter()
@@ -81,6 +422,7 @@ pub async fn search(query: &str) -> Result<Vec<SearchResult>, Box<dyn Error + Se
             version: r.version,
             description: r.description.unwrap_or_default(),
             source: crate::core::Source::Aur,
+            votes: r.num_votes,
         })
         .collect();
     #[cfg(feature = "cache")]
@@ -88,18 +430,70 @@ pub async fn search(query: &str) -> Result<Vec<SearchResult>, Box<dyn Error + Se
     Ok(results)
 }
 
+/// AUR packages maintained by `maintainer`, sorted by name
+///
+/// # Errors
+///
+/// Returns `NetworkError` if the request to the AUR fails or times out.
+pub async fn search_by_maintainer(maintainer: &str) -> Result<Vec<SearchResult>, ReapError> {
+    let query = format!(
+        "type=search&by=maintainer&arg={}",
+        urlencoding::encode(maintainer)
+    );
+    let mut results: Vec<SearchResult> = parse_rpc(&rpc(&query).await?)?
+        .results
+        .into_iter()
+        .map(|r| SearchResult {
+            name: r.name,
+            version: r.version,
+            description: r.description.unwrap_or_default(),
+            source: crate::core::Source::Aur,
+            votes: r.num_votes,
+        })
+        .collect();
+    results.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(results)
+}
+
 /// Get AUR search results (blocking)
 pub fn aur_search_results(query: &str) -> Vec<AurResult> {
-    let url = format!(
-        "https://aur.archlinux.org/rpc/?v=5&type=search&arg={}",
-        query
-    );
-    if let Ok(resp) = reqwest::blocking::get(&url) {
-        if let Ok(json) = resp.json::<AurResponse>() {
-            return json.results;
+    rpc_blocking(&format!("type=search&arg={}", urlencoding::encode(query)))
+        .and_then(|body| parse_rpc(&body))
+        .map(|resp| resp.results)
+        .unwrap_or_default()
+}
+
+/// AUR packages whose `provides` include `name` (blocking)
+pub fn aur_providers(name: &str) -> Vec<AurResult> {
+    rpc_blocking(&format!(
+        "type=search&by=provides&arg={}",
+        urlencoding::encode(name)
+    ))
+    .and_then(|body| parse_rpc(&body))
+    .map(|resp| resp.results)
+    .unwrap_or_default()
+}
+
+/// AUR packages that list `name` in `replaces` or `provides`, the usual
+/// sign of a package merged into another (blocking)
+pub fn aur_replacements(name: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for by in ["replaces", "provides"] {
+        let results = rpc_blocking(&format!(
+            "type=search&by={}&arg={}",
+            by,
+            urlencoding::encode(name)
+        ))
+        .and_then(|body| parse_rpc(&body))
+        .map(|resp| resp.results)
+        .unwrap_or_default();
+        for r in results {
+            if r.name != name && !names.contains(&r.name) {
+                names.push(r.name);
+            }
         }
     }
-    vec![]
+    names
 }
 
 #[cfg(feature = "cache")]
@@ -109,11 +503,11 @@ pub async fn get_pkgbuild_cached(pkg: &str) -> String {
 
 #[cfg(not(feature = "cache"))]
 pub async fn get_pkgbuild_cached(pkg: &str) -> String {
-    let url = format!(
-        "https://aur.archlinux.org/cgit/aur.git/plain/PKGBUILD?h={}",
-        pkg
-    );
-    match reqwest::get(&url).await {
+    if is_offline() {
+        return std::fs::read_to_string(crate::core::aur_source_cache(pkg).join("PKGBUILD"))
+            .unwrap_or_else(|_| String::from("[reap] PKGBUILD not found."));
+    }
+    match CLIENT.get(pkgbuild_url(pkg)).send().await {
         Ok(resp) => resp.text().await.unwrap_or_default(),
         Err(_) => String::from("[reap] PKGBUILD not found."),
     }
@@ -143,6 +537,7 @@ pub async fn install(pkgs: Vec<&str>) -> Result<(), Box<dyn std::error::Error +
                 for dep in &deps {
                     if !crate::pacman::is_installed(dep) {
                         println!("[reap] Installing missing dependency: {}", dep.yellow());
+                        let _transaction = crate::pacman::transaction();
                         let status = std::process::Command::new(&bin)
                             .arg("-S")
                             .arg(dep)
@@ -159,6 +554,7 @@ pub async fn install(pkgs: Vec<&str>) -> Result<(), Box<dyn std::error::Error +
             } else {
                 println!("[reap] No dependencies found for {}.", pkg);
             }
+            let _transaction = crate::pacman::transaction();
             let status = std::process::Command::new(&bin)
                 .arg("-S")
                 .arg(&pkg)
@@ -184,10 +580,13 @@ pub async fn install(pkgs: Vec<&str>) -> Result<(), Box<dyn std::error::Error +
 ///
 /// Returns an error if the uninstallation fails.
 pub fn uninstall(package: &str) {
-    let yay = which::which("yay").is_ok();
-    let bin = if yay { "yay" } else { "pacman" };
-    println!("[reap] Uninstalling {} ({} -R)...", package.yellow(), bin);
-    let status = Command::new(bin).arg("-R").arg(package).status();
+    println!("[reap] Uninstalling {} (pacman -R)...", package.yellow());
+    let _transaction = crate::pacman::transaction();
+    // Callers have already confirmed the removal
+    let status = crate::utils::escalate("pacman")
+        .args(["-R", "--noconfirm"])
+        .arg(package)
+        .status();
     match status {
         Ok(s) if s.success() => println!("[reap] Uninstalled {}.", package.green()),
         Ok(_) => eprintln!("[reap] Uninstall failed for {}.", package.red()),
@@ -195,18 +594,286 @@ pub fn uninstall(package: &str) {
     }
 }
 
+/// Fetch the current AUR PKGBUILD for `pkg` (blocking)
+///
+/// # Errors
+///
+/// Returns `NetworkError` if the request to the AUR fails or times out, or
+/// in offline mode if the package's sources were never cached.
+pub fn fetch_pkgbuild(pkg: &str) -> Result<String, ReapError> {
+    if is_offline() {
+        return std::fs::read_to_string(crate::core::aur_source_cache(pkg).join("PKGBUILD"))
+            .map_err(|_| {
+                ReapError::NetworkError(format!("offline and no cached PKGBUILD for {}", pkg))
+            });
+    }
+    BLOCKING_CLIENT
+        .get(pkgbuild_url(pkg))
+        .send()
+        .and_then(|resp| resp.error_for_status())
+        .and_then(|resp| resp.text())
+        .map_err(network_error)
+}
+
+/// Raw .SRCINFO URL for `pkg` on the configured AUR
+pub fn srcinfo_url(pkg: &str) -> String {
+    format!("{}/cgit/aur.git/plain/.SRCINFO?h={}", aur_url(), pkg)
+}
+
+/// Fetch the .SRCINFO of `pkg`, whose sources have variables expanded
+///
+/// # Errors
+///
+/// Returns `NetworkError` if the request to the AUR fails or times out.
+pub fn fetch_srcinfo(pkg: &str) -> Result<String, ReapError> {
+    BLOCKING_CLIENT
+        .get(srcinfo_url(pkg))
+        .send()
+        .and_then(|resp| resp.error_for_status())
+        .and_then(|resp| resp.text())
+        .map_err(network_error)
+}
+
+/// Package name suffixes conventionally used for VCS packages
+const VCS_SUFFIXES: [&str; 6] = ["-git", "-svn", "-hg", "-bzr", "-fossil", "-darcs"];
+
+/// Whether `pkg` builds from a VCS checkout: by name suffix, or because its
+/// PKGBUILD computes the version with `pkgver()`
+pub fn is_vcs_package(pkg: &str, pkgbuild: Option<&str>) -> bool {
+    VCS_SUFFIXES.iter().any(|suffix| pkg.ends_with(suffix))
+        || pkgbuild.is_some_and(|p| p.lines().any(|l| l.trim_start().starts_with("pkgver()")))
+}
+
+/// A VCS source of a package that follows a moving branch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VcsSource {
+    /// `git`, `svn` or `hg`
+    pub vcs: String,
+    pub url: String,
+    /// Branch from a `#branch=` fragment, the remote's default when `None`
+    pub branch: Option<String>,
+}
+
+/// VCS sources listed in a .SRCINFO. Sources pinned with `#commit=`,
+/// `#tag=` or `#revision=` never move and are left out.
+pub fn vcs_sources(srcinfo: &str) -> Vec<VcsSource> {
+    let mut sources = Vec::new();
+    for line in srcinfo.lines() {
+        let Some((key, value)) = line.trim().split_once(" = ") else {
+            continue;
+        };
+        if key != "source" && !key.starts_with("source_") {
+            continue;
+        }
+        let value = value.split_once("::").map_or(value, |(_, url)| url);
+        let (url, fragment) = value.split_once('#').unwrap_or((value, ""));
+        let url = url.split_once("?signed").map_or(url, |(u, _)| u);
+        let (vcs, url) = if let Some((scheme, rest)) = url.split_once('+')
+            && ["git", "svn", "hg"].contains(&scheme)
+        {
+            (scheme, rest)
+        } else if url.starts_with("git://") {
+            ("git", url)
+        } else {
+            continue;
+        };
+        let mut branch = None;
+        match fragment.split_once('=') {
+            Some(("branch", name)) => branch = Some(name.to_string()),
+            Some(("commit" | "tag" | "revision", _)) => continue,
+            _ => {}
+        }
+        let source = VcsSource {
+            vcs: vcs.to_string(),
+            url: url.to_string(),
+            branch,
+        };
+        if !sources.contains(&source) {
+            sources.push(source);
+        }
+    }
+    sources
+}
+
+/// Latest upstream commit (or revision) of a VCS source, queried without
+/// cloning it
+pub fn latest_commit(source: &VcsSource) -> Option<String> {
+    let mut cmd = match source.vcs.as_str() {
+        "git" => {
+            let reference = source
+                .branch
+                .as_ref()
+                .map_or_else(|| "HEAD".to_string(), |b| format!("refs/heads/{}", b));
+            let mut cmd = Command::new("git");
+            cmd.args(["ls-remote", &source.url, &reference]);
+            cmd
+        }
+        "svn" => {
+            let mut cmd = Command::new("svn");
+            cmd.args(["info", "--show-item", "revision", &source.url]);
+            cmd
+        }
+        "hg" => {
+            let mut cmd = Command::new("hg");
+            cmd.arg("identify");
+            if let Some(branch) = &source.branch {
+                cmd.args(["-r", branch]);
+            }
+            cmd.arg(&source.url);
+            cmd
+        }
+        _ => return None,
+    };
+    // Never block on a credentials prompt for a private or moved repo
+    cmd.env("GIT_TERMINAL_PROMPT", "0");
+    let output = cmd.stderr(std::process::Stdio::null()).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .next()
+        .map(str::to_string)
+}
+
+/// Latest commits of every moving VCS source in a .SRCINFO, in order.
+/// `None` if any of them can't be queried.
+pub fn upstream_commits(srcinfo: &str) -> Option<Vec<String>> {
+    vcs_sources(srcinfo).iter().map(latest_commit).collect()
+}
+
 /// Get PKGBUILD preview
 pub fn get_pkgbuild_preview(pkg: &str) -> String {
-    let url = format!(
-        "https://aur.archlinux.org/cgit/aur.git/plain/PKGBUILD?h={}",
-        pkg
-    );
-    if let Ok(resp) = reqwest::blocking::get(&url) {
-        if let Ok(text) = resp.text() {
-            return text;
-        }
+    fetch_pkgbuild(pkg).unwrap_or_else(|_| String::from("[reap] PKGBUILD not found."))
+}
+
+/// A comment from a package's AUR web page
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub id: u64,
+    pub author: String,
+    /// As shown on the AUR, e.g. `2024-01-15 10:20 (UTC)`
+    pub date: String,
+    pub text: String,
+}
+
+static COMMENT_DATE: once_cell::sync::Lazy<regex::Regex> =
+    once_cell::sync::Lazy::new(|| regex::Regex::new(r#"class="date">([^<]*)</a>"#).unwrap());
+static HTML_TAG: once_cell::sync::Lazy<regex::Regex> =
+    once_cell::sync::Lazy::new(|| regex::Regex::new(r"<[^>]*>").unwrap());
+
+/// Plain text of an HTML fragment, with whitespace collapsed
+pub(crate) fn html_text(html: &str) -> String {
+    let text = HTML_TAG
+        .replace_all(html, " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&amp;", "&");
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Comments in an AUR package page, newest first. The RPC has no comments,
+/// so they come from the page's `comment-header`/`article-content` blocks.
+/// Pinned comments appear only once.
+pub fn parse_comments(html: &str) -> Vec<Comment> {
+    let mut comments: Vec<Comment> = html
+        .split("<h4 id=\"comment-")
+        .skip(1)
+        .filter_map(|chunk| {
+            let id = chunk
+                .split(|c: char| !c.is_ascii_digit())
+                .next()?
+                .parse()
+                .ok()?;
+            let (header, rest) = chunk.split_once("</h4>")?;
+            let author = html_text(header.split_once('>')?.1);
+            let author = author
+                .split_once(" commented on")
+                .map_or(author.as_str(), |(name, _)| name)
+                .to_string();
+            let date = COMMENT_DATE
+                .captures(header)
+                .map(|c| c[1].trim().to_string())
+                .unwrap_or_default();
+            let body = rest.split_once("article-content")?.1;
+            let body = body.split_once('>')?.1;
+            let text = html_text(body.split_once("</div>").map_or(body, |(b, _)| b));
+            Some(Comment {
+                id,
+                author,
+                date,
+                text,
+            })
+        })
+        .collect();
+    comments.sort_by_key(|c| std::cmp::Reverse(c.id));
+    comments.dedup_by_key(|c| c.id);
+    comments
+}
+
+/// Fetch the comments on the first page of a package's AUR web page, newest first
+///
+/// # Errors
+///
+/// Returns `NetworkError` if the request to the AUR fails or times out, or
+/// in offline mode.
+pub fn fetch_comments(pkg: &str) -> Result<Vec<Comment>, ReapError> {
+    if is_offline() {
+        return Err(ReapError::NetworkError(format!(
+            "offline, comments for {} are not cached",
+            pkg
+        )));
     }
-    String::from("[reap] PKGBUILD not found.")
+    BLOCKING_CLIENT
+        .get(format!("{}/packages/{}", aur_url(), pkg))
+        .send()
+        .and_then(|resp| resp.error_for_status())
+        .and_then(|resp| resp.text())
+        .map(|html| parse_comments(&html))
+        .map_err(network_error)
+}
+
+/// Who flagged a package out-of-date and why, from its AUR flag comment page
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OutOfDateFlag {
+    pub flagged_by: Option<String>,
+    pub reason: Option<String>,
+}
+
+static FLAGGED_BY: once_cell::sync::Lazy<regex::Regex> = once_cell::sync::Lazy::new(|| {
+    regex::Regex::new(r"(\S+) flagged \S+ out-of-date on .*? for the following reason").unwrap()
+});
+
+/// Parse the `/pkgbase/<base>/flag-comment` page. The RPC only has the date.
+pub fn parse_flag_comment(html: &str) -> OutOfDateFlag {
+    let flagged_by = FLAGGED_BY
+        .captures(&html_text(html))
+        .map(|c| c[1].to_string())
+        .filter(|name| name != "None");
+    let reason = html
+        .split_once("<blockquote>")
+        .and_then(|(_, rest)| rest.split_once("</blockquote>"))
+        .map(|(quote, _)| html_text(quote))
+        .filter(|text| !text.is_empty());
+    OutOfDateFlag { flagged_by, reason }
+}
+
+/// Fetch who flagged the pkgbase `base` out-of-date and why
+///
+/// # Errors
+///
+/// Returns `NetworkError` if the request to the AUR fails or times out.
+pub fn fetch_flag_comment(base: &str) -> Result<OutOfDateFlag, ReapError> {
+    BLOCKING_CLIENT
+        .get(format!("{}/pkgbase/{}/flag-comment", aur_url(), base))
+        .send()
+        .and_then(|resp| resp.error_for_status())
+        .and_then(|resp| resp.text())
+        .map(|html| parse_flag_comment(&html))
+        .map_err(network_error)
 }
 
 /// Extract dependencies from PKGBUILD
@@ -299,8 +966,8 @@ pub fn install_local(path: &str) {
         "[reap] Installing local package from {} (sudo pacman -U)...",
         path.yellow()
     );
-    let status = Command::new("sudo")
-        .arg("pacman")
+    let _transaction = crate::pacman::transaction();
+    let status = crate::utils::escalate("pacman")
         .arg("-U")
         .arg(path)
         .status()
@@ -320,12 +987,11 @@ pub fn get_outdated() -> Vec<String> {
     let mut outdated = Vec::new();
     for pkg in installed {
         let local_ver = pacman::get_version(&pkg);
-        if let Ok(remote) = fetch_package_info(&pkg) {
-            if let Some(local_ver) = local_ver {
-                if local_ver != remote.version {
-                    outdated.push(pkg);
-                }
-            }
+        if let Ok(remote) = fetch_package_info(&pkg)
+            && let Some(local_ver) = local_ver
+            && pacman::is_newer(&remote.version, &local_ver)
+        {
+            outdated.push(pkg);
         }
     }
     outdated
@@ -341,11 +1007,9 @@ pub async fn parallel_search(
         queries.len()
     );
 
-    let client = Client::new();
     let tasks: Vec<_> = queries
         .iter()
         .map(|query| {
-            let client = client.clone();
             let query = query.clone();
 
             async move {
@@ -356,13 +1020,9 @@ pub async fn parallel_search(
                     return Ok(cached);
                 }
 
-                // Fetch from AUR with timeout
-                let url = format!(
-                    "https://aur.archlinux.org/rpc/?v=5&type=search&arg={}",
-                    query
-                );
-                let response = timeout(Duration::from_secs(10), client.get(&url).send()).await??;
-                let aur_resp: AurResponse = response.json().await?;
+                // Each request has the client's timeout, and rate limits are retried
+                let body = rpc(&format!("type=search&arg={}", query)).await?;
+                let aur_resp = parse_rpc(&body)?;
 
                 let results: Vec<SearchResult> = aur_resp
                     .results
@@ -372,6 +1032,7 @@ pub async fn parallel_search(
                         version: r.version,
                         description: r.description.unwrap_or_default(),
                         source: crate::core::Source::Aur,
+                        votes: r.num_votes,
                     })
                     .collect();
 
@@ -436,10 +1097,7 @@ pub async fn parallel_pkgbuild_fetch(
                 }
 
                 // Fetch PKGBUILD from AUR
-                let url = format!(
-                    "https://aur.archlinux.org/cgit/aur.git/plain/PKGBUILD?h={}",
-                    pkg
-                );
+                let url = pkgbuild_url(&pkg);
                 let response = timeout(Duration::from_secs(15), client.get(&url).send()).await??;
 
                 if response.status().is_success() {
@@ -507,3 +1165,109 @@ pub async fn warm_cache() -> Result<(), Box<dyn Error + Send + Sync>> {
     println!("[aur] Cache warming completed");
     Ok(())
 }
+
+/// Blocking client that doesn't follow redirects, so AUR web responses can
+/// tell a successful action apart from a redirect to the login page
+static WEB_CLIENT: once_cell::sync::Lazy<BlockingClient> = once_cell::sync::Lazy::new(|| {
+    BlockingClient::builder()
+        .timeout(AUR_SETTINGS.timeout)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("Failed to build HTTP client")
+});
+
+/// Where the AUR session cookie from `reap vote` logins is kept (mode 0600)
+pub fn aur_session_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("/tmp"))
+        .join("reap/aur_session")
+}
+
+/// The `AURSID` value from a `Set-Cookie` header
+pub fn session_cookie(set_cookie: &str) -> Option<String> {
+    set_cookie
+        .split(';')
+        .next()?
+        .trim()
+        .strip_prefix("AURSID=")
+        .filter(|sid| !sid.is_empty())
+        .map(String::from)
+}
+
+/// Log in to the AUR web interface, returning the session id. Only the
+/// session is kept (see `save_aur_session`), never the password.
+///
+/// # Errors
+///
+/// Returns `AuthFailed` if the AUR rejects the credentials and
+/// `NetworkError` if the request fails.
+pub fn aur_login(user: &str, password: &str) -> Result<String, ReapError> {
+    let resp = WEB_CLIENT
+        .post(format!("{}/login", aur_url()))
+        .header("Referer", format!("{}/login", aur_url()))
+        .form(&[
+            ("user", user),
+            ("passwd", password),
+            ("remember_me", "on"),
+            ("next", "/"),
+        ])
+        .send()
+        .map_err(network_error)?;
+    resp.headers()
+        .get_all(reqwest::header::SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .find_map(session_cookie)
+        .ok_or_else(|| ReapError::AuthFailed("invalid AUR username or password".to_string()))
+}
+
+/// Keep the AUR session id in a file only the user can read
+pub fn save_aur_session(sid: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    let path = aur_session_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(sid.as_bytes())
+}
+
+/// Vote for (or with `vote` false, unvote) an AUR package base
+///
+/// # Errors
+///
+/// Returns `AuthFailed` if the session is invalid or expired and
+/// `NetworkError` if the request fails.
+pub fn set_vote(pkgbase: &str, sid: &str, vote: bool) -> Result<(), ReapError> {
+    let action = if vote { "vote" } else { "unvote" };
+    let resp = WEB_CLIENT
+        .post(format!("{}/pkgbase/{}/{}", aur_url(), pkgbase, action))
+        .header("Cookie", format!("AURSID={}", sid))
+        .header("Referer", format!("{}/pkgbase/{}", aur_url(), pkgbase))
+        .send()
+        .map_err(network_error)?;
+    let to_login = resp
+        .headers()
+        .get(reqwest::header::LOCATION)
+        .and_then(|l| l.to_str().ok())
+        .is_some_and(|l| l.contains("/login"));
+    let status = resp.status();
+    if to_login || status == reqwest::StatusCode::UNAUTHORIZED {
+        return Err(ReapError::AuthFailed(
+            "AUR session is invalid or expired".to_string(),
+        ));
+    }
+    if status.is_client_error() || status.is_server_error() {
+        return Err(ReapError::NetworkError(format!(
+            "AUR {} for {} failed with HTTP {}",
+            action, pkgbase, status
+        )));
+    }
+    Ok(())
+}
@@ -75,8 +75,9 @@ impl Backend for AurBackend {
         crate::aur::search(query).await.unwrap_or_default()
     }
     async fn install(&self, package: &str) {
-        let log = crate::tui::LogPane::default();
-        log.push(&format!(
+        use crate::core::ProgressReporter;
+        let log = crate::core::StdoutReporter;
+        log.message(&format!(
             "[reap][backend] Installing {} using native AUR logic",
             package
         ));
@@ -111,7 +112,7 @@ impl Backend for PacmanBackend {
             .unwrap_or(false)
     }
     async fn search(&self, query: &str) -> Vec<SearchResult> {
-        crate::core::unified_search(query)
+        crate::core::unified_search(query, false)
             .now_or_never()
             .unwrap_or_default()
             .into_iter()
@@ -191,27 +192,26 @@ impl TapBackend {
         if let Ok(entries) = std::fs::read_dir(&config_dir) {
             for entry in entries.flatten() {
                 let path = entry.path();
-                if path.extension().and_then(|e| e.to_str()) == Some("toml") {
-                    if let Ok(toml) = std::fs::read_to_string(&path) {
-                        if let Ok(val) = toml::Value::from_str(&toml) {
-                            let name = val
-                                .get("name")
-                                .and_then(|n| n.as_str())
-                                .unwrap_or("")
-                                .to_string();
-                            let url = val
-                                .get("url")
-                                .and_then(|u| u.as_str())
-                                .unwrap_or("")
-                                .to_string();
-                            let priority = val
-                                .get("priority")
-                                .and_then(|p| p.as_integer())
-                                .unwrap_or(50) as u32;
-                            if !name.is_empty() && !url.is_empty() {
-                                taps.push((name, url, priority));
-                            }
-                        }
+                if path.extension().and_then(|e| e.to_str()) == Some("toml")
+                    && let Ok(toml) = std::fs::read_to_string(&path)
+                    && let Ok(val) = toml::Value::from_str(&toml)
+                {
+                    let name = val
+                        .get("name")
+                        .and_then(|n| n.as_str())
+                        .unwrap_or("")
+                        .to_string();
+                    let url = val
+                        .get("url")
+                        .and_then(|u| u.as_str())
+                        .unwrap_or("")
+                        .to_string();
+                    let priority = val
+                        .get("priority")
+                        .and_then(|p| p.as_integer())
+                        .unwrap_or(50) as u32;
+                    if !name.is_empty() && !url.is_empty() {
+                        taps.push((name, url, priority));
                     }
                 }
             }
@@ -255,6 +255,7 @@ impl Backend for AptBackend {
                         version: String::from("?"),
                         description: desc,
                         source: crate::core::Source::Pacman, // TODO: add Debian source
+                        votes: None,
                     });
                 }
             }
@@ -262,20 +263,15 @@ impl Backend for AptBackend {
         results
     }
     async fn install(&self, package: &str) {
-        let _ = std::process::Command::new("sudo")
-            .arg("apt")
+        let _ = crate::utils::escalate("apt")
             .arg("install")
             .arg("-y")
             .arg(package)
             .status();
     }
     async fn upgrade(&self) {
-        let _ = std::process::Command::new("sudo")
-            .arg("apt")
-            .arg("update")
-            .status();
-        let _ = std::process::Command::new("sudo")
-            .arg("apt")
+        let _ = crate::utils::escalate("apt").arg("update").status();
+        let _ = crate::utils::escalate("apt")
             .arg("upgrade")
             .arg("-y")
             .status();
@@ -1,5 +1,78 @@
 use clap::{Parser, Subcommand};
 
+/// Split pacman-style clusters like `-Syu` or `-Qo` into `-y -u -S`. clap
+/// would otherwise read the letters after `-S`/`-R`/`-Q`, which take values,
+/// as their first value (`-Syu` installing a package named `yu`).
+pub fn expand_pacman_flags<I: IntoIterator<Item = String>>(args: I) -> Vec<String> {
+    let mut expanded = Vec::new();
+    for arg in args {
+        let mut chars = arg.chars();
+        let is_cluster = chars.next() == Some('-')
+            && chars.next().is_some_and(|op| "SRQ".contains(op))
+            && arg.len() > 2
+            && chars.all(|c| "yuso".contains(c));
+        if is_cluster {
+            expanded.extend(arg[2..].chars().map(|c| format!("-{}", c)));
+            expanded.push(arg[..2].to_string());
+        } else {
+            expanded.push(arg);
+        }
+    }
+    expanded
+}
+
+/// Move flags given after a pacman-style operation in front of it, so its
+/// values stay together: `-S --noconfirm foo` and `-S foo --noconfirm bar`
+/// become `--noconfirm -S foo ...` instead of clap reading `foo` or `bar`
+/// as a subcommand. Run after `expand_pacman_flags`.
+pub fn hoist_operation_flags(mut expanded: Vec<String>) -> Vec<String> {
+    let is_operation = |arg: &str| {
+        matches!(
+            arg,
+            "-S" | "-R" | "-Q" | "--sync" | "--remove" | "--search" | "--local"
+        )
+    };
+    let Some(op) = expanded.iter().position(|a| is_operation(a)) else {
+        return expanded;
+    };
+    let command = <Cli as clap::CommandFactory>::command();
+    // Whether a flag is followed by a separate value, like `--overwrite <glob>`
+    let takes_value = |flag: &str| {
+        if flag.contains('=') {
+            return false;
+        }
+        command
+            .get_arguments()
+            .find(|a| match flag.strip_prefix("--") {
+                Some(long) => a.get_long() == Some(long),
+                None => flag.len() == 2 && a.get_short() == flag.chars().nth(1),
+            })
+            .is_some_and(|a| a.get_action().takes_values() && a.get_num_args().is_none())
+    };
+    let mut rest = expanded.split_off(op + 1);
+    let operation = expanded.pop().unwrap_or_default();
+    let (mut flags, mut values) = (Vec::new(), Vec::new());
+    let mut args = rest.drain(..);
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            values.push(arg);
+            values.extend(args.by_ref());
+        } else if arg.starts_with('-') && arg.len() > 1 && !is_operation(&arg) {
+            let value = takes_value(&arg);
+            flags.push(arg);
+            if value && let Some(v) = args.next() {
+                flags.push(v);
+            }
+        } else {
+            values.push(arg);
+        }
+    }
+    expanded.extend(flags);
+    expanded.push(operation);
+    expanded.extend(values);
+    expanded
+}
+
 #[derive(Parser, Debug)]
 #[command(
     name = "reap",
@@ -9,7 +82,7 @@ use clap::{Parser, Subcommand};
 )]
 pub struct Cli {
     #[command(subcommand)]
-    pub command: Commands,
+    pub command: Option<Commands>,
     #[arg(short = 'S', long = "sync", value_name = "PKG", num_args = 0.., conflicts_with_all = ["remove", "search", "local"])]
     pub sync: Option<Vec<String>>,
     #[arg(short = 'R', long = "remove", value_name = "PKG", num_args = 0.., conflicts_with_all = ["sync", "search", "upgrade", "local"], help = "Remove a package")]
@@ -18,6 +91,37 @@ pub struct Cli {
     pub local: Option<Vec<String>>,
     #[arg(short = 'Q', long = "search", value_name = "TERM", num_args = 0.., conflicts_with_all = ["sync", "remove", "upgrade", "local"], help = "Search for a package")]
     pub search: Option<Vec<String>>,
+    #[arg(
+        short = 's',
+        long = "sync-search",
+        requires = "sync",
+        help = "With -S, search for the given terms instead of installing (-Ss)"
+    )]
+    pub sync_search: bool,
+    #[arg(
+        short = 'o',
+        long = "owns",
+        requires = "search",
+        help = "With -Q, show which package owns the given files (-Qo)"
+    )]
+    pub owns: bool,
+    #[arg(
+        long = "all-sources",
+        help = "With search, list every source that has each package"
+    )]
+    pub all_sources: bool,
+    #[arg(
+        long = "no-dedup",
+        help = "With search or the browser, show every result, even the same package from several sources"
+    )]
+    pub no_dedup: bool,
+    #[arg(
+        long,
+        global = true,
+        value_name = "TEMPLATE",
+        help = "With search or list, print each package as TEMPLATE: %n name, %v version, %d description, %s source, %w votes, %% a literal %"
+    )]
+    pub format: Option<String>,
     #[arg(short = 'y', long = "refresh", help = "Refresh package database")]
     pub refresh: bool,
     #[arg(short = 'u', long = "upgrade", help = "Upgrade packages")]
@@ -31,14 +135,167 @@ pub struct Cli {
     #[arg(
         long = "backend",
         value_name = "BACKEND",
-        default_value = "aur",
-        help = "Select backend: aur, flatpak"
+        value_parser = ["tap", "pacman", "aur", "flatpak"],
+        help = "Install only from this backend instead of the configured backend_order"
     )]
-    pub backend: String,
+    pub backend: Option<String>,
+    #[arg(
+        long = "color",
+        value_name = "WHEN",
+        default_value = "auto",
+        value_parser = ["auto", "always", "never"],
+        help = "Colorize output: auto (terminal and no NO_COLOR), always or never"
+    )]
+    pub color: String,
+    #[arg(
+        long = "no-pager",
+        help = "Print long search results and diffs directly instead of through a pager"
+    )]
+    pub no_pager: bool,
     #[arg(long = "edit", help = "Edit PKGBUILD before building")]
     pub edit: bool,
+    #[arg(
+        long = "overwrite",
+        value_name = "GLOB",
+        help = "Let built packages replace conflicting files matching GLOB (repeatable; files of other packages are lost)"
+    )]
+    pub overwrite: Vec<String>,
+    #[arg(
+        long = "ignore-dependency",
+        alias = "assume-installed",
+        value_name = "DEP",
+        help = "Build and install AUR packages as if DEP were installed (repeatable; pacman --assume-installed, may produce a broken package)"
+    )]
+    pub ignore_dependency: Vec<String>,
+    #[arg(
+        long = "print-srcinfo",
+        requires = "sync",
+        help = "With -S, print the parsed .SRCINFO (version, dependencies, sources) instead of installing"
+    )]
+    pub print_srcinfo: bool,
+    #[arg(
+        long = "verify-reproducible",
+        help = "Build AUR packages twice and check the packages are bit-for-bit identical before installing"
+    )]
+    pub verify_reproducible: bool,
     #[arg(long = "noconfirm", help = "Skip confirmation prompts")]
     pub noconfirm: bool,
+    #[arg(
+        long = "confirm",
+        conflicts_with = "noconfirm",
+        help = "Always prompt before installing or removing, even if noconfirm is set in config"
+    )]
+    pub confirm: bool,
+    #[arg(
+        long = "default-yes",
+        conflicts_with = "default_no",
+        help = "Answer yes to prompts left unanswered for prompt_timeout_secs (60s if unset)"
+    )]
+    pub default_yes: bool,
+    #[arg(
+        long = "default-no",
+        help = "Answer no to prompts left unanswered for prompt_timeout_secs (60s if unset)"
+    )]
+    pub default_no: bool,
+    #[arg(
+        long = "chroot",
+        help = "Build AUR packages in a clean chroot (needs devtools)"
+    )]
+    pub chroot: bool,
+    #[arg(
+        long = "ignore-arch",
+        help = "Build AUR packages even if their PKGBUILD doesn't list this architecture"
+    )]
+    pub ignore_arch: bool,
+    #[arg(
+        long = "nocheck",
+        help = "Skip the check() function (test suite) of AUR builds"
+    )]
+    pub nocheck: bool,
+    #[arg(
+        long = "ignore-ood",
+        help = "Build AUR packages flagged out-of-date without asking"
+    )]
+    pub ignore_ood: bool,
+    #[arg(
+        long = "reinstall",
+        help = "Rebuild and reinstall packages that are already installed at the same version"
+    )]
+    pub reinstall: bool,
+    #[arg(
+        long = "asexplicit",
+        help = "Mark installed packages as explicitly installed, even ones installed as dependencies"
+    )]
+    pub as_explicit: bool,
+    #[arg(
+        long = "with-optdepends",
+        help = "After installing an AUR package, offer to install its missing optional dependencies"
+    )]
+    pub with_optdepends: bool,
+    #[arg(
+        long = "print-deps-size",
+        help = "Before installing, show the total download and installed size of the packages and their missing dependencies"
+    )]
+    pub print_deps_size: bool,
+    #[arg(
+        long = "kernel",
+        value_name = "KERNEL",
+        help = "Build kernel modules (DKMS) only for this kernel, by package (linux-lts) or release"
+    )]
+    pub kernel: Option<String>,
+    #[arg(
+        long = "recursive-check",
+        requires = "remove",
+        help = "With -R, also list everything a cascading removal (pacman -Rsc) would take along"
+    )]
+    pub recursive_check: bool,
+    #[arg(
+        long = "print",
+        requires = "remove",
+        help = "With -R, show what the removal would break and the full removal set, and remove nothing"
+    )]
+    pub print: bool,
+    #[arg(
+        long = "rebuild-all",
+        requires = "sync",
+        help = "Rebuild installed AUR packages (with -S), e.g. after a soname bump"
+    )]
+    pub rebuild_all: bool,
+    #[arg(
+        long = "linking",
+        value_name = "LIB",
+        requires = "rebuild_all",
+        help = "With --rebuild-all, only rebuild packages linking this library"
+    )]
+    pub linking: Option<String>,
+    #[arg(
+        long = "downloadonly",
+        visible_alias = "buildonly",
+        help = "Build or download packages into ~/.cache/reap/packages without installing them"
+    )]
+    pub download_only: bool,
+    #[arg(
+        long = "offline",
+        help = "Use cached AUR data and sources only, never the network"
+    )]
+    pub offline: bool,
+    #[arg(
+        long = "select-source",
+        help = "Choose the source when a package is in more than one (remembered per package)"
+    )]
+    pub select_source: bool,
+    #[arg(
+        long = "from-file",
+        value_name = "FILE",
+        requires = "sync",
+        help = "With -S, install the packages listed in FILE (one per line, optional source: prefix)"
+    )]
+    pub from_file: Option<std::path::PathBuf>,
+    #[arg(
+        long = "clean-after",
+        help = "Offer to remove build-only dependencies after building AUR packages"
+    )]
+    pub clean_after: bool,
     #[arg(long = "dry-run", help = "Show what would be done, but do not install")]
     pub dry_run: bool,
     #[arg(
@@ -75,7 +332,14 @@ pub struct Cli {
 pub enum Commands {
     /// Install a package
     Install {
-        pkg: String,
+        #[arg(required_unless_present = "maintainer")]
+        pkg: Option<String>,
+        #[arg(
+            long,
+            conflicts_with = "pkg",
+            help = "Install every AUR package this maintainer maintains"
+        )]
+        maintainer: Option<String>,
         #[arg(long)]
         repo: Option<String>,
         #[arg(long)]
@@ -89,16 +353,196 @@ pub enum Commands {
         #[arg(long)]
         parallel: bool,
     },
+    /// Build packages into a local pacman repo and add them with repo-add
+    RepoAdd {
+        /// Repo directory, created if missing
+        dir: String,
+        /// Packages to build; lists the repo's packages if empty
+        pkgs: Vec<String>,
+        #[arg(long, help = "Repo name (defaults to the directory name)")]
+        name: Option<String>,
+    },
+    /// Diff the last-built PKGBUILD against the current AUR one
+    Diff { pkg: String },
     /// Remove one or more packages
-    Remove { pkgs: Vec<String> },
+    Remove {
+        pkgs: Vec<String>,
+        #[arg(
+            long = "recursive-check",
+            help = "Also list everything a cascading removal (pacman -Rsc) would take along"
+        )]
+        recursive_check: bool,
+        #[arg(
+            long,
+            help = "Show what the removal would break and the full removal set, and remove nothing"
+        )]
+        print: bool,
+    },
     /// Install local packages
     Local { pkgs: Vec<String> },
     /// Search for packages
-    Search { terms: Vec<String> },
+    Search {
+        terms: Vec<String>,
+        #[arg(long, help = "List the AUR packages this maintainer maintains")]
+        maintainer: Option<String>,
+    },
+    /// Show past install, remove and upgrade operations
+    History {
+        #[arg(
+            short = 'n',
+            long,
+            default_value_t = 20,
+            help = "Number of operations to show"
+        )]
+        limit: usize,
+        #[arg(
+            long,
+            value_name = "ID",
+            help = "Reverse an operation as far as possible"
+        )]
+        undo: Option<u64>,
+    },
+    /// Show the recorded installs, upgrades, removals and failures of a package
+    Log {
+        pkg: String,
+        #[arg(long, help = "Print the events as JSON")]
+        json: bool,
+    },
+    /// Show the pacman mirrorlist, or rank it with reflector/rankmirrors and sync
+    Mirrors {
+        #[arg(
+            long,
+            help = "Replace the mirrorlist with the fastest mirrors, then sync"
+        )]
+        rank: bool,
+        #[arg(
+            long,
+            requires = "rank",
+            help = "Only rank mirrors in this country (reflector)"
+        )]
+        country: Option<String>,
+        #[arg(
+            short = 'n',
+            long,
+            requires = "rank",
+            default_value_t = crate::mirrors::DEFAULT_MIRROR_COUNT,
+            help = "Number of mirrors to keep"
+        )]
+        number: usize,
+    },
+    /// Show the latest Arch Linux news
+    News {
+        #[arg(
+            short = 'n',
+            long,
+            default_value_t = 5,
+            help = "Number of news items to show"
+        )]
+        limit: usize,
+    },
+    /// Vote for an AUR package
+    Vote { pkg: String },
+    /// Remove your vote for an AUR package
+    Unvote { pkg: String },
+    /// Show package details
+    Info {
+        pkg: String,
+        #[arg(long, help = "Also show the newest AUR comments")]
+        comments: bool,
+        #[arg(
+            short = 'n',
+            long,
+            default_value_t = 5,
+            requires = "comments",
+            help = "Number of comments to show"
+        )]
+        limit: usize,
+    },
+    /// Write installed AUR/tap/Flatpak packages, taps, config, profiles and pins to a file
+    Export { file: std::path::PathBuf },
+    /// Recreate a setup written by `reap export`: taps, config, profiles, pins and packages
+    Import { file: std::path::PathBuf },
+    /// Show which package owns a file (pacman, AUR, tap or Flatpak)
+    Owns { path: String },
+    /// Show an installed package's dependencies and optional dependencies and whether each is installed
+    Query {
+        #[arg(required_unless_present = "missing")]
+        pkg: Option<String>,
+        #[arg(long, help = "Show dependencies")]
+        deps: bool,
+        #[arg(long, help = "Show optional dependencies")]
+        optdeps: bool,
+        #[arg(
+            long,
+            help = "Only list what isn't installed; without a package, for every installed package"
+        )]
+        missing: bool,
+    },
+    /// Check that an installed package's files are unchanged (missing, modified, permissions)
+    Verify {
+        #[arg(required_unless_present = "all")]
+        pkg: Option<String>,
+        #[arg(long, conflicts_with = "pkg", help = "Check every installed package")]
+        all: bool,
+    },
     /// Check for package updates
-    Update,
-    /// Upgrade all packages
-    Upgrade { parallel: bool },
+    Update {
+        #[arg(long, help = "Send a desktop notification when updates are available")]
+        notify: bool,
+    },
+    /// Write a systemd user service and timer that upgrade on a schedule
+    GenerateTimer {
+        #[arg(
+            long,
+            default_value = "daily",
+            help = "OnCalendar= schedule, e.g. daily, weekly or Mon *-*-* 09:00"
+        )]
+        schedule: String,
+        #[arg(long, help = "Only upgrade AUR packages")]
+        aur_only: bool,
+        #[arg(
+            long,
+            conflicts_with = "aur_only",
+            help = "Only check for updates and send a desktop notification"
+        )]
+        notify_only: bool,
+        #[arg(
+            long,
+            value_name = "DIR",
+            help = "Unit dir instead of ~/.config/systemd/user"
+        )]
+        dir: Option<std::path::PathBuf>,
+    },
+    /// Upgrade repo, AUR and Flatpak packages (all three unless one is selected)
+    Upgrade {
+        #[arg(long, help = "Build AUR upgrades in parallel")]
+        parallel: bool,
+        #[arg(long, help = "Upgrade repo packages (pacman -Syu)")]
+        repo: bool,
+        #[arg(long, help = "Upgrade AUR packages")]
+        aur: bool,
+        #[arg(long, help = "Upgrade Flatpak apps")]
+        flatpak: bool,
+        #[arg(
+            long,
+            help = "Also rebuild VCS (-git, -svn, ...) packages with new upstream commits"
+        )]
+        devel: bool,
+        #[arg(long, help = "Print the upgrade plan as JSON (implies --dry-run)")]
+        json: bool,
+    },
+    /// Sync databases, upgrade repo packages, then AUR packages in dependency order (-Syu)
+    Sysupgrade {
+        #[arg(long, help = "Build independent AUR upgrades in parallel")]
+        parallel: bool,
+        #[arg(
+            long,
+            help = "Also rebuild VCS (-git, -svn, ...) packages with new upstream commits"
+        )]
+        devel: bool,
+        #[arg(long, help = "Upgrade Flatpak apps afterwards")]
+        flatpak: bool,
+    },
     /// Parallel upgrade specific packages
     ParallelUpgrade { pkgs: Vec<String> },
     /// Upgrade all packages
@@ -113,12 +557,60 @@ pub enum Commands {
     SyncDb,
     /// Pin a package
     Pin { pkg: String },
-    /// Launch the interactive TUI
-    Tui,
+    /// Change the install reason of an installed package (like pacman -D)
+    Mark {
+        pkg: String,
+        #[arg(
+            long,
+            conflicts_with = "asdeps",
+            required_unless_present = "asdeps",
+            help = "Mark as explicitly installed, so orphan cleanup keeps it"
+        )]
+        explicit: bool,
+        #[arg(long, help = "Mark as installed as a dependency")]
+        asdeps: bool,
+    },
+    /// Launch the interactive package browser
+    Tui {
+        #[arg(long, help = "Open the monitoring dashboard instead of the browser")]
+        dashboard: bool,
+    },
     /// Clean package cache
-    Clean,
+    #[command(alias = "clean-cache")]
+    Clean {
+        #[arg(
+            long,
+            help = "Only remove dependencies pulled in for AUR builds that nothing needs anymore"
+        )]
+        uninstalled_build_deps: bool,
+        #[arg(
+            long,
+            value_name = "N",
+            num_args = 0..=1,
+            default_missing_value = "2",
+            value_parser = clap::value_parser!(u64).range(1..),
+            help = "Only prune the pacman cache, keeping the N most recent versions of each package (default 2, at least 1)"
+        )]
+        keep: Option<u64>,
+    },
     /// Run system doctor
-    Doctor,
+    Doctor {
+        #[arg(long, help = "Attempt to repair detected issues")]
+        fix: bool,
+    },
+    /// Clean up after an interrupted reap run: stale pacman lock, leftover build dirs and failed installs
+    FixBroken,
+    /// Show which source reap would install a package from, and the candidates it considered
+    Resolve {
+        pkg: String,
+        #[arg(long, help = "Print the resolution as JSON")]
+        json: bool,
+    },
+    /// Package counts, available updates and cache sizes
+    Stats {
+        #[arg(long, help = "Print the stats as JSON")]
+        json: bool,
+    },
     /// Performance and caching operations
     Perf {
         #[command(subcommand)]
@@ -129,6 +621,20 @@ pub enum Commands {
         #[command(subcommand)]
         cmd: SecurityCmd,
     },
+    /// Refresh expired or soon-expiring tap publisher keys
+    RefreshKeys {
+        /// Taps whose keys to check (all enabled taps when omitted)
+        taps: Vec<String>,
+        #[arg(long, help = "Refresh the keys even if they are still valid")]
+        force: bool,
+    },
+    /// Set up or repair the pacman and gpg keyrings
+    Keyring {
+        #[arg(long, help = "Initialize and populate the pacman keyring")]
+        init: bool,
+        #[arg(long, help = "Refresh the keys in the pacman keyring")]
+        refresh: bool,
+    },
     /// GPG key refresh
     Gpg {
         #[command(subcommand)]
@@ -155,6 +661,31 @@ pub enum Commands {
         #[arg(long = "all", help = "Include orphaned pacman packages, not just AUR")]
         all: bool,
     },
+    /// List installed packages
+    List {
+        #[arg(long, help = "Only show foreign (non-repo) packages")]
+        foreign: bool,
+        #[arg(long, help = "Only show explicitly installed packages")]
+        explicit: bool,
+        #[arg(
+            long,
+            value_name = "SOURCE",
+            value_parser = ["aur", "pacman", "flatpak", "tap"],
+            help = "Only show packages from this source"
+        )]
+        source: Option<String>,
+        #[arg(
+            long = "reap",
+            help = "Show packages installed through reap with their source and trust score"
+        )]
+        reap: bool,
+        #[arg(
+            long,
+            conflicts_with_all = ["foreign", "explicit", "source", "reap"],
+            help = "Print explicitly installed packages as a package list for -S --from-file"
+        )]
+        installed: bool,
+    },
     /// Manage global configuration
     Config {
         #[command(subcommand)]
@@ -167,8 +698,14 @@ pub enum Commands {
     },
     /// Trust and security analysis
     Trust {
+        #[arg(
+            long,
+            value_name = "PKG",
+            help = "Recompute the trust score of a package, ignoring the cache"
+        )]
+        refresh: Option<String>,
         #[command(subcommand)]
-        cmd: TrustCmd,
+        cmd: Option<TrustCmd>,
     },
     /// Rate a package
     Rate {
@@ -227,6 +764,16 @@ pub enum TapCmd {
     Update,
     Sync,
     List,
+    /// Clone a tap again, shallow (`--depth`) or with full history
+    Clone {
+        name: String,
+        #[arg(
+            long,
+            value_name = "N",
+            help = "Commits of history to keep (full history if omitted)"
+        )]
+        depth: Option<u32>,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -249,12 +796,13 @@ pub enum ProfileCmd {
         #[arg(long, help = "Use predefined template (developer, gaming, minimal)")]
         template: Option<String>,
     },
-    /// Switch to a profile
+    /// Switch to a profile; later commands use it until switched again
+    #[command(visible_alias = "use")]
     Switch { name: String },
     /// List all profiles
     List,
-    /// Show profile details
-    Show { name: String },
+    /// Show a profile's effective settings (the active profile if no name is given)
+    Show { name: Option<String> },
     /// Delete a profile
     Delete { name: String },
     /// Edit profile settings
@@ -38,6 +38,84 @@ pub struct GlobalConfig {
     pub show_tips: Option<bool>,
     pub enable_cache: Option<bool>,
     pub enable_lua_hooks: Option<bool>,
+    /// Number of lines kept in the TUI log pane
+    pub log_scrollback: Option<usize>,
+    /// Prompt to review AUR PKGBUILDs that changed since they were last accepted
+    pub review_pkgbuilds: Option<bool>,
+    /// Command used to run pacman and friends as root, e.g. `doas`;
+    /// detected when unset (`sudo`, else `doas`)
+    #[serde(alias = "sudo_cmd")]
+    pub privilege_cmd: Option<String>,
+    /// Where AUR packages are built: none, nspawn or sandbox (bubblewrap)
+    pub build_isolation: Option<String>,
+    /// Always build AUR packages in a clean devtools chroot
+    pub chroot: Option<bool>,
+    /// Skip the `check()` (test suite) phase of AUR builds
+    pub nocheck: Option<bool>,
+    /// Seconds a build may sit on an unfinished output line before it is
+    /// taken to be waiting for input; 0 disables the check
+    pub build_prompt_timeout_secs: Option<u64>,
+    /// Show unread Arch news and ask before upgrading
+    pub news_check: Option<bool>,
+    /// Offer to rank mirrors before `-Syu` once the mirrorlist is older
+    /// than this many days; unset or 0 never asks
+    pub mirrorlist_max_age_days: Option<u64>,
+    /// Base URL of the AUR (or a mirror/private instance) used for git clones and PKGBUILDs
+    pub aur_url: Option<String>,
+    /// Base URL of the AUR RPC interface
+    pub aur_rpc_url: Option<String>,
+    /// Timeout in seconds for AUR requests
+    pub network_timeout_secs: Option<u64>,
+    /// AUR RPC requests per second, shared by all of reap's parallel tasks
+    pub aur_rpc_rate: Option<f64>,
+    /// Where AUR packages are built (`~` and `$VAR` are expanded), e.g. a
+    /// tmpfs; `$TMPDIR` or the cache dir when unset
+    pub build_dir: Option<String>,
+    /// Serve AUR data from the local cache only, like `--offline`
+    pub offline: Option<bool>,
+    /// GPG key id used to sign AUR packages reap builds (and local repo databases)
+    pub sign_key: Option<String>,
+    /// Show trust scores before installing and enforce the active profile's
+    /// signature policy, even under the default profile
+    pub trust_check: Option<bool>,
+    /// Seconds a computed trust score is reused before it is recomputed
+    pub trust_cache_ttl: Option<u64>,
+    /// Ask before installing orphaned AUR packages (refused under strict signatures)
+    pub block_orphaned_aur: Option<bool>,
+    /// Ask before installing AUR packages flagged out-of-date (refused under strict signatures)
+    pub warn_out_of_date: Option<bool>,
+    /// Mention when a package being installed from the AUR is also on
+    /// Flatpak, or a Flatpak app also in the AUR
+    pub suggest_flatpak: Option<bool>,
+    /// What to do with the missing optional dependencies of an installed
+    /// AUR package: `prompt` to pick them, `all` or `none`
+    pub install_optdepends: Option<String>,
+    /// AUR session cookie (`AURSID`) for `reap vote`; without it reap logs in
+    /// and keeps the session in its data dir, never the password
+    pub aur_cookie: Option<String>,
+    /// Seconds a prompt waits for an answer before taking its default (or
+    /// the `--default-yes`/`--default-no` answer); unset or 0 waits forever
+    pub prompt_timeout_secs: Option<u64>,
+    /// Clone taps with their full git history instead of only the latest commit
+    pub tap_full_clone: Option<bool>,
+    /// Pager for long search results and diffs; `$PAGER` or `less -R` when
+    /// unset, and an empty string turns paging off
+    pub pager: Option<String>,
+    /// Diff tool for PKGBUILD reviews and `reap diff`, run with the built
+    /// and the new PKGBUILD as its last two arguments (`delta`,
+    /// `diff --color`, `git diff --no-index`); the built-in diff when unset
+    pub diff_cmd: Option<String>,
+    /// Send desktop notifications from `reap update --notify`; when false the
+    /// summary is only printed
+    pub notify: Option<bool>,
+    /// Offer to remove the dependencies pulled in to build an AUR package
+    /// once nothing installed needs them, like `--clean-after`
+    pub clean_after: Option<bool>,
+    /// Source picked with `--select-source` per package: `aur` or `pacman`
+    pub source_choices: Option<std::collections::BTreeMap<String, String>>,
+    /// Source a package always comes from, ahead of the priority order:
+    /// `aur`, `pacman` or `flatpak`
+    pub source_overrides: Option<std::collections::BTreeMap<String, String>>,
 }
 
 impl Default for GlobalConfig {
@@ -56,6 +134,37 @@ impl Default for GlobalConfig {
             show_tips: Some(false),
             enable_cache: Some(true),
             enable_lua_hooks: Some(false),
+            log_scrollback: Some(1000),
+            review_pkgbuilds: Some(true),
+            build_isolation: Some("none".to_string()),
+            chroot: Some(false),
+            nocheck: Some(false),
+            build_prompt_timeout_secs: Some(crate::core::DEFAULT_BUILD_PROMPT_TIMEOUT_SECS),
+            news_check: Some(true),
+            mirrorlist_max_age_days: None,
+            aur_url: Some(DEFAULT_AUR_URL.to_string()),
+            aur_rpc_url: Some(DEFAULT_AUR_RPC_URL.to_string()),
+            network_timeout_secs: Some(30),
+            aur_rpc_rate: Some(DEFAULT_AUR_RPC_RATE),
+            offline: Some(false),
+            build_dir: None,
+            privilege_cmd: None,
+            sign_key: None,
+            trust_check: Some(false),
+            trust_cache_ttl: Some(86400),
+            block_orphaned_aur: Some(false),
+            warn_out_of_date: Some(false),
+            suggest_flatpak: Some(false),
+            install_optdepends: Some("none".to_string()),
+            aur_cookie: None,
+            source_choices: None,
+            clean_after: Some(false),
+            notify: Some(true),
+            pager: None,
+            diff_cmd: None,
+            tap_full_clone: Some(false),
+            source_overrides: None,
+            prompt_timeout_secs: None,
         }
     }
 }
@@ -67,7 +176,7 @@ impl GlobalConfig {
             .join("reap/reap.toml");
 
         if config_path.exists() {
-            println!("[config] Found config at {}", config_path.display());
+            eprintln!("[config] Found config at {}", config_path.display());
 
             if let Ok(contents) = fs::read_to_string(&config_path) {
                 match toml::from_str::<GlobalConfig>(&contents) {
@@ -82,9 +191,163 @@ impl GlobalConfig {
         println!("[config] Using default config.");
         GlobalConfig::default()
     }
+
+    /// The configured AUR base URL, falling back to upstream if unset or invalid
+    pub fn aur_url(&self) -> String {
+        endpoint_or_default("aur_url", self.aur_url.as_deref(), DEFAULT_AUR_URL)
+    }
+
+    /// The configured AUR RPC URL, falling back to upstream if unset or invalid
+    pub fn aur_rpc_url(&self) -> String {
+        endpoint_or_default(
+            "aur_rpc_url",
+            self.aur_rpc_url.as_deref(),
+            DEFAULT_AUR_RPC_URL,
+        )
+    }
+}
+
+pub const DEFAULT_AUR_URL: &str = "https://aur.archlinux.org";
+/// AUR RPC requests per second when `aur_rpc_rate` is unset
+pub const DEFAULT_AUR_RPC_RATE: f64 = 4.0;
+pub const DEFAULT_AUR_RPC_URL: &str = "https://aur.archlinux.org/rpc";
+
+/// Check that `url` is an absolute http(s) URL with a host, returning it
+/// without a trailing slash so paths can be appended
+pub fn validate_url(url: &str) -> Result<String, String> {
+    let parsed = reqwest::Url::parse(url.trim()).map_err(|e| format!("{}: {}", url, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("{}: scheme must be http or https", url));
+    }
+    if parsed.host_str().is_none_or(str::is_empty) {
+        return Err(format!("{}: missing host", url));
+    }
+    if parsed.query().is_some() || parsed.fragment().is_some() {
+        return Err(format!("{}: must not have a query or fragment", url));
+    }
+    Ok(parsed.as_str().trim_end_matches('/').to_string())
+}
+
+fn endpoint_or_default(key: &str, url: Option<&str>, default: &str) -> String {
+    match url.map(validate_url) {
+        Some(Ok(url)) => url,
+        Some(Err(e)) => {
+            eprintln!("[config] Invalid {key} ({e}), using {default}");
+            default.to_string()
+        }
+        None => default.to_string(),
+    }
+}
+
+/// Expand a leading `~` and `$VAR`/`${VAR}` references in a configured
+/// path, looking variables up with `var`. Unset variables expand to nothing.
+pub fn expand_path(
+    raw: &str,
+    home: &std::path::Path,
+    var: impl Fn(&str) -> Option<String>,
+) -> PathBuf {
+    let raw = raw.trim();
+    let (mut out, rest) = match raw.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            (home.to_string_lossy().into_owned(), rest)
+        }
+        _ => (String::new(), raw),
+    };
+    let mut chars = rest.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            out.push(c);
+            continue;
+        }
+        let braced = chars.next_if_eq(&'{').is_some();
+        let mut name = String::new();
+        while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+            name.push(c);
+        }
+        if braced && chars.next_if_eq(&'}').is_none() {
+            // Not a reference after all, keep it as written
+            out.push_str("${");
+            out.push_str(&name);
+            continue;
+        }
+        if name.is_empty() {
+            out.push('$');
+            if braced {
+                out.push_str("{}");
+            }
+            continue;
+        }
+        out.push_str(&var(&name).unwrap_or_default());
+    }
+    PathBuf::from(out)
+}
+
+/// Check that `dir` exists (creating it if needed) and is writable
+pub fn validate_build_dir(dir: &std::path::Path) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let probe = dir.join(format!(".reap-write-test-{}", std::process::id()));
+    fs::write(&probe, b"").map_err(|e| format!("{} is not writable: {}", dir.display(), e))?;
+    let _ = fs::remove_file(probe);
+    Ok(())
+}
+
+impl GlobalConfig {
+    /// Base directory AUR packages are built in: `build_dir`, else
+    /// `$TMPDIR`, else the user cache dir
+    pub fn build_root(&self) -> PathBuf {
+        let home = dirs::home_dir().unwrap_or_default();
+        if let Some(dir) = self.build_dir.as_deref().filter(|d| !d.trim().is_empty()) {
+            return expand_path(dir, &home, |name| std::env::var(name).ok());
+        }
+        std::env::var_os("TMPDIR")
+            .filter(|t| !t.is_empty())
+            .map(PathBuf::from)
+            .or_else(dirs::cache_dir)
+            .unwrap_or_else(|| PathBuf::from("/tmp"))
+    }
+}
+
+/// Check a `source_overrides` value: `aur`, `pacman` or `flatpak`. Taps
+/// are refused until packages can be built from them.
+pub fn validate_source_override(source: &str) -> Result<(), String> {
+    match source {
+        "aur" | "pacman" | "flatpak" => Ok(()),
+        _ if source.starts_with("tap:") => Err(format!(
+            "'{}': installing from taps is not supported yet",
+            source
+        )),
+        _ => Err(format!(
+            "'{}' is not a source (expected aur, pacman or flatpak)",
+            source
+        )),
+    }
+}
+
+impl GlobalConfig {
+    /// The source `pkg` is forced to come from by `source_overrides`, if any
+    pub fn source_override(&self, pkg: &str) -> Option<&str> {
+        self.source_overrides
+            .as_ref()
+            .and_then(|overrides| overrides.get(pkg))
+            .map(String::as_str)
+    }
 }
 
 pub fn set_config_key(key: &str, value_str: &str) {
+    if matches!(key, "aur_url" | "aur_rpc_url")
+        && let Err(e) = validate_url(value_str)
+    {
+        eprintln!("[config] Invalid {key}: {e}");
+        return;
+    }
+    if key == "build_dir" && !value_str.is_empty() {
+        let home = dirs::home_dir().unwrap_or_default();
+        let dir = expand_path(value_str, &home, |name| std::env::var(name).ok());
+        if let Err(e) = validate_build_dir(&dir) {
+            eprintln!("[config] Invalid build_dir: {e}");
+            return;
+        }
+    }
     let path = config_path();
     let mut doc = if path.exists() {
         fs::read_to_string(&path)
@@ -94,21 +357,54 @@ pub fn set_config_key(key: &str, value_str: &str) {
     } else {
         DocumentMut::new()
     };
-    doc[key] = value(value_str);
+    // `override.<pkg> <source>` goes to `[source_overrides]`; an empty
+    // source removes the override
+    if let Some(pkg) = key.strip_prefix("override.") {
+        if value_str.is_empty() {
+            if let Some(table) = doc
+                .get_mut("source_overrides")
+                .and_then(|t| t.as_table_like_mut())
+            {
+                table.remove(pkg);
+            }
+        } else if let Err(e) = validate_source_override(value_str) {
+            eprintln!("[config] Invalid override for {pkg}: {e}");
+            return;
+        } else {
+            doc["source_overrides"][pkg] = value(value_str);
+        }
+    } else {
+        doc[key] = value(value_str);
+    }
     let _ = fs::write(&path, doc.to_string());
 }
 
+/// Remember `choice` as the source for `pkg` under `[source_choices]`
+pub fn remember_source_choice(pkg: &str, choice: &str) -> std::io::Result<()> {
+    let path = config_path();
+    let mut doc = fs::read_to_string(&path)
+        .ok()
+        .and_then(|s| s.parse::<DocumentMut>().ok())
+        .unwrap_or_default();
+    doc["source_choices"][pkg] = value(choice);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, doc.to_string())
+}
+
 pub fn get_config_key(key: &str) -> Option<String> {
     let path = config_path();
-    if path.exists() {
-        if let Some(Ok(doc)) = fs::read_to_string(&path)
+    if path.exists()
+        && let Some(Ok(doc)) = fs::read_to_string(&path)
             .ok()
             .map(|s| s.parse::<DocumentMut>())
-        {
-            if let Some(val) = doc.get(key) {
-                return Some(val.to_string());
-            }
+        && let Some(val) = match key.strip_prefix("override.") {
+            Some(pkg) => doc.get("source_overrides").and_then(|t| t.get(pkg)),
+            None => doc.get(key),
         }
+    {
+        return Some(val.to_string());
     }
     None
 }
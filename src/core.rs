@@ -1,29 +1,25 @@
 use crate::aur;
 use crate::aur::upgrade_all;
-use crate::backend::{AurBackend, Backend};
+use crate::backend::Backend;
 use crate::cli::Cli;
 use crate::cli::{Commands, ConfigCmd, TapCmd};
 use crate::config::GlobalConfig;
 use crate::config::ReapConfig;
 use crate::flatpak;
+use crate::history::{OperationKind, OperationLog, PackageChange};
 use crate::hooks::{HookContext, post_install, pre_install};
 use crate::pacman;
 use crate::profiles::ProfileManager;
-use crate::tap::{Tap, discover_taps, find_tap_for_pkg};
+use crate::tap::{Tap, discover_taps, find_tap_for_pkg, find_tap_providers};
 use crate::trust::TrustEngine;
-use crate::tui;
-use crate::tui::LogPane;
 use crate::utils;
-use anyhow::anyhow;
 use anyhow::{Context, Result};
 use chrono::Local;
-use futures::FutureExt;
 use futures::future::join_all;
 use indicatif::{ProgressBar, ProgressStyle};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::io::BufRead;
 use std::path::PathBuf;
 use std::process::Command;
 use std::sync::Arc;
@@ -38,6 +34,243 @@ pub enum ReapError {
     CommandFailed(String),
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
+    /// `stage` is the step of the AUR build that failed, e.g. `build` or `arch`
+    #[error("Building {pkg} failed at the {stage} step")]
+    BuildFailed { pkg: String, stage: &'static str },
+    #[error("Dependency cycle between {}", .0.join(", "))]
+    DependencyCycle(Vec<String>),
+    #[error("Signature check failed for {pkg} (key {key}), use --insecure to override")]
+    SignatureInvalid { pkg: String, key: String },
+    #[error("{0} was not found in any source")]
+    SourceNotFound(String),
+    #[error("Network error: {0}")]
+    NetworkError(String),
+    #[error("Aborted by user")]
+    UserAborted,
+    #[error("Install task for {pkg} panicked: {message}")]
+    TaskPanicked { pkg: String, message: String },
+    #[error("{} of {total} packages failed: {}", .failed.len(), .failed.join(", "))]
+    PackagesFailed { failed: Vec<String>, total: usize },
+    #[error("AUR authentication failed: {0}")]
+    AuthFailed(String),
+    /// The AUR trust policy (orphaned or out-of-date packages) refused `pkg`
+    #[error("Refusing to install {pkg}: {reason}")]
+    TrustPolicy { pkg: String, reason: String },
+    /// Another running reap holds the lockfile next to the build dirs
+    #[error("Another reap is building, {} is held", .0.display())]
+    BuildLocked(PathBuf),
+}
+
+/// Refresh `keyid` from `keyserver` if it has expired or expires soon, so
+/// a publisher extending their key doesn't fail verification
+fn refresh_stale_key(keyid: &str, keyserver: &str, log: &dyn ProgressReporter) {
+    use crate::gpg::KeyFreshness;
+    let state = match crate::gpg::check_key_freshness(keyid) {
+        KeyFreshness::Expired => "has expired".to_string(),
+        KeyFreshness::Expiring(days) => format!("expires in {} day(s)", days),
+        KeyFreshness::Valid | KeyFreshness::Unknown => return,
+    };
+    log.message(&format!(
+        "[reap][gpg] Publisher key {} {}, refreshing from {}...",
+        keyid, state, keyserver
+    ));
+    if let Err(e) = crate::gpg::refresh_key(keyid, keyserver) {
+        log.message(&format!("[reap][gpg] Refreshing {} failed: {}", keyid, e));
+        return;
+    }
+    match crate::gpg::check_key_freshness(keyid) {
+        KeyFreshness::Expired => log.message(&format!(
+            "[reap][gpg] Key {} is still expired after refreshing",
+            keyid
+        )),
+        _ => log.message(&format!("[reap][gpg] Refreshed key {}", keyid)),
+    }
+}
+
+/// `reap refresh-keys`: refresh the publisher keys of `taps` (all enabled
+/// taps when empty) that expired or expire soon, or all of them with `force`
+pub fn handle_refresh_keys(
+    taps: &[String],
+    force: bool,
+    keyserver: Option<&str>,
+) -> Result<(), ReapError> {
+    use crate::gpg::KeyFreshness;
+    let keyserver = keyserver.unwrap_or(crate::gpg::DEFAULT_KEYSERVER);
+    let all = discover_taps();
+    for name in taps {
+        if !all.iter().any(|t| &t.name == name) {
+            return Err(ReapError::SourceNotFound(format!("tap {}", name)));
+        }
+    }
+    let mut failed = Vec::new();
+    for tap in all.iter().filter(|t| {
+        if taps.is_empty() {
+            t.enabled
+        } else {
+            taps.contains(&t.name)
+        }
+    }) {
+        let Some(publisher) = crate::tap::get_publisher_info(tap) else {
+            continue;
+        };
+        let Some(keyid) = publisher.gpg_key.split_whitespace().last() else {
+            continue;
+        };
+        let freshness = crate::gpg::check_key_freshness(keyid);
+        let state = match freshness {
+            KeyFreshness::Valid => "valid".to_string(),
+            KeyFreshness::Expiring(days) => format!("expires in {} day(s)", days),
+            KeyFreshness::Expired => "expired".to_string(),
+            KeyFreshness::Unknown => "not in keyring".to_string(),
+        };
+        if !force && !freshness.is_stale() {
+            println!("[reap] {}: key {} {}", tap.name, keyid, state);
+            continue;
+        }
+        println!(
+            "[reap] {}: key {} {}, refreshing from {}...",
+            tap.name, keyid, state, keyserver
+        );
+        match crate::gpg::refresh_key(keyid, keyserver) {
+            Ok(()) if crate::gpg::check_key_freshness(keyid) != KeyFreshness::Expired => {
+                println!("[reap] {}: refreshed key {}", tap.name, keyid)
+            }
+            Ok(()) => {
+                eprintln!("[reap] {}: key {} is still expired", tap.name, keyid);
+                failed.push(tap.name.clone());
+            }
+            Err(e) => {
+                eprintln!(
+                    "[reap] {}: refreshing key {} failed: {}",
+                    tap.name, keyid, e
+                );
+                failed.push(tap.name.clone());
+            }
+        }
+    }
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(ReapError::CommandFailed(format!(
+            "could not refresh keys of {}",
+            failed.join(", ")
+        )))
+    }
+}
+
+/// Verify a tap PKGBUILD against the tap publisher's key, importing the key
+/// if it is missing. With `opts.insecure` failures are logged but allowed.
+fn verify_tap_pkgbuild(
+    pkg: &str,
+    tap: &Tap,
+    log: &dyn ProgressReporter,
+    opts: &InstallOptions,
+) -> Result<(), ReapError> {
+    use colored::Colorize;
+    let pkg_dir = crate::tap::ensure_tap_cloned(tap).join(pkg);
+    let pkgb_path = pkg_dir.join("PKGBUILD");
+    let sig_path = pkg_dir.join("PKGBUILD.sig");
+    let insecure_or = |err: ReapError| {
+        if opts.insecure {
+            log.message(&format!(
+                "{} {}, continuing due to --insecure.",
+                "⚠️".yellow(),
+                err
+            ));
+            Ok(())
+        } else {
+            Err(err)
+        }
+    };
+    let Some(pubinfo) = crate::tap::get_publisher_info(tap) else {
+        return insecure_or(ReapError::SignatureInvalid {
+            pkg: pkg.to_string(),
+            key: "unknown publisher".to_string(),
+        });
+    };
+    let keyid = pubinfo.gpg_key.split_whitespace().last().unwrap_or("");
+    let verified_str = if pubinfo.verified {
+        "[✓ Verified GPG]".green().to_string()
+    } else {
+        "[Unverified]".yellow().to_string()
+    };
+    log.message(&format!(
+        "👤 {} from {} {}",
+        tap.name.bold(),
+        pubinfo.name,
+        verified_str
+    ));
+    log.message(&format!("🔑 GPG Key: {}", keyid));
+    // Check if key is in keyring
+    let key_present = Command::new("gpg")
+        .args(["--list-keys", keyid])
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false);
+    let keyserver = opts
+        .gpg_keyserver
+        .as_deref()
+        .unwrap_or(crate::gpg::DEFAULT_KEYSERVER);
+    if key_present {
+        refresh_stale_key(keyid, keyserver, log);
+    } else {
+        log.message(&format!(
+            "[reap][gpg] Importing publisher key {} from {}...",
+            keyid, keyserver
+        ));
+        let fetch = Command::new("gpg")
+            .args(["--keyserver", keyserver, "--recv-keys", keyid])
+            .status();
+        match fetch {
+            Ok(s) if s.success() => log.message(&format!(
+                "[reap][gpg] {} Successfully imported {}",
+                "✓".green(),
+                keyid
+            )),
+            Ok(_) | Err(_) => log.message(&format!(
+                "[reap][gpg] {} Failed to import publisher key {}",
+                "❌".red(),
+                keyid
+            )),
+        }
+    }
+    let invalid = ReapError::SignatureInvalid {
+        pkg: pkg.to_string(),
+        key: keyid.to_string(),
+    };
+    if !sig_path.exists() || !pkgb_path.exists() {
+        log.message(&format!("{} PKGBUILD.sig missing", "❌".red()));
+        return insecure_or(invalid);
+    }
+    let output = Command::new("gpg")
+        .args(["--status-fd", "2", "--verify"])
+        .arg(&sig_path)
+        .arg(&pkgb_path)
+        .output();
+    match output {
+        Ok(out) if out.status.success() => {
+            log.message(&format!("{} PKGBUILD signature verified", "✓".green()));
+            Ok(())
+        }
+        Ok(out)
+            if crate::gpg::classify_verify_failure(&String::from_utf8_lossy(&out.stderr))
+                == crate::gpg::VerifyFailure::NoKeyring =>
+        {
+            // Not a bad signature, there is just nothing to check it against
+            log.message(&format!(
+                "{} No usable GPG keyring to verify {}; {}",
+                "❌".red(),
+                pkg,
+                crate::gpg::KEYRING_HINT
+            ));
+            insecure_or(ReapError::CommandFailed(format!(
+                "no GPG keyring to verify {}, {}",
+                pkg,
+                crate::gpg::KEYRING_HINT
+            )))
+        }
+        _ => insecure_or(invalid),
+    }
 }
 
 /// Backup package state before install (files and pacman db)
@@ -48,14 +281,26 @@ pub fn backup_package_state(pkg: &str) -> Result<PathBuf> {
         .join(format!("reap/backup/{}/{}", pkg, timestamp));
     fs::create_dir_all(&backup_dir)
         .with_context(|| format!("Failed to create backup dir: {}", backup_dir.display()))?;
-    // Backup pacman db
-    let db_path = PathBuf::from(format!("/var/lib/pacman/local/{}-*", pkg));
-    let _ = std::process::Command::new("cp")
-        .arg("-r")
-        .arg(&db_path)
-        .arg(&backup_dir)
-        .status()
-        .with_context(|| "Failed to backup pacman db");
+    // Backup pacman db entry (`<pkg>-<version>`)
+    if let Some(version) = pacman::installed_versions().remove(pkg) {
+        let db_path = PathBuf::from(format!("/var/lib/pacman/local/{}-{}", pkg, version));
+        if db_path.is_dir() {
+            let _ = std::process::Command::new("cp")
+                .arg("-r")
+                .arg(&db_path)
+                .arg(&backup_dir)
+                .status()
+                .with_context(|| "Failed to backup pacman db");
+        }
+        // Checksums of what reap built, for `reap verify`
+        if pacman::is_foreign(pkg) {
+            crate::verify::write_manifest(
+                &backup_dir.join(crate::verify::MANIFEST_FILE),
+                &pacman::file_list(pkg),
+            )
+            .with_context(|| "Failed to write file manifest")?;
+        }
+    }
     // Backup /usr/bin/<pkg> if exists
     let bin_path = PathBuf::from(format!("/usr/bin/{}", pkg));
     if bin_path.exists() {
@@ -115,6 +360,89 @@ pub struct InstallOptions {
     pub strict_signatures: bool,
     #[allow(dead_code)]
     pub max_parallel: usize,
+    /// Build AUR packages in a clean devtools chroot
+    pub chroot: bool,
+    /// Pass `--ignorearch` to makepkg
+    pub ignore_arch: bool,
+    /// Pass `--nocheck` to makepkg
+    pub nocheck: bool,
+    /// Build AUR packages flagged out-of-date without asking
+    pub ignore_ood: bool,
+    /// Install again even when the same version is installed (no `--needed`)
+    pub reinstall: bool,
+    /// Mark installed packages as explicitly installed (`pacman -D --asexplicit`)
+    pub as_explicit: bool,
+    /// Offer to install the missing optional dependencies of AUR packages
+    pub with_optdepends: bool,
+    /// Show the total download and installed size before installing
+    pub print_deps_size: bool,
+    /// Kernel to build modules for, instead of every installed one
+    pub kernel: Option<String>,
+    /// Build or download packages into the output dir without installing them
+    pub download_only: bool,
+    /// Also copy built AUR packages to the output dir
+    pub export_packages: bool,
+    /// Output dir for built packages instead of `package_output_dir()`
+    pub package_dir: Option<PathBuf>,
+    /// Sources to resolve from instead of the configured `backend_order`
+    pub backend_order: Option<Vec<String>>,
+    /// Overrides `auto_resolve_deps` from the config
+    pub auto_resolve_deps: Option<bool>,
+    /// Only print the install plan
+    pub dry_run: bool,
+    /// Ask which source to use when a package is in more than one
+    pub select_source: bool,
+    /// Open AUR PKGBUILDs in `$EDITOR` before building
+    pub edit: bool,
+    /// Offer to remove build-only dependencies after an AUR build
+    pub clean_after: bool,
+    /// Only install from this tap
+    pub tap: Option<String>,
+    /// Globs of conflicting files `pacman -U` may overwrite (`--overwrite`)
+    pub overwrite: Vec<String>,
+    /// Build AUR packages twice and compare the results before installing
+    pub verify_reproducible: bool,
+    /// Dependencies to treat as installed (`--ignore-dependency`)
+    pub ignore_deps: Vec<String>,
+    /// Source picked by a qualified target (`aur/foo`), skipping source
+    /// resolution
+    pub source: Option<Source>,
+    /// Sync repo repo packages are installed from (`extra/foo`)
+    pub repo: Option<String>,
+}
+
+impl InstallOptions {
+    /// Where built and downloaded packages are left
+    pub fn output_dir(&self) -> PathBuf {
+        self.package_dir.clone().unwrap_or_else(package_output_dir)
+    }
+}
+
+/// Install options taken from the global CLI flags
+pub fn install_options(cli: &Cli) -> InstallOptions {
+    InstallOptions {
+        insecure: cli.insecure,
+        gpg_keyserver: cli.gpg_keyserver.clone(),
+        chroot: cli.chroot,
+        ignore_arch: cli.ignore_arch,
+        nocheck: cli.nocheck,
+        ignore_ood: cli.ignore_ood,
+        reinstall: cli.reinstall,
+        as_explicit: cli.as_explicit,
+        with_optdepends: cli.with_optdepends,
+        print_deps_size: cli.print_deps_size,
+        kernel: cli.kernel.clone(),
+        download_only: cli.download_only,
+        dry_run: cli.dry_run,
+        select_source: cli.select_source,
+        edit: cli.edit,
+        clean_after: cli.clean_after,
+        overwrite: cli.overwrite.clone(),
+        verify_reproducible: cli.verify_reproducible,
+        ignore_deps: cli.ignore_dependency.clone(),
+        backend_order: cli.backend.clone().map(|backend| vec![backend]),
+        ..InstallOptions::default()
+    }
 }
 
 pub fn get_installed_packages() -> HashMap<String, Source> {
@@ -137,386 +465,2059 @@ pub fn get_installed_packages() -> HashMap<String, Source> {
     pkgs
 }
 
-/// Resolve the best source for a package, using tap, repo, AUR, or flatpak, in priority order.
+/// An installed package with its version, source and install reason
+#[derive(Debug, Clone)]
+pub struct InstalledPackage {
+    pub name: String,
+    pub version: String,
+    pub source: Source,
+    /// Installed explicitly rather than as a dependency
+    pub explicit: bool,
+}
+
+/// List installed pacman and flatpak packages, sorted by name. Foreign
+/// (non-repo) pacman packages are reported as AUR.
+pub fn list_installed_packages() -> Vec<InstalledPackage> {
+    let query = |flag: &str| -> Vec<String> {
+        Command::new("pacman")
+            .arg(flag)
+            .output()
+            .map(|out| String::from_utf8_lossy(&out.stdout).into_owned())
+            .unwrap_or_default()
+            .lines()
+            .map(|l| l.to_string())
+            .collect()
+    };
+    let foreign: std::collections::HashSet<String> = query("-Qmq").into_iter().collect();
+    let explicit: std::collections::HashSet<String> = query("-Qeq").into_iter().collect();
+    let mut pkgs: Vec<InstalledPackage> = query("-Q")
+        .iter()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let name = parts.next()?.to_string();
+            let version = parts.next().unwrap_or("").to_string();
+            let source = if foreign.contains(&name) {
+                Source::Aur
+            } else {
+                Source::Pacman
+            };
+            let explicit = explicit.contains(&name);
+            Some(InstalledPackage {
+                name,
+                version,
+                source,
+                explicit,
+            })
+        })
+        .collect();
+    if let Ok(out) = Command::new("flatpak")
+        .args(["list", "--app", "--columns=application,version"])
+        .output()
+    {
+        for line in String::from_utf8_lossy(&out.stdout).lines() {
+            let mut parts = line.split('\t');
+            let name = parts.next().unwrap_or("").trim();
+            if name.is_empty() {
+                continue;
+            }
+            pkgs.push(InstalledPackage {
+                name: name.to_string(),
+                version: parts.next().unwrap_or("").trim().to_string(),
+                source: Source::Flatpak,
+                explicit: true,
+            });
+        }
+    }
+    pkgs.sort_by(|a, b| a.name.cmp(&b.name));
+    pkgs
+}
+
+/// Whether an installed package came from `source` (aur, pacman, flatpak or
+/// tap). Foreign packages that reap built from a tap, per the install
+/// registry, count as tap packages rather than AUR ones.
+pub fn installed_from_source(
+    pkg: &InstalledPackage,
+    source: &str,
+    registry: &crate::history::InstallRegistry,
+) -> bool {
+    let from_tap = pkg.source == Source::Aur
+        && registry
+            .get(&pkg.name)
+            .is_some_and(|record| record.tap.is_some());
+    match source {
+        "aur" => pkg.source == Source::Aur && !from_tap,
+        "tap" => from_tap,
+        "pacman" => pkg.source == Source::Pacman,
+        "flatpak" => pkg.source == Source::Flatpak,
+        _ => false,
+    }
+}
+
+/// Print installed packages, optionally keeping only foreign (non-repo),
+/// explicitly installed, or single-source packages
+pub fn handle_list(foreign: bool, explicit: bool, source: Option<&str>, format: Option<&str>) {
+    let registry = crate::history::InstallRegistry::load();
+    for pkg in list_installed_packages() {
+        if foreign && pkg.source == Source::Pacman {
+            continue;
+        }
+        if explicit && !pkg.explicit {
+            continue;
+        }
+        if let Some(name) = source
+            && !installed_from_source(&pkg, name, &registry)
+        {
+            continue;
+        }
+        match format {
+            Some(template) => println!("{}", format_installed_package(&pkg, template)),
+            None => println!("{} {} {}", pkg.source.label(), pkg.name, pkg.version),
+        }
+    }
+}
+
+/// One line of a package list for `-S --from-file`: a package name with an
+/// optional `source:` prefix (`aur:`, `pacman:`/`repo:`, `flatpak:`, `tap:`
+/// or `tap:<name>:`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageListEntry {
+    pub name: String,
+    /// Backend to install from, as named in `backend_order`
+    pub source: Option<String>,
+    /// Tap to install from, for `tap:<name>:` entries
+    pub tap: Option<String>,
+    /// Sync repo to install from, for `<repo>/name` targets
+    pub repo: Option<String>,
+}
+
+impl PackageListEntry {
+    /// Parse one line; blank lines and `#` comments yield `None`
+    pub fn parse(line: &str) -> Result<Option<Self>, String> {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            return Ok(None);
+        }
+        // Package names never contain ':', so the last one ends the prefix
+        let Some((prefix, name)) = line.rsplit_once(':') else {
+            return Ok(Some(Self {
+                name: line.to_string(),
+                source: None,
+                tap: None,
+                repo: None,
+            }));
+        };
+        let (source, tap) = match prefix.split_once(':') {
+            Some(("tap", tap)) => ("tap", Some(tap.to_string())),
+            _ => match prefix {
+                "aur" | "tap" | "flatpak" => (prefix, None),
+                "pacman" | "repo" => ("pacman", None),
+                _ => return Err(format!("unknown source '{}'", prefix)),
+            },
+        };
+        Ok(Some(Self {
+            name: name.trim().to_string(),
+            source: Some(source.to_string()),
+            tap,
+            repo: None,
+        }))
+    }
+
+    /// Parse an install target: `aur/foo`, `flatpak/org.foo.Bar` or
+    /// `<repo>/foo` for one of the sync `repos` picks the source like
+    /// paru and yay do, anything else is read like a package list line
+    pub fn parse_target(target: &str, repos: &[String]) -> Result<Self, String> {
+        if let Some((prefix, name)) = target.split_once('/') {
+            let (source, repo) = match prefix {
+                "aur" | "flatpak" => (prefix, None),
+                _ if repos.iter().any(|r| r == prefix) => ("pacman", Some(prefix.to_string())),
+                _ => return Err(format!("unknown source or repo '{}'", prefix)),
+            };
+            return Ok(Self {
+                name: name.to_string(),
+                source: Some(source.to_string()),
+                tap: None,
+                repo,
+            });
+        }
+        Ok(Self::parse(target)?.unwrap_or_else(|| Self {
+            name: target.to_string(),
+            source: None,
+            tap: None,
+            repo: None,
+        }))
+    }
+
+    /// The source this entry forces, skipping source resolution. Taps
+    /// still go through tap lookup.
+    pub fn forced_source(&self) -> Option<Source> {
+        match self.source.as_deref()? {
+            "aur" => Some(Source::Aur),
+            "pacman" => Some(Source::Pacman),
+            "flatpak" => Some(Source::Flatpak),
+            _ => None,
+        }
+    }
+
+    /// The entry as a package list line
+    pub fn to_line(&self) -> String {
+        match (&self.tap, &self.repo, &self.source) {
+            (Some(tap), _, _) => format!("tap:{}:{}", tap, self.name),
+            (None, Some(repo), _) => format!("{}/{}", repo, self.name),
+            (None, None, Some(source)) => format!("{}:{}", source, self.name),
+            (None, None, None) => self.name.clone(),
+        }
+    }
+
+    /// `base` restricted to this entry's source
+    pub fn install_options(&self, base: &InstallOptions) -> InstallOptions {
+        InstallOptions {
+            backend_order: self
+                .source
+                .clone()
+                .map(|s| vec![s])
+                .or_else(|| base.backend_order.clone()),
+            tap: self.tap.clone(),
+            source: self.forced_source(),
+            repo: self.repo.clone(),
+            ..base.clone()
+        }
+    }
+}
+
+/// Parse a package list file, one package per line
+pub fn parse_package_list(text: &str) -> Result<Vec<PackageListEntry>, String> {
+    let mut entries = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        match PackageListEntry::parse(line) {
+            Ok(Some(entry)) => entries.push(entry),
+            Ok(None) => {}
+            Err(e) => return Err(format!("line {}: {}", n + 1, e)),
+        }
+    }
+    Ok(entries)
+}
+
+/// Explicitly installed packages as package list entries, with the source
+/// reap recorded for tap packages
+pub fn installed_package_list() -> Vec<PackageListEntry> {
+    let registry = crate::history::InstallRegistry::load();
+    list_installed_packages()
+        .into_iter()
+        .filter(|pkg| pkg.explicit)
+        .map(|pkg| {
+            let record = registry.get(&pkg.name);
+            let (source, tap) = match (&pkg.source, record.map(|r| &r.source)) {
+                (_, Some(Source::Custom(tap))) => ("tap", Some(tap.clone())),
+                (Source::Flatpak, _) => ("flatpak", None),
+                (Source::Aur, _) => ("aur", None),
+                _ => ("pacman", None),
+            };
+            PackageListEntry {
+                name: pkg.name,
+                source: Some(source.to_string()),
+                tap,
+                repo: None,
+            }
+        })
+        .collect()
+}
+
+/// `reap list --installed`: explicitly installed packages as a package list
+/// that `reap -S --from-file` reinstalls
+pub fn handle_list_installed() {
+    println!(
+        "# reap package list, {}",
+        Local::now().format("%Y-%m-%d %H:%M")
+    );
+    for entry in installed_package_list() {
+        println!("{}", entry.to_line());
+    }
+}
+
+/// `reap -S --from-file <path>`: install every package in a package list
+/// that isn't installed yet, in parallel
+pub async fn handle_install_from_file(
+    path: &std::path::Path,
+    confirm: bool,
+    opts: &InstallOptions,
+) -> Result<BulkReport, ReapError> {
+    let text = fs::read_to_string(path)?;
+    let entries = parse_package_list(&text)
+        .map_err(|e| ReapError::CommandFailed(format!("{}: {}", path.display(), e)))?;
+    install_package_list(entries, confirm, opts).await
+}
+
+/// Install the `entries` that aren't installed yet, in parallel
+pub async fn install_package_list(
+    entries: Vec<PackageListEntry>,
+    confirm: bool,
+    opts: &InstallOptions,
+) -> Result<BulkReport, ReapError> {
+    let installed = get_installed_packages();
+    let (present, missing): (Vec<_>, Vec<_>) = entries
+        .into_iter()
+        .partition(|e| installed.contains_key(&e.name));
+    if !present.is_empty() {
+        println!(
+            "[reap] {} of the listed packages are already installed",
+            present.len()
+        );
+    }
+    if missing.is_empty() {
+        println!("[reap] Nothing to install");
+        return Ok(BulkReport::default());
+    }
+    println!("[reap] Installing {} packages:", missing.len());
+    for entry in &missing {
+        println!("    {}", entry.to_line());
+    }
+    if !confirm_proceed(confirm, "Proceed with installation?") {
+        return Err(ReapError::UserAborted);
+    }
+    let config = Arc::new(ReapConfig::load());
+    let log: Arc<dyn ProgressReporter> = Arc::new(StdoutReporter);
+    let options: HashMap<String, InstallOptions> = missing
+        .iter()
+        .map(|e| (e.name.clone(), e.install_options(opts)))
+        .collect();
+    let names: Vec<String> = missing.into_iter().map(|e| e.name).collect();
+    Ok(parallel_install_with(&names, config, log, options).await)
+}
+
+/// Print the provenance recorded for every package reap has installed
+pub fn handle_list_reap() {
+    let registry = crate::history::InstallRegistry::load();
+    if registry.packages.is_empty() {
+        println!(
+            "No packages recorded in {}",
+            crate::history::InstallRegistry::path().display()
+        );
+        return;
+    }
+    for (name, record) in &registry.packages {
+        let origin = match &record.tap {
+            Some(tap) => format!("{}{}", record.source.label(), tap),
+            None => record.source.label().to_string(),
+        };
+        let trust = record
+            .trust_score
+            .map(|s| format!("{:.1}", s))
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "{:<30} {:<20} {} trust {}",
+            name,
+            origin,
+            record.installed_at.format("%Y-%m-%d %H:%M"),
+            trust
+        );
+    }
+}
+
+/// The package a file belongs to, and where it came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileOwner {
+    pub package: String,
+    pub version: Option<String>,
+    /// Source label, e.g. `[AUR]`, `[CUSTOM]mytap` or `[FLATPAK]`
+    pub source: String,
+}
+
+/// Find the package owning `path`: pacman's database (labelled with the
+/// source reap recorded for it, or as AUR if it is foreign), then Flatpak
+/// installations, then packages reap built but never installed
+pub fn find_file_owner(path: &std::path::Path) -> Option<FileOwner> {
+    if let Some((package, version)) = pacman::owner(path) {
+        let registry = crate::history::InstallRegistry::load();
+        let source = match registry.get(&package) {
+            Some(record) => format!(
+                "{}{}",
+                record.source.label(),
+                record.tap.as_deref().unwrap_or("")
+            ),
+            None if pacman::list_installed_aur().contains(&package) => {
+                Source::Aur.label().to_string()
+            }
+            None => Source::Pacman.label().to_string(),
+        };
+        return Some(FileOwner {
+            package,
+            version: Some(version),
+            source,
+        });
+    }
+    if let Some(app) = flatpak::owner_of(path, &flatpak::installation_dirs()) {
+        return Some(FileOwner {
+            package: app,
+            version: None,
+            source: Source::Flatpak.label().to_string(),
+        });
+    }
+    let packages = fs::read_dir(package_output_dir()).ok()?;
+    let wanted = path.to_string_lossy();
+    packages
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|p| p.to_string_lossy().contains(".pkg.tar"))
+        .find(|p| {
+            pacman::package_file_list(p)
+                .iter()
+                .any(|f| f.trim_end_matches('/') == wanted)
+        })
+        .map(|p| FileOwner {
+            package: p
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            version: None,
+            // Built with --downloadonly, so pacman doesn't track it
+            source: "[BUILT]".to_string(),
+        })
+}
+
+/// `reap owns <path>` / `-Qo`: print the package owning `path`. Bare
+/// command names are looked up on `PATH`.
+pub fn handle_owns(path: &str) -> Result<(), ReapError> {
+    let given = PathBuf::from(path);
+    let resolved = if given.exists() {
+        // Flatpak exports are symlinks into the installation
+        fs::canonicalize(&given).unwrap_or(given)
+    } else if !path.contains('/')
+        && let Ok(found) = which::which(path)
+    {
+        found
+    } else {
+        return Err(ReapError::Io(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("no such file: {}", path),
+        )));
+    };
+    let owner = find_file_owner(&resolved).or_else(|| {
+        // pacman may own a symlink rather than its target
+        let given = std::path::Path::new(path);
+        (given.is_absolute() && given != resolved)
+            .then(|| find_file_owner(given))
+            .flatten()
+    });
+    match owner {
+        Some(owner) => {
+            let version = owner.version.map(|v| format!(" {}", v)).unwrap_or_default();
+            println!(
+                "{} is owned by {}{} {}",
+                resolved.display(),
+                owner.package,
+                version,
+                owner.source
+            );
+            Ok(())
+        }
+        None => Err(ReapError::SourceNotFound(format!(
+            "The owner of {}",
+            resolved.display()
+        ))),
+    }
+}
+
+/// A differently-named package that satisfies a request through `provides`
+#[derive(Debug, Clone)]
+pub struct Provider {
+    pub name: String,
+    pub source: Source,
+    pub tap: Option<Tap>,
+}
+
+/// Whether a `provides` entry such as `ffmpeg` or `ffmpeg=6.1` satisfies `name`
+pub fn provides_matches(entry: &str, name: &str) -> bool {
+    entry.split(['<', '>', '=']).next().unwrap_or(entry).trim() == name
+}
+
+/// Collect providers of `name` from taps, the repos and the AUR, in that order
+pub fn find_providers(name: &str, config: &GlobalConfig) -> Vec<Provider> {
+    let mut providers: Vec<Provider> = Vec::new();
+    if config.backend_order.contains(&"tap".to_string()) {
+        providers.extend(
+            find_tap_providers(name)
+                .into_iter()
+                .map(|(pkg, tap)| Provider {
+                    name: pkg,
+                    source: Source::Custom(tap.name.clone()),
+                    tap: Some(tap),
+                }),
+        );
+    }
+    if config.backend_order.contains(&"pacman".to_string())
+        && let Ok(out) = Command::new("pacman")
+            .args(["-Sddp", "--print-format", "%n", "--noconfirm", name])
+            .output()
+        && out.status.success()
+    {
+        for line in String::from_utf8_lossy(&out.stdout).lines() {
+            let line = line.trim();
+            if !line.is_empty() && line != name {
+                providers.push(Provider {
+                    name: line.to_string(),
+                    source: Source::Pacman,
+                    tap: None,
+                });
+            }
+        }
+    }
+    if config.backend_order.contains(&"aur".to_string()) {
+        for result in aur::aur_providers(name) {
+            if result.name != name {
+                providers.push(Provider {
+                    name: result.name,
+                    source: Source::Aur,
+                    tap: None,
+                });
+            }
+        }
+    }
+    providers
+}
+
+/// Pick one provider, prompting when there is more than one
+pub fn choose_provider(name: &str, mut providers: Vec<Provider>) -> Option<Provider> {
+    match providers.len() {
+        0 => None,
+        1 => providers.pop(),
+        _ => {
+            let items: Vec<String> = providers
+                .iter()
+                .map(|p| format!("{} {}", p.source.label(), p.name))
+                .collect();
+            let prompt = format!(
+                ":: There are {} providers available for {}:",
+                items.len(),
+                name
+            );
+            crate::interactive::InteractiveManager::new()
+                .select_from_list(&items, &prompt)
+                .map(|i| providers.swap_remove(i))
+        }
+    }
+}
+
+/// If nothing is named exactly `pkg` in the taps, repos or AUR, the
+/// packages that provide it instead, in backend order. Never prompts; the
+/// install path lets the user pick with [`choose_provider`].
+pub fn virtual_providers(pkg: &str, config: &GlobalConfig) -> Vec<Provider> {
+    let taps = discover_taps();
+    if find_tap_for_pkg(pkg, &taps, None).is_some()
+        || pacman::is_group(pkg)
+        || repo_has_package(pkg, "core")
+        || repo_has_package(pkg, "extra")
+        || aur::aur_search_results(pkg).iter().any(|r| r.name == pkg)
+    {
+        return Vec::new();
+    }
+    find_providers(pkg, config)
+}
+
+/// Source, tap name, priority and tap a package resolves to, and the package
+/// providing it when the name only comes from a `provides`
+pub type ResolvedSource = (Source, Option<String>, u32, Option<Tap>, Option<String>);
+
+/// Resolve the best source for a package, using tap, repo, AUR, providers, or flatpak, in priority order.
+/// A name that only comes from `provides` resolves to the first provider
+/// in backend order, without prompting.
 pub fn resolve_package_source(
     pkg: &str,
     forced_tap: Option<&str>,
     config: &GlobalConfig,
-) -> Option<(Source, Option<String>, u32, Option<Tap>)> {
+) -> Option<ResolvedSource> {
     let taps = discover_taps();
-    // 1. Taps (highest priority)
-    if let Some(tap) = find_tap_for_pkg(pkg, &taps, forced_tap) {
+    // 0. A source override from reap.toml, unless --tap picked one
+    if forced_tap.is_none()
+        && let Some(source) = config.source_override(pkg)
+    {
+        match source {
+            "aur" => return Some((Source::Aur, None, 10, None, None)),
+            "pacman" => return Some((Source::Pacman, None, 20, None, None)),
+            "flatpak" => return Some((Source::Flatpak, None, 1, None, None)),
+            // Tap builds aren't supported yet, so tap overrides can't be honored
+            _ => eprintln!(
+                "[reap] Ignoring source override {} for {}: {}",
+                source,
+                pkg,
+                crate::config::validate_source_override(source).unwrap_err()
+            ),
+        }
+    }
+    // 1. Taps (highest priority). A forced tap is used even if taps are
+    // left out of backend_order.
+    if (forced_tap.is_some() || config.backend_order.contains(&"tap".to_string()))
+        && let Some(tap) = find_tap_for_pkg(pkg, &taps, forced_tap)
+    {
         return Some((
             Source::Custom(tap.name.clone()),
             Some(tap.name.clone()),
             tap.priority,
             Some(tap),
+            None,
         ));
     }
+    // Package groups only exist in the repos, never resolve them to AUR
+    if pacman::is_group(pkg) {
+        return Some((Source::Pacman, None, 20, None, None));
+    }
     // 2. Pacman repo
     if config.backend_order.contains(&"pacman".to_string())
         && (repo_has_package(pkg, "core") || repo_has_package(pkg, "extra"))
     {
-        return Some((Source::Pacman, None, 20, None));
+        return Some((Source::Pacman, None, 20, None, None));
     }
     // 3. AUR
     if config.backend_order.contains(&"aur".to_string())
         && aur::aur_search_results(pkg).iter().any(|r| r.name == pkg)
     {
-        return Some((Source::Aur, None, 10, None));
+        return Some((Source::Aur, None, 10, None, None));
+    }
+    // 4. Packages that provide the name
+    if let Some(provider) = find_providers(pkg, config).into_iter().next() {
+        let prio = match provider.source {
+            Source::Aur => 10,
+            Source::Pacman => 20,
+            _ => provider.tap.as_ref().map(|t| t.priority).unwrap_or(1),
+        };
+        let tap_name = provider.tap.as_ref().map(|t| t.name.clone());
+        return Some((
+            provider.source,
+            tap_name,
+            prio,
+            provider.tap,
+            Some(provider.name),
+        ));
     }
-    // 4. Flatpak
+    // 5. Flatpak
     if config.backend_order.contains(&"flatpak".to_string()) {
         let output = std::process::Command::new("flatpak")
             .arg("search")
             .arg(pkg)
             .output();
-        if let Ok(out) = output {
-            if out.status.success() && !String::from_utf8_lossy(&out.stdout).trim().is_empty() {
-                return Some((Source::Flatpak, None, 1, None));
-            }
+        if let Ok(out) = output
+            && out.status.success()
+            && !String::from_utf8_lossy(&out.stdout).trim().is_empty()
+        {
+            return Some((Source::Flatpak, None, 1, None, None));
         }
     }
     None
 }
 
-/// Install a package using prioritized source resolution and log the decision.
-pub async fn install_with_priority(
-    pkg: &str,
-    _config: Arc<ReapConfig>,
-    _confirm: bool,
-    log: Arc<LogPane>,
-    opts: &InstallOptions,
-) {
-    use owo_colors::OwoColorize;
-    let start = Instant::now();
-    
-    // Print colorized header
-    println!("\n{} Installing package: {}", 
-        "📦".bright_blue(), 
-        pkg.bright_white().bold()
-    );
-    
-    let ctx = HookContext {
-        pkg: pkg.to_string(),
-        version: None,
-        source: None,
-        install_path: None,
-        tap: None,
-    };
-    
-    println!("{} Running pre-install hooks...", "🔧".bright_cyan());
-    log.push(&format!("{} pre_install executing for {}", "🔧".to_string(), pkg));
-    pre_install(&ctx);
-    
-    let global_config = GlobalConfig::load();
-    if let Some((source, tap_name, prio, tap_obj)) =
-        resolve_package_source(pkg, None, &global_config)
-    {
-        // Print source information with colors
-        match &source {
-            Source::Aur => println!("{} Source: {} (Priority: {})", 
-                "📍".bright_yellow(), 
-                "AUR".bright_magenta(), 
-                prio.to_string().bright_green()
-            ),
-            Source::Flatpak => println!("{} Source: {} (Priority: {})", 
-                "📍".bright_yellow(), 
-                "Flatpak".bright_blue(), 
-                prio.to_string().bright_green()
-            ),
-            Source::Pacman => println!("{} Source: {} (Priority: {})", 
-                "📍".bright_yellow(), 
-                "Pacman".bright_cyan(), 
-                prio.to_string().bright_green()
-            ),
-            Source::Custom(name) => println!("{} Source: {} {} (Priority: {})", 
-                "📍".bright_yellow(), 
-                "Tap".bright_purple(), 
-                name.bright_white(), 
-                prio.to_string().bright_green()
-            ),
-            _ => println!("{} Source: {} (Priority: {})", 
-                "📍".bright_yellow(), 
-                format!("{:?}", source).bright_white(), 
-                prio.to_string().bright_green()
-            ),
+/// One source that can install a package, offered by `--select-source`
+#[derive(Debug, Clone)]
+pub struct SourceCandidate {
+    pub source: Source,
+    pub tap: Option<Tap>,
+    pub version: Option<String>,
+}
+
+impl SourceCandidate {
+    /// How the choice is stored in `source_choices`: `aur`, `pacman` or `tap:<name>`
+    pub fn key(&self) -> String {
+        match &self.tap {
+            Some(tap) => format!("tap:{}", tap.name),
+            None if self.source == Source::Aur => "aur".to_string(),
+            None => "pacman".to_string(),
         }
-        
-        // Prepare hook context
-        let ctx = HookContext {
-            pkg: pkg.to_string(),
-            version: None,
-            source: Some(format!("{:?}", source)),
-            install_path: None,
-            tap: tap_name.clone(),
+    }
+
+    /// The `(source, tap name, priority, tap)` that `resolve_package_source` returns
+    pub fn resolved(self) -> (Source, Option<String>, u32, Option<Tap>) {
+        let prio = match (&self.tap, &self.source) {
+            (Some(tap), _) => tap.priority,
+            (None, Source::Aur) => 10,
+            (None, _) => 20,
         };
-        log.push(&format!(
+        (
+            self.source,
+            self.tap.as_ref().map(|t| t.name.clone()),
+            prio,
+            self.tap,
+        )
+    }
+}
+
+/// Every tap, repo and AUR source in `backend_order` with a package named
+/// exactly `pkg`, in priority order, with the version each would install
+pub fn source_candidates(pkg: &str, config: &GlobalConfig) -> Vec<SourceCandidate> {
+    let mut candidates = Vec::new();
+    if config.backend_order.contains(&"tap".to_string()) {
+        for tap in discover_taps()
+            .into_iter()
+            .filter(|t| t.enabled && crate::tap::tap_has_package(t, pkg))
+        {
+            let pkgbuild = crate::tap::ensure_tap_cloned(&tap)
+                .join(pkg)
+                .join("PKGBUILD");
+            let version = fs::read_to_string(pkgbuild)
+                .ok()
+                .and_then(|content| utils::parse_pkgname_ver(&content))
+                .map(|(_, ver)| ver);
+            candidates.push(SourceCandidate {
+                source: Source::Custom(tap.name.clone()),
+                tap: Some(tap),
+                version,
+            });
+        }
+    }
+    if config.backend_order.contains(&"pacman".to_string())
+        && let Ok(out) = Command::new("pacman").arg("-Si").arg(pkg).output()
+        && out.status.success()
+    {
+        let version = String::from_utf8_lossy(&out.stdout)
+            .lines()
+            .find(|l| l.starts_with("Version"))
+            .and_then(|l| l.split_once(':'))
+            .map(|(_, v)| v.trim().to_string());
+        candidates.push(SourceCandidate {
+            source: Source::Pacman,
+            tap: None,
+            version,
+        });
+    }
+    if config.backend_order.contains(&"aur".to_string())
+        && let Ok(info) = aur::fetch_package_info(pkg)
+    {
+        candidates.push(SourceCandidate {
+            source: Source::Aur,
+            tap: None,
+            version: Some(info.version),
+        });
+    }
+    candidates
+}
+
+/// How `reap resolve` names a source: `aur`, `pacman`, `flatpak`, `tap:<name>`, ...
+pub fn source_key(source: &Source, tap: Option<&str>) -> String {
+    match (tap, source) {
+        (Some(tap), _) => format!("tap:{}", tap),
+        (None, Source::Custom(tap)) => format!("tap:{}", tap),
+        _ => source.label().trim_matches(['[', ']']).to_lowercase(),
+    }
+}
+
+/// One source `reap resolve` considered
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolveCandidate {
+    pub source: String,
+    pub tap: Option<String>,
+    pub priority: u32,
+    pub version: Option<String>,
+}
+
+/// What `resolve_package_source` decided for a package, for `reap resolve`
+#[derive(Debug, Clone, Serialize)]
+pub struct Resolution {
+    pub package: String,
+    /// The chosen source, `None` when nothing provides the package
+    pub source: Option<String>,
+    pub priority: Option<u32>,
+    pub tap: Option<String>,
+    /// The package installed instead when the name comes from its `provides`
+    pub provider: Option<String>,
+    /// Sources with a package of exactly this name, in priority order
+    pub candidates: Vec<ResolveCandidate>,
+}
+
+impl Resolution {
+    pub fn collect(pkg: &str, config: &GlobalConfig) -> Self {
+        let chosen = resolve_package_source(pkg, None, config);
+        let candidates = source_candidates(pkg, config)
+            .into_iter()
+            .map(|candidate| {
+                let version = candidate.version.clone();
+                let (source, tap, priority, _) = candidate.resolved();
+                ResolveCandidate {
+                    source: source_key(&source, tap.as_deref()),
+                    tap,
+                    priority,
+                    version,
+                }
+            })
+            .collect();
+        let (source, priority, tap, provider) = match chosen {
+            Some((source, tap, priority, _, provider)) => (
+                Some(source_key(&source, tap.as_deref())),
+                Some(priority),
+                tap,
+                provider,
+            ),
+            None => (None, None, None, None),
+        };
+        Resolution {
+            package: pkg.to_string(),
+            source,
+            priority,
+            tap,
+            provider,
+            candidates,
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = match (&self.source, &self.provider) {
+            (None, _) => format!("{}: not found in any source\n", self.package),
+            (Some(source), Some(provider)) => format!(
+                "{}: {} (provided by {}, priority {})\n",
+                self.package,
+                source,
+                provider,
+                self.priority.unwrap_or_default()
+            ),
+            (Some(source), None) => format!(
+                "{}: {} (priority {})\n",
+                self.package,
+                source,
+                self.priority.unwrap_or_default()
+            ),
+        };
+        if !self.candidates.is_empty() {
+            out.push_str("Candidates:\n");
+        }
+        for candidate in &self.candidates {
+            out.push_str(&format!(
+                "  {:<20} {:<16} priority {}\n",
+                candidate.source,
+                candidate.version.as_deref().unwrap_or("?"),
+                candidate.priority
+            ));
+        }
+        out.trim_end().to_string()
+    }
+}
+
+/// `reap resolve`: print the source a package would be installed from
+pub async fn handle_resolve(pkg: &str, json: bool) -> Result<(), ReapError> {
+    let pkg = pkg.to_string();
+    let resolution =
+        tokio::task::spawn_blocking(move || Resolution::collect(&pkg, &GlobalConfig::load()))
+            .await
+            .map_err(|e| ReapError::CommandFailed(e.to_string()))?;
+    if json {
+        let out = serde_json::to_string_pretty(&resolution)
+            .map_err(|e| ReapError::CommandFailed(e.to_string()))?;
+        println!("{}", out);
+    } else {
+        println!("{}", resolution.render());
+    }
+    if resolution.source.is_none() {
+        return Err(ReapError::SourceNotFound(resolution.package));
+    }
+    Ok(())
+}
+
+/// The source to install `pkg` from when it is in more than one: the one
+/// remembered in `source_choices`, or with `select` the one picked from a
+/// prompt showing each candidate's version and trust (then remembered).
+/// `None` leaves the choice to `resolve_package_source`.
+async fn select_package_source(
+    pkg: &str,
+    config: &GlobalConfig,
+    select: bool,
+    log: &dyn ProgressReporter,
+) -> Option<(Source, Option<String>, u32, Option<Tap>)> {
+    // Overrides are applied by resolve_package_source
+    if config.source_override(pkg).is_some() {
+        return None;
+    }
+    let remembered = config
+        .source_choices
+        .as_ref()
+        .and_then(|choices| choices.get(pkg))
+        .cloned();
+    if remembered.is_none() && !select {
+        return None;
+    }
+    // Packages can't be built from taps yet, so they aren't offered
+    let candidates: Vec<SourceCandidate> = {
+        let (name, cfg) = (pkg.to_string(), config.clone());
+        tokio::task::spawn_blocking(move || source_candidates(&name, &cfg))
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|candidate| candidate.tap.is_none())
+            .collect()
+    };
+    if let Some(choice) = &remembered {
+        if let Some(candidate) = candidates.iter().find(|c| &c.key() == choice) {
+            log.message(&format!(
+                "[reap] Using remembered source {} for {}",
+                choice, pkg
+            ));
+            return Some(candidate.clone().resolved());
+        }
+        log.message(&format!(
+            "[reap] Remembered source {} no longer has {}, using the default",
+            choice, pkg
+        ));
+    }
+    if !select || candidates.len() < 2 || !std::io::IsTerminal::is_terminal(&std::io::stdin()) {
+        return None;
+    }
+    let trust_engine = TrustEngine::new();
+    let mut items = Vec::new();
+    for candidate in &candidates {
+        let trust = trust_engine
+            .evaluate_trust_score(pkg, &candidate.source)
+            .await;
+        items.push(format!(
+            "{:<20} {:<16} {}",
+            candidate.key(),
+            candidate.version.as_deref().unwrap_or("?"),
+            trust_engine.trust_summary(&trust)
+        ));
+    }
+    let prompt = format!(":: {} is available from {} sources:", pkg, items.len());
+    let index = crate::interactive::InteractiveManager::new().select_from_list(&items, &prompt)?;
+    let candidate = candidates.into_iter().nth(index)?;
+    match crate::config::remember_source_choice(pkg, &candidate.key()) {
+        Ok(()) => log.message(&format!(
+            "[reap] Remembering {} for {} (source_choices in reap.toml)",
+            candidate.key(),
+            pkg
+        )),
+        Err(e) => log.message(&format!("[reap] Failed to remember the source: {}", e)),
+    }
+    Some(candidate.resolved())
+}
+
+/// Receives progress from install steps, so core logic can be driven
+/// without a TUI (from the CLI, a GUI, or a library consumer).
+pub trait ProgressReporter: Send + Sync {
+    /// Report progress for a named step such as `fetch`, `build` or `install`
+    fn step(&self, step: &str, msg: &str);
+    /// Report a free-form status line
+    fn message(&self, msg: &str) {
+        self.step("info", msg);
+    }
+    /// Ask the user to answer a prompt of a running build, `None` when
+    /// nobody can
+    fn prompt(&self, _step: &str, _question: &str) -> Option<String> {
+        None
+    }
+}
+
+/// Reporter that prints colorized progress to stdout
+pub struct StdoutReporter;
+
+impl ProgressReporter for StdoutReporter {
+    fn step(&self, step: &str, msg: &str) {
+        use colored::Colorize;
+        match step {
+            "fetch" => println!("{} {}", "📥".bright_blue(), msg.bright_white()),
+            "build" => println!("{} {}", "🔨".bright_yellow(), msg.bright_white()),
+            "install" => println!("{} {}", "📦".bright_green(), msg.bright_white()),
+            "deps" => println!("{} {}", "🔗".bright_cyan(), msg.bright_white()),
+            "error" => println!("{} {}", "❌".bright_red(), msg.bright_red()),
+            "success" => println!("{} {}", "✅".bright_green(), msg.bright_green()),
+            _ => println!("{} {}", "ℹ️".bright_blue(), msg.bright_white()),
+        }
+    }
+    fn message(&self, msg: &str) {
+        println!("{}", msg);
+    }
+    fn prompt(&self, _step: &str, question: &str) -> Option<String> {
+        use colored::Colorize;
+        use std::io::{IsTerminal, Write};
+        if !std::io::stdin().is_terminal() {
+            return None;
+        }
+        print!("{} {} ", "⌨️".bright_yellow(), question.bright_white());
+        let _ = std::io::stdout().flush();
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer).ok()?;
+        Some(answer.trim_end_matches(['\r', '\n']).to_string())
+    }
+}
+
+/// Whether to prompt before installs and removals. `--confirm` forces
+/// prompting; otherwise `--noconfirm`/`--yes` or `noconfirm` in the config skip it.
+pub fn should_confirm(cli: &Cli) -> bool {
+    if cli.confirm {
+        true
+    } else if cli.noconfirm || cli.yes {
+        false
+    } else {
+        !GlobalConfig::load().noconfirm
+    }
+}
+
+/// Whether the command line will run pacman (or other tools) as root, so
+/// sudo credentials are worth acquiring up front
+pub fn needs_root(cli: &Cli) -> bool {
+    if cli.dry_run {
+        return false;
+    }
+    if cli.sync.is_some() {
+        return !(cli.sync_search || cli.print_srcinfo);
+    }
+    if cli.remove.is_some() || cli.local.is_some() {
+        return true;
+    }
+    matches!(
+        cli.command,
+        Some(
+            Commands::Install { .. }
+                | Commands::BatchInstall { .. }
+                | Commands::Remove { .. }
+                | Commands::Local { .. }
+                | Commands::Import { .. }
+                | Commands::Upgrade { json: false, .. }
+                | Commands::Sysupgrade { .. }
+                | Commands::ParallelUpgrade { .. }
+                | Commands::UpgradeAll
+                | Commands::Rollback { .. }
+                | Commands::SyncDb
+                | Commands::Mark { .. }
+                | Commands::Clean { .. }
+                | Commands::Orphan { remove: true, .. }
+        )
+    ) || matches!(cli.command, Some(Commands::Keyring { init, refresh }) if init || refresh)
+}
+
+/// Ask `Proceed? [Y/n]` style questions, or return true straight away when
+/// prompting is disabled
+pub fn confirm_proceed(confirm: bool, message: &str) -> bool {
+    !confirm || crate::interactive::InteractiveManager::confirm_action(message, true)
+}
+
+/// `name` without the usual AUR packaging suffixes and punctuation, in
+/// lowercase, for matching it against Flatpak app names
+fn app_key(name: &str) -> String {
+    let mut name = name.to_lowercase();
+    while let Some(stripped) = [
+        "-bin",
+        "-git",
+        "-appimage",
+        "-electron",
+        "-nightly",
+        "-beta",
+    ]
+    .iter()
+    .find_map(|suffix| name.strip_suffix(suffix))
+    {
+        name = stripped.to_string();
+    }
+    name.chars().filter(|c| c.is_alphanumeric()).collect()
+}
+
+/// The Flatpak app among `results` that is the same application as the AUR
+/// package `pkg`, matched on the last part of the app ID or the app's name
+/// (`visual-studio-code-bin` is `com.visualstudio.code`, "Visual Studio Code")
+pub fn flatpak_alternative(pkg: &str, results: &[aur::SearchResult]) -> Option<String> {
+    let key = app_key(pkg);
+    results
+        .iter()
+        .filter(|r| r.source == Source::Flatpak)
+        .find(|r| {
+            let id_name = r.name.rsplit('.').next().unwrap_or(&r.name);
+            // flatpak::search describes apps as "<name> - <summary>"
+            let app_name = r.description.split(" - ").next().unwrap_or("");
+            app_key(id_name) == key || app_key(app_name) == key
+        })
+        .map(|r| r.name.clone())
+}
+
+/// With `suggest_flatpak`, a hint that a package resolved to the AUR is also
+/// on Flatpak, or a Flatpak app also in the AUR. Purely informational.
+async fn alternative_source_hint(pkg: &str, source: &Source) -> Option<String> {
+    let name = pkg.to_string();
+    match source {
+        Source::Aur => {
+            let results = tokio::task::spawn_blocking(move || {
+                if flatpak::is_flatpak_available() {
+                    flatpak::search(&name)
+                } else {
+                    Vec::new()
+                }
+            })
+            .await
+            .ok()?;
+            let app = flatpak_alternative(pkg, &results)?;
+            Some(format!(
+                "{} is also available as Flatpak {} (skip the build with -S flatpak:{})",
+                pkg, app, app
+            ))
+        }
+        Source::Flatpak => {
+            let candidate = pkg.rsplit('.').next().unwrap_or(pkg).to_lowercase();
+            let query = candidate.clone();
+            tokio::task::spawn_blocking(move || aur::fetch_package_info(&query))
+                .await
+                .ok()?
+                .ok()?;
+            Some(format!(
+                "{} is also in the AUR as {} (build it natively with -S aur:{})",
+                pkg, candidate, candidate
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Install a package using prioritized source resolution and log the decision.
+/// The caller confirms the install itself; `confirm` lets this ask the
+/// choices along the way (providers, `--select-source`, optional
+/// dependencies).
+pub async fn install_with_priority(
+    pkg: &str,
+    config: Arc<ReapConfig>,
+    confirm: bool,
+    log: Arc<dyn ProgressReporter>,
+    opts: &InstallOptions,
+) -> Result<(), ReapError> {
+    use colored::Colorize;
+    let start = Instant::now();
+
+    let mut global_config = GlobalConfig::load();
+    if let Some(order) = &opts.backend_order {
+        global_config.backend_order = order.clone();
+    }
+    if let Some(auto_resolve_deps) = opts.auto_resolve_deps {
+        global_config.auto_resolve_deps = auto_resolve_deps;
+    }
+    // Virtual packages install whichever package provides them, asking
+    // which one when there are several (the first in backend order without
+    // confirmation). Resolution uses the blocking AUR client, keep it off
+    // the runtime threads.
+    let provider = {
+        let (name, cfg) = (pkg.to_string(), global_config.clone());
+        let providers = tokio::task::spawn_blocking(move || virtual_providers(&name, &cfg))
+            .await
+            .unwrap_or_default();
+        if confirm {
+            choose_provider(pkg, providers)
+        } else {
+            providers.into_iter().next()
+        }
+    };
+    if let Some(provider) = &provider {
+        log.message(&format!(
+            "[reap] {} is provided by {} {}",
+            pkg,
+            provider.source.label(),
+            provider.name
+        ));
+    }
+    let pkg = provider.as_ref().map(|p| p.name.as_str()).unwrap_or(pkg);
+
+    // Print colorized header
+    println!(
+        "\n{} Installing package: {}",
+        "📦".bright_blue(),
+        pkg.bright_white().bold()
+    );
+
+    let ctx = HookContext {
+        pkg: pkg.to_string(),
+        version: None,
+        source: None,
+        install_path: None,
+        tap: None,
+    };
+
+    println!("{} Running pre-install hooks...", "🔧".bright_cyan());
+    log.message(&format!("{} pre_install executing for {}", "🔧", pkg));
+    pre_install(&ctx);
+
+    let selected = match &opts.source {
+        Some(source) => {
+            let prio = match source {
+                Source::Aur => 10,
+                Source::Pacman => 20,
+                _ => 1,
+            };
+            Some((source.clone(), None, prio, None))
+        }
+        None => {
+            select_package_source(pkg, &global_config, opts.select_source && confirm, &*log).await
+        }
+    };
+    let resolved = match selected {
+        Some(resolved) => Some(resolved),
+        None => {
+            let (name, cfg) = (pkg.to_string(), global_config.clone());
+            let tap = opts.tap.clone();
+            tokio::task::spawn_blocking(move || resolve_package_source(&name, tap.as_deref(), &cfg))
+                .await
+                .ok()
+                .flatten()
+                .map(|(source, tap_name, prio, tap, _)| (source, tap_name, prio, tap))
+        }
+    };
+    let result = if let Some((source, tap_name, prio, tap_obj)) = resolved {
+        // Print source information with colors
+        match &source {
+            Source::Aur => println!(
+                "{} Source: {} (Priority: {})",
+                "📍".bright_yellow(),
+                "AUR".bright_magenta(),
+                prio.to_string().bright_green()
+            ),
+            Source::Flatpak => println!(
+                "{} Source: {} (Priority: {})",
+                "📍".bright_yellow(),
+                "Flatpak".bright_blue(),
+                prio.to_string().bright_green()
+            ),
+            Source::Pacman => println!(
+                "{} Source: {} (Priority: {})",
+                "📍".bright_yellow(),
+                "Pacman".bright_cyan(),
+                prio.to_string().bright_green()
+            ),
+            Source::Custom(name) => println!(
+                "{} Source: {} {} (Priority: {})",
+                "📍".bright_yellow(),
+                "Tap".bright_purple(),
+                name.bright_white(),
+                prio.to_string().bright_green()
+            ),
+            _ => println!(
+                "{} Source: {} (Priority: {})",
+                "📍".bright_yellow(),
+                format!("{:?}", source).bright_white(),
+                prio.to_string().bright_green()
+            ),
+        }
+
+        // Prepare hook context
+        let ctx = HookContext {
+            pkg: pkg.to_string(),
+            version: None,
+            source: Some(format!("{:?}", source)),
+            install_path: None,
+            tap: tap_name.clone(),
+        };
+        log.message(&format!(
             "[reap][priority] Resolved source for '{}': {}{} (priority {})",
             pkg,
             source.label(),
             tap_name.as_deref().unwrap_or(""),
             prio
         ));
-        match source {
-            Source::Custom(ref _tap_repo) => {
-                if let Some(tap) = tap_obj {
-                    let tap_path = crate::tap::ensure_tap_cloned(&tap);
-                    let pkg_dir = tap_path.join(pkg);
-                    let pkgb_path = pkg_dir.join("PKGBUILD");
-                    let sig_path = pkg_dir.join("PKGBUILD.sig");
-                    let pubinfo = crate::tap::get_publisher_info(&tap);
-                    if let Some(pubinfo) = pubinfo {
-                        let keyid = pubinfo.gpg_key.split_whitespace().last().unwrap_or("");
-                        let verified_str = if pubinfo.verified {
-                            "[✓ Verified GPG]".green().to_string()
-                        } else {
-                            "[Unverified]".yellow().to_string()
-                        };
-                        log.push(&format!(
-                            "👤 {} from {} {}",
-                            tap.name.bold(),
-                            pubinfo.name,
-                            verified_str
-                        ));
-                        log.push(&format!("🔑 GPG Key: {}", keyid));
-                        // Check if key is in keyring
-                        let key_present = std::process::Command::new("gpg")
-                            .args(["--list-keys", keyid])
-                            .output()
-                            .map(|o| o.status.success())
-                            .unwrap_or(false);
-                        if !key_present {
-                            let keyserver = opts
-                                .gpg_keyserver
-                                .as_deref()
-                                .unwrap_or("hkps://keys.openpgp.org");
-                            log.push(&format!(
-                                "[reap][gpg] Importing publisher key {} from {}...",
-                                keyid, keyserver
-                            ));
-                            let fetch = std::process::Command::new("gpg")
-                                .args(["--keyserver", keyserver, "--recv-keys", keyid])
-                                .status();
-                            match fetch {
-                                Ok(s) if s.success() => log.push(&format!(
-                                    "[reap][gpg] {} Successfully imported {}",
-                                    "✓".green(),
-                                    keyid
-                                )),
-                                Ok(_) | Err(_) => log.push(&format!(
-                                    "[reap][gpg] {} Failed to import publisher key {}",
-                                    "❌".red(),
-                                    keyid
-                                )),
-                            }
-                        }
-                        // Verify PKGBUILD.sig
-                        if sig_path.exists() && pkgb_path.exists() {
-                            let verify = std::process::Command::new("gpg")
-                                .arg("--verify")
-                                .arg(&sig_path)
-                                .arg(&pkgb_path)
-                                .status();
-                            if let Ok(s) = verify {
-                                if s.success() {
-                                    log.push(&format!(
-                                        "{} PKGBUILD signature verified",
-                                        "✓".green()
-                                    ));
-                                } else {
-                                    log.push(&format!(
-                                        "{} Verification failed for PKGBUILD.sig (key: {})",
-                                        "❌".red(),
-                                        keyid
-                                    ));
-                                    if !opts.insecure {
-                                        log.push(&format!(
-                                            "{} Aborting install. Use --insecure to override.",
-                                            "✋".red()
-                                        ));
-                                        return;
-                                    } else {
-                                        log.push(&format!(
-                                            "{} Continuing install due to --insecure.",
-                                            "⚠️".yellow()
-                                        ));
-                                    }
-                                }
-                            } else {
-                                log.push(&format!(
-                                    "{} Verification failed for PKGBUILD.sig (key: {})",
-                                    "❌".red(),
-                                    keyid
-                                ));
-                                if !opts.insecure {
-                                    log.push(&format!(
-                                        "{} Aborting install. Use --insecure to override.",
-                                        "✋".red()
-                                    ));
-                                    return;
-                                } else {
-                                    log.push(&format!(
-                                        "{} Continuing install due to --insecure.",
-                                        "⚠️".yellow()
-                                    ));
-                                }
-                            }
-                        } else {
-                            log.push(&format!("{} PKGBUILD.sig missing. Aborting install. Use --insecure to override.", "❌".red()));
-                            if !opts.insecure {
-                                return;
-                            } else {
-                                log.push(&format!(
-                                    "{} Continuing install due to --insecure.",
-                                    "⚠️".yellow()
-                                ));
-                            }
-                        }
-                    } else {
-                        log.push(&format!(
-                            "{} Warning: Tap publisher not verified. Installing with --insecure.",
-                            "⚠️".yellow()
-                        ));
-                        if !opts.insecure {
-                            return;
-                        }
-                    }
+        if global_config.suggest_flatpak.unwrap_or(false)
+            && let Some(hint) = alternative_source_hint(pkg, &source).await
+        {
+            log.message(&format!("{} {}", "💡".bright_yellow(), hint));
+        }
+        let installed = match source {
+            Source::Custom(ref tap_repo) => {
+                if let Some(tap) = tap_obj
+                    && let Err(e) = verify_tap_pkgbuild(pkg, &tap, log.as_ref(), opts)
+                {
+                    log.message(&format!("{} {}", "✋".red(), e.to_string().red()));
+                    return Err(e);
                 }
                 // ...proceed with install if verified or --insecure...
+                Err(ReapError::CommandFailed(format!(
+                    "building {} from tap {} is not supported yet",
+                    pkg, tap_repo
+                )))
+            }
+            Source::Pacman if opts.download_only => {
+                let dir = opts.output_dir();
+                let _ = fs::create_dir_all(&dir);
+                if pacman::download(&repo_target(pkg, opts), &dir) {
+                    log.message(&format!("[✓] Downloaded {} to {}", pkg, dir.display()));
+                    Ok(())
+                } else {
+                    Err(ReapError::CommandFailed(format!(
+                        "pacman -Sw {} failed",
+                        pkg
+                    )))
+                }
             }
             Source::Pacman => {
-                log.push(&format!("[reap][pacman] Installing {} from repo", pkg));
-                pacman::install(pkg);
-                log.push(&format!("[✓] Installed {} from Pacman", pkg));
+                log.message(&format!("[reap][pacman] Installing {} from repo", pkg));
+                pacman::install(&repo_target(pkg, opts));
+                if pacman::is_installed(pkg) {
+                    log.message(&format!("[✓] Installed {} from Pacman", pkg));
+                    Ok(())
+                } else {
+                    Err(ReapError::CommandFailed(format!(
+                        "pacman -S {} failed",
+                        pkg
+                    )))
+                }
             }
             Source::Aur => {
-                println!("{} Building {} from AUR source...", "🔨".bright_yellow(), pkg.bright_white());
-                log.push(&format!("[reap][aur] Installing {} from AUR", pkg));
-                let opts = InstallOptions {
-                    insecure: false,
-                    gpg_keyserver: None,
-                    fast_mode: false,
-                    strict_signatures: false,
-                    max_parallel: 4,
+                println!(
+                    "{} Building {} from AUR source...",
+                    "🔨".bright_yellow(),
+                    pkg.bright_white()
+                );
+                log.message(&format!("[reap][aur] Installing {} from AUR", pkg));
+                let opts = aur_build_options(opts);
+                let deps = if global_config.auto_resolve_deps {
+                    install_aur_dependencies(pkg, Arc::clone(&config), Arc::clone(&log), &opts)
+                        .await
+                } else {
+                    Ok(())
+                };
+                if let Err(e) = &deps {
+                    log.message(&format!("[reap][deps] {}", e.to_string().red()));
+                }
+                let res = match deps {
+                    Ok(()) => install_aur_native(pkg, log.as_ref(), &opts).await,
+                    Err(e) => Err(e),
                 };
-                let _ = install_aur_native(pkg, &log, &opts).await;
-                println!("{} Successfully installed {} from AUR!", "✅".bright_green(), pkg.bright_white().bold());
-                log.push(&format!("[✓] Installed {} from AUR", pkg));
+                if res.is_ok() && opts.download_only {
+                    println!(
+                        "{} Built {} into {}",
+                        "📦".bright_green(),
+                        pkg.bright_white().bold(),
+                        opts.output_dir().display()
+                    );
+                } else if res.is_ok() {
+                    println!(
+                        "{} Successfully installed {} from AUR!",
+                        "✅".bright_green(),
+                        pkg.bright_white().bold()
+                    );
+                    log.message(&format!("[✓] Installed {} from AUR", pkg));
+                    let mode = OptDependsMode::from_config(
+                        opts.with_optdepends,
+                        global_config.install_optdepends.as_deref(),
+                    );
+                    if mode != OptDependsMode::None {
+                        install_optdepends(pkg, mode, confirm, config, Arc::clone(&log), &opts)
+                            .await;
+                    }
+                }
+                res
             }
+            Source::Flatpak if opts.download_only => Err(ReapError::CommandFailed(
+                "--downloadonly is not supported for Flatpak".to_string(),
+            )),
             Source::Flatpak => {
-                log.push(&format!("[reap][flatpak] Installing {} from Flatpak", pkg));
-                let _ = flatpak::install_flatpak(pkg).await;
+                log.message(&format!("[reap][flatpak] Installing {} from Flatpak", pkg));
+                flatpak::install_flatpak(pkg)
+                    .await
+                    .map_err(|e| ReapError::CommandFailed(e.to_string()))
+            }
+            _ => {
+                log.message(&format!("[!] Unknown source for {}", pkg));
+                Err(ReapError::SourceNotFound(pkg.to_string()))
+            }
+        };
+        if installed.is_ok()
+            && !opts.download_only
+            && opts.as_explicit
+            && matches!(source, Source::Pacman | Source::Aur)
+        {
+            if pacman::mark(pkg, true) {
+                log.message(&format!("[reap] Marked {} as explicitly installed", pkg));
+            } else {
+                log.message(&format!(
+                    "[reap] Failed to mark {} as explicitly installed",
+                    pkg
+                ));
+            }
+        }
+        if installed.is_ok() && !opts.download_only {
+            let trust_score = TrustEngine::new()
+                .compute_trust_score(pkg, &source)
+                .await
+                .overall_score;
+            let record = crate::history::InstallRecord {
+                source: source.clone(),
+                tap: tap_name.clone(),
+                installed_at: chrono::Utc::now(),
+                trust_score: Some(trust_score),
+            };
+            if let Err(e) = crate::history::record_install(pkg, record) {
+                log.message(&format!("[reap][history] Failed to record {}: {}", pkg, e));
             }
-            _ => log.push(&format!("[!] Unknown source for {}", pkg)),
         }
         println!("{} Running post-install hooks...", "🔧".bright_cyan());
-        log.push(&format!("[reap][hook] post_install executing for {}", pkg));
+        log.message(&format!("[reap][hook] post_install executing for {}", pkg));
         post_install(&ctx);
-        
+
         let elapsed = start.elapsed();
-        println!("\n{} Installation completed in {:.2}s", 
-            "⏱️".bright_blue(), 
+        println!(
+            "\n{} Installation completed in {:.2}s",
+            "⏱️".bright_blue(),
             elapsed.as_secs_f64().to_string().bright_green()
         );
-        log.push(&format!(
+        log.message(&format!(
             "[reap][timing] install_with_priority for {} took: {:?}",
             pkg, elapsed
         ));
+        installed
     } else {
-        println!("{} Could not resolve source for {}", 
-            "❌".bright_red(), 
+        println!(
+            "{} Could not resolve source for {}",
+            "❌".bright_red(),
             pkg.bright_white()
         );
-        log.push(&format!(
+        log.message(&format!(
             "[reap][error] Could not resolve source for {}",
             pkg
         ));
         crate::utils::rollback(pkg);
-    }
+        Err(ReapError::SourceNotFound(pkg.to_string()))
+    };
     // Backup before install
     if let Ok(backup_path) = backup_package_state(pkg) {
-        log.push(&format!(
+        log.message(&format!(
             "[reap][backup] State backed up to {}",
             backup_path.display()
         ));
     }
-    // Show PKGBUILD diff before install
-    show_pkgbuild_diff(pkg);
+    result
 }
 
-pub async fn unified_search(query: &str) -> Vec<aur::SearchResult> {
-    use crate::tap::search_tap_indexes;
-    let mut tap_results = Vec::new();
-    // Remove unused variable: self
-    for (name, desc, repo, _source) in search_tap_indexes(query) {
-        tap_results.push(aur::SearchResult {
-            name,
-            version: String::new(),
-            description: desc,
-            source: Source::Custom(repo),
-        });
+/// `pkg` qualified with the sync repo picked for it (`extra/foo`), if any
+fn repo_target(pkg: &str, opts: &InstallOptions) -> String {
+    match &opts.repo {
+        Some(repo) => format!("{}/{}", repo, pkg),
+        None => pkg.to_string(),
     }
-    let aur_fut = async { aur::search(query).await.unwrap_or_else(|_| vec![]) };
-    let flatpak_fut = async { flatpak::search(query) };
-    let (aur, flatpak): (Vec<aur::SearchResult>, Vec<aur::SearchResult>) =
-        tokio::join!(aur_fut, flatpak_fut);
-    // Deduplicate by name, favoring tap > aur > flatpak
-    let mut seen = std::collections::HashSet::new();
-    let mut results = Vec::new();
-    for r in tap_results.into_iter().chain(aur).chain(flatpak) {
-        if seen.insert(r.name.clone()) {
-            results.push(r);
-        }
+}
+
+/// Search every backend, tap > pacman > aur > flatpak. With `dedup`,
+/// results are merged by `dedup_search_results`.
+pub async fn unified_search(query: &str, dedup: bool) -> Vec<aur::SearchResult> {
+    let backends = ["tap", "pacman", "aur", "flatpak"].map(String::from);
+    let results = search_backends(query, &backends).await;
+    if dedup {
+        dedup_search_results(results)
+    } else {
+        results
     }
-    results
 }
 
-pub fn print_search_results(results: &[aur::SearchResult]) {
-    use owo_colors::OwoColorize;
+/// Drop a result when one earlier in priority order has the same name and
+/// either the same source or the same version. Packages of the same name
+/// that differ in version (e.g. `extra/foo` and a newer AUR `foo`) are kept;
+/// results without a version (tap indexes) only merge with their own source.
+pub fn dedup_search_results(results: Vec<aur::SearchResult>) -> Vec<aur::SearchResult> {
+    let mut kept: Vec<aur::SearchResult> = Vec::new();
     for r in results {
-        let tag = match &r.source {
-            Source::Custom(tap) => format!("[tap:{}]", tap).yellow().to_string(),
-            Source::Aur => "[aur]".blue().to_string(),
-            Source::Flatpak => "[flatpak]".green().to_string(),
-            Source::Pacman => "[pacman]".magenta().to_string(),
-            _ => format!("[{}]", r.source.label()),
-        };
-        println!("{:<20} ▸ {:<40} {}", r.name.bold(), r.description, tag);
+        let duplicate = kept.iter().any(|k| {
+            k.name == r.name
+                && (k.source == r.source || (!r.version.is_empty() && k.version == r.version))
+        });
+        if !duplicate {
+            kept.push(r);
+        }
     }
+    kept
 }
 
-// === Bulk Install Logic ===
-pub async fn parallel_install(pkgs: &[String], config: Arc<ReapConfig>, log: Arc<LogPane>) {
-    let max_parallel = 4; // or config.parallel
-    let semaphore = Arc::new(Semaphore::new(max_parallel));
-    let mut tasks = Vec::new();
-    for pkg in pkgs {
-        let sem = Arc::clone(&semaphore);
-        let pkg = pkg.clone();
-        let config = Arc::clone(&config);
-        let log = Arc::clone(&log);
-        let permit_fut = sem.acquire_owned();
-        tasks.push(tokio::spawn(async move {
-            let _permit = permit_fut.await.unwrap();
-            install_with_priority(&pkg, config, true, log, &InstallOptions::default()).await;
-        }));
+/// Search results from every backend in `backends`, tap > pacman > aur > flatpak
+async fn search_backends(query: &str, backends: &[String]) -> Vec<aur::SearchResult> {
+    use crate::tap::search_tap_indexes;
+    let has = |backend: &str| backends.iter().any(|b| b == backend);
+    let mut results = Vec::new();
+    if has("tap") {
+        for (name, desc, repo, _source) in search_tap_indexes(query) {
+            results.push(aur::SearchResult {
+                name,
+                version: String::new(),
+                description: desc,
+                source: Source::Custom(repo),
+                votes: None,
+            });
+        }
     }
-    let _ = join_all(tasks).await;
+    if has("pacman") {
+        results.extend(pacman::search(query));
+    }
+    let aur_fut = async {
+        if has("aur") {
+            aur::search(query).await.unwrap_or_else(|_| vec![])
+        } else {
+            vec![]
+        }
+    };
+    let flatpak_fut = async {
+        if has("flatpak") {
+            flatpak::search(query)
+        } else {
+            vec![]
+        }
+    };
+    let (aur, flatpak): (Vec<aur::SearchResult>, Vec<aur::SearchResult>) =
+        tokio::join!(aur_fut, flatpak_fut);
+    results.extend(aur);
+    results.extend(flatpak);
+    results
 }
 
-pub async fn parallel_upgrade(pkgs: &[String], config: Arc<ReapConfig>, log: Arc<LogPane>) {
-    let mut tasks = Vec::new();
-    for pkg in pkgs {
-        let config = Arc::clone(&config);
-        let log = Arc::clone(&log);
-        let pkg = pkg.clone();
-        tasks.push(tokio::spawn(async move {
-            install_with_priority(&pkg, config, true, log, &InstallOptions::default()).await;
-        }));
+/// One package name with every source that has it
+#[derive(Debug, Clone)]
+pub struct SearchGroup {
+    pub name: String,
+    pub description: String,
+    pub results: Vec<aur::SearchResult>,
+}
+
+/// Group search results by package, keeping the order names first appear
+/// in. Flatpak app ids are matched by their last component, so
+/// `org.mozilla.firefox` groups with `firefox`.
+pub fn group_search_results(results: Vec<aur::SearchResult>) -> Vec<SearchGroup> {
+    let mut groups: Vec<SearchGroup> = Vec::new();
+    for result in results {
+        let name = match result.source {
+            Source::Flatpak => result
+                .name
+                .rsplit('.')
+                .next()
+                .unwrap_or(&result.name)
+                .to_lowercase(),
+            _ => result.name.clone(),
+        };
+        match groups.iter_mut().find(|g| g.name == name) {
+            Some(group) => {
+                if group.description.is_empty() {
+                    group.description = result.description.clone();
+                }
+                group.results.push(result);
+            }
+            None => groups.push(SearchGroup {
+                name,
+                description: result.description.clone(),
+                results: vec![result],
+            }),
+        }
     }
-    let _ = join_all(tasks).await;
-    log.push("[reap] All upgrades complete.");
+    groups
+}
+
+/// Print grouped results with a ✓/✗ for every configured backend, paged
+/// when they don't fit the terminal
+pub fn print_search_groups(groups: &[SearchGroup], backends: &[String]) {
+    use colored::Colorize;
+    use std::fmt::Write;
+    let installed = pacman::installed_versions();
+    let mut out = String::new();
+    for group in groups {
+        let mut tags = Vec::new();
+        for backend in backends {
+            let matches: Vec<&aur::SearchResult> = group
+                .results
+                .iter()
+                .filter(|r| {
+                    matches!(
+                        (&r.source, backend.as_str()),
+                        (Source::Custom(_), "tap")
+                            | (Source::Pacman, "pacman")
+                            | (Source::Aur, "aur")
+                            | (Source::Flatpak, "flatpak")
+                    )
+                })
+                .collect();
+            let label = if backend == "pacman" { "repo" } else { backend };
+            if matches.is_empty() {
+                tags.push(format!("[{} ✗]", label).dimmed().to_string());
+            }
+            for r in matches {
+                let label = match &r.source {
+                    Source::Custom(tap) => format!("tap:{}", tap),
+                    _ => label.to_string(),
+                };
+                let version = if r.version.is_empty() {
+                    String::new()
+                } else {
+                    format!(" {}", r.version)
+                };
+                tags.push(format!("[{} ✓{}]", label, version).green().to_string());
+            }
+        }
+        let marker = if installed.contains_key(&group.name) {
+            " (installed)".cyan().to_string()
+        } else {
+            String::new()
+        };
+        let _ = writeln!(
+            out,
+            "{:<20}{} ▸ {:<40} {}",
+            group.name.bold(),
+            marker,
+            group.description,
+            tags.join(" ")
+        );
+    }
+    utils::page(&out);
+}
+
+/// Print search results, paged when they don't fit the terminal
+/// Expand a `--format` template like pacman's `--print-format`: `%n` name,
+/// `%v` version, `%d` description, `%s` source, `%w` votes and `%%` a
+/// literal `%`. `value` gives the text of each token; unknown ones are
+/// kept as written.
+pub fn expand_format(template: &str, value: impl Fn(char) -> Option<String>) -> String {
+    let mut out = String::new();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('%') => out.push('%'),
+            Some(token) => match value(token) {
+                Some(text) => out.push_str(&text),
+                None => {
+                    out.push('%');
+                    out.push(token);
+                }
+            },
+            None => out.push('%'),
+        }
+    }
+    out
+}
+
+/// A search result as a `--format` template
+pub fn format_search_result(r: &aur::SearchResult, template: &str) -> String {
+    expand_format(template, |token| match token {
+        'n' => Some(r.name.clone()),
+        'v' => Some(r.version.clone()),
+        'd' => Some(r.description.clone()),
+        's' => Some(source_key(&r.source, None)),
+        'w' => Some(r.votes.map(|v| v.to_string()).unwrap_or_default()),
+        _ => None,
+    })
+}
+
+/// An installed package as a `--format` template; it has no description
+/// or votes, so `%d` and `%w` are empty
+pub fn format_installed_package(pkg: &InstalledPackage, template: &str) -> String {
+    expand_format(template, |token| match token {
+        'n' => Some(pkg.name.clone()),
+        'v' => Some(pkg.version.clone()),
+        's' => Some(source_key(&pkg.source, None)),
+        'd' | 'w' => Some(String::new()),
+        _ => None,
+    })
+}
+
+pub fn print_search_results(results: &[aur::SearchResult]) {
+    use colored::Colorize;
+    use std::fmt::Write;
+    let mut out = String::new();
+    for r in results {
+        let tag = match &r.source {
+            Source::Custom(tap) => format!("[tap:{}]", tap).yellow().to_string(),
+            Source::Aur => "[aur]".blue().to_string(),
+            Source::Flatpak => "[flatpak]".green().to_string(),
+            Source::Pacman => "[pacman]".magenta().to_string(),
+            _ => format!("[{}]", r.source.label()),
+        };
+        let name = if r.version.is_empty() {
+            r.name.bold().to_string()
+        } else {
+            format!("{} {}", r.name.bold(), r.version.dimmed())
+        };
+        let _ = writeln!(out, "{:<20} ▸ {:<40} {}", name, r.description, tag);
+    }
+    utils::page(&out);
+}
+
+// === Bulk Install Logic ===
+
+/// Per-package outcome of a bulk install or upgrade
+#[derive(Debug, Default)]
+pub struct BulkReport {
+    pub succeeded: Vec<String>,
+    /// Failed packages with the reason they failed
+    pub failed: Vec<(String, String)>,
+    pub elapsed: std::time::Duration,
+}
+
+impl BulkReport {
+    pub fn record(&mut self, pkg: &str, result: Result<(), ReapError>) {
+        match result {
+            Ok(()) => self.succeeded.push(pkg.to_string()),
+            Err(e) => self.failed.push((pkg.to_string(), e.to_string())),
+        }
+    }
+
+    pub fn merge(&mut self, other: BulkReport) {
+        self.succeeded.extend(other.succeeded);
+        self.failed.extend(other.failed);
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// Every failure as `pkg: reason`, for the history log
+    pub fn failure_summary(&self) -> Option<String> {
+        (!self.is_success()).then(|| {
+            self.failed
+                .iter()
+                .map(|(pkg, reason)| format!("{}: {}", pkg, reason))
+                .collect::<Vec<_>>()
+                .join("; ")
+        })
+    }
+
+    /// An error naming the failed packages, so callers can exit nonzero
+    pub fn into_result(self) -> Result<(), ReapError> {
+        if self.is_success() {
+            return Ok(());
+        }
+        let failed: Vec<String> = self.failed.into_iter().map(|(pkg, _)| pkg).collect();
+        Err(ReapError::PackagesFailed {
+            total: failed.len() + self.succeeded.len(),
+            failed,
+        })
+    }
+
+    /// Print a table of every package's outcome, the total time and the
+    /// installed size of what succeeded
+    pub fn print(&self, title: &str) {
+        use colored::Colorize;
+        println!("\n{} {} summary:", "📋".bright_yellow(), title);
+        for pkg in &self.succeeded {
+            println!("  {} {}", "✓".green(), pkg);
+        }
+        for (pkg, reason) in &self.failed {
+            println!("  {} {:<30} {}", "✗".red(), pkg, reason.red());
+        }
+        let size: u64 = self
+            .succeeded
+            .iter()
+            .filter_map(|pkg| pacman::installed_size(pkg))
+            .sum();
+        println!(
+            "  {} succeeded, {} failed in {:.1}s, {:.1} MiB installed",
+            self.succeeded.len(),
+            self.failed.len(),
+            self.elapsed.as_secs_f64(),
+            size as f64 / (1024.0 * 1024.0)
+        );
+    }
+}
+
+/// Print where each package would come from, how much it downloads
+/// (repo and Flatpak packages) and how long it usually takes to build (AUR
+/// packages, from earlier builds)
+pub async fn print_install_plan(pkgs: &[String]) {
+    use colored::Colorize;
+    let pkgs = pkgs.to_vec();
+    // detect_source and the size lookups block on the network and pacman
+    let plan = tokio::task::spawn_blocking(move || {
+        let times = crate::history::BuildTimes::load();
+        pkgs.into_iter()
+            .map(|pkg| {
+                let source = detect_source(&pkg, None, false);
+                let (download, build) = match &source {
+                    Some(Source::Aur) | Some(Source::Custom(_)) => (None, times.estimate(&pkg)),
+                    Some(Source::Flatpak) => (flatpak::download_size(&pkg), None),
+                    Some(_) => (pacman::download_size(&pkg), None),
+                    None => (None, None),
+                };
+                (pkg, source, download, build)
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .unwrap_or_default();
+
+    println!("{} Install plan:", "📋".bright_yellow());
+    let mut total_download = 0u64;
+    let mut total_build = std::time::Duration::ZERO;
+    let mut unknown_builds = 0;
+    for (pkg, source, download, build) in &plan {
+        let Some(source) = source else {
+            println!(
+                "  {} {:<30} {}",
+                "✗".red(),
+                pkg,
+                "not found in any source".red()
+            );
+            continue;
+        };
+        let detail = match (download, build, source) {
+            (Some(bytes), _, _) => {
+                total_download += bytes;
+                format!("download {:.1} MiB", *bytes as f64 / (1024.0 * 1024.0))
+            }
+            (None, Some(time), _) => {
+                total_build += *time;
+                format!("build ~{}", utils::format_duration(*time))
+            }
+            (None, None, Source::Aur | Source::Custom(_)) => {
+                unknown_builds += 1;
+                "build time unknown (not built before)".to_string()
+            }
+            (None, None, _) => "download size unknown".to_string(),
+        };
+        println!(
+            "  {} {:<30} {:<10} {}",
+            "•".cyan(),
+            pkg,
+            source.label(),
+            detail
+        );
+    }
+    let mut summary = format!(
+        "  {:.1} MiB to download, ~{} of builds",
+        total_download as f64 / (1024.0 * 1024.0),
+        utils::format_duration(total_build)
+    );
+    if unknown_builds > 0 {
+        summary.push_str(&format!(" plus {} without an estimate", unknown_builds));
+    }
+    println!("{}", summary);
+}
+
+/// Everything installing a set of packages adds to the system, for
+/// `--print-deps-size`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InstallFootprint {
+    /// Repo packages, with the dependencies of every target that aren't
+    /// installed yet
+    pub repo: Vec<pacman::PackageSize>,
+    /// AUR and tap packages to build, with the installed size of the
+    /// version already installed (if any) as the estimate
+    pub aur: Vec<(String, Option<u64>)>,
+    /// Flatpak apps with their download size
+    pub flatpak: Vec<(String, Option<u64>)>,
+    /// Targets found in no source
+    pub not_found: Vec<String>,
+}
+
+impl InstallFootprint {
+    /// Total download and installed bytes, and the number of packages
+    /// without an estimate in them
+    pub fn totals(&self) -> (u64, u64, usize) {
+        let mut download: u64 = self.repo.iter().map(|p| p.download).sum();
+        let mut installed: u64 = self.repo.iter().map(|p| p.installed).sum();
+        let mut unknown = 0;
+        for (_, size) in &self.aur {
+            match size {
+                Some(bytes) => installed += bytes,
+                None => unknown += 1,
+            }
+        }
+        for (_, size) in &self.flatpak {
+            match size {
+                Some(bytes) => download += bytes,
+                None => unknown += 1,
+            }
+        }
+        (download, installed, unknown)
+    }
+
+    /// The packages and their totals, like pacman's transaction summary
+    pub fn render(&self) -> String {
+        use std::fmt::Write;
+        let mib = |bytes: u64| format!("{:.2} MiB", bytes as f64 / (1024.0 * 1024.0));
+        let mut out = String::new();
+        let names: Vec<&str> = self.repo.iter().map(|p| p.name.as_str()).collect();
+        let aur: Vec<&str> = self.aur.iter().map(|(name, _)| name.as_str()).collect();
+        let apps: Vec<&str> = self.flatpak.iter().map(|(name, _)| name.as_str()).collect();
+        for (label, names) in [
+            ("Repo packages", names),
+            ("AUR packages", aur),
+            ("Flatpak apps", apps),
+        ] {
+            if !names.is_empty() {
+                let _ = writeln!(out, "{} ({}): {}", label, names.len(), names.join("  "));
+            }
+        }
+        if !self.not_found.is_empty() {
+            let _ = writeln!(out, "Not found: {}", self.not_found.join("  "));
+        }
+        let (download, installed, unknown) = self.totals();
+        let _ = writeln!(out, "\nTotal Download Size:   {:>12}", mib(download));
+        let _ = writeln!(out, "Total Installed Size:  {:>12}", mib(installed));
+        if unknown > 0 {
+            let _ = writeln!(
+                out,
+                "  not counting {} package(s) without a size estimate",
+                unknown
+            );
+        }
+        out
+    }
+}
+
+/// Resolve the full set of packages installing `pkgs` would add: repo
+/// targets with their missing dependencies, the AUR packages to build
+/// (skipping `ignored` dependencies) and the repo packages those need, and
+/// Flatpak apps. Sizes come from `pacman -Si`; AUR packages are estimated
+/// from the version already installed, if any.
+pub async fn install_footprint(pkgs: &[String], ignored: &[String]) -> InstallFootprint {
+    let targets = pkgs.to_vec();
+    let sources = tokio::task::spawn_blocking(move || {
+        let repos = if targets.iter().any(|pkg| pkg.contains('/')) {
+            pacman::sync_repos()
+        } else {
+            Vec::new()
+        };
+        targets
+            .into_iter()
+            .map(|pkg| match PackageListEntry::parse_target(&pkg, &repos) {
+                // `extra/foo` stays qualified for pacman
+                Ok(entry) if entry.forced_source().is_some() => {
+                    let name = match &entry.repo {
+                        Some(repo) => format!("{}/{}", repo, entry.name),
+                        None => entry.name.clone(),
+                    };
+                    (name, entry.forced_source())
+                }
+                _ => {
+                    let source = detect_source(&pkg, None, false);
+                    (pkg, source)
+                }
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .unwrap_or_default();
+
+    let mut footprint = InstallFootprint::default();
+    let (mut repo_targets, mut aur_targets, mut taps, mut apps) =
+        (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    for (pkg, source) in sources {
+        match source {
+            Some(Source::Aur) => aur_targets.push(pkg),
+            Some(Source::Custom(_)) => taps.push(pkg),
+            Some(Source::Flatpak) => apps.push(pkg),
+            Some(_) => repo_targets.push(pkg),
+            None => footprint.not_found.push(pkg),
+        }
+    }
+
+    let mut aur: Vec<String> = aur_dependency_graph(&aur_targets, ignored)
+        .await
+        .into_keys()
+        .collect();
+    aur.sort();
+    let mut repo_deps: Vec<String> = Vec::new();
+    if !aur.is_empty()
+        && let Ok(infos) = aur::fetch_info_batch(&aur).await
+    {
+        for info in infos {
+            for dep in info.depends.iter().chain(&info.make_depends) {
+                let name = dep_name(dep).to_string();
+                if !aur.contains(&name) && !ignored.contains(&name) && !repo_deps.contains(dep) {
+                    repo_deps.push(dep.clone());
+                }
+            }
+        }
+    }
+    aur.extend(taps);
+
+    let (repo, aur, flatpak) = tokio::task::spawn_blocking(move || {
+        let mut wanted = repo_targets;
+        wanted.extend(
+            unsatisfied_deps(&repo_deps)
+                .iter()
+                .map(|dep| dep_name(dep).to_string()),
+        );
+        let repo = pacman::sync_sizes(&pacman::install_set(&wanted));
+        let aur: Vec<(String, Option<u64>)> = aur
+            .into_iter()
+            .map(|pkg| {
+                let size = pacman::installed_size(&pkg);
+                (pkg, size)
+            })
+            .collect();
+        let flatpak: Vec<(String, Option<u64>)> = apps
+            .into_iter()
+            .map(|app| {
+                let size = flatpak::download_size(&app);
+                (app, size)
+            })
+            .collect();
+        (repo, aur, flatpak)
+    })
+    .await
+    .unwrap_or_default();
+    footprint.repo = repo;
+    footprint.aur = aur;
+    footprint.flatpak = flatpak;
+    footprint
+}
+
+/// Run `task` for every package on its own tokio task, at most `max_parallel`
+/// at a time. Each task's error or panic is recorded against its package.
+/// Only fetching and building overlap: the tasks' pacman transactions take
+/// turns through [`pacman::transaction`].
+pub async fn run_parallel<F, Fut>(pkgs: &[String], max_parallel: usize, task: F) -> BulkReport
+where
+    F: Fn(String) -> Fut,
+    Fut: std::future::Future<Output = Result<(), ReapError>> + Send + 'static,
+{
+    let start = Instant::now();
+    let semaphore = Arc::new(Semaphore::new(max_parallel.max(1)));
+    let mut handles = Vec::new();
+    for pkg in pkgs {
+        let permit_fut = Arc::clone(&semaphore).acquire_owned();
+        let fut = task(pkg.clone());
+        handles.push(tokio::spawn(async move {
+            let _permit = permit_fut.await;
+            fut.await
+        }));
+    }
+    let mut report = BulkReport::default();
+    for (pkg, res) in pkgs.iter().zip(join_all(handles).await) {
+        let res = match res {
+            Ok(res) => res,
+            Err(e) if e.is_panic() => {
+                let payload = e.into_panic();
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown panic".to_string());
+                Err(ReapError::TaskPanicked {
+                    pkg: pkg.clone(),
+                    message,
+                })
+            }
+            Err(e) => Err(ReapError::CommandFailed(e.to_string())),
+        };
+        report.record(pkg, res);
+    }
+    report.elapsed = start.elapsed();
+    report
+}
+
+/// Install already confirmed `pkgs` concurrently with the same `opts`
+pub async fn parallel_install(
+    pkgs: &[String],
+    config: Arc<ReapConfig>,
+    log: Arc<dyn ProgressReporter>,
+    opts: &InstallOptions,
+) -> BulkReport {
+    let options = pkgs.iter().map(|pkg| (pkg.clone(), opts.clone())).collect();
+    parallel_install_with(pkgs, config, log, options).await
+}
+
+/// [`parallel_install`] with per-package install options
+pub async fn parallel_install_with(
+    pkgs: &[String],
+    config: Arc<ReapConfig>,
+    log: Arc<dyn ProgressReporter>,
+    options: HashMap<String, InstallOptions>,
+) -> BulkReport {
+    let max_parallel = config.parallel;
+    install_concurrently(pkgs, max_parallel, config, log, options, None).await
+}
+
+pub async fn parallel_upgrade(
+    pkgs: &[String],
+    config: Arc<ReapConfig>,
+    log: Arc<dyn ProgressReporter>,
+) -> BulkReport {
+    run_parallel(pkgs, config.parallel, |pkg| {
+        let config = Arc::clone(&config);
+        let log = Arc::clone(&log);
+        async move {
+            install_with_priority(&pkg, config, false, log, &InstallOptions::default()).await
+        }
+    })
+    .await
 }
 
 pub fn repo_has_package(pkg: &str, repo: &str) -> bool {
@@ -536,16 +2537,19 @@ pub fn get_enabled_binary_repos() -> Vec<String> {
     let conf = std::fs::read_to_string("/etc/pacman.conf").unwrap_or_default();
     let mut repos = Vec::new();
     for line in conf.lines() {
-        if let Some(repo) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
-            if repo.ends_with("-aur") || repo == "chaotic-aur" || repo == "ghostctl-aur" {
-                repos.push(repo.to_string());
-            }
+        if let Some(repo) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']'))
+            && (repo.ends_with("-aur") || repo == "chaotic-aur" || repo == "ghostctl-aur")
+        {
+            repos.push(repo.to_string());
         }
     }
     repos
 }
 
 pub fn detect_source(pkg: &str, repo: Option<&str>, binary_only: bool) -> Option<Source> {
+    if pacman::is_group(pkg) {
+        return Some(Source::Pacman);
+    }
     if let Some(repo_name) = repo {
         if repo_has_package(pkg, repo_name) {
             return Some(Source::BinaryRepo(repo_name.to_string()));
@@ -568,27 +2572,225 @@ pub fn detect_source(pkg: &str, repo: Option<&str>, binary_only: bool) -> Option
             .arg("search")
             .arg(pkg)
             .output();
-        if let Ok(out) = output {
-            if out.status.success() && !String::from_utf8_lossy(&out.stdout).trim().is_empty() {
-                return Some(Source::Flatpak);
-            }
+        if let Ok(out) = output
+            && out.status.success()
+            && !String::from_utf8_lossy(&out.stdout).trim().is_empty()
+        {
+            return Some(Source::Flatpak);
         }
     }
     None
 }
 
-pub fn handle_install(pkgs: Vec<String>) {
-    let backend: Box<dyn Backend> = Box::new(AurBackend::new());
+/// Install packages one at a time through the same source resolution as
+/// `install_with_priority` (tap > repo > AUR > flatpak).
+pub async fn handle_install(pkgs: Vec<String>, confirm: bool, opts: &InstallOptions) -> BulkReport {
+    // Nothing to do, and nothing for the history
+    if pkgs.is_empty() {
+        return BulkReport::default();
+    }
+    if opts.dry_run {
+        print_install_plan(&pkgs).await;
+    }
+    if opts.print_deps_size {
+        println!("[reap] Resolving dependencies...");
+        let footprint = install_footprint(&pkgs, &opts.ignore_deps).await;
+        print!("\n{}", footprint.render());
+        if !opts.dry_run && !confirm_proceed(confirm, "Proceed with installation?") {
+            println!("[reap] Install cancelled");
+            return BulkReport::default();
+        }
+    }
+    if opts.dry_run {
+        return BulkReport::default();
+    }
+    let start = Instant::now();
+    let before = pacman::installed_versions();
+    let requested = pkgs.clone();
+    let config = Arc::new(ReapConfig::load());
+    let log: Arc<dyn ProgressReporter> = Arc::new(StdoutReporter);
+    let enhanced = enhanced_install_context();
+    let mut report = BulkReport::default();
+    let repos = if pkgs.iter().any(|pkg| pkg.contains('/')) {
+        pacman::sync_repos()
+    } else {
+        Vec::new()
+    };
+    // Explicit sources and groups are resolved first, so the whole set is
+    // confirmed once before anything is built
+    let mut targets: Vec<(String, InstallOptions)> = Vec::new();
     for pkg in pkgs {
+        // `aur/foo`, `extra/foo` or `flatpak:org.foo.Bar` picks the source
+        let (pkg, opts) = match PackageListEntry::parse_target(&pkg, &repos) {
+            Ok(entry) if entry.source.is_some() => {
+                let entry_opts = entry.install_options(opts);
+                (entry.name, entry_opts)
+            }
+            Ok(_) => (pkg, opts.clone()),
+            Err(e) => {
+                eprintln!("[reap] {}: {}", pkg, e);
+                report.record(&pkg, Err(ReapError::SourceNotFound(pkg.clone())));
+                continue;
+            }
+        };
+        match expand_group(&pkg, false) {
+            Some(members) => targets.extend(members.into_iter().map(|m| (m, opts.clone()))),
+            None => targets.push((pkg, opts)),
+        }
+    }
+    // --print-deps-size has already asked
+    if !opts.print_deps_size && confirm && !targets.is_empty() {
+        let names: Vec<&str> = targets.iter().map(|(pkg, _)| pkg.as_str()).collect();
+        println!("\n[reap] Packages ({}): {}", names.len(), names.join("  "));
+        if !confirm_proceed(confirm, "Proceed with installation?") {
+            println!("[reap] Install cancelled");
+            return report;
+        }
+    }
+    for (pkg, opts) in targets {
         println!("[reap] Installing {}...", pkg);
-        tokio::runtime::Runtime::new()
-            .unwrap()
-            .block_on(backend.install(&pkg));
+        let (config, log) = (Arc::clone(&config), Arc::clone(&log));
+        let res = install_routed(&pkg, config, confirm, log, &opts, enhanced.as_ref()).await;
+        report.record(&pkg, res);
+    }
+    report.elapsed = start.elapsed();
+    if !opts.download_only {
+        log_operation(
+            OperationKind::Install,
+            &requested,
+            &before,
+            report.failure_summary(),
+        );
+    }
+    report
+}
+
+/// The active profile and trust engine, when a non-default profile or any
+/// trust gate is configured. Those only take effect through the enhanced path.
+fn enhanced_install_context() -> Option<(ProfileManager, TrustEngine)> {
+    let profile_manager = ProfileManager::new();
+    let global = GlobalConfig::load();
+    (profile_manager.active_profile_name() != "default"
+        || global.trust_check.unwrap_or(false)
+        || global.block_orphaned_aur.unwrap_or(false)
+        || global.warn_out_of_date.unwrap_or(false))
+    .then(|| (profile_manager, TrustEngine::new()))
+}
+
+/// Install `pkg` through [`install_with_priority_enhanced`] when `enhanced`
+/// is set, [`install_with_priority`] otherwise
+async fn install_routed(
+    pkg: &str,
+    config: Arc<ReapConfig>,
+    confirm: bool,
+    log: Arc<dyn ProgressReporter>,
+    opts: &InstallOptions,
+    enhanced: Option<&(ProfileManager, TrustEngine)>,
+) -> Result<(), ReapError> {
+    match enhanced {
+        Some((profiles, trust)) => {
+            install_with_priority_enhanced(pkg, config, confirm, log, opts, profiles, trust).await
+        }
+        None => install_with_priority(pkg, config, confirm, log, opts).await,
+    }
+}
+
+/// Install `pkgs` concurrently, each with its entry in `options`, the way
+/// [`handle_install`] installs them one by one, and record the operation
+async fn install_concurrently(
+    pkgs: &[String],
+    max_parallel: usize,
+    config: Arc<ReapConfig>,
+    log: Arc<dyn ProgressReporter>,
+    options: HashMap<String, InstallOptions>,
+    progress: Option<ProgressBar>,
+) -> BulkReport {
+    let before = pacman::installed_versions();
+    let enhanced = Arc::new(enhanced_install_context());
+    let options = Arc::new(options);
+    let report = run_parallel(pkgs, max_parallel, |pkg| {
+        let (config, log) = (Arc::clone(&config), Arc::clone(&log));
+        let (options, enhanced) = (Arc::clone(&options), Arc::clone(&enhanced));
+        let progress = progress.clone();
+        async move {
+            let opts = options.get(&pkg).cloned().unwrap_or_default();
+            let res =
+                install_routed(&pkg, config, false, log, &opts, enhanced.as_ref().as_ref()).await;
+            if let Some(pb) = progress {
+                pb.inc(1);
+            }
+            res
+        }
+    })
+    .await;
+    if !options.values().all(|opts| opts.download_only) {
+        log_operation(
+            OperationKind::Install,
+            pkgs,
+            &before,
+            report.failure_summary(),
+        );
+    }
+    report
+}
+
+/// Add an operation to `history.json`, with the packages whose version
+/// changed since the `before` snapshot
+fn log_operation(
+    kind: OperationKind,
+    packages: &[String],
+    before: &std::collections::BTreeMap<String, String>,
+    error: Option<String>,
+) {
+    let mut changes = PackageChange::between(before, &pacman::installed_versions());
+    let registry = crate::history::InstallRegistry::load();
+    for change in &mut changes {
+        change.source = registry.get(&change.name).map(|r| r.source.clone());
+    }
+    crate::history::annotate_changes(&mut changes);
+    if let Err(e) = crate::history::record_operation(kind, packages.to_vec(), changes, error) {
+        eprintln!("[reap][history] Failed to record {}: {}", kind.label(), e);
+    }
+}
+
+/// Expand a repo package group into its members, printing them and asking
+/// for confirmation like pacman does. Returns `None` if `name` is not a group
+/// and an empty list if the user declined.
+pub fn expand_group(name: &str, confirm: bool) -> Option<Vec<String>> {
+    let members = pacman::group_members(name);
+    if members.is_empty() {
+        return None;
+    }
+    println!(
+        ":: There are {} members in group {}:\n   {}",
+        members.len(),
+        name,
+        members.join(" ")
+    );
+    if confirm_proceed(confirm, &format!("Install all members of {}?", name)) {
+        Some(members)
+    } else {
+        Some(Vec::new())
     }
 }
 
-pub async fn handle_install_parallel(pkgs: Vec<String>, max_parallel: usize) {
-    let semaphore = Arc::new(Semaphore::new(max_parallel));
+/// Replace any groups in `pkgs` with their members
+pub fn expand_groups(pkgs: &[String], confirm: bool) -> Vec<String> {
+    pkgs.iter()
+        .flat_map(|pkg| expand_group(pkg, confirm).unwrap_or_else(|| vec![pkg.clone()]))
+        .collect()
+}
+
+/// Install already confirmed `pkgs`, `max_parallel` at a time, with a
+/// progress bar
+pub async fn handle_install_parallel(
+    pkgs: Vec<String>,
+    max_parallel: usize,
+    opts: &InstallOptions,
+) -> BulkReport {
+    let pkgs = expand_groups(&pkgs, false);
+    let config = Arc::new(ReapConfig::load());
+    let log: Arc<dyn ProgressReporter> = Arc::new(StdoutReporter);
     let pb = ProgressBar::new(pkgs.len() as u64);
     pb.set_style(
         ProgressStyle::default_bar()
@@ -596,325 +2798,4650 @@ pub async fn handle_install_parallel(pkgs: Vec<String>, max_parallel: usize) {
             .expect("Failed to create ProgressStyle")
             .progress_chars("#>-"),
     );
-    let mut handles = Vec::new();
+    let options = pkgs.iter().map(|pkg| (pkg.clone(), opts.clone())).collect();
+    let report =
+        install_concurrently(&pkgs, max_parallel, config, log, options, Some(pb.clone())).await;
+    pb.finish_with_message("Done.");
+    report
+}
+
+/// What removing a set of packages takes with it, shown before `-R`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RemovalPreview {
+    /// Installed packages that depend on a target, with the targets each
+    /// one needs. Plain `-R` refuses to break them; `-Rsc` removes them.
+    pub breaks: Vec<(String, Vec<String>)>,
+    /// Packages `pacman -Rsc` would remove besides the targets
+    pub cascade: Vec<String>,
+}
+
+impl RemovalPreview {
+    /// Preview removing `targets`, from their `pacman -Qi` entries and the
+    /// full `pacman -Rsc` removal set
+    pub fn new(targets: &[String], deps: &[pacman::PackageDeps], removal_set: &[String]) -> Self {
+        let mut breaks: std::collections::BTreeMap<String, Vec<String>> = Default::default();
+        for target in deps.iter().filter(|d| targets.contains(&d.name)) {
+            for dependent in target.required_by.iter().filter(|d| !targets.contains(d)) {
+                breaks
+                    .entry(dependent.clone())
+                    .or_default()
+                    .push(target.name.clone());
+            }
+        }
+        Self {
+            breaks: breaks.into_iter().collect(),
+            cascade: removal_set
+                .iter()
+                .filter(|pkg| !targets.contains(pkg))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// The installed packages the removal would break and what a cascading
+    /// removal would take along; empty if removing is safe
+    pub fn render(&self) -> String {
+        use std::fmt::Write;
+        let mut out = String::new();
+        if !self.breaks.is_empty() {
+            let _ = writeln!(
+                out,
+                "⚠️  {} installed package(s) depend on what's being removed:",
+                self.breaks.len()
+            );
+            for (dependent, needs) in &self.breaks {
+                let _ = writeln!(out, "  - {} (needs {})", dependent, needs.join(", "));
+            }
+        }
+        if !self.cascade.is_empty() {
+            let _ = writeln!(
+                out,
+                "`pacman -Rsc` would also remove {} package(s): {}",
+                self.cascade.len(),
+                self.cascade.join("  ")
+            );
+        }
+        out
+    }
+}
+
+/// Remove `pkgs` after showing which installed packages depend on them.
+/// `recursive_check` also lists everything a cascading `pacman -Rsc` would
+/// remove; `print` shows the preview and the removal set and removes
+/// nothing. Removing packages others depend on is only done as the
+/// cascading removal, after asking.
+pub fn handle_removal(pkgs: &[String], confirm: bool, recursive_check: bool, print: bool) {
+    if pkgs.is_empty() {
+        return;
+    }
+    let mut removal_set = if recursive_check || print {
+        pacman::removal_set(pkgs)
+    } else {
+        Vec::new()
+    };
+    let preview = RemovalPreview::new(pkgs, &pacman::package_deps(pkgs), &removal_set);
+    print!("{}", preview.render());
+    if print {
+        println!("[reap] Would remove: {}", removal_set.join("  "));
+        return;
+    }
+    // Plain -R refuses to break dependents, so the only way through is the cascade
+    let cascade = !preview.breaks.is_empty();
+    if cascade {
+        if removal_set.is_empty() {
+            removal_set = pacman::removal_set(pkgs);
+        }
+        println!("[reap] Removing them takes the cascading removal (pacman -Rsc)");
+        if !crate::interactive::InteractiveManager::new().confirm_removal(&removal_set) {
+            println!("[reap] Removal aborted");
+            return;
+        }
+    } else if confirm && !crate::interactive::InteractiveManager::new().confirm_removal(pkgs) {
+        return;
+    }
+    let before = pacman::installed_versions();
+    if cascade {
+        println!("[reap] Removing {}...", removal_set.join(", "));
+        let _transaction = pacman::transaction();
+        match crate::utils::escalate("pacman")
+            .args(["-Rsc", "--noconfirm"])
+            .args(pkgs)
+            .status()
+        {
+            Ok(s) if s.success() => {}
+            Ok(_) => eprintln!("[reap] pacman -Rsc failed for {}", pkgs.join(", ")),
+            Err(e) => eprintln!("[reap] Failed to run pacman -Rsc: {}", e),
+        }
+    } else {
+        for pkg in pkgs {
+            println!("[reap] Removing {}...", pkg);
+            aur::uninstall(pkg);
+        }
+    }
+    let remaining: Vec<&str> = pkgs
+        .iter()
+        .filter(|pkg| pacman::is_installed(pkg))
+        .map(String::as_str)
+        .collect();
+    let error =
+        (!remaining.is_empty()).then(|| format!("still installed: {}", remaining.join(", ")));
+    log_operation(OperationKind::Remove, pkgs, &before, error);
+}
+
+pub fn handle_local_install(pkgs: &[String]) {
     for pkg in pkgs {
-        let permit = semaphore.clone().acquire_owned().await.unwrap();
-        let pb = pb.clone();
-        let pkg = pkg.clone();
-        handles.push(tokio::spawn(async move {
-            let _permit = permit;
-            let _ = std::panic::AssertUnwindSafe(async {
-                handle_install(vec![pkg.clone()]);
-            })
-            .catch_unwind()
-            .await;
-            pb.inc(1);
-            Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
-        }));
+        println!("[reap] Installing local package {}...", pkg);
+        aur::install_local(pkg);
+    }
+}
+
+pub async fn handle_search(
+    terms: &[String],
+    all_sources: bool,
+    no_dedup: bool,
+    format: Option<&str>,
+) {
+    // Offline results come from the last online search and may be stale
+    let cached = if aur::is_offline() { " (cached)" } else { "" };
+    let backends = if all_sources {
+        GlobalConfig::load().backend_order
+    } else {
+        Vec::new()
+    };
+    // --format output is for scripts, so it's only the formatted lines
+    let print = |results: &[aur::SearchResult]| match format {
+        Some(template) => {
+            for r in results {
+                println!("{}", format_search_result(r, template));
+            }
+        }
+        None => print_search_results(results),
+    };
+    for term in terms {
+        if format.is_none() {
+            println!("[reap] Searching for {}{}...", term, cached);
+        }
+        if all_sources {
+            let results = search_backends(term, &backends).await;
+            if format.is_some() {
+                // One line per source that has the package
+                print(&results);
+            } else {
+                print_search_groups(&group_search_results(results), &backends);
+            }
+            continue;
+        }
+        if no_dedup {
+            // Every result from every backend, even repeats of one package
+            print(&unified_search(term, false).await);
+            continue;
+        }
+        match aur::search(term).await {
+            Ok(results) => print(&results),
+            Err(e) => eprintln!("[reap] Search failed for '{}': {}", term, e),
+        }
+    }
+}
+
+/// `reap search --maintainer <name>`: the AUR packages `maintainer` maintains
+pub async fn handle_search_maintainer(
+    maintainer: &str,
+    format: Option<&str>,
+) -> Result<(), ReapError> {
+    if let Some(template) = format {
+        for r in aur::search_by_maintainer(maintainer).await? {
+            println!("{}", format_search_result(&r, template));
+        }
+        return Ok(());
+    }
+    println!(
+        "[reap] Searching for packages maintained by {}...",
+        maintainer
+    );
+    let results = aur::search_by_maintainer(maintainer).await?;
+    if results.is_empty() {
+        println!("[reap] {} maintains no AUR packages", maintainer);
+    } else {
+        print_search_results(&results);
+    }
+    Ok(())
+}
+
+/// `reap install --maintainer <name>`: install every AUR package
+/// `maintainer` maintains that isn't installed yet
+pub async fn handle_install_maintainer(
+    maintainer: &str,
+    confirm: bool,
+    opts: &InstallOptions,
+) -> Result<(), ReapError> {
+    let results = aur::search_by_maintainer(maintainer).await?;
+    if results.is_empty() {
+        return Err(ReapError::SourceNotFound(format!(
+            "AUR packages maintained by {}",
+            maintainer
+        )));
+    }
+    let installed = pacman::installed_versions();
+    let (present, missing): (Vec<String>, Vec<String>) = results
+        .into_iter()
+        .map(|r| r.name)
+        .partition(|name| installed.contains_key(name));
+    if !present.is_empty() {
+        println!("[reap] Already installed: {}", present.join(", "));
+    }
+    if missing.is_empty() {
+        println!(
+            "[reap] Every package maintained by {} is already installed",
+            maintainer
+        );
+        return Ok(());
+    }
+    println!(
+        "[reap] Packages maintained by {} ({}): {}",
+        maintainer,
+        missing.len(),
+        missing.join(", ")
+    );
+    if !confirm_proceed(confirm, &format!("Install {} packages?", missing.len())) {
+        return Err(ReapError::UserAborted);
+    }
+    // The set was confirmed above
+    handle_install(missing, false, opts).await.into_result()
+}
+
+/// `-S --print-srcinfo <pkg>...`: print the parsed .SRCINFO of each
+/// package from its tap, the AUR or (offline) the source cache, without
+/// building anything
+pub async fn handle_print_srcinfo(pkgs: &[String]) -> Result<(), ReapError> {
+    for pkg in pkgs {
+        let name = pkg.clone();
+        let srcinfo = tokio::task::spawn_blocking(move || {
+            let taps = discover_taps();
+            if let Some(tap) = taps.iter().find(|t| crate::tap::tap_has_package(t, &name)) {
+                return crate::srcinfo::parse_srcinfo(
+                    &crate::tap::ensure_tap_cloned(tap).join(&name),
+                );
+            }
+            if aur::is_offline() {
+                return crate::srcinfo::parse_srcinfo(&aur_source_cache(&name));
+            }
+            let text = aur::fetch_srcinfo(&name)?;
+            crate::srcinfo::SrcInfo::parse(&text)
+                .map_err(|e| ReapError::CommandFailed(format!("{}: {}", name, e)))
+        })
+        .await
+        .map_err(|e| ReapError::CommandFailed(e.to_string()))??;
+        print!("{}", srcinfo.render(host_arch()));
+    }
+    Ok(())
+}
+
+/// Show details of `pkg` from its source and, for AUR packages with
+/// `comments`, the newest `limit` comments from its AUR page.
+pub async fn handle_info(pkg: &str, comments: bool, limit: usize) -> Result<(), ReapError> {
+    use colored::Colorize;
+    let name = pkg.to_string();
+    // detect_source and the AUR lookups use the blocking client
+    let source = tokio::task::spawn_blocking(move || detect_source(&name, None, false))
+        .await
+        .ok()
+        .flatten()
+        .ok_or_else(|| ReapError::SourceNotFound(pkg.to_string()))?;
+    match source {
+        Source::Aur => {
+            let body = aur::rpc(&format!("type=info&arg[]={}", pkg)).await?;
+            let json: serde_json::Value = serde_json::from_str(&body)
+                .map_err(|e| ReapError::NetworkError(format!("invalid AUR response: {}", e)))?;
+            let info = &json["results"][0];
+            let field = |key: &str| match &info[key] {
+                serde_json::Value::String(s) => s.clone(),
+                serde_json::Value::Null => "-".to_string(),
+                v => v.to_string(),
+            };
+            println!("{} {} (AUR)", pkg.bold(), field("Version"));
+            println!("  Description: {}", field("Description"));
+            println!("  URL:         {}", field("URL"));
+            println!("  Maintainer:  {}", field("Maintainer"));
+            println!("  Votes:       {}", field("NumVotes"));
+            println!("  Popularity:  {}", field("Popularity"));
+            if let Some(ts) = info["OutOfDate"].as_i64()
+                && let Some(flagged) = chrono::DateTime::from_timestamp(ts, 0)
+            {
+                println!(
+                    "  {}",
+                    format!("Flagged out-of-date on {}", flagged.format("%Y-%m-%d")).yellow()
+                );
+            }
+        }
+        Source::Pacman | Source::ChaoticAUR | Source::GhostctlAUR | Source::BinaryRepo(_) => {
+            Command::new("pacman").arg("-Si").arg(pkg).status()?;
+        }
+        Source::Flatpak => {
+            Command::new("flatpak")
+                .args(["remote-info", "flathub", pkg])
+                .status()?;
+        }
+        Source::Custom(ref tap) => println!("{} (tap {})", pkg.bold(), tap),
+    }
+    if comments {
+        if source != Source::Aur {
+            println!("[reap] Comments are only available for AUR packages");
+            return Ok(());
+        }
+        let name = pkg.to_string();
+        let list = tokio::task::spawn_blocking(move || aur::fetch_comments(&name))
+            .await
+            .map_err(|e| ReapError::CommandFailed(e.to_string()))??;
+        if list.is_empty() {
+            println!("[reap] No comments on {}", pkg);
+        }
+        for comment in list.iter().take(limit) {
+            println!("\n{} {}", comment.author.cyan(), comment.date.dimmed());
+            println!("  {}", comment.text);
+        }
+    }
+    Ok(())
+}
+
+/// Vote for `pkg` on the AUR (or with `vote` false, remove the vote) and
+/// report the new vote count. Uses `aur_cookie` from reap.toml or the saved
+/// session, logging in interactively when there is neither.
+pub async fn handle_vote(pkg: &str, vote: bool) -> Result<(), ReapError> {
+    use crate::interactive::InteractiveManager;
+    if aur::is_offline() {
+        return Err(ReapError::NetworkError(
+            "voting needs network access".to_string(),
+        ));
+    }
+    let info_query = format!("type=info&arg[]={}", pkg);
+    let info = aur::rpc(&info_query).await?;
+    let json: serde_json::Value = serde_json::from_str(&info)
+        .map_err(|e| ReapError::NetworkError(format!("invalid AUR response: {}", e)))?;
+    let pkgbase = json["results"][0]["PackageBase"]
+        .as_str()
+        .ok_or_else(|| ReapError::SourceNotFound(pkg.to_string()))?
+        .to_string();
+
+    let configured = GlobalConfig::load().aur_cookie;
+    let pkgbase_task = pkgbase.clone();
+    tokio::task::spawn_blocking(move || {
+        let saved = fs::read_to_string(aur::aur_session_path())
+            .ok()
+            .map(|sid| sid.trim().to_string())
+            .filter(|sid| !sid.is_empty());
+        let login = || -> Result<String, ReapError> {
+            println!("[reap] Log in to the AUR ({})", aur::aur_url());
+            print!("Username: ");
+            std::io::Write::flush(&mut std::io::stdout())?;
+            let user = match crate::interactive::read_prompt_line() {
+                crate::interactive::PromptInput::Line(user) => user,
+                _ => return Err(ReapError::AuthFailed("no username entered".to_string())),
+            };
+            let password = InteractiveManager::read_password("Password: ")?;
+            let sid = aur::aur_login(user.trim(), &password)?;
+            if let Err(e) = aur::save_aur_session(&sid) {
+                eprintln!("[reap] Failed to save the AUR session: {}", e);
+            }
+            Ok(sid)
+        };
+        match (configured, saved) {
+            (Some(sid), _) => aur::set_vote(&pkgbase_task, &sid, vote).map_err(|e| match e {
+                ReapError::AuthFailed(_) => ReapError::AuthFailed(
+                    "aur_cookie in reap.toml is invalid or expired".to_string(),
+                ),
+                e => e,
+            }),
+            (None, Some(sid)) => match aur::set_vote(&pkgbase_task, &sid, vote) {
+                Err(ReapError::AuthFailed(_)) => {
+                    let _ = fs::remove_file(aur::aur_session_path());
+                    println!("[reap] AUR session expired");
+                    aur::set_vote(&pkgbase_task, &login()?, vote)
+                }
+                res => res,
+            },
+            (None, None) => aur::set_vote(&pkgbase_task, &login()?, vote),
+        }
+    })
+    .await
+    .map_err(|e| ReapError::CommandFailed(e.to_string()))??;
+
+    let votes = aur::rpc(&info_query)
+        .await
+        .ok()
+        .and_then(|body| serde_json::from_str::<serde_json::Value>(&body).ok())
+        .and_then(|json| json["results"][0]["NumVotes"].as_u64());
+    let action = if vote {
+        "Voted for"
+    } else {
+        "Removed vote for"
+    };
+    match votes {
+        Some(n) => println!("[reap] {} {} ({} votes)", action, pkgbase, n),
+        None => println!("[reap] {} {}", action, pkgbase),
+    }
+    Ok(())
+}
+
+/// Check AUR packages for updates. With `notify`, also send a desktop
+/// notification when there are any (for the generated timer).
+pub async fn handle_update(notify: bool) {
+    use colored::Colorize;
+    println!("{} Checking for package updates...", "🔍".bright_blue());
+
+    // fetch_package_info uses the blocking client, keep it off the runtime threads
+    let updates_available = tokio::task::spawn_blocking(|| {
+        let config = crate::config::ReapConfig::load();
+        let installed = crate::pacman::list_installed_aur();
+        let mut updates_available: Vec<(String, String, String)> = Vec::new();
+
+        println!(
+            "{} Scanning {} AUR packages...",
+            "📦".bright_cyan(),
+            installed.len()
+        );
+
+        for pkg in installed {
+            if config.is_ignored(&pkg) {
+                println!(
+                    "{} Skipping ignored package: {}",
+                    "⏭️".yellow(),
+                    pkg.dimmed()
+                );
+                continue;
+            }
+
+            match crate::aur::fetch_package_info(&pkg) {
+                Ok(remote) => {
+                    let local_ver = crate::pacman::get_version(&pkg);
+                    if let Some(local) = local_ver
+                        && pacman::is_newer(&remote.version, &local)
+                    {
+                        updates_available.push((pkg.clone(), local, remote.version));
+                    }
+                }
+                Err(e @ ReapError::NetworkError(_)) => {
+                    println!("{} Skipping {}: {}", "⚠️".yellow(), pkg, e);
+                }
+                Err(_) => {}
+            }
+        }
+        updates_available
+    })
+    .await
+    .unwrap_or_default();
+
+    if updates_available.is_empty() {
+        println!("{} All AUR packages are up to date!", "✅".bright_green());
+    } else {
+        println!(
+            "\n{} {} package(s) can be updated:",
+            "📋".bright_yellow(),
+            updates_available.len().to_string().bright_white()
+        );
+        for (pkg, local_ver, remote_ver) in &updates_available {
+            println!(
+                "  {} {} → {}",
+                pkg.bright_white(),
+                local_ver.red(),
+                remote_ver.bright_green()
+            );
+        }
+        println!(
+            "\n{} Run {} to upgrade all packages",
+            "💡".bright_blue(),
+            "reap -Syu".bright_cyan()
+        );
+        if notify && GlobalConfig::load().notify.unwrap_or(true) {
+            let names: Vec<&str> = updates_available
+                .iter()
+                .map(|(p, _, _)| p.as_str())
+                .collect();
+            let (summary, body) = utils::update_notification(&names);
+            // Without a notification daemon the summary still reaches the
+            // journal of the timer's service
+            if !utils::send_notification(&summary, &body) {
+                println!("[reap] {}: {}", summary, body);
+            }
+        }
+    }
+}
+
+/// `reap generate-timer`: write `reap-upgrade.service` and
+/// `reap-upgrade.timer` to the systemd user unit dir
+pub fn handle_generate_timer(
+    schedule: &str,
+    aur_only: bool,
+    notify_only: bool,
+    dir: Option<PathBuf>,
+) -> Result<(), ReapError> {
+    let dir = dir.unwrap_or_else(utils::systemd_user_dir);
+    let exe = std::env::current_exe()
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|_| "reap".to_string());
+    fs::create_dir_all(&dir)?;
+    let service = dir.join("reap-upgrade.service");
+    let timer = dir.join("reap-upgrade.timer");
+    fs::write(
+        &service,
+        utils::upgrade_service_unit(&exe, aur_only, notify_only),
+    )?;
+    fs::write(&timer, utils::upgrade_timer_unit(schedule))?;
+    println!("[reap] Wrote {}", service.display());
+    println!("[reap] Wrote {}", timer.display());
+    println!("\nEnable the timer with:");
+    println!("    systemctl --user daemon-reload");
+    println!("    systemctl --user enable --now reap-upgrade.timer");
+    println!("Check when it runs next with:");
+    println!("    systemctl --user list-timers reap-upgrade.timer");
+    Ok(())
+}
+
+/// `pacman -Sy`, returning whether the databases synced
+pub fn handle_sync_db() -> bool {
+    use colored::Colorize;
+    println!("{} Synchronizing package databases...", "🔄".bright_blue());
+
+    let status = {
+        let _transaction = pacman::transaction();
+        crate::utils::escalate("pacman").arg("-Sy").status()
+    };
+
+    match status {
+        Ok(s) if s.success() => {
+            println!("{} Database sync completed", "✅".bright_green());
+            true
+        }
+        Ok(_) => {
+            eprintln!("{} Failed to sync database", "❌".bright_red());
+            false
+        }
+        Err(e) => {
+            eprintln!("{} Error syncing database: {}", "❌".bright_red(), e);
+            false
+        }
+    }
+}
+
+/// Upgrade repo packages with pacman so AUR builds run against an
+/// up-to-date system. `refresh` syncs the databases first (`-Syu`).
+pub fn handle_system_upgrade(refresh: bool) -> bool {
+    use colored::Colorize;
+    let flag = if refresh { "-Syu" } else { "-Su" };
+    println!(
+        "{} Upgrading repo packages (pacman {})...",
+        "🔄".bright_blue(),
+        flag
+    );
+    let _transaction = pacman::transaction();
+    match crate::utils::escalate("pacman").arg(flag).status() {
+        Ok(s) if s.success() => true,
+        Ok(_) => {
+            eprintln!("{} System upgrade failed", "❌".bright_red());
+            false
+        }
+        Err(e) => {
+            eprintln!("{} Error running pacman: {}", "❌".bright_red(), e);
+            false
+        }
+    }
+}
+
+pub async fn handle_upgrade_all() {
+    use colored::Colorize;
+    println!("{} Upgrading all packages...", "🚀".bright_blue());
+    let before = pacman::installed_versions();
+    let error = aur::upgrade_all().await.err().map(|e| e.to_string());
+    if let Some(e) = &error {
+        eprintln!("{} Upgrade all failed: {}", "❌".bright_red(), e);
+    }
+    log_operation(OperationKind::Upgrade, &[], &before, error);
+}
+
+/// Record the dependencies pacman installed while building `pkg` (anything
+/// new since `before` that is installed as a dependency) and, with
+/// `clean_after`, offer to remove the ones nothing needs
+fn track_build_deps(
+    pkg: &str,
+    before: &std::collections::BTreeMap<String, String>,
+    clean_after: bool,
+    log: &dyn ProgressReporter,
+) {
+    let as_deps = pacman::installed_as_deps();
+    let new_deps: Vec<String> = pacman::installed_versions()
+        .into_keys()
+        .filter(|name| !before.contains_key(name) && as_deps.contains(name))
+        .collect();
+    if new_deps.is_empty() {
+        return;
+    }
+    let path = crate::history::BuildDeps::path();
+    let mut tracked = crate::history::BuildDeps::load_from(&path);
+    tracked.record(pkg, new_deps.iter().cloned());
+    if clean_after {
+        log.step(
+            "clean",
+            &format!("Build pulled in: {}", new_deps.join(", ")),
+        );
+        match remove_build_deps(&new_deps, true) {
+            Ok(removed) => tracked.forget(&removed),
+            Err(e) => log.step("clean", &format!("❌ {}", e)),
+        }
+    }
+    if let Err(e) = tracked.save_to(&path) {
+        log.step(
+            "clean",
+            &format!("Failed to record build dependencies: {}", e),
+        );
+    }
+}
+
+/// The `candidates` that are orphans, i.e. installed as dependencies but
+/// required (or optionally required) by nothing. Deps shared with other
+/// installed packages are never orphans, so they are kept.
+pub fn removable_build_deps(
+    candidates: &[String],
+    orphans: &std::collections::BTreeSet<String>,
+) -> Vec<String> {
+    candidates
+        .iter()
+        .filter(|dep| orphans.contains(*dep))
+        .cloned()
+        .collect()
+}
+
+/// Remove the `candidates` nothing needs, after asking when `confirm`.
+/// Removing one can orphan another candidate (a makedepend's own deps), so
+/// this repeats until no more become removable. Returns what was removed.
+pub fn remove_build_deps(candidates: &[String], confirm: bool) -> Result<Vec<String>, ReapError> {
+    let mut removable = removable_build_deps(candidates, &pacman::orphans());
+    if removable.is_empty() {
+        println!("[reap] No build dependencies to remove");
+        return Ok(Vec::new());
+    }
+    if !confirm_proceed(
+        confirm,
+        &format!("Remove build dependencies {}?", removable.join(", ")),
+    ) {
+        return Ok(Vec::new());
+    }
+    let mut removed = Vec::new();
+    while !removable.is_empty() {
+        let _transaction = pacman::transaction();
+        let status = crate::utils::escalate("pacman")
+            .args(["-R", "--noconfirm"])
+            .args(&removable)
+            .status()?;
+        if !status.success() {
+            return Err(ReapError::CommandFailed(format!(
+                "pacman -R {} failed",
+                removable.join(" ")
+            )));
+        }
+        println!("[reap] Removed {}", removable.join(", "));
+        removed.append(&mut removable);
+        removable = removable_build_deps(candidates, &pacman::orphans());
+    }
+    Ok(removed)
+}
+
+/// `reap clean --uninstalled-build-deps`: remove the tracked build
+/// dependencies nothing needs anymore
+pub fn handle_clean_build_deps(confirm: bool) -> Result<(), ReapError> {
+    let path = crate::history::BuildDeps::path();
+    let mut tracked = crate::history::BuildDeps::load_from(&path);
+    // Forget deps that were removed some other way
+    let installed = pacman::installed_versions();
+    let gone: Vec<String> = tracked
+        .all()
+        .into_iter()
+        .filter(|dep| !installed.contains_key(dep))
+        .collect();
+    tracked.forget(&gone);
+    let candidates: Vec<String> = tracked.all().into_iter().collect();
+    let removed = remove_build_deps(&candidates, confirm)?;
+    tracked.forget(&removed);
+    tracked
+        .save_to(&path)
+        .map_err(|e| ReapError::CommandFailed(e.to_string()))
+}
+
+pub fn handle_clean() {
+    println!("[reap] Cleaning package cache...");
+    let _transaction = pacman::transaction();
+    let status = crate::utils::escalate("pacman")
+        .arg("-Sc")
+        .arg("--noconfirm")
+        .status();
+
+    match status {
+        Ok(s) if s.success() => println!("[reap] Cache cleaned successfully"),
+        Ok(_) => eprintln!("[reap] Failed to clean cache"),
+        Err(e) => eprintln!("[reap] Error cleaning cache: {}", e),
+    }
+}
+
+/// Cached package files to remove so only the `keep` newest versions of
+/// each package (per architecture) stay, like `paccache -rk N`. Versions
+/// come from the file names and compare like `vercmp`; the modification
+/// time only breaks ties. Signatures go with their package.
+pub fn cache_prune_candidates(
+    files: &[(String, std::time::SystemTime)],
+    keep: usize,
+) -> Vec<String> {
+    type Versions<'a> = Vec<(&'a str, String, std::time::SystemTime)>;
+    let mut groups: HashMap<(&str, &str), Versions> = HashMap::new();
+    for (file, modified) in files {
+        let Some((stem, _)) = file.split_once(".pkg.tar") else {
+            continue;
+        };
+        if file.ends_with(".sig") || file.ends_with(".part") {
+            continue;
+        }
+        // name-pkgver-pkgrel-arch, where only the name may contain dashes
+        let mut parts = stem.rsplitn(4, '-');
+        let (Some(arch), Some(pkgrel), Some(pkgver), Some(name)) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+        groups.entry((name, arch)).or_default().push((
+            file.as_str(),
+            format!("{}-{}", pkgver, pkgrel),
+            *modified,
+        ));
+    }
+    let names: std::collections::HashSet<&str> =
+        files.iter().map(|(file, _)| file.as_str()).collect();
+    let mut remove = Vec::new();
+    for versions in groups.values_mut() {
+        versions.sort_by(|a, b| {
+            pacman::vercmp(&b.1, &a.1)
+                .then_with(|| b.2.cmp(&a.2))
+                .then_with(|| b.0.cmp(a.0))
+        });
+        for (file, _, _) in versions.iter().skip(keep) {
+            remove.push(file.to_string());
+            let sig = format!("{}.sig", file);
+            if names.contains(sig.as_str()) {
+                remove.push(sig);
+            }
+        }
+    }
+    remove.sort();
+    remove
+}
+
+/// `reap clean --keep N`: prune the pacman cache down to the `keep` most
+/// recent versions of each package, reporting the space freed
+pub fn handle_clean_keep(keep: usize) -> Result<(), ReapError> {
+    let dir = std::path::Path::new(PACMAN_CACHE_DIR);
+    let files: Vec<(String, std::time::SystemTime)> = fs::read_dir(dir)?
+        .flatten()
+        .filter_map(|entry| {
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            Some((entry.file_name().to_string_lossy().into_owned(), modified))
+        })
+        .collect();
+    let remove = cache_prune_candidates(&files, keep);
+    if remove.is_empty() {
+        println!(
+            "[reap] No cached package has more than {} version(s), nothing to prune",
+            keep
+        );
+        return Ok(());
+    }
+    let paths: Vec<PathBuf> = remove.iter().map(|file| dir.join(file)).collect();
+    let bytes: u64 = paths
+        .iter()
+        .filter_map(|path| fs::metadata(path).ok())
+        .map(|meta| meta.len())
+        .sum();
+    println!(
+        "[reap] Removing {} old cached package file(s), keeping {} version(s) of each package...",
+        remove.len(),
+        keep
+    );
+    let status = crate::utils::escalate("rm")
+        .arg("-f")
+        .args(&paths)
+        .status()?;
+    if !status.success() {
+        return Err(ReapError::CommandFailed(format!(
+            "rm exited with {} while pruning {}",
+            status, PACMAN_CACHE_DIR
+        )));
+    }
+    println!(
+        "[reap] Pruned the package cache, freed {:.1} MiB",
+        bytes as f64 / (1024.0 * 1024.0)
+    );
+    Ok(())
+}
+
+pub async fn handle_doctor(fix: bool) {
+    println!("[reap] Running system diagnostics...");
+    match crate::utils::doctor_report() {
+        Ok(report) => println!("[reap] Doctor report:\n{}", report),
+        Err(e) => eprintln!("[reap] Doctor error: {}", e),
+    }
+    if fix {
+        let report = crate::utils::doctor_fix().await;
+        for item in &report.fixed {
+            println!("[reap] ✅ Fixed: {}", item);
+        }
+        for item in &report.manual {
+            println!("[reap] ⚠️ Needs attention: {}", item);
+        }
+        if report.fixed.is_empty() && report.manual.is_empty() {
+            println!("[reap] Nothing to fix");
+        }
+    }
+}
+
+/// `reap fix-broken`: clean up after a reap run that was killed. Removes
+/// the pacman db lock when no process holds it open and a reap lockfile
+/// whose reap is gone, removes leftover AUR build dirs and retries installs
+/// that failed, asking before each step when `confirm`.
+pub async fn handle_fix_broken(confirm: bool, opts: &InstallOptions) -> Result<(), ReapError> {
+    if let Some(pid) = utils::running_processes("reap").first() {
+        return Err(ReapError::CommandFailed(format!(
+            "another reap is running (pid {}), let it finish first",
+            pid
+        )));
+    }
+    let mut fixed = false;
+
+    let lock = std::path::Path::new(utils::PACMAN_DB_LOCK);
+    let holders = if lock.exists() {
+        utils::lock_holders(lock, true)
+    } else {
+        Vec::new()
+    };
+    if utils::is_stale_db_lock(lock, !holders.is_empty()) {
+        fixed = true;
+        let msg = format!("Remove the stale pacman lock {}?", lock.display());
+        if confirm_proceed(confirm, &msg) {
+            let status = utils::escalate("rm").arg("-f").arg(lock).status()?;
+            if !status.success() {
+                return Err(ReapError::CommandFailed(format!(
+                    "rm exited with {} while removing {}",
+                    status,
+                    lock.display()
+                )));
+            }
+            println!("[reap] Removed {}", lock.display());
+        }
+    } else if lock.exists() {
+        let pids: Vec<String> = holders.iter().map(u32::to_string).collect();
+        println!(
+            "[reap] {} is held open (pid {}), leaving it alone",
+            lock.display(),
+            pids.join(", ")
+        );
+    }
+
+    let lockfile = build_root().join(crate::interrupt::LOCKFILE);
+    if crate::interrupt::is_stale_lockfile(&lockfile, crate::interrupt::is_running) {
+        fixed = true;
+        let msg = format!("Remove the stale reap lockfile {}?", lockfile.display());
+        if confirm_proceed(confirm, &msg) {
+            fs::remove_file(&lockfile)?;
+            println!(
+                "[reap] Removed the stale reap lockfile {}",
+                lockfile.display()
+            );
+        }
+    }
+
+    let leftover = leftover_build_dirs(&build_root());
+    if !leftover.is_empty() {
+        fixed = true;
+        println!("[reap] Build dirs left by an interrupted build:");
+        for dir in &leftover {
+            println!("  {}", dir.display());
+        }
+        if confirm_proceed(confirm, "Remove them?") {
+            for dir in &leftover {
+                match fs::remove_dir_all(dir) {
+                    Ok(()) => println!("[reap] Removed {}", dir.display()),
+                    Err(e) => eprintln!("[reap] Failed to remove {}: {}", dir.display(), e),
+                }
+            }
+        }
+    }
+
+    let installed = pacman::installed_versions();
+    let failed: Vec<String> = crate::history::OperationLog::load()
+        .failed_installs()
+        .into_iter()
+        .filter(|pkg| !installed.contains_key(pkg))
+        .collect();
+    if !failed.is_empty() {
+        fixed = true;
+        println!(
+            "[reap] Failed installs not installed since: {}",
+            failed.join(", ")
+        );
+        if confirm_proceed(confirm, "Retry installing them?") {
+            handle_install(failed, false, opts).await.into_result()?;
+        }
+    }
+
+    if !fixed {
+        println!("[reap] Nothing to fix");
+    }
+    Ok(())
+}
+
+/// `reap stats`: package counts and the disk space reap and pacman use
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReapStats {
+    pub installed_packages: usize,
+    /// Pacman packages from no sync repo (AUR, taps, local builds)
+    pub foreign_packages: usize,
+    pub flatpak_apps: usize,
+    pub enabled_taps: usize,
+    pub repo_updates: usize,
+    /// `None` when the AUR couldn't be asked (offline or a network error)
+    pub aur_updates: Option<usize>,
+    /// Cached sources, RPC responses, built packages, chroot and leftover build dirs
+    pub build_cache_bytes: u64,
+    pub backup_bytes: u64,
+    pub pacman_cache_bytes: u64,
+}
+
+/// Pacman's package cache
+const PACMAN_CACHE_DIR: &str = "/var/cache/pacman/pkg";
+
+impl ReapStats {
+    pub fn collect() -> Self {
+        let installed = get_installed_packages();
+        let flatpak_apps = installed
+            .values()
+            .filter(|s| matches!(s, Source::Flatpak))
+            .count();
+        let foreign = pacman::list_installed_aur();
+        let aur_updates = if aur::is_offline() {
+            None
+        } else {
+            aur::fetch_versions(&foreign)
+                .ok()
+                .map(|remote| count_aur_updates(&pacman::installed_versions(), &remote))
+        };
+        let cache_dir = dirs::cache_dir().unwrap_or_else(|| PathBuf::from("/tmp"));
+        let leftover_builds: u64 = fs::read_dir(build_root())
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter(|e| e.file_name().to_string_lossy().starts_with("reap-aur-"))
+                    .map(|e| crate::tap::dir_size(&e.path()))
+                    .sum()
+            })
+            .unwrap_or(0);
+        Self {
+            installed_packages: installed.len(),
+            foreign_packages: foreign.len(),
+            flatpak_apps,
+            enabled_taps: discover_taps().iter().filter(|t| t.enabled).count(),
+            repo_updates: utils::pending_repo_updates().len(),
+            aur_updates,
+            build_cache_bytes: crate::tap::dir_size(&cache_dir.join("reap")) + leftover_builds,
+            backup_bytes: crate::tap::dir_size(&crate::verify::backup_root()),
+            pacman_cache_bytes: crate::tap::dir_size(std::path::Path::new(PACMAN_CACHE_DIR)),
+        }
+    }
+
+    /// Human readable dashboard
+    pub fn render(&self) -> String {
+        let mib = |bytes: u64| format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0));
+        let aur = self
+            .aur_updates
+            .map_or_else(|| "unknown".to_string(), |n| n.to_string());
+        [
+            format!(
+                "Installed packages: {} ({} foreign, {} flatpak)",
+                self.installed_packages, self.foreign_packages, self.flatpak_apps
+            ),
+            format!(
+                "Updates available:  {} repo, {} AUR",
+                self.repo_updates, aur
+            ),
+            format!("Enabled taps:       {}", self.enabled_taps),
+            format!("Build cache:        {}", mib(self.build_cache_bytes)),
+            format!("Backups:            {}", mib(self.backup_bytes)),
+            format!("Pacman cache:       {}", mib(self.pacman_cache_bytes)),
+        ]
+        .join("\n")
+    }
+}
+
+/// Of the installed packages, how many have a newer version in `remote`
+pub fn count_aur_updates(
+    installed: &std::collections::BTreeMap<String, String>,
+    remote: &HashMap<String, String>,
+) -> usize {
+    remote
+        .iter()
+        .filter(|(name, version)| {
+            installed
+                .get(*name)
+                .is_some_and(|local| pacman::is_newer(version, local))
+        })
+        .count()
+}
+
+/// `reap stats [--json]`
+pub async fn handle_stats(json: bool) -> Result<(), ReapError> {
+    let stats = tokio::task::spawn_blocking(ReapStats::collect)
+        .await
+        .map_err(|e| ReapError::CommandFailed(e.to_string()))?;
+    if json {
+        let out = serde_json::to_string_pretty(&stats)
+            .map_err(|e| ReapError::CommandFailed(e.to_string()))?;
+        println!("{}", out);
+    } else {
+        println!("{}", stats.render());
+        if stats.build_cache_bytes + stats.backup_bytes > 1024 * 1024 * 1024 {
+            println!("[reap] Tip: `reap clean` frees the build cache");
+        }
+    }
+    Ok(())
+}
+
+/// Installed AUR packages whose AUR version differs from the local one,
+/// skipping anything in the ignore list
+fn outdated_aur_packages(config: &ReapConfig) -> Vec<String> {
+    let installed = crate::pacman::list_installed_aur();
+    let mut to_upgrade: Vec<String> = Vec::new();
+    for pkg in installed {
+        if config.is_ignored(&pkg) {
+            eprintln!("[reap] Skipping ignored package: {}", pkg);
+            continue;
+        }
+        match crate::aur::fetch_package_info(&pkg) {
+            Ok(remote) => {
+                let local_ver = crate::pacman::get_version(&pkg);
+                // Only genuine upgrades, not an older AUR version or a
+                // differently written one
+                if local_ver.is_none_or(|local| pacman::is_newer(&remote.version, &local)) {
+                    to_upgrade.push(pkg.to_string());
+                }
+            }
+            Err(e @ ReapError::NetworkError(_)) => eprintln!("[reap] Skipping {}: {}", pkg, e),
+            Err(_) => {}
+        }
+    }
+    to_upgrade
+}
+
+/// Installed VCS packages whose upstream moved past the commits they were
+/// last built from. Packages built before reap tracked them get their
+/// current upstream commits recorded as the baseline instead (with
+/// `record`, otherwise they are skipped).
+fn outdated_devel_packages(config: &ReapConfig, skip: &[String], record: bool) -> Vec<String> {
+    let mut store = crate::history::VcsCommits::load();
+    let mut outdated = Vec::new();
+    let mut recorded = false;
+    for pkg in crate::pacman::list_installed_aur() {
+        if skip.contains(&pkg) || config.is_ignored(&pkg) {
+            continue;
+        }
+        let built = std::fs::read_to_string(built_pkgbuild_path(&pkg)).ok();
+        if !aur::is_vcs_package(&pkg, built.as_deref()) {
+            continue;
+        }
+        let srcinfo = match aur::fetch_srcinfo(&pkg) {
+            Ok(srcinfo) => srcinfo,
+            Err(e) => {
+                eprintln!("[reap] Skipping devel check for {}: {}", pkg, e);
+                continue;
+            }
+        };
+        let Some(upstream) = aur::upstream_commits(&srcinfo) else {
+            eprintln!(
+                "[reap] Skipping devel check for {}: upstream unreachable",
+                pkg
+            );
+            continue;
+        };
+        if upstream.is_empty() {
+            continue;
+        }
+        if store.get(&pkg).is_none() {
+            if !record {
+                continue;
+            }
+            eprintln!("[reap] Tracking upstream commits of {} from now on", pkg);
+            store.set(&pkg, upstream);
+            recorded = true;
+        } else if store.is_outdated(&pkg, &upstream) {
+            outdated.push(pkg);
+        }
+    }
+    if recorded && let Err(e) = store.save_to(&crate::history::VcsCommits::path()) {
+        eprintln!("[reap] Failed to save VCS commits: {}", e);
+    }
+    outdated
+}
+
+/// Outcome of upgrading one backend
+#[derive(Debug, Default)]
+pub struct UpgradeReport {
+    pub backend: &'static str,
+    pub upgraded: usize,
+    pub failed: Vec<String>,
+    /// Why the backend was skipped, if it was
+    pub skipped: Option<&'static str>,
+}
+
+/// Upgrade repo packages with `pacman -Syu`
+pub async fn upgrade_repo() -> UpgradeReport {
+    let mut report = UpgradeReport {
+        backend: "repo",
+        ..Default::default()
+    };
+    let pending = tokio::task::spawn_blocking(utils::pending_repo_updates)
+        .await
+        .unwrap_or_default();
+    if pending.is_empty() {
+        println!("[reap][repo] All repo packages up to date.");
+        return report;
+    }
+    if handle_system_upgrade(true) {
+        report.upgraded = pending.len();
+    } else {
+        report.failed = pending;
+    }
+    report
+}
+
+/// Outdated AUR packages, and with `devel` the VCS packages with new
+/// upstream commits. `record` lets the devel check store baselines.
+fn aur_upgrade_candidates(
+    config: &ReapConfig,
+    devel: bool,
+    record: bool,
+) -> (Vec<String>, Vec<String>) {
+    let outdated = outdated_aur_packages(config);
+    let devel_updates = if devel && !aur::is_offline() {
+        outdated_devel_packages(config, &outdated, record)
+    } else {
+        Vec::new()
+    };
+    (outdated, devel_updates)
+}
+
+/// Upgrade outdated AUR packages with the native build path. `devel` also
+/// rebuilds VCS packages whose upstream has new commits.
+pub async fn upgrade_aur(parallel: bool, devel: bool) -> UpgradeReport {
+    use colored::Colorize;
+    use futures::stream::{self, StreamExt};
+    let mut report = UpgradeReport {
+        backend: "aur",
+        ..Default::default()
+    };
+    let config = crate::config::ReapConfig::load();
+    let check_config = config.clone();
+    let to_upgrade = tokio::task::spawn_blocking(move || {
+        let (mut to_upgrade, devel_updates) = aur_upgrade_candidates(&check_config, devel, true);
+        to_upgrade.extend(devel_updates);
+        to_upgrade
+    })
+    .await
+    .unwrap_or_default();
+    if to_upgrade.is_empty() {
+        println!("[reap][aur] All AUR packages up to date.");
+        return report;
+    }
+    println!("[reap][aur] Upgrading: {:?}", to_upgrade);
+    // Packages that depend on each other upgrade dependencies first
+    let pkgs = to_upgrade.clone();
+    let graph = tokio::task::spawn_blocking(move || upgrade_dependency_graph(&pkgs))
+        .await
+        .unwrap_or_default();
+    let levels = build_levels(&graph).unwrap_or_else(|e| {
+        eprintln!("[reap][aur] {}, upgrading in any order", e);
+        vec![to_upgrade]
+    });
+    let opts = InstallOptions::default();
+    let jobs = if parallel { config.parallel.max(1) } else { 1 };
+    for level in levels {
+        let (ready, blocked): (Vec<String>, Vec<String>) = level.into_iter().partition(|pkg| {
+            graph
+                .get(pkg)
+                .is_none_or(|deps| !deps.iter().any(|d| report.failed.contains(d)))
+        });
+        for pkg in blocked {
+            eprintln!(
+                "[reap][aur] Skipping {}: a dependency failed to upgrade",
+                pkg
+            );
+            report.failed.push(pkg);
+        }
+        let results: Vec<(String, Result<(), ReapError>)> = stream::iter(ready)
+            .map(|pkg| {
+                let opts = &opts;
+                async move {
+                    let res = install_aur_native(&pkg, &StdoutReporter, opts).await;
+                    (pkg, res)
+                }
+            })
+            .buffer_unordered(jobs)
+            .collect()
+            .await;
+        for (pkg, res) in results {
+            match res {
+                Ok(()) => report.upgraded += 1,
+                Err(e) => {
+                    eprintln!(
+                        "[reap][aur] Failed to upgrade {}: {}",
+                        pkg,
+                        e.to_string().red()
+                    );
+                    report.failed.push(pkg);
+                }
+            }
+        }
+    }
+    report
+}
+
+/// Restrict the dependencies of each package being upgraded to the other
+/// packages being upgraded, dropping version constraints
+pub fn upgrade_graph(
+    deps: HashMap<String, Vec<String>>,
+    pkgs: &[String],
+) -> HashMap<String, Vec<String>> {
+    deps.into_iter()
+        .map(|(pkg, deps)| {
+            let mut deps: Vec<String> = deps
+                .iter()
+                .map(|d| dep_name(d).to_string())
+                .filter(|d| *d != pkg && pkgs.contains(d))
+                .collect();
+            deps.sort();
+            deps.dedup();
+            (pkg, deps)
+        })
+        .collect()
+}
+
+/// Which of the AUR packages `pkgs` each one depends (or make-depends) on,
+/// from their PKGBUILDs. Fetches over the blocking client.
+fn upgrade_dependency_graph(pkgs: &[String]) -> HashMap<String, Vec<String>> {
+    let parser = crate::enhanced_aur::EnhancedAurManager::new();
+    let deps = pkgs
+        .iter()
+        .map(|pkg| {
+            let pkgbuild = aur::get_pkgbuild_preview(pkg);
+            let deps = parser
+                .parse_pkgbuild(pkg, &pkgbuild)
+                .map(|info| {
+                    info.dependencies
+                        .iter()
+                        .chain(&info.make_dependencies)
+                        .cloned()
+                        .collect()
+                })
+                .unwrap_or_default();
+            (pkg.clone(), deps)
+        })
+        .collect();
+    upgrade_graph(deps, pkgs)
+}
+
+/// Upgrade installed Flatpak apps
+pub async fn upgrade_flatpak_apps() -> UpgradeReport {
+    let mut report = UpgradeReport {
+        backend: "flatpak",
+        ..Default::default()
+    };
+    if !flatpak::is_flatpak_available() {
+        report.skipped = Some("not available");
+        return report;
+    }
+    let pending = tokio::process::Command::new("flatpak")
+        .args(["remote-ls", "--updates", "--app", "--columns=application"])
+        .output()
+        .await
+        .map(|o| String::from_utf8_lossy(&o.stdout).lines().count())
+        .unwrap_or(0);
+    if pending == 0 {
+        println!("[reap][flatpak] All Flatpak apps up to date.");
+        return report;
+    }
+    match flatpak::upgrade_flatpak().await {
+        Ok(()) => report.upgraded = pending,
+        Err(e) => report.failed.push(e.to_string()),
+    }
+    report
+}
+
+/// `reap news`: the newest `limit` Arch news items, which then count as read
+pub async fn handle_news(limit: usize) -> Result<(), ReapError> {
+    let items = crate::news::fetch_news().await?;
+    let shown = &items[..limit.min(items.len())];
+    if shown.is_empty() {
+        println!("[reap] No Arch news");
+        return Ok(());
+    }
+    crate::news::print_items(shown);
+    if let Err(e) = crate::news::mark_seen(&crate::news::seen_path(), shown) {
+        eprintln!("[reap] Failed to record the news as read: {}", e);
+    }
+    Ok(())
+}
+
+/// Before an upgrade, show Arch news published since the last one seen and
+/// ask whether to go on, since it may announce a manual intervention.
+/// Returns false when the user stops the upgrade, and also when nobody is
+/// there to answer (`--noconfirm`, a prompt timeout): unread news stops an
+/// unattended upgrade. News only counts as read after a "yes". Unreachable
+/// news doesn't hold anything up.
+pub async fn check_news_before_upgrade() -> bool {
+    if aur::is_offline() || !GlobalConfig::load().news_check.unwrap_or(true) {
+        return true;
+    }
+    let items = match crate::news::fetch_news().await {
+        Ok(items) => items,
+        Err(e) => {
+            eprintln!("[reap] Couldn't check Arch news: {}", e);
+            return true;
+        }
+    };
+    let path = crate::news::seen_path();
+    let unread = crate::news::unread(&items, crate::news::last_seen(&path), chrono::Utc::now());
+    if unread.is_empty() {
+        return true;
+    }
+    use colored::Colorize;
+    println!(
+        "[reap] {} Unread Arch news since your last upgrade:",
+        "⚠️".yellow()
+    );
+    crate::news::print_items(&unread);
+    use crate::interactive::{Answer, InteractiveManager};
+    match InteractiveManager::ask("Continue with the upgrade?", false) {
+        Answer::User(true) => {
+            if let Err(e) = crate::news::mark_seen(&path, &unread) {
+                eprintln!("[reap] Failed to record the news as read: {}", e);
+            }
+            true
+        }
+        Answer::User(false) => false,
+        Answer::NoConfirm | Answer::TimedOut | Answer::Closed => {
+            println!("\n[reap] Not upgrading unattended past unread news, read it and run again");
+            false
+        }
+    }
+}
+
+/// `reap mirrors [--rank]`: show how old the mirrorlist is and the mirrors
+/// pacman uses, or rank them and force a database sync
+pub fn handle_mirrors(rank: bool, country: Option<&str>, number: usize) -> Result<(), ReapError> {
+    let path = std::path::Path::new(crate::mirrors::MIRRORLIST);
+    if rank {
+        crate::mirrors::rank_mirrors(country, number)?;
+        let _transaction = pacman::transaction();
+        let status = utils::escalate("pacman").arg("-Syy").status()?;
+        if !status.success() {
+            return Err(ReapError::CommandFailed(format!(
+                "pacman -Syy exited with {}",
+                status
+            )));
+        }
+        return Ok(());
+    }
+    let servers = crate::mirrors::active_servers(&fs::read_to_string(path)?);
+    if let Some(age) = crate::mirrors::mirrorlist_age(path) {
+        println!(
+            "[reap] {} was updated {} days ago",
+            path.display(),
+            age.as_secs() / 86400
+        );
+    }
+    for server in &servers {
+        println!("  {}", server);
+    }
+    if crate::mirrors::Ranker::detect().is_none() {
+        println!(
+            "[reap] Install reflector (or pacman-contrib for rankmirrors) to rank them with `reap mirrors --rank`"
+        );
+    }
+    Ok(())
+}
+
+/// With `mirrorlist_max_age_days` set, offer to rank mirrors when the
+/// mirrorlist is older than that. Returns whether they were ranked (and
+/// the databases synced).
+pub fn check_mirrors_before_upgrade() -> bool {
+    let max_age_days = GlobalConfig::load().mirrorlist_max_age_days.unwrap_or(0);
+    let Some(age) =
+        crate::mirrors::mirrorlist_age(std::path::Path::new(crate::mirrors::MIRRORLIST))
+    else {
+        return false;
+    };
+    if !crate::mirrors::is_stale(age, max_age_days) {
+        return false;
+    }
+    let msg = format!(
+        "The mirrorlist is {} days old, rank mirrors first?",
+        age.as_secs() / 86400
+    );
+    if !crate::interactive::InteractiveManager::confirm_action(&msg, false) {
+        return false;
+    }
+    match handle_mirrors(true, None, crate::mirrors::DEFAULT_MIRROR_COUNT) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("[reap] Couldn't rank mirrors: {}", e);
+            false
+        }
+    }
+}
+
+/// Upgrade Flatpak apps on a task of their own when `enabled`, so the
+/// blocking pacman and makepkg runs of the other backends don't hold it up
+async fn spawn_flatpak_upgrade(enabled: bool) -> Option<UpgradeReport> {
+    if !enabled {
+        return None;
+    }
+    Some(
+        tokio::spawn(upgrade_flatpak_apps())
+            .await
+            .unwrap_or_else(|e| UpgradeReport {
+                backend: "flatpak",
+                failed: vec![e.to_string()],
+                ..Default::default()
+            }),
+    )
+}
+
+/// Upgrade the selected backends, or all of them (repo, AUR, Flatpak) when
+/// none are selected, and print a combined summary. `devel` is passed on to
+/// [`upgrade_aur`]. Flatpak apps upgrade alongside the repo and AUR ones,
+/// which stay in order so AUR packages build against an upgraded system.
+pub async fn handle_upgrade(parallel: bool, repo: bool, aur: bool, flatpak: bool, devel: bool) {
+    let all = !(repo || aur || flatpak);
+    if (all || repo) && !check_news_before_upgrade().await {
+        println!("[reap] Upgrade cancelled");
+        return;
+    }
+    let before = pacman::installed_versions();
+    let packages = async {
+        let mut reports = Vec::new();
+        if all || repo {
+            reports.push(upgrade_repo().await);
+        }
+        if all || aur {
+            reports.push(upgrade_aur(parallel, devel).await);
+        }
+        reports
+    };
+    let (mut reports, apps) = tokio::join!(packages, spawn_flatpak_upgrade(all || flatpak));
+    reports.extend(apps);
+    finish_upgrade(&reports, &before);
+}
+
+/// One package `reap upgrade --dry-run` would upgrade or rebuild
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedUpgrade {
+    pub name: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// Rebuilt for new upstream commits of a VCS package (`--devel`)
+    pub devel: bool,
+    /// Dependencies it needs that nothing installed satisfies yet
+    pub new_dependencies: Vec<String>,
+}
+
+/// Everything an upgrade would change, computed without touching the system
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UpgradePlan {
+    pub repo: Vec<PlannedUpgrade>,
+    /// Packages the repo upgrade would newly install as dependencies
+    pub repo_new_dependencies: Vec<String>,
+    pub aur: Vec<PlannedUpgrade>,
+    pub flatpak: Vec<String>,
+}
+
+impl UpgradePlan {
+    pub fn is_empty(&self) -> bool {
+        self.repo.is_empty() && self.aur.is_empty() && self.flatpak.is_empty()
+    }
+
+    /// Work out the upgrade of the selected backends, or all of them when
+    /// none are selected. Blocking.
+    pub fn collect(repo: bool, aur: bool, flatpak: bool, devel: bool) -> Self {
+        let all = !(repo || aur || flatpak);
+        let mut plan = UpgradePlan::default();
+        let installed = pacman::installed_versions();
+        if all || repo {
+            plan.repo = utils::pending_repo_update_versions()
+                .into_iter()
+                .map(|(name, from, to)| PlannedUpgrade {
+                    name,
+                    from: Some(from).filter(|v| !v.is_empty()),
+                    to: Some(to).filter(|v| !v.is_empty()),
+                    devel: false,
+                    new_dependencies: Vec::new(),
+                })
+                .collect();
+            if !plan.repo.is_empty() {
+                plan.repo_new_dependencies = repo_upgrade_targets()
+                    .into_iter()
+                    .filter(|name| !installed.contains_key(name))
+                    .collect();
+            }
+        }
+        if all || aur {
+            let config = ReapConfig::load();
+            let (outdated, devel_updates) = aur_upgrade_candidates(&config, devel, false);
+            let names: Vec<String> = outdated.iter().chain(&devel_updates).cloned().collect();
+            let remote = aur::fetch_versions(&names).unwrap_or_default();
+            plan.aur = names
+                .iter()
+                .map(|name| PlannedUpgrade {
+                    name: name.clone(),
+                    from: installed.get(name).cloned(),
+                    to: remote.get(name).cloned(),
+                    devel: devel_updates.contains(name),
+                    new_dependencies: aur_new_dependencies(name),
+                })
+                .collect();
+        }
+        if (all || flatpak) && flatpak::is_flatpak_available() {
+            plan.flatpak = Command::new("flatpak")
+                .args(["remote-ls", "--updates", "--app", "--columns=application"])
+                .output()
+                .map(|o| {
+                    String::from_utf8_lossy(&o.stdout)
+                        .lines()
+                        .map(|l| l.trim().to_string())
+                        .filter(|l| !l.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default();
+        }
+        plan
+    }
+
+    pub fn render(&self) -> String {
+        if self.is_empty() {
+            return "Nothing to upgrade.".to_string();
+        }
+        let line = |pkg: &PlannedUpgrade| {
+            let mut line = format!(
+                "  {:<28} {} -> {}",
+                pkg.name,
+                pkg.from.as_deref().unwrap_or("?"),
+                pkg.to.as_deref().unwrap_or("?")
+            );
+            if pkg.devel {
+                line.push_str(" (new upstream commits)");
+            }
+            if !pkg.new_dependencies.is_empty() {
+                line.push_str(&format!(
+                    " (new dependencies: {})",
+                    pkg.new_dependencies.join(", ")
+                ));
+            }
+            line
+        };
+        let mut out = Vec::new();
+        if !self.repo.is_empty() {
+            out.push(format!("Repo packages to upgrade ({}):", self.repo.len()));
+            out.extend(self.repo.iter().map(line));
+            if !self.repo_new_dependencies.is_empty() {
+                out.push(format!(
+                    "  New dependencies: {}",
+                    self.repo_new_dependencies.join(", ")
+                ));
+            }
+        }
+        if !self.aur.is_empty() {
+            out.push(format!("AUR packages to rebuild ({}):", self.aur.len()));
+            out.extend(self.aur.iter().map(line));
+        }
+        if !self.flatpak.is_empty() {
+            out.push(format!("Flatpak apps to update ({}):", self.flatpak.len()));
+            out.extend(self.flatpak.iter().map(|app| format!("  {}", app)));
+        }
+        out.join("\n")
+    }
+}
+
+/// Every package `pacman -Su` would install from the local sync databases,
+/// including new dependencies
+fn repo_upgrade_targets() -> Vec<String> {
+    Command::new("pacman")
+        .args(["-Sup", "--print-format", "%n"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| {
+            String::from_utf8_lossy(&o.stdout)
+                .lines()
+                .map(|l| l.trim().to_string())
+                .filter(|l| !l.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Dependencies of the current AUR version of `pkg` that nothing installed
+/// satisfies
+fn aur_new_dependencies(pkg: &str) -> Vec<String> {
+    let Some(srcinfo) = aur::fetch_srcinfo(pkg)
+        .ok()
+        .and_then(|text| crate::srcinfo::SrcInfo::parse(&text).ok())
+    else {
+        return Vec::new();
+    };
+    let arch = host_arch();
+    let mut deps = srcinfo.get(pkg, "depends", arch);
+    deps.extend(srcinfo.get(pkg, "makedepends", arch));
+    deps.extend(srcinfo.get(pkg, "checkdepends", arch));
+    deps.sort();
+    deps.dedup();
+    unsatisfied_deps(&deps)
+}
+
+/// `reap upgrade --dry-run [--json]`: print what an upgrade would change
+pub async fn handle_upgrade_plan(
+    repo: bool,
+    aur: bool,
+    flatpak: bool,
+    devel: bool,
+    json: bool,
+) -> Result<(), ReapError> {
+    let plan = tokio::task::spawn_blocking(move || UpgradePlan::collect(repo, aur, flatpak, devel))
+        .await
+        .map_err(|e| ReapError::CommandFailed(e.to_string()))?;
+    if json {
+        let out = serde_json::to_string_pretty(&plan)
+            .map_err(|e| ReapError::CommandFailed(e.to_string()))?;
+        println!("{}", out);
+    } else {
+        println!("{}", plan.render());
+    }
+    Ok(())
+}
+
+/// Print the combined summary of `reports` and record the upgrade in the
+/// history
+fn finish_upgrade(reports: &[UpgradeReport], before: &std::collections::BTreeMap<String, String>) {
+    use colored::Colorize;
+    println!("\n{} Upgrade summary:", "📋".bright_yellow());
+    for report in reports {
+        if let Some(reason) = report.skipped {
+            println!("  {:<8} skipped ({})", report.backend, reason);
+        } else if report.failed.is_empty() {
+            println!("  {:<8} {} upgraded", report.backend, report.upgraded);
+        } else {
+            println!(
+                "  {:<8} {} upgraded, {} failed: {}",
+                report.backend,
+                report.upgraded,
+                report.failed.len().to_string().red(),
+                report.failed.join(", ")
+            );
+        }
+    }
+    let failed: Vec<&str> = reports
+        .iter()
+        .flat_map(|r| r.failed.iter().map(String::as_str))
+        .collect();
+    // Everything was up to date or skipped, so there is nothing to record
+    if failed.is_empty() && reports.iter().all(|r| r.upgraded == 0) {
+        return;
+    }
+    let error = (!failed.is_empty()).then(|| format!("failed: {}", failed.join(", ")));
+    log_operation(OperationKind::Upgrade, &[], before, error);
+}
+
+/// `reap sysupgrade` / `-Syu`: sync the databases (unless `refresh` is
+/// false, for `-Su`), upgrade repo packages, and only once that succeeded
+/// rebuild outdated AUR packages in dependency order, so nothing is built
+/// against a partially upgraded system. With `flatpak`, Flatpak apps
+/// upgrade alongside.
+pub async fn handle_sysupgrade(refresh: bool, parallel: bool, devel: bool, flatpak: bool) -> bool {
+    if !check_news_before_upgrade().await {
+        println!("[reap] Upgrade cancelled");
+        return false;
+    }
+    // Ranking mirrors syncs the databases against the new ones
+    let refresh = !check_mirrors_before_upgrade() && refresh;
+    let before = pacman::installed_versions();
+    let apps = spawn_flatpak_upgrade(flatpak);
+    let (mut reports, apps) = tokio::join!(upgrade_repo_then_aur(refresh, parallel, devel), apps);
+    reports.extend(apps);
+    finish_upgrade(&reports, &before);
+    reports.iter().all(|r| r.failed.is_empty())
+}
+
+/// The repo and AUR half of [`handle_sysupgrade`], repo report first
+async fn upgrade_repo_then_aur(refresh: bool, parallel: bool, devel: bool) -> Vec<UpgradeReport> {
+    let mut repo = UpgradeReport {
+        backend: "repo",
+        ..Default::default()
+    };
+    let synced = !refresh || handle_sync_db();
+    if synced {
+        let pending = tokio::task::spawn_blocking(utils::pending_repo_updates)
+            .await
+            .unwrap_or_default();
+        if pending.is_empty() {
+            println!("[reap][repo] All repo packages up to date.");
+        } else if handle_system_upgrade(false) {
+            repo.upgraded = pending.len();
+        } else {
+            repo.failed = pending;
+        }
+    } else {
+        repo.failed.push("database sync".to_string());
+    }
+    let repo_ok = repo.failed.is_empty();
+    let mut reports = vec![repo];
+    if repo_ok {
+        reports.push(upgrade_aur(parallel, devel).await);
+    } else {
+        eprintln!(
+            "[reap] Repo upgrade failed, not rebuilding AUR packages against a partial upgrade"
+        );
+        reports.push(UpgradeReport {
+            backend: "aur",
+            skipped: Some("repo upgrade failed"),
+            ..Default::default()
+        });
+    }
+    reports
+}
+
+/// `reap history`: the newest `limit` operations from `history.json`
+pub fn handle_history(limit: usize) {
+    use colored::Colorize;
+    let log = OperationLog::load();
+    if log.operations.is_empty() {
+        println!("[reap] No operations recorded yet");
+        return;
+    }
+    for op in log.recent(limit) {
+        let count = |f: fn(&PackageChange) -> bool| op.changes.iter().filter(|c| f(c)).count();
+        let added = count(|c| c.old_version.is_none());
+        let removed = count(|c| c.new_version.is_none());
+        let changed = op.changes.len() - added - removed;
+        let result = match &op.error {
+            None => "✓".green().to_string(),
+            Some(e) => format!("{} {}", "✗".red(), e.red()),
+        };
+        let packages = if op.packages.is_empty() {
+            "(all)".to_string()
+        } else {
+            op.packages.join(", ")
+        };
+        println!(
+            "#{:<4} {}  {:<8} {:<30} +{} ~{} -{}  {}{}",
+            op.id,
+            op.at.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+            op.kind.label(),
+            packages,
+            added,
+            changed,
+            removed,
+            result,
+            if op.undone {
+                " (undone)".dimmed().to_string()
+            } else {
+                String::new()
+            }
+        );
+    }
+}
+
+/// `reap log <pkg> [--json]`: every recorded event of one package
+pub fn handle_package_log(pkg: &str, json: bool) -> Result<(), ReapError> {
+    let log = crate::history::PackageLog::collect(
+        &OperationLog::load(),
+        &crate::history::InstallRegistry::load(),
+        pkg,
+    );
+    if json {
+        let out = serde_json::to_string_pretty(&log)
+            .map_err(|e| ReapError::CommandFailed(e.to_string()))?;
+        println!("{}", out);
+    } else {
+        println!("{}", log.render());
+    }
+    Ok(())
+}
+
+/// `reap history --undo <id>`: reverse an operation as far as possible.
+/// Packages it installed are removed; packages it removed or upgraded go
+/// back to their old version from a cached package file, and removed
+/// packages without one are reinstalled from their source.
+pub async fn handle_undo(id: u64, confirm: bool) -> Result<(), ReapError> {
+    use colored::Colorize;
+    let path = OperationLog::path();
+    let mut log = OperationLog::load_from(&path);
+    let op = log
+        .get(id)
+        .cloned()
+        .ok_or_else(|| ReapError::CommandFailed(format!("no operation #{} in the history", id)))?;
+    if op.undone {
+        return Err(ReapError::CommandFailed(format!(
+            "operation #{} was already undone",
+            id
+        )));
+    }
+    let installed = pacman::installed_versions();
+    let mut remove = Vec::new();
+    let mut restore = Vec::new();
+    let mut reinstall = Vec::new();
+    let mut stuck = Vec::new();
+    for change in &op.changes {
+        match (&change.old_version, &change.new_version) {
+            (None, Some(_)) if installed.contains_key(&change.name) => {
+                remove.push(change.name.clone())
+            }
+            (None, _) => {}
+            (Some(old), new) => {
+                match pacman::cached_package(&change.name, old, &package_output_dir()) {
+                    Some(file) => restore.push(file),
+                    None if new.is_none() => reinstall.push(change.name.clone()),
+                    None => stuck.push(format!("{} {}", change.name, old)),
+                }
+            }
+        }
+    }
+    println!(
+        "[reap] Undoing #{} ({} {})",
+        id,
+        op.kind.label(),
+        op.packages.join(", ")
+    );
+    for name in &remove {
+        println!("  {} remove {}", "-".red(), name);
+    }
+    for file in &restore {
+        println!("  {} restore {}", "↺".cyan(), file.display());
+    }
+    for name in &reinstall {
+        println!("  {} reinstall {} (no cached package)", "+".green(), name);
+    }
+    for name in &stuck {
+        println!(
+            "  {} {} is not cached, keeping the current version",
+            "⚠️".yellow(),
+            name
+        );
+    }
+    if remove.is_empty() && restore.is_empty() && reinstall.is_empty() {
+        println!("[reap] Nothing to undo");
+        return Ok(());
+    }
+    if !confirm_proceed(confirm, "Undo this operation?") {
+        return Err(ReapError::UserAborted);
+    }
+    // Already confirmed above, pacman mustn't ask again
+    let pacman_run = |args: &[&str], targets: Vec<std::ffi::OsString>| {
+        let _transaction = pacman::transaction();
+        let status = crate::utils::escalate("pacman")
+            .args(args)
+            .arg("--noconfirm")
+            .args(targets)
+            .status()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(ReapError::CommandFailed(format!(
+                "pacman {} failed",
+                args.join(" ")
+            )))
+        }
+    };
+    if !remove.is_empty() {
+        pacman_run(&["-R"], remove.iter().map(Into::into).collect())?;
+    }
+    if !restore.is_empty() {
+        pacman_run(&["-U"], restore.iter().map(Into::into).collect())?;
+    }
+    if !reinstall.is_empty() {
+        handle_install(reinstall, false, &InstallOptions::default())
+            .await
+            .into_result()?;
+    }
+    log.mark_undone(id);
+    log.save_to(&path)
+        .map_err(|e| ReapError::CommandFailed(e.to_string()))?;
+    println!("[reap] Undid operation #{}", id);
+    Ok(())
+}
+
+pub fn handle_rollback(pkg: &str) {
+    // Restore or remove utils::rollback and hooks::on_rollback
+    if let Some(rollback_fn) = std::option::Option::Some(utils::rollback) {
+        rollback_fn(pkg);
+    }
+}
+
+pub fn handle_orphan(remove: bool, all: bool, confirm: bool) {
+    let output = std::process::Command::new("pacman")
+        .args(["-Qdtq"])
+        .output();
+    let mut aur_orphans = Vec::new();
+    let mut repo_orphans = Vec::new();
+    if let Ok(out) = output {
+        for pkg in String::from_utf8_lossy(&out.stdout).lines() {
+            let repo_check = std::process::Command::new("pacman")
+                .arg("-Si")
+                .arg(pkg)
+                .output();
+            let is_repo = repo_check
+                .as_ref()
+                .map(|o| o.status.success())
+                .unwrap_or(false);
+            if is_repo {
+                repo_orphans.push(pkg.to_string());
+            } else {
+                aur_orphans.push(pkg.to_string());
+            }
+        }
+    }
+    if !aur_orphans.is_empty() {
+        println!("Orphaned AUR packages:\n");
+        let registry = crate::history::InstallRegistry::load();
+        for pkg in &aur_orphans {
+            match registry.get(pkg) {
+                Some(record) => println!(
+                    "    {} (installed by reap from {} on {})",
+                    pkg,
+                    record.source.label(),
+                    record.installed_at.format("%Y-%m-%d")
+                ),
+                None => println!("    {}", pkg),
+            }
+        }
+        if remove && confirm_proceed(confirm, "Remove these orphaned AUR packages?") {
+            for pkg in &aur_orphans {
+                println!("[reap] Uninstalling orphaned AUR package: {}", pkg);
+                crate::aur::uninstall(pkg);
+            }
+        } else {
+            println!("\nRun with --remove to uninstall.");
+        }
+    } else {
+        println!("No orphaned AUR packages found.");
+    }
+    if all && !repo_orphans.is_empty() {
+        println!("\nOrphaned pacman packages:\n");
+        for pkg in &repo_orphans {
+            println!("    {}", pkg);
+        }
+        if remove && confirm_proceed(confirm, "Remove these orphaned pacman packages?") {
+            for pkg in &repo_orphans {
+                println!("[reap] Uninstalling orphaned pacman package: {}", pkg);
+                crate::aur::uninstall(pkg);
+            }
+        } else {
+            println!("\nRun with --remove to uninstall.");
+        }
+    }
+}
+
+/// Where the PKGBUILD of the last successful AUR build of `pkg` is kept
+pub fn built_pkgbuild_path(pkg: &str) -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("/tmp"))
+        .join("reap/pkgbuilds")
+        .join(pkg)
+        .join("PKGBUILD")
+}
+
+/// Lines added (`+ `) or removed (`- `) between two PKGBUILDs
+pub fn pkgbuild_changes(old: &str, new: &str) -> Vec<String> {
+    diff::lines(old, new)
+        .into_iter()
+        .filter_map(|d| match d {
+            diff::Result::Left(l) => Some(format!("- {}", l)),
+            diff::Result::Right(r) => Some(format!("+ {}", r)),
+            diff::Result::Both(..) => None,
+        })
+        .collect()
+}
+
+/// Number of lines added or removed between two PKGBUILDs
+pub fn count_changed_lines(old: &str, new: &str) -> usize {
+    pkgbuild_changes(old, new).len()
+}
+
+/// Check a build dir's `PKGBUILD.sig` against its PKGBUILD with gpg, `None`
+/// if the package ships no signature
+fn verify_pkgbuild_signature(build_dir: &std::path::Path) -> Option<bool> {
+    let sig = build_dir.join("PKGBUILD.sig");
+    if !sig.exists() {
+        return None;
+    }
+    Some(
+        Command::new("gpg")
+            .arg("--verify")
+            .arg(&sig)
+            .arg(build_dir.join("PKGBUILD"))
+            .stderr(std::process::Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success()),
+    )
+}
+
+/// Timestamp format in AUR build dir names, `reap-aur-<pkg>-<timestamp>`
+const BUILD_DIR_STAMP: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Whether `dir_name` is a build dir `install_aur_native` made for `pkg`
+/// (and not for another package whose name starts with `pkg-`)
+pub fn is_aur_build_dir(pkg: &str, dir_name: &str) -> bool {
+    dir_name
+        .strip_prefix(&format!("reap-aur-{}-", pkg))
+        .is_some_and(|stamp| chrono::NaiveDateTime::parse_from_str(stamp, BUILD_DIR_STAMP).is_ok())
+}
+
+/// Base directory of AUR build dirs, `GlobalConfig::build_root` read once
+static BUILD_ROOT: once_cell::sync::Lazy<PathBuf> =
+    once_cell::sync::Lazy::new(|| GlobalConfig::load().build_root());
+
+/// Where `install_aur_native` makes its build dirs (`build_dir` in reap.toml)
+pub fn build_root() -> PathBuf {
+    BUILD_ROOT.clone()
+}
+
+/// Leftover AUR build dirs of `pkg` in the build root
+pub fn aur_build_dirs(pkg: &str) -> Vec<PathBuf> {
+    fs::read_dir(build_root())
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| {
+                    p.is_dir()
+                        && p.file_name()
+                            .and_then(|n| n.to_str())
+                            .is_some_and(|n| is_aur_build_dir(pkg, n))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Whether `dir_name` is a build dir `install_aur_native` made for any package
+fn is_any_aur_build_dir(dir_name: &str) -> bool {
+    dir_name.strip_prefix("reap-aur-").is_some_and(|rest| {
+        rest.match_indices('-').any(|(i, _)| {
+            i > 0 && chrono::NaiveDateTime::parse_from_str(&rest[i + 1..], BUILD_DIR_STAMP).is_ok()
+        })
+    })
+}
+
+/// AUR build dirs of any package in `root`. Finished builds remove
+/// theirs, so these are left by builds that were interrupted (or are still
+/// running in another reap).
+pub fn leftover_build_dirs(root: &std::path::Path) -> Vec<PathBuf> {
+    fs::read_dir(root)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| {
+                    p.is_dir()
+                        && p.file_name()
+                            .and_then(|n| n.to_str())
+                            .is_some_and(is_any_aur_build_dir)
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// SHA-256 of a PKGBUILD, hex encoded, used to remember accepted revisions
+pub fn pkgbuild_hash(pkgbuild: &str) -> String {
+    ring::digest::digest(&ring::digest::SHA256, pkgbuild.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Show the PKGBUILD diff with `diff_cmd` from reap.toml. Returns false
+/// when it isn't set or couldn't run, so the built-in diff is shown instead.
+fn run_diff_cmd(pkg: &str, local: &str, remote: &str) -> bool {
+    let Some(diff_cmd) = GlobalConfig::load()
+        .diff_cmd
+        .filter(|cmd| !cmd.trim().is_empty())
+    else {
+        return false;
+    };
+    let dir = std::env::temp_dir().join(format!("reap-diff-{}-{}", pkg, std::process::id()));
+    let (old, new) = (dir.join("PKGBUILD.built"), dir.join("PKGBUILD.aur"));
+    let written = fs::create_dir_all(&dir)
+        .and_then(|_| fs::write(&old, local))
+        .and_then(|_| fs::write(&new, remote));
+    let status = written.and_then(|_| utils::diff_command(&diff_cmd, &old, &new).status());
+    let _ = fs::remove_dir_all(&dir);
+    match status {
+        // diff tools exit 1 when the files differ, 127 is the shell's
+        // "command not found"
+        Ok(status) if status.code() != Some(127) => true,
+        Ok(_) => {
+            eprintln!(
+                "[reap] diff_cmd '{}' not found, using the built-in diff",
+                diff_cmd
+            );
+            false
+        }
+        Err(e) => {
+            eprintln!("[reap] Failed to run diff_cmd '{}': {}", diff_cmd, e);
+            false
+        }
+    }
+}
+
+fn print_pkgbuild_diff(pkg: &str, local: &str, remote: &str) {
+    use colored::Colorize;
+    use std::fmt::Write;
+    if run_diff_cmd(pkg, local, remote) {
+        match count_changed_lines(local, remote) {
+            0 => println!("[reap] PKGBUILD for {} unchanged since last build", pkg),
+            n => println!("[reap] {} lines changed in PKGBUILD for {}", n, pkg),
+        }
+        return;
+    }
+    let mut out = String::new();
+    for d in diff::lines(local, remote) {
+        let _ = match d {
+            diff::Result::Left(l) => writeln!(out, "{}", format!("- {}", l).red()),
+            diff::Result::Right(r) => writeln!(out, "{}", format!("+ {}", r).green()),
+            diff::Result::Both(l, _) => writeln!(out, "  {}", l),
+        };
+    }
+    let _ = match count_changed_lines(local, remote) {
+        0 => writeln!(
+            out,
+            "[reap] PKGBUILD for {} unchanged since last build",
+            pkg
+        ),
+        n => writeln!(out, "[reap] {} lines changed in PKGBUILD for {}", n, pkg),
+    };
+    utils::page(&out);
+}
+
+fn read_built_pkgbuild(pkg: &str) -> String {
+    std::fs::read_to_string(built_pkgbuild_path(pkg)).unwrap_or_else(|_| {
+        println!(
+            "[reap] No built PKGBUILD stored for {}, showing the AUR PKGBUILD in full",
+            pkg
+        );
+        String::new()
+    })
+}
+
+/// Diff the last-built PKGBUILD of `pkg` against the current AUR one.
+/// Fetches over the blocking client, so async callers should use `spawn_blocking`.
+pub fn show_pkgbuild_diff(pkg: &str) {
+    let local = read_built_pkgbuild(pkg);
+    let remote = crate::aur::get_pkgbuild_preview(pkg);
+    print_pkgbuild_diff(pkg, &local, &remote);
+}
+
+// Parallel upgrades review one package at a time
+static REVIEW_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// The commit checked out in the AUR clone `build_dir`, or the PKGBUILD hash
+/// when it isn't a git checkout. Reviews are remembered by this.
+pub fn review_revision(build_dir: &std::path::Path, pkgbuild: &str) -> String {
+    Command::new("git")
+        .arg("-C")
+        .arg(build_dir)
+        .args(["rev-parse", "--verify", "HEAD"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .filter(|commit| !commit.is_empty())
+        .unwrap_or_else(|| pkgbuild_hash(pkgbuild))
+}
+
+/// Every file changed in the AUR repo at `build_dir` since commit `since`,
+/// or `None` if the checkout doesn't have that commit
+fn aur_repo_diff(build_dir: &std::path::Path, since: &str) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(build_dir)
+        .args(["diff", "--stat", "--patch", "--color=always"])
+        .arg(format!("{}..HEAD", since))
+        .output()
+        .ok()
+        .filter(|o| o.status.success())?;
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Ask the user to review the AUR repo of `pkg` in `build_dir` unless its
+/// current commit was accepted before. Shows everything changed since the
+/// last accepted commit (install scripts and patches too), or else the
+/// PKGBUILD diff against the last build. Accepted commits are remembered in
+/// `reviews.json`.
+pub fn review_pkgbuild(pkg: &str, build_dir: &std::path::Path, pkgbuild: &str) -> bool {
+    let revision = review_revision(build_dir, pkgbuild);
+    let reviews = crate::history::PkgbuildReviews::load();
+    if reviews.is_accepted(pkg, &revision) {
+        println!("[reap] PKGBUILD for {} already reviewed", pkg);
+        return true;
+    }
+    let _guard = REVIEW_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    println!("\n[reap] Review PKGBUILD for {}:", pkg);
+    let since = reviews.accepted.get(pkg);
+    match since.and_then(|since| aur_repo_diff(build_dir, since)) {
+        Some(diff) if !diff.trim().is_empty() => {
+            println!(
+                "[reap] Changes to {} since the last accepted commit {}:",
+                pkg,
+                since.map_or("", |c| &c[..c.len().min(12)])
+            );
+            utils::page(&diff);
+        }
+        _ => print_pkgbuild_diff(pkg, &read_built_pkgbuild(pkg), pkgbuild),
+    }
+    let accepted = crate::interactive::InteractiveManager::confirm_action(
+        &format!("Accept PKGBUILD for {}?", pkg),
+        true,
+    );
+    // A --noconfirm run didn't actually look at it, so don't remember it as reviewed
+    if accepted && !crate::interactive::noconfirm() {
+        let path = crate::history::PkgbuildReviews::path();
+        let mut reviews = crate::history::PkgbuildReviews::load_from(&path);
+        reviews.accept(pkg, &revision);
+        if let Err(e) = reviews.save_to(&path) {
+            eprintln!("[reap] Failed to save PKGBUILD review for {}: {}", pkg, e);
+        }
+    }
+    accepted
+}
+
+/// Installed packages that conflict with `pkg`, from the .SRCINFO in
+/// `build_dir` (so split packages and arch-specific conflicts are right)
+/// or else the PKGBUILD, checked against every installed package (see
+/// [`conflicting_packages`]).
+pub fn installed_conflicts(pkg: &str, build_dir: &std::path::Path, pkgbuild: &str) -> Vec<String> {
+    let (conflicts, provides) = match crate::srcinfo::parse_srcinfo(build_dir) {
+        Ok(srcinfo) => (
+            srcinfo.get(pkg, "conflicts", host_arch()),
+            srcinfo.get(pkg, "provides", host_arch()),
+        ),
+        Err(_) => {
+            match crate::enhanced_aur::EnhancedAurManager::new().parse_pkgbuild(pkg, pkgbuild) {
+                Ok(info) => (info.conflicts, info.provides),
+                Err(_) => return Vec::new(),
+            }
+        }
+    };
+    conflicting_packages(pkg, &conflicts, &provides, &pacman::package_deps(&[]))
+}
+
+/// The `installed` packages pacman would refuse to keep next to `pkg`:
+/// those `conflicts` names directly or through their `provides`, and those
+/// whose own `Conflicts With` names `pkg` or one of its `provides`. Version
+/// constraints are matched on the name only. An installed `pkg` itself is
+/// an upgrade, not a conflict.
+pub fn conflicting_packages(
+    pkg: &str,
+    conflicts: &[String],
+    provides: &[String],
+    installed: &[pacman::PackageDeps],
+) -> Vec<String> {
+    let ours: Vec<&str> = std::iter::once(pkg)
+        .chain(provides.iter().map(|p| dep_name(p)))
+        .collect();
+    let mut found: Vec<String> = installed
+        .iter()
+        .filter(|other| other.name != pkg)
+        .filter(|other| {
+            let names: Vec<&str> = std::iter::once(other.name.as_str())
+                .chain(other.provides.iter().map(|p| dep_name(p)))
+                .collect();
+            conflicts.iter().any(|c| names.contains(&dep_name(c)))
+                || other.conflicts.iter().any(|c| ours.contains(&dep_name(c)))
+        })
+        .map(|other| other.name.clone())
+        .collect();
+    found.sort();
+    found.dedup();
+    found
+}
+
+/// Where `makepkg` runs for AUR builds (`build_isolation` in reap.toml)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildIsolation {
+    /// Build as the invoking user, as makepkg normally does
+    None,
+    /// Build inside a systemd-nspawn container
+    Nspawn,
+    /// Build inside bubblewrap without network access and with a read-only root
+    Sandbox,
+}
+
+impl BuildIsolation {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "none" | "" => Some(Self::None),
+            "nspawn" => Some(Self::Nspawn),
+            "sandbox" | "bwrap" | "bubblewrap" => Some(Self::Sandbox),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Nspawn => "nspawn",
+            Self::Sandbox => "sandbox",
+        }
+    }
+
+    /// The configured isolation, or why it can't be used on this system
+    pub fn from_config(config: &GlobalConfig) -> Result<Self, String> {
+        let value = config.build_isolation.as_deref().unwrap_or("none");
+        let isolation = Self::parse(value).ok_or_else(|| {
+            format!(
+                "Unknown build_isolation '{}', expected none, nspawn or sandbox",
+                value
+            )
+        })?;
+        match isolation {
+            Self::None => Ok(isolation),
+            Self::Nspawn => Err(
+                "build_isolation = \"nspawn\" is not supported yet, use \"sandbox\" or \"none\""
+                    .to_string(),
+            ),
+            Self::Sandbox => which::which("bwrap").map(|_| isolation).map_err(|_| {
+                "build_isolation = \"sandbox\" needs bubblewrap: sudo pacman -S bubblewrap"
+                    .to_string()
+            }),
+        }
+    }
+}
+
+/// Run `cmd`, forwarding its stdout and stderr to `log` under `step`
+fn stream_logged(
+    cmd: &mut Command,
+    step: &str,
+    log: &dyn ProgressReporter,
+) -> std::io::Result<std::process::ExitStatus> {
+    stream_logged_with(cmd, step, log, None)
+}
+
+/// Like [`stream_logged`] for a makepkg build, which may stop at a prompt of
+/// the PKGBUILD (a license to accept, ...): when the output ends in an
+/// unfinished line and goes quiet for `build_prompt_timeout_secs`, the
+/// question is passed to `log` to answer, or the build is given end of
+/// input and fails with a clear message when it can't be answered.
+fn stream_build(
+    cmd: &mut Command,
+    step: &str,
+    log: &dyn ProgressReporter,
+) -> std::io::Result<std::process::ExitStatus> {
+    let secs = GlobalConfig::load()
+        .build_prompt_timeout_secs
+        .unwrap_or(DEFAULT_BUILD_PROMPT_TIMEOUT_SECS);
+    stream_logged_with(
+        cmd,
+        step,
+        log,
+        (secs > 0).then(|| std::time::Duration::from_secs(secs)),
+    )
+}
+
+/// Seconds of silence after an unfinished line before a build is taken to
+/// be waiting for input
+pub const DEFAULT_BUILD_PROMPT_TIMEOUT_SECS: u64 = 30;
+
+/// Move the complete lines (ended by `\n` or a `\r` progress update) out of
+/// `pending`, leaving an unfinished last line in it. Blank lines are dropped.
+pub fn take_lines(pending: &mut Vec<u8>) -> Vec<String> {
+    let Some(end) = pending.iter().rposition(|b| matches!(b, b'\n' | b'\r')) else {
+        return Vec::new();
+    };
+    let rest = pending.split_off(end + 1);
+    let done = std::mem::replace(pending, rest);
+    done.split(|b| matches!(b, b'\n' | b'\r'))
+        .map(|line| String::from_utf8_lossy(line).trim_end().to_string())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+/// [`stream_logged`], answering prompts after `prompt_timeout` of silence
+/// when set (see [`stream_build`])
+fn stream_logged_with(
+    cmd: &mut Command,
+    step: &str,
+    log: &dyn ProgressReporter,
+    prompt_timeout: Option<std::time::Duration>,
+) -> std::io::Result<std::process::ExitStatus> {
+    use std::io::{Read, Write};
+    use std::process::Stdio;
+    use std::sync::mpsc::RecvTimeoutError;
+    if prompt_timeout.is_some() {
+        cmd.stdin(Stdio::piped());
+    }
+    // Ctrl-C is winding down, nothing new starts
+    if crate::interrupt::interrupted() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Interrupted,
+            "interrupted",
+        ));
+    }
+    let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+    let _tracked = crate::interrupt::ChildGuard::track(&child, cmd);
+    let mut stdin = child.stdin.take();
+    // makepkg reports its phases on stderr, read both pipes as output
+    // arrives so the phase timing below is accurate and unfinished prompt
+    // lines are seen
+    let (tx, rx) = std::sync::mpsc::channel::<(usize, Vec<u8>)>();
+    let forward = |stream: usize,
+                   mut pipe: Box<dyn Read + Send>,
+                   tx: std::sync::mpsc::Sender<(usize, Vec<u8>)>| {
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            while let Ok(n) = pipe.read(&mut buf) {
+                if n == 0 || tx.send((stream, buf[..n].to_vec())).is_err() {
+                    break;
+                }
+            }
+        })
+    };
+    let readers = [
+        forward(0, Box::new(child.stdout.take().unwrap()), tx.clone()),
+        forward(1, Box::new(child.stderr.take().unwrap()), tx),
+    ];
+    let mut pending: [Vec<u8>; 2] = [Vec::new(), Vec::new()];
+    let mut check_started: Option<Instant> = None;
+    let mut handle_line = |line: &str| {
+        log.step(step, line);
+        let Some(phase) = makepkg_phase(line) else {
+            return;
+        };
+        if let Some(started) = check_started.take() {
+            log.step(
+                step,
+                &format!("check() took {}", utils::format_duration(started.elapsed())),
+            );
+        }
+        if phase == "check" {
+            log.step(
+                step,
+                "⏳ Running check() (the package's test suite), --nocheck skips it",
+            );
+            check_started = Some(Instant::now());
+        }
+    };
+    loop {
+        let received = match prompt_timeout {
+            Some(timeout) => rx.recv_timeout(timeout),
+            None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok((stream, chunk)) => {
+                pending[stream].extend_from_slice(&chunk);
+                for line in take_lines(&mut pending[stream]) {
+                    handle_line(&line);
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) => {
+                // Quiet after a full line is just a long compile
+                let Some(partial) = pending.iter_mut().find(|p| !p.is_empty()) else {
+                    continue;
+                };
+                let question = String::from_utf8_lossy(partial).trim().to_string();
+                partial.clear();
+                handle_line(&question);
+                let Some(input) = stdin.as_mut() else {
+                    continue;
+                };
+                match log.prompt(step, &question) {
+                    Some(answer) => {
+                        let _ = writeln!(input, "{}", answer);
+                    }
+                    None => {
+                        log.step(
+                            "error",
+                            &format!(
+                                "The build is waiting for input reap can't give it (\"{}\"). Build it by hand with makepkg, or from a terminal",
+                                question
+                            ),
+                        );
+                        // End of input makes the PKGBUILD's read fail
+                        stdin = None;
+                    }
+                }
+            }
+        }
+    }
+    for line in pending
+        .iter()
+        .map(|p| String::from_utf8_lossy(p).trim().to_string())
+        .filter(|l| !l.is_empty())
+    {
+        handle_line(&line);
+    }
+    for reader in readers {
+        let _ = reader.join();
+    }
+    drop(stdin);
+    let status = child.wait()?;
+    if let Some(started) = check_started {
+        log.step(
+            step,
+            &format!(
+                "check() stopped after {}",
+                utils::format_duration(started.elapsed())
+            ),
+        );
+    }
+    Ok(status)
+}
+
+/// The makepkg phase a line of its output starts, e.g. `check` for
+/// `==> Starting check()...`, or `package` when it enters fakeroot
+pub fn makepkg_phase(line: &str) -> Option<&str> {
+    let msg = line.trim().strip_prefix("==> ")?;
+    if msg.starts_with("Entering fakeroot environment") {
+        return Some("package");
+    }
+    msg.strip_prefix("Starting ")?
+        .split_once("()")
+        .map(|(phase, _)| phase)
+}
+
+/// Build in `build_dir` under bubblewrap, without installing the result.
+/// Only downloading and verifying the sources (`--verifysource`) happens
+/// outside the sandbox; extraction, `prepare()` and the build run inside it
+/// with no network, writing only to `build_dir` and a private /tmp.
+/// `epoch` pins `SOURCE_DATE_EPOCH` for the build.
+fn build_sandboxed(
+    build_dir: &std::path::Path,
+    makepkg_args: &[&str],
+    epoch: Option<&str>,
+    log: &dyn ProgressReporter,
+) -> std::io::Result<std::process::ExitStatus> {
+    let status = stream_build(
+        Command::new("makepkg")
+            .args(["--verifysource", "--noconfirm"])
+            .args(makepkg_args)
+            .current_dir(build_dir),
+        "fetch",
+        log,
+    )?;
+    if !status.success() {
+        return Ok(status);
+    }
+    let mut bwrap = Command::new("bwrap");
+    if let Some(epoch) = epoch {
+        bwrap.env("SOURCE_DATE_EPOCH", epoch);
+    }
+    stream_build(
+        bwrap
+            .args(["--ro-bind", "/", "/", "--dev", "/dev", "--proc", "/proc"])
+            .args(["--tmpfs", "/tmp"])
+            .arg("--bind")
+            .arg(build_dir)
+            .arg(build_dir)
+            .args(["--unshare-all", "--die-with-parent", "--chdir"])
+            .arg(build_dir)
+            .args(["makepkg", "--noconfirm", "--force"])
+            .args(makepkg_args),
+        "build",
+        log,
+    )
+}
+
+/// Package files makepkg built in `build_dir` (`makepkg --packagelist`)
+fn built_packages(
+    build_dir: &std::path::Path,
+    makepkg_args: &[&str],
+) -> std::io::Result<Vec<PathBuf>> {
+    Command::new("makepkg")
+        .arg("--packagelist")
+        .args(makepkg_args)
+        .current_dir(build_dir)
+        .output()
+        .map(|o| {
+            String::from_utf8_lossy(&o.stdout)
+                .lines()
+                .map(|l| PathBuf::from(l.trim()))
+                .filter(|p| p.exists())
+                .collect()
+        })
+}
+
+/// Detach-sign the packages makepkg built in `build_dir` with `key`, the
+/// same way `makepkg --sign` does
+fn sign_built_packages(
+    build_dir: &std::path::Path,
+    makepkg_args: &[&str],
+    key: &str,
+    log: &dyn ProgressReporter,
+) -> std::io::Result<std::process::ExitStatus> {
+    let mut status = std::process::ExitStatus::default();
+    for package in built_packages(build_dir, makepkg_args)? {
+        log.step("sign", &format!("Signing {}", package.display()));
+        status = stream_logged(
+            Command::new("gpg")
+                .args([
+                    "--batch",
+                    "--yes",
+                    "--detach-sign",
+                    "--use-agent",
+                    "--no-armor",
+                ])
+                .args(["--local-user", key, "--output"])
+                .arg(format!("{}.sig", package.display()))
+                .arg(&package),
+            "sign",
+            log,
+        )?;
+        if !status.success() {
+            break;
+        }
+    }
+    Ok(status)
+}
+
+/// Whether `gpg --list-secret-keys --with-colons` output describes a key
+/// that can sign at unix time `now`
+pub fn secret_key_status(colons: &str, now: i64) -> Result<(), String> {
+    let sec = colons
+        .lines()
+        .find(|line| line.starts_with("sec:"))
+        .ok_or("no secret key")?;
+    let fields: Vec<&str> = sec.split(':').collect();
+    match fields.get(1).copied() {
+        Some("e") => return Err("key has expired".to_string()),
+        Some("r") => return Err("key has been revoked".to_string()),
+        Some("i" | "d" | "n") => return Err("key is invalid or disabled".to_string()),
+        _ => {}
+    }
+    if let Some(expires) = fields.get(6).and_then(|f| f.parse::<i64>().ok())
+        && expires <= now
+    {
+        return Err("key has expired".to_string());
+    }
+    if let Some(caps) = fields.get(11)
+        && !caps.is_empty()
+        && !caps.contains('S')
+    {
+        return Err("key cannot sign".to_string());
+    }
+    Ok(())
+}
+
+/// Check that the secret key `key` exists and can sign
+pub fn check_sign_key(key: &str) -> Result<(), ReapError> {
+    let output = Command::new("gpg")
+        .args(["--list-secret-keys", "--with-colons", key])
+        .output()?;
+    secret_key_status(
+        &String::from_utf8_lossy(&output.stdout),
+        chrono::Utc::now().timestamp(),
+    )
+    .map_err(|e| ReapError::CommandFailed(format!("signing key {} is not usable: {}", key, e)))
+}
+
+/// `SOURCE_DATE_EPOCH` for a reproducible build: the time of the AUR
+/// commit the PKGBUILD comes from, else the PKGBUILD's mtime
+fn source_date_epoch(build_dir: &std::path::Path) -> String {
+    let committed = Command::new("git")
+        .arg("-C")
+        .arg(build_dir)
+        .args(["log", "-1", "--format=%ct"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .filter(|epoch| !epoch.is_empty());
+    committed.unwrap_or_else(|| {
+        fs::metadata(build_dir.join("PKGBUILD"))
+            .and_then(|m| m.modified())
+            .unwrap_or_else(|_| std::time::SystemTime::now())
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+            .to_string()
+    })
+}
+
+/// SHA256 of every file under `root`, keyed by its path relative to `root`
+pub fn file_hashes(root: &std::path::Path) -> std::collections::BTreeMap<String, String> {
+    fn walk(
+        root: &std::path::Path,
+        dir: &std::path::Path,
+        out: &mut std::collections::BTreeMap<String, String>,
+    ) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            match entry.file_type() {
+                Ok(t) if t.is_dir() => walk(root, &path, out),
+                Ok(t) => {
+                    let rel = path
+                        .strip_prefix(root)
+                        .unwrap_or(&path)
+                        .to_string_lossy()
+                        .into_owned();
+                    let hash = if t.is_symlink() {
+                        fs::read_link(&path)
+                            .map(|target| format!("-> {}", target.display()))
+                            .unwrap_or_default()
+                    } else {
+                        crate::verify::sha256_file(&path).unwrap_or_default()
+                    };
+                    out.insert(rel, hash);
+                }
+                Err(_) => {}
+            }
+        }
+    }
+    let mut out = std::collections::BTreeMap::new();
+    walk(root, root, &mut out);
+    out
+}
+
+/// How the contents of two builds of a package differ, one line per file
+pub fn compare_file_hashes(
+    first: &std::collections::BTreeMap<String, String>,
+    second: &std::collections::BTreeMap<String, String>,
+) -> Vec<String> {
+    let mut diffs = Vec::new();
+    for (path, hash) in first {
+        match second.get(path) {
+            None => diffs.push(format!("only in the first build: {}", path)),
+            Some(other) if other != hash => diffs.push(format!("differs: {}", path)),
+            Some(_) => {}
+        }
+    }
+    for path in second.keys().filter(|p| !first.contains_key(*p)) {
+        diffs.push(format!("only in the second build: {}", path));
+    }
+    diffs
+}
+
+/// File-by-file differences between two package archives
+fn package_content_diff(first: &std::path::Path, second: &std::path::Path) -> Vec<String> {
+    let unpack = |package: &std::path::Path, name: &str| {
+        let dir = package.with_file_name(format!(".reap-unpacked-{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).ok()?;
+        let ok = Command::new("bsdtar")
+            .arg("-xf")
+            .arg(package)
+            .arg("-C")
+            .arg(&dir)
+            .status()
+            .is_ok_and(|s| s.success());
+        ok.then_some(dir)
+    };
+    let (Some(a), Some(b)) = (unpack(first, "first"), unpack(second, "second")) else {
+        return vec!["could not unpack the packages to compare them".to_string()];
+    };
+    let diffs = compare_file_hashes(&file_hashes(&a), &file_hashes(&b));
+    let _ = fs::remove_dir_all(&a);
+    let _ = fs::remove_dir_all(&b);
+    diffs
+}
+
+/// `--verify-reproducible`: set the first build's packages aside, `rebuild`,
+/// and compare the two. Returns the rebuild's status; a package that isn't
+/// bit-for-bit identical is only kept if the user agrees.
+fn verify_reproducible_build(
+    pkg: &str,
+    build_dir: &std::path::Path,
+    makepkg_args: &[&str],
+    rebuild: impl FnOnce() -> std::io::Result<std::process::ExitStatus>,
+    log: &dyn ProgressReporter,
+) -> Result<std::process::ExitStatus, ReapError> {
+    let first_dir = build_dir.join(".reap-first-build");
+    fs::create_dir_all(&first_dir)?;
+    for package in built_packages(build_dir, makepkg_args)? {
+        let Some(name) = package.file_name() else {
+            continue;
+        };
+        // PKGDEST may be on another filesystem
+        let dest = first_dir.join(name);
+        if fs::rename(&package, &dest).is_err() {
+            fs::copy(&package, &dest)?;
+            fs::remove_file(&package)?;
+        }
+    }
+    log.step("reproducible", "Building a second time to compare");
+    let status = rebuild()?;
+    if !status.success() {
+        return Ok(status);
+    }
+    let mut identical = true;
+    for package in built_packages(build_dir, makepkg_args)? {
+        let Some(name) = package.file_name() else {
+            continue;
+        };
+        let earlier = first_dir.join(name);
+        let name = name.to_string_lossy();
+        match (
+            crate::verify::sha256_file(&earlier),
+            crate::verify::sha256_file(&package),
+        ) {
+            (Ok(a), Ok(b)) if a == b => {
+                log.step(
+                    "reproducible",
+                    &format!("✓ {} is bit-for-bit identical (sha256 {})", name, a),
+                );
+            }
+            _ => {
+                identical = false;
+                log.step(
+                    "reproducible",
+                    &format!("❌ {} differs between the two builds", name),
+                );
+                for diff in package_content_diff(&earlier, &package) {
+                    log.step("reproducible", &format!("   {}", diff));
+                }
+            }
+        }
+    }
+    let _ = fs::remove_dir_all(&first_dir);
+    if identical
+        || crate::interactive::InteractiveManager::confirm_action(
+            &format!("{} did not build reproducibly. Keep it anyway?", pkg),
+            false,
+        )
+    {
+        Ok(status)
+    } else {
+        Err(ReapError::UserAborted)
+    }
+}
+
+/// Install the dependencies of the pkgbase in `build_dir` that `makepkg -s`
+/// would (checkdepends only without `nocheck`), except the `ignored` ones,
+/// which pacman assumes installed. makepkg has no `--assume-installed`, so
+/// with any ignored the build then runs with `--nodeps`.
+fn install_build_deps(
+    build_dir: &std::path::Path,
+    ignored: &[String],
+    nocheck: bool,
+    log: &dyn ProgressReporter,
+) -> Result<(), ReapError> {
+    let srcinfo = crate::srcinfo::parse_srcinfo(build_dir)?;
+    let arch = host_arch();
+    let names = srcinfo.pkgnames();
+    let mut deps: Vec<String> = Vec::new();
+    // makedepends and checkdepends only come from the pkgbase
+    if let Some(first) = names.first() {
+        deps.extend(srcinfo.get(first, "makedepends", arch));
+        if !nocheck {
+            deps.extend(srcinfo.get(first, "checkdepends", arch));
+        }
+    }
+    for name in &names {
+        deps.extend(srcinfo.get(name, "depends", arch));
+    }
+    deps.sort();
+    deps.dedup();
+    let missing = unsatisfied_deps(&without_ignored(&deps, ignored));
+    if missing.is_empty() {
+        return Ok(());
+    }
+    log.step(
+        "deps",
+        &format!("Installing dependencies: {}", missing.join(", ")),
+    );
+    let _transaction = pacman::transaction();
+    let status = stream_logged(
+        crate::utils::escalate("pacman")
+            .args(["-S", "--asdeps", "--needed", "--noconfirm"])
+            .args(assume_installed_args(ignored))
+            .args(&missing),
+        "deps",
+        log,
+    )?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(ReapError::BuildFailed {
+            pkg: srcinfo.pkgbase.clone(),
+            stage: "deps",
+        })
+    }
+}
+
+/// `pacman --overwrite` arguments, one per glob
+pub fn overwrite_args(globs: &[String]) -> Vec<String> {
+    globs
+        .iter()
+        .flat_map(|glob| ["--overwrite".to_string(), glob.clone()])
+        .collect()
+}
+
+/// Name part of a dependency like `foo>=1.2`
+pub fn dep_name(dep: &str) -> &str {
+    dep.split(['<', '>', '=']).next().unwrap_or(dep)
+}
+
+/// `pacman --assume-installed` arguments, one per ignored dependency
+pub fn assume_installed_args(deps: &[String]) -> Vec<String> {
+    deps.iter()
+        .flat_map(|dep| ["--assume-installed".to_string(), dep.clone()])
+        .collect()
+}
+
+/// `deps` without the ones named in `ignored`, whatever their version constraints
+pub fn without_ignored(deps: &[String], ignored: &[String]) -> Vec<String> {
+    deps.iter()
+        .filter(|dep| !ignored.iter().any(|i| dep_name(i) == dep_name(dep)))
+        .cloned()
+        .collect()
+}
+
+/// Of the package files of a split pkgbase at `version`, the ones for `names`
+pub fn split_package_files(packages: &[PathBuf], names: &[String], version: &str) -> Vec<PathBuf> {
+    packages
+        .iter()
+        .filter(|p| {
+            let file = p
+                .file_name()
+                .map(|f| f.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            names
+                .iter()
+                .any(|name| file.starts_with(&format!("{}-{}-", name, version)))
+        })
+        .cloned()
+        .collect()
+}
+
+/// Parse the answer to the split package prompt: numbers or names
+/// separated by spaces or commas, or `all`. An empty answer gives an empty
+/// list, meaning the default.
+pub fn parse_split_selection(input: &str, names: &[String]) -> Result<Vec<String>, String> {
+    let mut chosen: Vec<String> = Vec::new();
+    for token in input.split([' ', ',']).filter(|t| !t.is_empty()) {
+        let picked: Vec<String> = if token == "all" || token == "a" {
+            names.to_vec()
+        } else if let Ok(n) = token.parse::<usize>() {
+            match names.get(n.wrapping_sub(1)) {
+                Some(name) => vec![name.clone()],
+                None => return Err(format!("no package number {}", n)),
+            }
+        } else if names.iter().any(|name| name == token) {
+            vec![token.to_string()]
+        } else {
+            return Err(format!("{} is not one of the packages", token));
+        };
+        for name in picked {
+            if !chosen.contains(&name) {
+                chosen.push(name);
+            }
+        }
+    }
+    Ok(chosen)
+}
+
+/// Which packages of the split pkgbase `base` to install for a request for
+/// `pkg`: the requested one (or all of them when the pkgbase itself was
+/// asked for), unless the user picks others at the prompt
+fn choose_split_packages(pkg: &str, base: &str, names: &[String]) -> Vec<String> {
+    let default = if names.iter().any(|n| n == pkg) {
+        vec![pkg.to_string()]
+    } else {
+        names.to_vec()
+    };
+    if crate::interactive::noconfirm() || !std::io::IsTerminal::is_terminal(&std::io::stdin()) {
+        return default;
+    }
+    println!(":: {} is a split package:", base);
+    for (i, name) in names.iter().enumerate() {
+        println!("  {}: {}", i + 1, name);
+    }
+    loop {
+        print!(
+            "Packages to install (numbers, names or 'all') [{}]: ",
+            default.join(" ")
+        );
+        let _ = std::io::Write::flush(&mut std::io::stdout());
+        let input = match crate::interactive::read_prompt_line() {
+            crate::interactive::PromptInput::Line(input) => input,
+            _ => return default,
+        };
+        match parse_split_selection(input.trim(), names) {
+            Ok(chosen) if chosen.is_empty() => return default,
+            Ok(chosen) => return chosen,
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
+/// Make sure the kernels `pkg` builds modules for have their headers,
+/// offering to install the missing ones, and warn when the running
+/// kernel's are missing. Returns the kernels DKMS should build for: every
+/// installed kernel, or the one picked with `--kernel`. Packages that
+/// build against a single kernel without DKMS get none.
+fn prepare_kernel_headers(
+    pkg: &str,
+    deps: &[String],
+    wanted: Option<&str>,
+    log: &dyn ProgressReporter,
+) -> Result<Vec<crate::kernel::Kernel>, ReapError> {
+    let log_line = |msg: &str| log.step("kernel", msg);
+    let kernels =
+        crate::kernel::installed_kernels(std::path::Path::new(crate::kernel::MODULES_DIR));
+    if kernels.is_empty() {
+        return Ok(Vec::new());
+    }
+    if let Some(running) = crate::kernel::running_release()
+        && let Some(kernel) = kernels.iter().find(|k| k.release == running && !k.headers)
+    {
+        log_line(&format!(
+            "⚠️ Headers for the running kernel {} are missing ({})",
+            running,
+            kernel.headers_package()
+        ));
+    }
+    let selected = crate::kernel::select(&kernels, wanted).map_err(ReapError::CommandFailed)?;
+    let dkms = crate::kernel::uses_dkms(deps);
+    if !dkms && selected.len() > 1 {
+        log_line(&format!(
+            "{} builds against one kernel; a -dkms variant builds for each installed kernel",
+            pkg
+        ));
+    }
+    let missing: Vec<String> = selected
+        .iter()
+        .filter(|k| !k.headers)
+        .map(|k| k.headers_package())
+        .collect();
+    if !missing.is_empty() {
+        log_line(&format!(
+            "{} needs kernel headers, missing: {}",
+            pkg,
+            missing.join(", ")
+        ));
+        if crate::interactive::InteractiveManager::confirm_action(
+            &format!(":: Install {}?", missing.join(" ")),
+            true,
+        ) {
+            let _transaction = pacman::transaction();
+            let installed = crate::utils::escalate("pacman")
+                .args(["-S", "--needed", "--noconfirm", "--asdeps"])
+                .args(&missing)
+                .status()
+                .map(|s| s.success())
+                .unwrap_or(false);
+            if !installed {
+                return Err(ReapError::CommandFailed(format!(
+                    "Failed to install {}",
+                    missing.join(", ")
+                )));
+            }
+        }
+    }
+    Ok(if dkms { selected } else { Vec::new() })
+}
+
+/// Build and install the DKMS modules for each of `kernels`
+/// (`dkms autoinstall -k`)
+fn build_dkms_modules(kernels: &[crate::kernel::Kernel], log: &dyn ProgressReporter) {
+    for kernel in kernels {
+        let built = crate::utils::escalate("dkms")
+            .args(["autoinstall", "-k", &kernel.release])
+            .status()
+            .map(|s| s.success())
+            .unwrap_or(false);
+        let msg = if built {
+            format!(
+                "✅ Built modules for {} ({})",
+                kernel.package, kernel.release
+            )
+        } else {
+            format!(
+                "❌ dkms failed for {} ({}), see `dkms status`",
+                kernel.package, kernel.release
+            )
+        };
+        log.step("kernel", &msg);
+    }
+}
+
+/// `pacman -U` arguments installing built packages with `opts`: `--needed`
+/// unless reinstalling, replacing files matching the `--overwrite` globs and
+/// treating `--ignore-deps` dependencies as present
+pub fn pacman_install_args(opts: &InstallOptions) -> Vec<String> {
+    let mut args = vec!["-U".to_string(), "--noconfirm".to_string()];
+    if !opts.reinstall {
+        args.push("--needed".to_string());
+    }
+    args.extend(overwrite_args(&opts.overwrite));
+    args.extend(assume_installed_args(&opts.ignore_deps));
+    args
+}
+
+/// `pacman -U` arguments that let the install replace the conflicting
+/// installed packages the user agreed to remove. `--ask=4` answers pacman's
+/// conflict question with yes, so the removal and the install are one
+/// transaction and a failed install leaves the old packages in place. It
+/// answers every conflict in the transaction, so `replaces` must be all of
+/// them, as [`installed_conflicts`] finds.
+pub fn replace_conflicts_args(replaces: &[String]) -> Vec<String> {
+    if replaces.is_empty() {
+        Vec::new()
+    } else {
+        vec!["--ask=4".to_string()]
+    }
+}
+
+/// Install the packages makepkg built in `build_dir` with `pacman -U` (see
+/// [`pacman_install_args`]). `only` limits a split pkgbase to the named
+/// packages at the given version; `replaces` are the confirmed conflicts
+/// (see [`replace_conflicts_args`]).
+fn install_built_packages(
+    build_dir: &std::path::Path,
+    makepkg_args: &[&str],
+    opts: &InstallOptions,
+    only: Option<&(Vec<String>, String)>,
+    replaces: &[String],
+    log: &dyn ProgressReporter,
+) -> std::io::Result<std::process::ExitStatus> {
+    let mut packages = built_packages(build_dir, makepkg_args)?;
+    if let Some((names, version)) = only {
+        packages = split_package_files(&packages, names, version);
+        if packages.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no built package for {}", names.join(", ")),
+            ));
+        }
+    }
+    let _transaction = pacman::transaction();
+    stream_logged(
+        crate::utils::escalate("pacman")
+            .args(pacman_install_args(opts))
+            .args(replace_conflicts_args(replaces))
+            .args(&packages),
+        "install",
+        log,
+    )
+}
+
+// === AUR Dependency Builds ===
+
+/// Of `deps`, the ones no installed package satisfies (`pacman -T`)
+fn unsatisfied_deps(deps: &[String]) -> Vec<String> {
+    if deps.is_empty() {
+        return Vec::new();
+    }
+    match Command::new("pacman").arg("-T").args(deps).output() {
+        Ok(out) => String::from_utf8_lossy(&out.stdout)
+            .lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect(),
+        Err(_) => deps.to_vec(),
+    }
+}
+
+/// Whether a repo package satisfies `dep`, directly or through `provides`
+fn repo_satisfies(dep: &str) -> bool {
+    Command::new("pacman")
+        .args(["-Sddp", "--print-format", "%n", "--noconfirm", dep])
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
+/// Dependency DAG of the AUR packages that must be built for `targets`,
+/// mapping each package to the AUR packages it needs first, skipping the
+/// `ignored` ones. Installed and repo dependencies are left to makepkg.
+pub async fn aur_dependency_graph(
+    targets: &[String],
+    ignored: &[String],
+) -> HashMap<String, Vec<String>> {
+    let fetch_info = |level: Vec<String>| async move { aur::fetch_info_batch(&level).await };
+    resolve_aur_deps(targets, ignored, fetch_info, |wanted| {
+        unsatisfied_deps(wanted)
+            .iter()
+            .map(|dep| dep_name(dep).to_string())
+            .filter(|dep| !repo_satisfies(dep))
+            .collect()
+    })
+    .await
+}
+
+/// [`aur_dependency_graph`] with the lookups passed in: `fetch_info` gets
+/// the AUR info of a whole tree level at once (the packages it leaves out
+/// aren't in the AUR), and `needs_build` picks the names of the
+/// dependencies nothing installed or in the repos satisfies. Resolving a
+/// tree takes one `fetch_info` call per level.
+pub async fn resolve_aur_deps<F, Fut>(
+    targets: &[String],
+    ignored: &[String],
+    fetch_info: F,
+    needs_build: impl Fn(&[String]) -> Vec<String>,
+) -> HashMap<String, Vec<String>>
+where
+    F: Fn(Vec<String>) -> Fut,
+    Fut: std::future::Future<Output = Result<Vec<aur::AurResult>, ReapError>>,
+{
+    let mut graph: HashMap<String, Vec<String>> = HashMap::new();
+    let mut not_aur: std::collections::HashSet<String> = std::collections::HashSet::new();
+    let mut level: Vec<String> = Vec::new();
+    for pkg in targets {
+        if !level.contains(pkg) {
+            level.push(pkg.clone());
+        }
+    }
+    while !level.is_empty() {
+        let infos: HashMap<String, aur::AurResult> = match fetch_info(level.clone()).await {
+            Ok(results) => results.into_iter().map(|r| (r.name.clone(), r)).collect(),
+            Err(e) => {
+                eprintln!("[reap] Failed to fetch AUR dependency info: {}", e);
+                HashMap::new()
+            }
+        };
+        let mut next: Vec<String> = Vec::new();
+        for pkg in level {
+            let Some(info) = infos.get(&pkg) else {
+                // Targets stay in the graph for the caller to build or report
+                if targets.contains(&pkg) {
+                    graph.insert(pkg, Vec::new());
+                } else {
+                    not_aur.insert(pkg);
+                }
+                continue;
+            };
+            let wanted: Vec<String> = info
+                .depends
+                .iter()
+                .chain(&info.make_depends)
+                .cloned()
+                .collect();
+            let aur_deps = needs_build(&without_ignored(&wanted, ignored));
+            for dep in &aur_deps {
+                if !graph.contains_key(dep)
+                    && !infos.contains_key(dep)
+                    && !not_aur.contains(dep)
+                    && !next.contains(dep)
+                {
+                    next.push(dep.clone());
+                }
+            }
+            graph.insert(pkg, aur_deps);
+        }
+        level = next;
+    }
+    // Dependencies the AUR doesn't have are left to makepkg
+    for deps in graph.values_mut() {
+        deps.retain(|dep| !not_aur.contains(dep));
+    }
+    graph
+}
+
+/// Group a dependency DAG into build levels, where every package's
+/// dependencies sit in an earlier level. Errors if the graph has a cycle.
+pub fn build_levels(graph: &HashMap<String, Vec<String>>) -> Result<Vec<Vec<String>>, ReapError> {
+    let mut remaining: HashMap<&str, Vec<&str>> = graph
+        .iter()
+        .map(|(pkg, deps)| {
+            let deps = deps
+                .iter()
+                .map(String::as_str)
+                .filter(|d| graph.contains_key(*d) && d != pkg)
+                .collect();
+            (pkg.as_str(), deps)
+        })
+        .collect();
+    let mut levels = Vec::new();
+    while !remaining.is_empty() {
+        let mut level: Vec<String> = remaining
+            .iter()
+            .filter(|(_, deps)| deps.iter().all(|d| !remaining.contains_key(d)))
+            .map(|(pkg, _)| pkg.to_string())
+            .collect();
+        if level.is_empty() {
+            let mut cycle: Vec<String> = remaining.keys().map(|k| k.to_string()).collect();
+            cycle.sort();
+            return Err(ReapError::DependencyCycle(cycle));
+        }
+        level.sort();
+        for pkg in &level {
+            remaining.remove(pkg.as_str());
+        }
+        levels.push(level);
+    }
+    Ok(levels)
+}
+
+/// Build and install the AUR dependencies of `pkg` level by level, building
+/// independent packages in parallel up to `config.parallel`. Their pacman
+/// transactions take turns ([`pacman::transaction`]). A package is only
+/// built once everything it depends on installed.
+pub async fn install_aur_dependencies(
+    pkg: &str,
+    config: Arc<ReapConfig>,
+    log: Arc<dyn ProgressReporter>,
+    opts: &InstallOptions,
+) -> Result<(), ReapError> {
+    let target = vec![pkg.to_string()];
+    let ignored = opts.ignore_deps.clone();
+    let mut graph = aur_dependency_graph(&target, &ignored).await;
+    // The target itself is built by the caller
+    graph.remove(pkg);
+    if graph.is_empty() {
+        return Ok(());
+    }
+    let levels = build_levels(&graph)?;
+    // Dependents can only be built against installed dependencies, so with
+    // --downloadonly they are installed here and exported alongside
+    let opts = InstallOptions {
+        download_only: false,
+        export_packages: opts.download_only || opts.export_packages,
+        ..opts.clone()
+    };
+    log.step(
+        "deps",
+        &format!(
+            "Building {} AUR dependencies of {} in {} levels",
+            graph.len(),
+            pkg,
+            levels.len()
+        ),
+    );
+    let semaphore = Arc::new(Semaphore::new(config.parallel.max(1)));
+    let mut failed: std::collections::HashSet<String> = std::collections::HashSet::new();
+    for level in levels {
+        let mut names = Vec::new();
+        let mut tasks = Vec::new();
+        for dep in level {
+            if let Some(missing) = graph[&dep].iter().find(|d| failed.contains(*d)) {
+                log.step("deps", &format!("Skipping {}, {} failed", dep, missing));
+                failed.insert(dep);
+                continue;
+            }
+            let sem = Arc::clone(&semaphore);
+            let log = Arc::clone(&log);
+            let opts = opts.clone();
+            names.push(dep.clone());
+            tasks.push(tokio::spawn(async move {
+                let _permit = sem.acquire_owned().await.unwrap();
+                let ok = install_aur_native(&dep, log.as_ref(), &opts).await.is_ok();
+                // Installed only for its dependents, so orphan cleanup can remove it later
+                if ok && !pacman::mark(&dep, false) {
+                    log.step("deps", &format!("Failed to mark {} as a dependency", dep));
+                }
+                ok
+            }));
+        }
+        // A panicked build counts as failed, so its dependents are skipped
+        for (dep, res) in names.into_iter().zip(join_all(tasks).await) {
+            match res {
+                Ok(true) => {}
+                Ok(false) => {
+                    failed.insert(dep);
+                }
+                Err(e) => {
+                    log.step("deps", &format!("Build of {} panicked: {}", dep, e));
+                    failed.insert(dep);
+                }
+            }
+        }
+    }
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        let mut failed: Vec<String> = failed.into_iter().collect();
+        failed.sort();
+        log.step(
+            "deps",
+            &format!("Failed to build dependencies: {}", failed.join(", ")),
+        );
+        Err(ReapError::BuildFailed {
+            pkg: pkg.to_string(),
+            stage: "dependencies",
+        })
+    }
+}
+
+/// Whether any ELF file owned by `pkg` links against a library whose name
+/// contains `lib`, according to `ldd`
+pub fn links_against(pkg: &str, lib: &str) -> bool {
+    use std::io::Read;
+    pacman::owned_files(pkg).iter().any(|file| {
+        let mut magic = [0u8; 4];
+        let is_elf = fs::File::open(file)
+            .and_then(|mut f| f.read_exact(&mut magic))
+            .is_ok()
+            && magic == *b"\x7fELF";
+        is_elf
+            && Command::new("ldd")
+                .arg(file)
+                .output()
+                .map(|o| String::from_utf8_lossy(&o.stdout).contains(lib))
+                .unwrap_or(false)
+    })
+}
+
+/// Install options of a `--rebuild-all` build. A rebuild keeps the installed
+/// pkgver-pkgrel, so it has to reinstall or pacman skips it as up to date.
+pub fn rebuild_options(opts: &InstallOptions) -> InstallOptions {
+    InstallOptions {
+        reinstall: true,
+        ..opts.clone()
+    }
+}
+
+/// Rebuild installed AUR packages, e.g. after a soname bump. With `linking`,
+/// only packages linking that library are rebuilt. Packages are rebuilt in
+/// dependency order so a rebuilt library is in place before its dependents.
+pub async fn handle_rebuild_all(linking: Option<&str>, confirm: bool, opts: &InstallOptions) {
+    use colored::Colorize;
+    let linking = linking.map(str::to_string);
+    let graph = tokio::task::spawn_blocking(move || {
+        let installed = pacman::list_installed_aur();
+        let targets: Vec<String> = installed
+            .iter()
+            .filter(|pkg| linking.as_deref().is_none_or(|lib| links_against(pkg, lib)))
+            .cloned()
+            .collect();
+        targets
+            .into_iter()
+            .map(|pkg| {
+                let deps = pacman::installed_depends(&pkg);
+                (pkg, deps)
+            })
+            .collect::<HashMap<String, Vec<String>>>()
+    })
+    .await
+    .unwrap_or_default();
+    if graph.is_empty() {
+        println!("[reap] No AUR packages to rebuild.");
+        return;
+    }
+    let order: Vec<String> = match build_levels(&graph) {
+        Ok(levels) => levels.into_iter().flatten().collect(),
+        Err(e) => {
+            eprintln!("[reap] {}", e.to_string().red());
+            return;
+        }
+    };
+    println!("[reap] AUR packages to rebuild ({}):", order.len());
+    for pkg in &order {
+        println!("    {}", pkg);
+    }
+    if !confirm_proceed(confirm, "Rebuild these packages?") {
+        return;
+    }
+    let opts = rebuild_options(opts);
+    let mut rebuilt = Vec::new();
+    let mut failed = Vec::new();
+    for pkg in order {
+        match install_aur_native(&pkg, &StdoutReporter, &opts).await {
+            Ok(()) => rebuilt.push(pkg),
+            Err(e) => {
+                eprintln!("[reap] Rebuild of {} failed: {}", pkg, e.to_string().red());
+                failed.push(pkg);
+            }
+        }
+    }
+    println!("\n{} Rebuild summary:", "📋".bright_yellow());
+    println!("  rebuilt ({}): {}", rebuilt.len(), rebuilt.join(", "));
+    if !failed.is_empty() {
+        println!(
+            "  failed ({}): {}",
+            failed.len().to_string().red(),
+            failed.join(", ")
+        );
+    }
+}
+
+/// Fill in optional dependency descriptions the installed package lacks
+/// from the AUR's `OptDepends` entries
+pub fn fill_optdep_descriptions(optdepends: &mut [pacman::OptDep], aur: &[String]) {
+    for optdep in optdepends.iter_mut().filter(|o| o.description.is_none()) {
+        optdep.description = aur
+            .iter()
+            .map(|entry| pacman::OptDep::parse(entry))
+            .find(|entry| entry.name == optdep.name)
+            .and_then(|entry| entry.description);
+    }
+}
+
+/// What to do with the missing optional dependencies of an installed AUR
+/// package (`install_optdepends` in reap.toml)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptDependsMode {
+    None,
+    /// List them and ask which to install
+    Prompt,
+    All,
+}
+
+impl OptDependsMode {
+    /// The configured mode; `--with-optdepends` prompts unless `all` is set
+    pub fn from_config(with_optdepends: bool, configured: Option<&str>) -> Self {
+        let mode = match configured.map(|v| v.trim().to_lowercase()).as_deref() {
+            Some("prompt") | Some("ask") => Self::Prompt,
+            Some("all") => Self::All,
+            Some("none") | Some("") | None => Self::None,
+            Some(other) => {
+                eprintln!(
+                    "[reap] Unknown install_optdepends '{}', expected prompt, all or none",
+                    other
+                );
+                Self::None
+            }
+        };
+        match mode {
+            Self::None if with_optdepends => Self::Prompt,
+            mode => mode,
+        }
+    }
+}
+
+/// Offer to install the optional dependencies of the just installed `pkg`
+/// that aren't installed, each through the normal source resolution and
+/// marked as a dependency. Without `confirm` nothing is picked at the prompt.
+async fn install_optdepends(
+    pkg: &str,
+    mode: OptDependsMode,
+    confirm: bool,
+    config: Arc<ReapConfig>,
+    log: Arc<dyn ProgressReporter>,
+    opts: &InstallOptions,
+) {
+    use colored::Colorize;
+    let name = pkg.to_string();
+    let optdepends = tokio::task::spawn_blocking(move || {
+        let optdepends: Vec<pacman::OptDep> = pacman::package_deps(&[name])
+            .into_iter()
+            .flat_map(|p| p.optdepends)
+            .collect();
+        let names: Vec<String> = optdepends.iter().map(|o| o.name.clone()).collect();
+        let missing = unsatisfied_deps(&names);
+        optdepends
+            .into_iter()
+            .filter(|o| missing.contains(&o.name))
+            .collect::<Vec<_>>()
+    })
+    .await
+    .unwrap_or_default();
+    if optdepends.is_empty() {
+        return;
+    }
+    let names: Vec<String> = optdepends
+        .iter()
+        .map(|o| {
+            o.name
+                .split(['<', '>', '='])
+                .next()
+                .unwrap_or(&o.name)
+                .to_string()
+        })
+        .collect();
+    println!(":: Optional dependencies of {} that aren't installed:", pkg);
+    for (i, optdep) in optdepends.iter().enumerate() {
+        match &optdep.description {
+            Some(description) => println!("  {}: {}: {}", i + 1, names[i].bold(), description),
+            None => println!("  {}: {}", i + 1, names[i].bold()),
+        }
+    }
+    let chosen = match mode {
+        OptDependsMode::None => return,
+        OptDependsMode::All => names,
+        OptDependsMode::Prompt => loop {
+            if !confirm || !std::io::IsTerminal::is_terminal(&std::io::stdin()) {
+                break Vec::new();
+            }
+            print!("Optional dependencies to install (numbers, names or 'all') [none]: ");
+            let _ = std::io::Write::flush(&mut std::io::stdout());
+            let input = match crate::interactive::read_prompt_line() {
+                crate::interactive::PromptInput::Line(input) => input,
+                _ => break Vec::new(),
+            };
+            match parse_split_selection(input.trim(), &names) {
+                Ok(chosen) => break chosen,
+                Err(e) => println!("{}", e),
+            }
+        },
+    };
+    let dep_opts = InstallOptions {
+        with_optdepends: false,
+        as_explicit: false,
+        source: None,
+        repo: None,
+        ..opts.clone()
+    };
+    for dep in chosen {
+        let res = Box::pin(install_with_priority(
+            &dep,
+            Arc::clone(&config),
+            false,
+            Arc::clone(&log),
+            &dep_opts,
+        ))
+        .await;
+        match res {
+            Ok(()) => {
+                pacman::mark(&dep, false);
+            }
+            Err(e) => log.message(&format!(
+                "[reap] Failed to install optional dependency {}: {}",
+                dep, e
+            )),
+        }
+    }
+}
+
+/// `reap query [<pkg>] --deps|--optdeps [--missing]`: the dependencies and
+/// optional dependencies of an installed package (both unless one is
+/// asked for) and whether each is installed. `--missing` lists only what
+/// isn't installed, across every installed package when none is named.
+pub async fn handle_query(
+    pkg: Option<&str>,
+    deps: bool,
+    optdeps: bool,
+    missing: bool,
+) -> Result<(), ReapError> {
+    use colored::Colorize;
+    let (deps, optdeps) = if deps || optdeps {
+        (deps, optdeps)
+    } else {
+        (true, true)
+    };
+    let names: Vec<String> = pkg.map(|p| vec![p.to_string()]).unwrap_or_default();
+    let (mut packages, foreign) = tokio::task::spawn_blocking(move || {
+        (pacman::package_deps(&names), pacman::list_installed_aur())
+    })
+    .await
+    .unwrap_or_default();
+    if let Some(pkg) = pkg
+        && packages.is_empty()
+    {
+        return Err(ReapError::CommandFailed(format!(
+            "{} is not installed",
+            pkg
+        )));
+    }
+
+    // Descriptions come from the package; ask the AUR for the ones missing
+    let lacking: Vec<String> = packages
+        .iter()
+        .filter(|p| foreign.contains(&p.name))
+        .filter(|p| p.optdepends.iter().any(|o| o.description.is_none()))
+        .map(|p| p.name.clone())
+        .collect();
+    if optdeps
+        && !lacking.is_empty()
+        && let Ok(info) = aur::fetch_info_batch(&lacking).await
+    {
+        for result in info {
+            if let Some(package) = packages.iter_mut().find(|p| p.name == result.name) {
+                fill_optdep_descriptions(&mut package.optdepends, &result.opt_depends);
+            }
+        }
+    }
+
+    let all: Vec<String> = packages
+        .iter()
+        .flat_map(|p| {
+            let depends = p.depends.iter().filter(|_| deps).cloned();
+            let optdepends = p
+                .optdepends
+                .iter()
+                .filter(|_| optdeps)
+                .map(|o| o.name.clone());
+            depends.chain(optdepends).collect::<Vec<_>>()
+        })
+        .collect();
+    let unsatisfied: std::collections::HashSet<String> =
+        tokio::task::spawn_blocking(move || unsatisfied_deps(&all))
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+    let entry = |name: &str, description: Option<&str>| {
+        let line = match description {
+            Some(description) => format!("{}: {}", name, description),
+            None => name.to_string(),
+        };
+        if unsatisfied.contains(name) {
+            format!("  {} {}", "✗".red(), line.yellow().bold())
+        } else {
+            format!("  {} {}", "✓".green(), line)
+        }
+    };
+
+    let mut shown = 0;
+    for package in &packages {
+        let depends: Vec<String> = package
+            .depends
+            .iter()
+            .filter(|_| deps)
+            .filter(|d| !missing || unsatisfied.contains(*d))
+            .map(|d| entry(d, None))
+            .collect();
+        let optdepends: Vec<String> = package
+            .optdepends
+            .iter()
+            .filter(|_| optdeps)
+            .filter(|o| !missing || unsatisfied.contains(&o.name))
+            .map(|o| entry(&o.name, o.description.as_deref()))
+            .collect();
+        if pkg.is_none() && depends.is_empty() && optdepends.is_empty() {
+            continue;
+        }
+        shown += 1;
+        println!("{} {}", package.name.bold(), package.version);
+        if deps {
+            println!("Dependencies:");
+            if depends.is_empty() {
+                println!("  None");
+            }
+            depends.iter().for_each(|line| println!("{}", line));
+        }
+        if optdeps {
+            println!("Optional dependencies:");
+            if optdepends.is_empty() {
+                println!("  None");
+            }
+            optdepends.iter().for_each(|line| println!("{}", line));
+        }
+    }
+    if pkg.is_none() && shown == 0 {
+        println!("[reap] Nothing is missing");
+    }
+    Ok(())
+}
+
+/// `reap mark <pkg> --explicit|--asdeps`: change the install reason of an
+/// installed package, e.g. to keep a dependency out of orphan cleanup
+pub fn handle_mark(pkg: &str, explicit: bool) -> Result<(), ReapError> {
+    if !pacman::is_installed(pkg) {
+        return Err(ReapError::CommandFailed(format!(
+            "{} is not installed",
+            pkg
+        )));
+    }
+    let reason = if explicit {
+        "explicitly installed"
+    } else {
+        "a dependency"
+    };
+    if !pacman::mark(pkg, explicit) {
+        return Err(ReapError::CommandFailed(format!(
+            "pacman -D failed to mark {} as {}",
+            pkg, reason
+        )));
+    }
+    println!("[reap] Marked {} as {}", pkg, reason);
+    Ok(())
+}
+
+/// Package name of a package file, e.g. `foo-bar` for
+/// `foo-bar-1.0-1-x86_64.pkg.tar.zst`
+pub fn package_file_name(file: &str) -> Option<&str> {
+    let (stem, _) = file.split_once(".pkg.tar")?;
+    let mut parts = stem.rsplitn(4, '-');
+    let (_arch, _pkgrel, _pkgver) = (parts.next()?, parts.next()?, parts.next()?);
+    parts.next().filter(|name| !name.is_empty())
+}
+
+/// `reap repo-add <dir> <pkgs...>`: build `pkgs` into `dir` and add them to
+/// the local pacman repo there, creating it on first use. Without packages,
+/// lists what reap has added to the repo so far.
+pub async fn handle_repo_add(
+    dir: &str,
+    pkgs: &[String],
+    name: Option<&str>,
+    opts: &InstallOptions,
+) -> Result<(), ReapError> {
+    use colored::Colorize;
+    let dir = fs::canonicalize(dir).or_else(|_| {
+        fs::create_dir_all(dir)?;
+        fs::canonicalize(dir)
+    })?;
+    let repo = name
+        .map(str::to_string)
+        .or_else(|| dir.file_name().map(|n| n.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "custom".to_string());
+    let db = dir.join(format!("{}.db.tar.gz", repo));
+    let db_key = db.display().to_string();
+    if pkgs.is_empty() {
+        let repos = crate::history::LocalRepos::load();
+        match repos.packages(&db_key) {
+            Some(packages) => {
+                println!("[reap] Packages in [{}] ({}):", repo, db.display());
+                for (pkg, entry) in packages {
+                    println!(
+                        "    {:<30} {} ({})",
+                        pkg,
+                        entry.file,
+                        entry.added_at.format("%Y-%m-%d %H:%M")
+                    );
+                }
+            }
+            None => println!("[reap] reap has not added any packages to {}", db.display()),
+        }
+        return Ok(());
+    }
+    if which::which("repo-add").is_err() {
+        return Err(ReapError::CommandFailed(
+            "repo-add not found, it ships with pacman".to_string(),
+        ));
+    }
+    // Sign the database with the same key as the packages
+    let sign_key = GlobalConfig::load().sign_key.filter(|key| !key.is_empty());
+    if let Some(key) = &sign_key {
+        check_sign_key(key)?;
+    }
+    let start = std::time::SystemTime::now();
+    let opts = InstallOptions {
+        download_only: true,
+        package_dir: Some(dir.clone()),
+        ..opts.clone()
+    };
+    let report = handle_install(pkgs.to_vec(), false, &opts).await;
+    report.print("Build");
+    // Anything written since the builds started, dependencies included
+    let mut files: Vec<PathBuf> = fs::read_dir(&dir)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            name.contains(".pkg.tar") && !name.ends_with(".sig")
+        })
+        .filter(|entry| {
+            entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .is_ok_and(|modified| modified >= start)
+        })
+        .map(|entry| entry.path())
+        .collect();
+    files.sort();
+    if files.is_empty() {
+        println!("[reap] No new packages to add to [{}]", repo);
+        return report.into_result();
+    }
+    let mut repo_add = Command::new("repo-add");
+    repo_add.args(["--new", "--remove"]);
+    if let Some(key) = &sign_key {
+        repo_add.args(["--sign", "--key", key]);
+    }
+    let status = repo_add.arg(&db).args(&files).status()?;
+    if !status.success() {
+        return Err(ReapError::CommandFailed(format!(
+            "repo-add {} failed",
+            db.display()
+        )));
+    }
+    let path = crate::history::LocalRepos::path();
+    let mut repos = crate::history::LocalRepos::load_from(&path);
+    for file in &files {
+        let file_name = file.file_name().unwrap_or_default().to_string_lossy();
+        if let Some(pkg) = package_file_name(&file_name) {
+            repos.record(&db_key, pkg, &file_name);
+        }
+    }
+    if let Err(e) = repos.save_to(&path) {
+        eprintln!("[reap] Failed to record repo packages: {}", e);
+    }
+    println!(
+        "{} Added {} packages to [{}]. To use it, add to /etc/pacman.conf:\n\n[{}]\nSigLevel = Optional TrustAll\nServer = file://{}\n",
+        "✅".green(),
+        files.len(),
+        repo,
+        repo,
+        dir.display()
+    );
+    report.into_result()
+}
+
+/// `CARCH` of the system makepkg.conf and its drop-ins, else the machine
+/// from `uname -m`, read once
+static HOST_ARCH: once_cell::sync::Lazy<String> = once_cell::sync::Lazy::new(|| {
+    let mut confs = vec![PathBuf::from("/etc/makepkg.conf")];
+    if let Ok(entries) = fs::read_dir("/etc/makepkg.conf.d") {
+        let mut dropins: Vec<PathBuf> = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "conf"))
+            .collect();
+        dropins.sort();
+        confs.extend(dropins);
+    }
+    // Later files override earlier ones, as makepkg sources them in order
+    confs
+        .iter()
+        .rev()
+        .find_map(|conf| parse_carch(&fs::read_to_string(conf).ok()?))
+        .unwrap_or_else(|| {
+            let machine = Command::new("uname")
+                .arg("-m")
+                .output()
+                .ok()
+                .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+                .filter(|m| !m.is_empty())
+                .unwrap_or_else(|| std::env::consts::ARCH.to_string());
+            makepkg_arch(&machine).to_string()
+        })
+});
+
+/// Architecture of this machine as makepkg names it (`CARCH`)
+pub fn host_arch() -> &'static str {
+    HOST_ARCH.as_str()
+}
+
+/// The `CARCH` a makepkg.conf sets, if any
+pub fn parse_carch(conf: &str) -> Option<String> {
+    conf.lines()
+        .filter_map(|line| line.trim().strip_prefix("CARCH="))
+        .map(|value| value.trim().trim_matches(['"', '\'']).to_string())
+        .rfind(|value| !value.is_empty())
+}
+
+/// makepkg's name for a `uname -m` machine (or Rust target arch), e.g.
+/// `armv7l` is `armv7h` and `x86` is `i686`
+pub fn makepkg_arch(machine: &str) -> &str {
+    match machine {
+        "i386" | "i486" | "i586" | "i686" | "x86" => "i686",
+        "armv7l" | "armv7" | "arm" => "armv7h",
+        "armv6l" => "armv6h",
+        "arm64" | "aarch64" => "aarch64",
+        other => other,
+    }
+}
+
+/// Whether a PKGBUILD `arch` array allows building on `host`
+pub fn supports_arch(archs: &[String], host: &str) -> bool {
+    archs.is_empty() || archs.iter().any(|a| a == "any" || a == host)
+}
+
+/// Root of the clean chroot used for `--chroot` builds
+pub fn chroot_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(|| PathBuf::from("/tmp"))
+        .join("reap/chroot")
+}
+
+/// Where `--downloadonly` leaves built and downloaded packages
+pub fn package_output_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(|| PathBuf::from("/tmp"))
+        .join("reap/packages")
+}
+
+/// Copy the packages makepkg built in `build_dir` to `out`, returning their
+/// new paths
+fn export_built_packages(
+    build_dir: &std::path::Path,
+    makepkg_args: &[&str],
+    out: &std::path::Path,
+) -> std::io::Result<Vec<PathBuf>> {
+    fs::create_dir_all(out)?;
+    let mut exported = Vec::new();
+    for package in built_packages(build_dir, makepkg_args)? {
+        if let Some(name) = package.file_name() {
+            let dest = out.join(name);
+            fs::copy(&package, &dest)?;
+            let sig = PathBuf::from(format!("{}.sig", package.display()));
+            if sig.exists() {
+                fs::copy(&sig, format!("{}.sig", dest.display()))?;
+            }
+            exported.push(dest);
+        }
+    }
+    Ok(exported)
+}
+
+/// AUR checkout and downloaded sources of the last successful build of `pkg`,
+/// used by `--offline` installs
+pub fn aur_source_cache(pkg: &str) -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(|| PathBuf::from("/tmp"))
+        .join("reap/sources")
+        .join(pkg)
+}
+
+/// Name makepkg stores a PKGBUILD `source` entry under, e.g. `foo-1.0.tar.gz`
+/// for `https://example.org/foo-1.0.tar.gz` or `foo` for `git+https://example.org/foo.git#tag=v1`
+pub fn source_filename(entry: &str) -> String {
+    if let Some((name, _)) = entry.split_once("::") {
+        return name.to_string();
+    }
+    let proto = entry.split_once("://").map_or("", |(proto, _)| proto);
+    let proto = proto.split('+').next().unwrap_or(proto);
+    if matches!(proto, "bzr" | "fossil" | "git" | "hg" | "svn") {
+        let url = entry.split(['#', '?']).next().unwrap_or(entry);
+        let name = url.trim_end_matches('/').rsplit('/').next().unwrap_or(url);
+        name.strip_suffix(".git").unwrap_or(name).to_string()
+    } else {
+        entry.rsplit('/').next().unwrap_or(entry).to_string()
+    }
+}
+
+/// Sources listed in `.SRCINFO` output for `arch` that are missing from `dir`
+pub fn missing_sources(srcinfo: &str, dir: &std::path::Path, arch: &str) -> Vec<String> {
+    let arch_key = format!("source_{}", arch);
+    srcinfo
+        .lines()
+        .filter_map(|line| line.trim().split_once(" = "))
+        .filter(|(key, _)| *key == "source" || *key == arch_key)
+        .map(|(_, entry)| source_filename(entry))
+        .filter(|name| !dir.join(name).exists())
+        .collect()
+}
+
+/// Keep the checkout and downloaded sources of a successful build, leaving
+/// out extracted sources and built packages
+fn save_source_cache(pkg: &str, build_dir: &std::path::Path) -> std::io::Result<()> {
+    let cache = aur_source_cache(pkg);
+    let _ = fs::remove_dir_all(&cache);
+    fs::create_dir_all(&cache)?;
+    for entry in fs::read_dir(build_dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name_str = name.to_string_lossy();
+        if name_str == "src" || name_str == "pkg" || name_str.contains(".pkg.tar") {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            fs_extra::dir::copy(entry.path(), &cache, &fs_extra::dir::CopyOptions::new())
+                .map_err(std::io::Error::other)?;
+        } else {
+            fs::copy(entry.path(), cache.join(&name))?;
+        }
+    }
+    Ok(())
+}
+
+/// Set up `build_dir` from the source cache for an offline build, failing if
+/// `pkg` was never built or any of its sources are missing
+fn restore_source_cache(pkg: &str, build_dir: &std::path::Path) -> Result<(), ReapError> {
+    let cache = aur_source_cache(pkg);
+    if !cache.join("PKGBUILD").exists() {
+        return Err(ReapError::NetworkError(format!(
+            "offline and no cached sources for {}",
+            pkg
+        )));
+    }
+    fs::create_dir_all(build_dir)?;
+    let mut copy_opts = fs_extra::dir::CopyOptions::new();
+    copy_opts.content_only = true;
+    fs_extra::dir::copy(&cache, build_dir, &copy_opts)
+        .map_err(|e| ReapError::CommandFailed(e.to_string()))?;
+    let srcinfo = Command::new("makepkg")
+        .arg("--printsrcinfo")
+        .current_dir(build_dir)
+        .output()?;
+    let missing = missing_sources(
+        &String::from_utf8_lossy(&srcinfo.stdout),
+        build_dir,
+        host_arch(),
+    );
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(ReapError::NetworkError(format!(
+            "offline and sources for {} are not cached: {}",
+            pkg,
+            missing.join(", ")
+        )))
     }
-    let _ = join_all(handles).await;
-    pb.finish_with_message("All installs complete.");
-    println!("[reap] All installs complete.");
 }
 
-pub fn handle_removal(pkgs: &[String]) {
-    for pkg in pkgs {
-        println!("[reap] Removing {}...", pkg);
-        aur::uninstall(pkg);
+/// Build `pkg` from `build_dir` in a clean devtools chroot under the cache dir,
+/// creating the chroot on first use and upgrading it otherwise. The built
+/// packages are left in `build_dir`.
+pub fn build_in_chroot(
+    pkg: &str,
+    build_dir: &std::path::Path,
+    makepkg_args: &[&str],
+    log: &dyn ProgressReporter,
+) -> std::io::Result<std::process::ExitStatus> {
+    for tool in ["mkarchroot", "arch-nspawn", "makechrootpkg"] {
+        if which::which(tool).is_err() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!(
+                    "{} not found, chroot builds need devtools: sudo pacman -S devtools",
+                    tool
+                ),
+            ));
+        }
+    }
+    let chroot = chroot_dir();
+    let root = chroot.join("root");
+    let status = if root.exists() {
+        log.step("chroot", &format!("Updating chroot at {}", root.display()));
+        stream_logged(
+            crate::utils::escalate("arch-nspawn").arg(&root).args([
+                "pacman",
+                "-Syu",
+                "--noconfirm",
+            ]),
+            "chroot",
+            log,
+        )?
+    } else {
+        log.step("chroot", &format!("Creating chroot at {}", root.display()));
+        std::fs::create_dir_all(&chroot)?;
+        stream_logged(
+            crate::utils::escalate("mkarchroot")
+                .arg(&root)
+                .arg("base-devel"),
+            "chroot",
+            log,
+        )?
+    };
+    if !status.success() {
+        return Ok(status);
     }
+    log.step("build", &format!("Building {} in clean chroot", pkg));
+    stream_build(
+        Command::new("makechrootpkg")
+            .arg("-c")
+            .arg("-r")
+            .arg(&chroot)
+            .arg("--")
+            .args(makepkg_args)
+            .current_dir(build_dir),
+        "build",
+        log,
+    )
 }
 
-pub fn handle_local_install(pkgs: &[String]) {
-    for pkg in pkgs {
-        println!("[reap] Installing local package {}...", pkg);
-        aur::install_local(pkg);
-    }
+/// Bare mirror of the AUR git repository of `pkg`, kept between builds so
+/// rebuilds only fetch new commits
+pub fn aur_mirror_dir(pkg: &str) -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(|| PathBuf::from("/tmp"))
+        .join("reap/git")
+        .join(format!("{}.git", pkg))
 }
 
-pub fn handle_search(terms: &[String]) {
-    for term in terms {
-        println!("[reap] Searching for {}...", term);
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        match rt.block_on(aur::search(term)) {
-            Ok(results) => print_search_results(&results),
-            Err(e) => eprintln!("[reap] Search failed for '{}': {}", term, e),
-        }
-    }
+/// The commit a git dir's HEAD points at, `None` for an empty repository
+fn git_head(git_dir: &std::path::Path) -> Option<String> {
+    Command::new("git")
+        .arg("--git-dir")
+        .arg(git_dir)
+        .args(["rev-parse", "--verify", "--quiet", "HEAD"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
 }
 
-pub fn handle_update() {
-    use owo_colors::OwoColorize;
-    println!("{} Checking for package updates...", "🔍".bright_blue());
-    
-    let config = crate::config::ReapConfig::load();
-    let installed = crate::pacman::list_installed_aur();
-    let mut updates_available: Vec<(String, String, String)> = Vec::new();
-    
-    println!("{} Scanning {} AUR packages...", "📦".bright_cyan(), installed.len());
-    
-    for pkg in installed {
-        if config.is_ignored(&pkg) {
-            println!("{} Skipping ignored package: {}", "⏭️".yellow(), pkg.dimmed());
-            continue;
+/// Bring the bare mirror of `pkg`'s AUR repository up to date, cloning it
+/// the first time, and return its path. Says so when a fetch found no new
+/// commits.
+pub fn fetch_aur_repo(pkg: &str, log: &dyn ProgressReporter) -> Result<PathBuf, ReapError> {
+    let repo_url = crate::aur::git_url(pkg);
+    let mirror = aur_mirror_dir(pkg);
+    let log_line = |step: &str, msg: &str| log.step(step, msg);
+    let fresh = !mirror.join("HEAD").exists();
+    let before = if fresh {
+        if let Some(parent) = mirror.parent() {
+            fs::create_dir_all(parent)?;
         }
-        
-        if let Ok(remote) = crate::aur::fetch_package_info(&pkg) {
-            let local_ver = crate::pacman::get_version(&pkg);
-            if let Some(local) = local_ver {
-                if local != remote.version {
-                    updates_available.push((pkg.clone(), local, remote.version));
-                }
+        // A half-written mirror from an interrupted clone
+        let _ = fs::remove_dir_all(&mirror);
+        None
+    } else {
+        git_head(&mirror)
+    };
+    let mut cmd = Command::new("git");
+    if fresh {
+        cmd.args(["clone", "--mirror", &repo_url]).arg(&mirror);
+    } else {
+        log_line("fetch", &format!("Updating cached git mirror of {}", pkg));
+        cmd.arg("--git-dir")
+            .arg(&mirror)
+            .args(["fetch", "--prune", &repo_url, "+refs/*:refs/*"]);
+    }
+    match stream_logged(&mut cmd, "clone", log) {
+        Ok(status) if status.success() => {}
+        Ok(_) => {
+            log_line("clone", &format!("❌ Failed to fetch repo for {}", pkg));
+            if fresh {
+                let _ = fs::remove_dir_all(&mirror);
             }
+            return Err(ReapError::NetworkError(format!(
+                "git {} of {} failed",
+                if fresh { "clone" } else { "fetch" },
+                repo_url
+            )));
         }
-    }
-    
-    if updates_available.is_empty() {
-        println!("{} All AUR packages are up to date!", "✅".bright_green());
-    } else {
-        println!("\n{} {} package(s) can be updated:", "📋".bright_yellow(), updates_available.len().to_string().bright_white());
-        for (pkg, local_ver, remote_ver) in &updates_available {
-            println!("  {} {} → {}", 
-                pkg.bright_white(), 
-                local_ver.red(), 
-                remote_ver.bright_green()
-            );
+        Err(e) => {
+            log_line("clone", &format!("❌ Failed to run git for {}: {}", pkg, e));
+            return Err(ReapError::Io(e));
         }
-        println!("\n{} Run {} to upgrade all packages", 
-            "💡".bright_blue(), 
-            "reap -Syu".bright_cyan()
+    }
+    let after = git_head(&mirror);
+    if after.is_none() {
+        // The AUR serves an empty repository for names it doesn't know,
+        // don't keep a mirror of it
+        let _ = fs::remove_dir_all(&mirror);
+    } else if !fresh && before == after {
+        log_line(
+            "fetch",
+            &format!(
+                "No new commits for {} since the last fetch, the PKGBUILD is unchanged",
+                pkg
+            ),
         );
     }
+    Ok(mirror)
 }
 
-pub fn handle_sync_db() {
-    use owo_colors::OwoColorize;
-    println!("{} Synchronizing package databases...", "🔄".bright_blue());
-    
-    let status = std::process::Command::new("sudo")
-        .arg("pacman")
-        .arg("-Sy")
-        .status();
-    
-    match status {
-        Ok(s) if s.success() => println!("{} Database sync completed", "✅".bright_green()),
-        Ok(_) => eprintln!("{} Failed to sync database", "❌".bright_red()),
-        Err(e) => eprintln!("{} Error syncing database: {}", "❌".bright_red(), e),
+/// Check the AUR repository of `pkg` out into `build_dir` from its cached
+/// mirror (see `fetch_aur_repo`), streaming git's output
+fn clone_aur_repo(
+    pkg: &str,
+    build_dir: &std::path::Path,
+    log: &dyn ProgressReporter,
+) -> Result<(), ReapError> {
+    let mirror = fetch_aur_repo(pkg, log)?;
+    if !mirror.exists() {
+        // Empty repository: leave an empty build dir, the missing PKGBUILD
+        // is reported by the caller
+        fs::create_dir_all(build_dir)?;
+        return Ok(());
     }
-}
-
-pub fn handle_upgrade_all() {
-    use owo_colors::OwoColorize;
-    println!("{} Upgrading all packages...", "🚀".bright_blue());
-    let rt = tokio::runtime::Runtime::new().unwrap();
-    if let Err(e) = rt.block_on(aur::upgrade_all()) {
-        eprintln!("{} Upgrade all failed: {}", "❌".bright_red(), e);
+    let status = stream_logged(
+        Command::new("git")
+            .args(["clone", "--quiet"])
+            .arg(&mirror)
+            .arg(build_dir),
+        "clone",
+        log,
+    )?;
+    if !status.success() {
+        log.step("clone", &format!("❌ Failed to check out {}", pkg));
+        return Err(ReapError::CommandFailed(format!(
+            "git clone of {} failed",
+            mirror.display()
+        )));
     }
-}
-
-pub fn handle_clean() {
-    println!("[reap] Cleaning package cache...");
-    let status = std::process::Command::new("sudo")
-        .arg("pacman")
-        .arg("-Sc")
-        .arg("--noconfirm")
+    // Point the checkout at the AUR rather than the local mirror
+    let _ = Command::new("git")
+        .arg("-C")
+        .arg(build_dir)
+        .args(["remote", "set-url", "origin", &crate::aur::git_url(pkg)])
         .status();
-
-    match status {
-        Ok(s) if s.success() => println!("[reap] Cache cleaned successfully"),
-        Ok(_) => eprintln!("[reap] Failed to clean cache"),
-        Err(e) => eprintln!("[reap] Error cleaning cache: {}", e),
-    }
+    Ok(())
 }
 
-pub fn handle_doctor() {
-    println!("[reap] Running system diagnostics...");
-    match crate::utils::doctor_report() {
-        Ok(report) => println!("[reap] Doctor report:\n{}", report),
-        Err(e) => eprintln!("[reap] Doctor error: {}", e),
-    }
+/// Where a package the AUR no longer has went
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MovedPackage {
+    /// Moved to an official repository
+    Repo { repo: String, version: String },
+    /// Merged into (or replaced by) other AUR packages
+    Replaced(Vec<String>),
+    /// No trace of it, probably deleted
+    Gone,
 }
 
-pub fn handle_upgrade(parallel: bool) {
-    let config = crate::config::ReapConfig::load();
-    let installed = crate::pacman::list_installed_aur();
-    let mut to_upgrade: Vec<String> = Vec::new();
-    for pkg in installed {
-        if config.is_ignored(&pkg) {
-            println!("[reap] Skipping ignored package: {}", pkg);
-            continue;
+impl MovedPackage {
+    /// Look up where `pkg` went: the sync repos first, then AUR packages
+    /// replacing or providing it. Uses the blocking client.
+    pub fn find(pkg: &str) -> Self {
+        if let Some((repo, version)) = pacman::sync_info(pkg) {
+            return MovedPackage::Repo { repo, version };
         }
-        if let Ok(remote) = crate::aur::fetch_package_info(&pkg) {
-            let local_ver = crate::pacman::get_version(&pkg);
-            if local_ver.as_deref() != Some(&remote.version) {
-                to_upgrade.push(pkg.to_string());
-            }
+        match aur::aur_replacements(pkg) {
+            replacements if replacements.is_empty() => MovedPackage::Gone,
+            replacements => MovedPackage::Replaced(replacements),
         }
     }
-    if to_upgrade.is_empty() {
-        println!("[reap] All AUR packages up to date.");
-        return;
-    }
-    println!("[reap] Upgrading: {:?}", to_upgrade);
-    if parallel {
-        tokio::runtime::Runtime::new()
-            .unwrap()
-            .block_on(handle_install_parallel(to_upgrade, config.parallel));
-    } else {
-        for pkg in to_upgrade {
-            let _ = tokio::runtime::Runtime::new()
-                .unwrap()
-                .block_on(crate::aur::install(vec![pkg.as_str()]));
+
+    /// What to tell the user instead of a failed clone
+    pub fn guidance(&self, pkg: &str) -> String {
+        match self {
+            MovedPackage::Repo { repo, version } => format!(
+                "{} is no longer in the AUR: it moved to [{}] ({}). Install the prebuilt package with `sudo pacman -S {}` instead of building it",
+                pkg, repo, version, pkg
+            ),
+            MovedPackage::Replaced(by) if by.len() == 1 => format!(
+                "{} is no longer in the AUR: it was merged into {}. Install that instead with `reap -S {}`",
+                pkg, by[0], by[0]
+            ),
+            MovedPackage::Replaced(by) => format!(
+                "{} is no longer in the AUR. These packages replace or provide it: {}",
+                pkg,
+                by.join(", ")
+            ),
+            MovedPackage::Gone => format!(
+                "{} is not in the AUR or the repos; it may have been deleted or renamed. Try `reap search {}`",
+                pkg, pkg
+            ),
         }
     }
 }
 
-pub fn handle_rollback(pkg: &str) {
-    // Restore or remove utils::rollback and hooks::on_rollback
-    if let Some(rollback_fn) = std::option::Option::Some(utils::rollback) {
-        rollback_fn(pkg);
+/// After `pkg` couldn't be fetched from the AUR, explain where it went and
+/// offer to install the repo package or the single replacement instead.
+/// Returns `None` when the user declines, leaving the original error.
+async fn install_moved_package(
+    pkg: &str,
+    log: &dyn ProgressReporter,
+    opts: &InstallOptions,
+) -> Option<Result<(), ReapError>> {
+    use crate::interactive::InteractiveManager;
+    let name = pkg.to_string();
+    let moved = tokio::task::spawn_blocking(move || MovedPackage::find(&name))
+        .await
+        .unwrap_or(MovedPackage::Gone);
+    log.step("fetch", &format!("❌ {}", moved.guidance(pkg)));
+    if opts.download_only || opts.dry_run {
+        return None;
     }
-}
-
-pub fn handle_orphan(remove: bool, all: bool) {
-    let output = std::process::Command::new("pacman")
-        .args(["-Qdtq"])
-        .output();
-    let mut aur_orphans = Vec::new();
-    let mut repo_orphans = Vec::new();
-    if let Ok(out) = output {
-        for pkg in String::from_utf8_lossy(&out.stdout).lines() {
-            let repo_check = std::process::Command::new("pacman")
-                .arg("-Si")
-                .arg(pkg)
-                .output();
-            let is_repo = repo_check
-                .as_ref()
-                .map(|o| o.status.success())
-                .unwrap_or(false);
-            if is_repo {
-                repo_orphans.push(pkg.to_string());
+    match &moved {
+        MovedPackage::Repo { repo, .. }
+            if InteractiveManager::confirm_action(
+                &format!("Install {} from [{}] instead?", pkg, repo),
+                true,
+            ) =>
+        {
+            let name = pkg.to_string();
+            let _ = tokio::task::spawn_blocking(move || pacman::install(&name)).await;
+            Some(if pacman::is_installed(pkg) {
+                Ok(())
             } else {
-                aur_orphans.push(pkg.to_string());
-            }
-        }
-    }
-    if !aur_orphans.is_empty() {
-        println!("Orphaned AUR packages:\n");
-        for pkg in &aur_orphans {
-            println!("    {}", pkg);
+                Err(ReapError::CommandFailed(format!(
+                    "pacman -S {} failed",
+                    pkg
+                )))
+            })
         }
-        if remove {
-            for pkg in &aur_orphans {
-                println!("[reap] Uninstalling orphaned AUR package: {}", pkg);
-                crate::aur::uninstall(pkg);
-            }
-        } else {
-            println!("\nRun with --remove to uninstall.");
+        MovedPackage::Replaced(by)
+            if by.len() == 1
+                && InteractiveManager::confirm_action(
+                    &format!("Install {} instead?", by[0]),
+                    true,
+                ) =>
+        {
+            Some(Box::pin(install_aur_native(&by[0], log, opts)).await)
         }
-    } else {
-        println!("No orphaned AUR packages found.");
+        _ => None,
     }
-    if all && !repo_orphans.is_empty() {
-        println!("\nOrphaned pacman packages:\n");
-        for pkg in &repo_orphans {
-            println!("    {}", pkg);
-        }
-        if remove {
-            for pkg in &repo_orphans {
-                println!("[reap] Uninstalling orphaned pacman package: {}", pkg);
-                crate::aur::uninstall(pkg);
-            }
-        } else {
-            println!("\nRun with --remove to uninstall.");
-        }
+}
+
+/// Options an AUR build and its dependency builds get from the install.
+/// Signature strictness and the keyserver carry over unchanged.
+pub fn aur_build_options(opts: &InstallOptions) -> InstallOptions {
+    InstallOptions {
+        max_parallel: 4,
+        ..opts.clone()
     }
 }
 
-pub fn show_pkgbuild_diff(pkg: &str) {
-    let local_path = std::env::temp_dir().join(format!("reap-aur-{}/PKGBUILD", pkg));
-    let local = std::fs::read_to_string(&local_path).unwrap_or_default();
-    let remote = crate::aur::get_pkgbuild_preview(pkg);
-    let diff = diff::lines(&local, &remote);
-    for d in diff {
-        match d {
-            diff::Result::Left(l) => println!("\x1b[31m- {}\x1b[0m", l),
-            diff::Result::Right(r) => println!("\x1b[32m+ {}\x1b[0m", r),
-            diff::Result::Both(l, _) => println!("  {}", l),
-        }
+/// Strict mode refuses to build a package flagged out-of-date
+pub fn refuse_out_of_date(pkg: &str, opts: &InstallOptions) -> Result<(), ReapError> {
+    if opts.strict_signatures {
+        return Err(ReapError::TrustPolicy {
+            pkg: pkg.to_string(),
+            reason: "flagged out-of-date, pass --ignore-ood to build it anyway".to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Warn that `pkg` is flagged out-of-date, with when, by whom and why, and
+/// ask whether to build it anyway. Strict mode refuses instead.
+async fn confirm_out_of_date(
+    pkg: &str,
+    base: &str,
+    flagged: i64,
+    opts: &InstallOptions,
+    log: &dyn ProgressReporter,
+) -> Result<(), ReapError> {
+    let owned = base.to_string();
+    let flag = tokio::task::spawn_blocking(move || aur::fetch_flag_comment(&owned))
+        .await
+        .ok()
+        .and_then(Result::ok)
+        .unwrap_or_default();
+    let date = chrono::DateTime::from_timestamp(flagged, 0)
+        .map(|d| d.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| "an unknown date".to_string());
+    let mut msg = format!("⚠️ {} was flagged out-of-date on {}", pkg, date);
+    if let Some(by) = &flag.flagged_by {
+        msg.push_str(&format!(" by {}", by));
+    }
+    if let Some(reason) = &flag.reason {
+        msg.push_str(&format!(": {}", reason));
+    }
+    log.step("fetch", &msg);
+    refuse_out_of_date(pkg, opts)?;
+    let build = crate::interactive::InteractiveManager::confirm_action(
+        &format!(":: Build {} anyway?", pkg),
+        false,
+    );
+    if build {
+        Ok(())
+    } else {
+        Err(ReapError::UserAborted)
     }
 }
 
 pub async fn install_aur_native(
     pkg: &str,
-    log: &LogPane,
+    log: &dyn ProgressReporter,
     opts: &InstallOptions,
 ) -> Result<(), ReapError> {
     use chrono::Local;
     use std::env;
     use std::fs;
-    use std::process::{Command, Stdio};
-    let now = Local::now().format("%Y-%m-%d %H:%M:%S");
-    let cache_dir = dirs::cache_dir().unwrap_or_else(|| PathBuf::from("/tmp"));
-    let build_dir = cache_dir.join(format!("reap-aur-{}-{}", pkg, now));
-    let repo_url = format!("https://aur.archlinux.org/{}.git", pkg);
-    let log_line = |step: &str, msg: &str| {
-        use owo_colors::OwoColorize;
-        let entry = format!("[{}][reap][aur][{}] {}", now, step, msg);
-        log.push(&entry);
-        // Also print colorized output to console
-        match step {
-            "fetch" => println!("{} {}", "📥".bright_blue(), msg.bright_white()),
-            "build" => println!("{} {}", "🔨".bright_yellow(), msg.bright_white()),
-            "install" => println!("{} {}", "📦".bright_green(), msg.bright_white()),
-            "deps" => println!("{} {}", "🔗".bright_cyan(), msg.bright_white()),
-            "error" => println!("{} {}", "❌".bright_red(), msg.bright_red()),
-            "success" => println!("{} {}", "✅".bright_green(), msg.bright_green()),
-            _ => println!("{} {}", "ℹ️".bright_blue(), msg.bright_white()),
+    use std::process::Command;
+    let now = Local::now().format(BUILD_DIR_STAMP);
+    let build_dir = build_root().join(format!("reap-aur-{}-{}", pkg, now));
+    // Ctrl-C removes the build dir instead of leaving it behind
+    let _build_dir_guard = crate::interrupt::BuildDirGuard::register(&build_dir)?;
+    let log_line = |step: &str, msg: &str| log.step(step, msg);
+    let offline = crate::aur::is_offline();
+    let global = GlobalConfig::load();
+    // --- Signing key ---
+    // An unusable key would otherwise only fail after the build
+    let sign_key = global.sign_key.clone().filter(|key| !key.is_empty());
+    if let Some(key) = &sign_key
+        && let Err(e) = check_sign_key(key)
+    {
+        log_line("sign", &format!("❌ {}", e));
+        return Err(e);
+    }
+    // --- Fetch PKGBUILD ---
+    if offline {
+        log_line(
+            "fetch",
+            &format!("Using cached sources for {} (offline)", pkg),
+        );
+        if let Err(e) = restore_source_cache(pkg, &build_dir) {
+            log_line("fetch", &format!("❌ {}", e));
+            let _ = fs::remove_dir_all(&build_dir);
+            return Err(e);
+        }
+    } else {
+        log_line("fetch", &format!("Fetching PKGBUILD for {}", pkg));
+        // Split packages live in their pkgbase's repo
+        let name = pkg.to_string();
+        let info = tokio::task::spawn_blocking(move || aur::fetch_package_info(&name))
+            .await
+            .ok()
+            .and_then(Result::ok);
+        let base = info
+            .as_ref()
+            .map(|info| info.base.clone())
+            .unwrap_or_else(|| pkg.to_string());
+        if base != pkg {
+            log_line("fetch", &format!("{} is built from pkgbase {}", pkg, base));
         }
+        if let Some(flagged) = info.and_then(|info| info.out_of_date)
+            && !opts.ignore_ood
+            && let Err(e) = confirm_out_of_date(pkg, &base, flagged, opts, log).await
+        {
+            return Err(e);
+        }
+        if let Err(e) = clone_aur_repo(&base, &build_dir, log) {
+            let _ = fs::remove_dir_all(&build_dir);
+            // A failed clone may mean the package is gone from the AUR
+            let name = pkg.to_string();
+            let unknown = matches!(e, ReapError::NetworkError(_))
+                && tokio::task::spawn_blocking(move || {
+                    matches!(
+                        aur::fetch_package_info(&name),
+                        Err(ReapError::SourceNotFound(_))
+                    )
+                })
+                .await
+                .unwrap_or(false);
+            if unknown && let Some(result) = install_moved_package(pkg, log, opts).await {
+                return result;
+            }
+            return Err(e);
+        }
+    }
+    let pkgb_path = build_dir.join("PKGBUILD");
+    // Cloning a name the AUR doesn't know yields an empty repository
+    if !pkgb_path.exists() {
+        log_line("clone", &format!("❌ {} has no PKGBUILD in the AUR", pkg));
+        let _ = fs::remove_dir_all(&build_dir);
+        if !offline && let Some(result) = install_moved_package(pkg, log, opts).await {
+            return result;
+        }
+        return Err(ReapError::SourceNotFound(pkg.to_string()));
+    }
+    // --- Split packages ---
+    // Only the chosen packages of a split pkgbase are installed
+    let split_only = match crate::srcinfo::parse_srcinfo(&build_dir) {
+        Ok(srcinfo) if srcinfo.packages.len() > 1 => {
+            let names: Vec<String> = srcinfo.pkgnames().iter().map(|n| n.to_string()).collect();
+            log_line(
+                "split",
+                &format!(
+                    "{} builds {} packages: {}",
+                    srcinfo.pkgbase,
+                    names.len(),
+                    names.join(", ")
+                ),
+            );
+            let chosen = choose_split_packages(pkg, &srcinfo.pkgbase, &names);
+            if chosen.len() < names.len() {
+                log_line("split", &format!("Installing {}", chosen.join(", ")));
+                srcinfo.version().map(|version| (chosen, version))
+            } else {
+                None
+            }
+        }
+        _ => None,
     };
-    // --- Fetch PKGBUILD ---
-    log_line("fetch", &format!("Fetching PKGBUILD for {}", pkg));
-    let mut clone_cmd = Command::new("git");
-    clone_cmd
-        .arg("clone")
-        .arg(&repo_url)
-        .arg(&build_dir)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped());
-    match clone_cmd.spawn().and_then(|mut child| {
-        let stdout = child.stdout.take().unwrap();
-        let stderr = child.stderr.take().unwrap();
-        let mut reader = std::io::BufReader::new(stdout);
-        let mut err_reader = std::io::BufReader::new(stderr);
-        let mut buf = String::new();
-        let mut err_buf = String::new();
-        while reader.read_line(&mut buf).unwrap_or(0) > 0 {
-            log_line("clone", buf.trim_end());
-            buf.clear();
-        }
-        while err_reader.read_line(&mut err_buf).unwrap_or(0) > 0 {
-            log_line("clone", err_buf.trim_end());
-            err_buf.clear();
-        }
-        child.wait()
-    }) {
-        Ok(status) if status.success() => {}
-        Ok(_) => {
-            log_line("clone", &format!("❌ Failed to clone repo for {}", pkg));
-            return Err(ReapError::CommandFailed("git clone failed".to_string()));
+    // --- Conflicts ---
+    // Catch conflicts now rather than after a long build. Nothing is
+    // installed with --downloadonly, so there is nothing to conflict with.
+    let pkgbuild = fs::read_to_string(&pkgb_path).unwrap_or_default();
+    let conflicts = if opts.download_only {
+        Vec::new()
+    } else {
+        installed_conflicts(pkg, &build_dir, &pkgbuild)
+    };
+    // Nothing is removed yet: pacman replaces the confirmed packages in the
+    // same transaction that installs the build, so a failed build keeps them
+    let mut replaces = Vec::new();
+    for conflict in conflicts {
+        log_line(
+            "conflict",
+            &format!("{} conflicts with installed package {}", pkg, conflict),
+        );
+        let remove = crate::interactive::InteractiveManager::confirm_action(
+            &format!(
+                ":: {} and {} are in conflict. Remove {}?",
+                pkg, conflict, conflict
+            ),
+            false,
+        );
+        if !remove {
+            let _ = fs::remove_dir_all(&build_dir);
+            return Err(ReapError::UserAborted);
         }
-        Err(e) => {
+        log_line(
+            "conflict",
+            &format!("{} will be replaced once {} is built", conflict, pkg),
+        );
+        replaces.push(conflict);
+    }
+    // --- Arch ---
+    // Fail before building rather than deep inside makepkg
+    let archs = crate::enhanced_aur::EnhancedAurManager::new()
+        .parse_pkgbuild(pkg, &pkgbuild)
+        .map(|info| info.arch)
+        .unwrap_or_default();
+    let host = host_arch();
+    if !supports_arch(&archs, host) {
+        let msg = format!(
+            "{} does not list {} in arch=({})",
+            pkg,
+            host,
+            archs.join(" ")
+        );
+        if !opts.ignore_arch {
             log_line(
-                "clone",
-                &format!("❌ Failed to run git clone for {}: {}", pkg, e),
+                "arch",
+                &format!("❌ {}; use --ignore-arch to build anyway", msg),
             );
-            return Err(ReapError::Io(e));
+            let _ = fs::remove_dir_all(&build_dir);
+            return Err(ReapError::BuildFailed {
+                pkg: pkg.to_string(),
+                stage: "arch",
+            });
         }
+        log_line("arch", &format!("⚠️ {}; building with --ignorearch", msg));
     }
-    let pkgb_path = build_dir.join("PKGBUILD");
+    // --- Kernel modules ---
+    // Modules are built against the headers of each kernel they're for
+    let module_deps: Vec<String> = crate::srcinfo::parse_srcinfo(&build_dir)
+        .map(|srcinfo| {
+            let mut deps = srcinfo.get(pkg, "depends", host);
+            deps.extend(srcinfo.get(pkg, "makedepends", host));
+            deps
+        })
+        .unwrap_or_default();
+    let dkms_kernels = if !opts.download_only && crate::kernel::builds_modules(&module_deps) {
+        match prepare_kernel_headers(pkg, &module_deps, opts.kernel.as_deref(), log) {
+            Ok(kernels) => kernels,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&build_dir);
+                return Err(e);
+            }
+        }
+    } else {
+        Vec::new()
+    };
     // --- Diff ---
+    // Only interactive sessions can review; fast mode skips review entirely
+    let review = !opts.fast_mode
+        && std::io::IsTerminal::is_terminal(&std::io::stdin())
+        && global.review_pkgbuilds.unwrap_or(true);
+    if review && !review_pkgbuild(pkg, &build_dir, &pkgbuild) {
+        let _ = fs::remove_dir_all(&build_dir);
+        return Err(ReapError::UserAborted);
+    }
     // --- Edit ---
-    if opts.insecure {
+    // Independent of --insecure: verification stays on, and an edited
+    // PKGBUILD no longer matches what was reviewed or signed
+    if opts.edit {
         log_line("edit", "Editing PKGBUILD");
         let editor = env::var("EDITOR").unwrap_or_else(|_| "nano".to_string());
         let status = Command::new(editor).arg(&pkgb_path).status();
@@ -923,47 +7450,293 @@ pub async fn install_aur_native(
             Ok(_) => log_line("edit", "Editor exited with error status."),
             Err(e) => log_line("edit", &format!("Failed to launch editor: {}", e)),
         }
+        let edited = fs::read_to_string(&pkgb_path).unwrap_or_default();
+        let changed = count_changed_lines(&pkgbuild, &edited);
+        if changed > 0 {
+            log_line(
+                "edit",
+                &format!("⚠️ {} lines changed from the AUR PKGBUILD", changed),
+            );
+            let signature = verify_pkgbuild_signature(&build_dir);
+            if signature == Some(false) {
+                log_line("edit", "⚠️ PKGBUILD.sig no longer verifies");
+            }
+            let proceed = opts.insecure
+                || crate::interactive::InteractiveManager::confirm_action(
+                    &format!("Build the edited PKGBUILD for {}?", pkg),
+                    false,
+                );
+            if !proceed {
+                let _ = fs::remove_dir_all(&build_dir);
+                return Err(ReapError::UserAborted);
+            }
+        }
+    }
+    // --- Dry Run ---
+    if opts.dry_run {
+        log_line("dry-run", &format!("Would build and install: {}", pkg));
+        let _ = fs::remove_dir_all(&build_dir);
+        log_line("cleanup", &format!("Cleaned up {}", build_dir.display()));
+        return Ok(());
+    }
+    // --- Build ---
+    let chroot = opts.chroot || global.chroot.unwrap_or(false);
+    // The active profile may build with its own makepkg.conf
+    let makepkg_conf = match ProfileManager::new().get_active_profile() {
+        Ok(profile) => profile.makepkg_conf_path(),
+        Err(e) => {
+            log_line("build", &format!("❌ {}", e));
+            let _ = fs::remove_dir_all(&build_dir);
+            return Err(ReapError::CommandFailed(e.to_string()));
+        }
+    };
+    let makepkg_conf = makepkg_conf.map(|path| path.to_string_lossy().into_owned());
+    let mut makepkg_args: Vec<&str> = Vec::new();
+    match &makepkg_conf {
+        // The chroot reads the makepkg.conf inside it
+        Some(path) if chroot => log_line(
+            "build",
+            &format!(
+                "⚠️ Ignoring makepkg_conf {} for the chroot build, it uses the chroot's own",
+                path
+            ),
+        ),
+        Some(path) => {
+            log_line("build", &format!("Using makepkg.conf {}", path));
+            makepkg_args.extend(["--config", path.as_str()]);
+        }
+        None => log_line("build", "Using makepkg.conf /etc/makepkg.conf"),
+    }
+    if opts.ignore_arch {
+        makepkg_args.push("--ignorearch");
+    }
+    let nocheck = opts.nocheck || global.nocheck.unwrap_or(false);
+    if nocheck {
+        log_line("build", "Skipping check() (--nocheck)");
+        makepkg_args.push("--nocheck");
+    }
+    if !opts.ignore_deps.is_empty() {
+        log_line(
+            "deps",
+            &format!(
+                "⚠️ Assuming {} installed, the built package may be broken without {}",
+                opts.ignore_deps.join(", "),
+                if opts.ignore_deps.len() == 1 {
+                    "it"
+                } else {
+                    "them"
+                }
+            ),
+        );
+        // The chroot installs its own copy, only the host install skips them
+        if !chroot {
+            makepkg_args.push("--nodeps");
+        }
+    }
+    // makepkg never installs by itself: its -s and -i would run pacman
+    // behind reap's back, racing the other builds of a parallel install for
+    // the pacman db lock. Dependencies go in first, the packages after.
+    if !chroot && let Err(e) = install_build_deps(&build_dir, &opts.ignore_deps, nocheck, log) {
+        let _ = fs::remove_dir_all(&build_dir);
+        return Err(e);
+    }
+    if offline {
+        // Build VCS packages from the cached checkout instead of fetching
+        makepkg_args.push("--holdver");
+    }
+    // Plain makepkg runs sign themselves; chroot and sandbox builds can't
+    // reach the keyring, so their packages are signed afterwards
+    let mut plain_args = makepkg_args.clone();
+    if let Some(key) = &sign_key {
+        plain_args.extend(["--sign", "--key", key.as_str()]);
+    }
+    let makepkg_args = makepkg_args.as_slice();
+    if let Some(estimate) = crate::history::BuildTimes::load().estimate(pkg) {
+        log_line(
+            "build",
+            &format!(
+                "Estimated build time: ~{}",
+                utils::format_duration(estimate)
+            ),
+        );
+    }
+    // Chroot builds keep their dependencies inside the chroot
+    let installed_before = if chroot {
+        None
+    } else {
+        Some(pacman::installed_versions())
+    };
+    // A clean chroot takes precedence over build_isolation. Every build is
+    // installed through pacman -U.
+    let isolation = if chroot {
+        None
+    } else {
+        match BuildIsolation::from_config(&global) {
+            Ok(isolation) => Some(isolation),
+            Err(e) => {
+                log_line("build", &format!("❌ {}", e));
+                let _ = fs::remove_dir_all(&build_dir);
+                return Err(ReapError::CommandFailed(e));
+            }
+        }
+    };
+    let plain = isolation == Some(BuildIsolation::None);
+    if let Some(isolation) = &isolation {
+        log_line(
+            "build",
+            &format!(
+                "Running makepkg for {} ({} isolation)",
+                pkg,
+                isolation.label()
+            ),
+        );
+    }
+    if !opts.overwrite.is_empty() && !opts.download_only {
+        log_line(
+            "install",
+            &format!(
+                "⚠️ Files matching {} may be overwritten",
+                opts.overwrite.join(", ")
+            ),
+        );
     }
-    // --- Dry Run ---
-    if opts.insecure {
-        log_line("dry-run", &format!("Would build and install: {}", pkg));
-        let _ = fs::remove_dir_all(&build_dir);
-        log_line("cleanup", &format!("Cleaned up {}", build_dir.display()));
-        return Ok(());
+    // Both builds of --verify-reproducible need the same timestamps
+    let epoch = opts
+        .verify_reproducible
+        .then(|| source_date_epoch(&build_dir));
+    if epoch.is_some() && chroot {
+        log_line(
+            "reproducible",
+            "SOURCE_DATE_EPOCH can't be passed into the chroot, timestamps may differ",
+        );
     }
-    // --- Build ---
-    log_line("build", &format!("Running makepkg for {}", pkg));
-    let mut makepkg_cmd = Command::new("makepkg");
-    makepkg_cmd
-        .arg("-si")
-        .arg("--noconfirm")
-        .arg("--needed")
-        .current_dir(&build_dir)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped());
-    match makepkg_cmd.spawn().and_then(|mut child| {
-        let stdout = child.stdout.take().unwrap();
-        let stderr = child.stderr.take().unwrap();
-        let mut reader = std::io::BufReader::new(stdout);
-        let mut err_reader = std::io::BufReader::new(stderr);
-        let mut buf = String::new();
-        let mut err_buf = String::new();
-        while reader.read_line(&mut buf).unwrap_or(0) > 0 {
-            log_line("build", buf.trim_end());
-            buf.clear();
-        }
-        while err_reader.read_line(&mut err_buf).unwrap_or(0) > 0 {
-            log_line("build", err_buf.trim_end());
-            err_buf.clear();
-        }
-        child.wait()
-    }) {
+    // `rebuild` builds again from freshly extracted sources
+    let run_build = |rebuild: bool| match isolation {
+        None => build_in_chroot(pkg, &build_dir, makepkg_args, log),
+        Some(BuildIsolation::None) => {
+            let mut cmd = Command::new("makepkg");
+            cmd.arg("--noconfirm")
+                .args(&plain_args)
+                .current_dir(&build_dir);
+            if rebuild {
+                cmd.args(["--cleanbuild", "--force"]);
+            } else if opts.reinstall {
+                // Build again even if a package of this version was left behind
+                cmd.arg("--force");
+            }
+            if let Some(epoch) = &epoch {
+                cmd.env("SOURCE_DATE_EPOCH", epoch);
+            }
+            stream_build(&mut cmd, "build", log)
+        }
+        Some(BuildIsolation::Sandbox) => {
+            build_sandboxed(&build_dir, makepkg_args, epoch.as_deref(), log)
+        }
+        Some(BuildIsolation::Nspawn) => unreachable!("rejected by BuildIsolation::from_config"),
+    };
+    let build_start = Instant::now();
+    let built = run_build(false);
+    let build_time = build_start.elapsed();
+    let built = match built {
+        Ok(status) if status.success() && opts.verify_reproducible => {
+            match verify_reproducible_build(pkg, &build_dir, makepkg_args, || run_build(true), log)
+            {
+                Ok(status) => Ok(status),
+                Err(e) => {
+                    let _ = fs::remove_dir_all(&build_dir);
+                    return Err(e);
+                }
+            }
+        }
+        built => built,
+    };
+    let built = match (built, &sign_key) {
+        (Ok(status), Some(key)) if status.success() && !plain => {
+            sign_built_packages(&build_dir, makepkg_args, key, log)
+        }
+        (built, _) => built,
+    };
+    let built = match built {
+        Ok(status) if status.success() && !opts.download_only => install_built_packages(
+            &build_dir,
+            makepkg_args,
+            opts,
+            split_only.as_ref(),
+            &replaces,
+            log,
+        ),
+        built => built,
+    };
+    match built {
         Ok(status) if status.success() => {
-            log_line("install", &format!("✅ {} installed successfully!", pkg));
+            if opts.download_only || opts.export_packages {
+                match export_built_packages(&build_dir, makepkg_args, &opts.output_dir()) {
+                    Ok(packages) => {
+                        for package in packages {
+                            log_line("package", &format!("📦 {}", package.display()));
+                        }
+                    }
+                    Err(e) => {
+                        log_line("package", &format!("❌ Failed to export packages: {}", e));
+                        let _ = fs::remove_dir_all(&build_dir);
+                        return Err(ReapError::Io(e));
+                    }
+                }
+            }
+            if !opts.download_only {
+                log_line("install", &format!("✅ {} installed successfully!", pkg));
+                build_dkms_modules(&dkms_kernels, log);
+                // Keep the built PKGBUILD as the baseline for `reap diff`
+                let stored = built_pkgbuild_path(pkg);
+                if let Some(parent) = stored.parent()
+                    && let Err(e) =
+                        fs::create_dir_all(parent).and_then(|_| fs::copy(&pkgb_path, &stored))
+                {
+                    log_line("install", &format!("Failed to store PKGBUILD: {}", e));
+                }
+            }
+            if let Err(e) = crate::history::record_build_time(pkg, build_time) {
+                log_line("build", &format!("Failed to record build time: {}", e));
+            }
+            // Remember the upstream commits this build came from for --devel
+            if !offline
+                && aur::is_vcs_package(pkg, Some(&pkgbuild))
+                && let Ok(srcinfo) = fs::read_to_string(build_dir.join(".SRCINFO"))
+                && let Some(commits) = aur::upstream_commits(&srcinfo)
+                && let Err(e) = crate::history::record_vcs_commits(pkg, commits)
+            {
+                log_line("build", &format!("Failed to record VCS commits: {}", e));
+            }
+            if let Some(before) = &installed_before {
+                track_build_deps(
+                    pkg,
+                    before,
+                    opts.clean_after || global.clean_after.unwrap_or(false),
+                    log,
+                );
+            }
+            if !offline && let Err(e) = save_source_cache(pkg, &build_dir) {
+                log_line("install", &format!("Failed to cache sources: {}", e));
+            }
         }
         Ok(_) => {
             log_line("install", &format!("❌ makepkg failed for {}", pkg));
-            return Err(ReapError::CommandFailed("makepkg failed".to_string()));
+            // makepkg checks validpgpkeys against the user's keyring
+            if pkgbuild.contains("validpgpkeys")
+                && !crate::gpg::keyring_initialized(&crate::gpg::user_keyring_dir())
+            {
+                log_line(
+                    "install",
+                    &format!(
+                        "Source signatures can't be checked without a keyring; {}",
+                        crate::gpg::KEYRING_HINT
+                    ),
+                );
+            }
+            return Err(ReapError::BuildFailed {
+                pkg: pkg.to_string(),
+                stage: "build",
+            });
         }
         Err(e) => {
             log_line(
@@ -978,118 +7751,148 @@ pub async fn install_aur_native(
     Ok(())
 }
 
-/// Recursively resolve all missing dependencies for a list of packages (AUR + repo)
-/// Hybrid dependency resolver: tap > AUR > system
+/// Dispatch a parsed subcommand. Callers provide the async runtime.
 pub async fn handle_cli(cli: &Cli) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // Define or remove gpg_cmd if not needed
-    match &cli.command {
+    let Some(command) = &cli.command else {
+        return Ok(());
+    };
+    let confirm = should_confirm(cli);
+    match command {
         Commands::Install {
             pkg,
+            maintainer,
             repo,
             binary_only,
-            .. // Remove or ignore unused variable: diff
+            diff,
         } => {
-            let source = detect_source(pkg, repo.as_deref(), *binary_only).unwrap_or(Source::Aur);
-            let task = InstallTask::new(
-                pkg.to_string(),
-                source.clone(),
-            );
-            let log_pane = tui::LogPane::default();
-            let backend = cli.backend.as_str();
-            let try_pacman = backend == "pacman" || backend == "auto";
-            let mut tried_pacman = false;
-            let mut pacman_success = false;
-            if try_pacman {
-                // Try native pacman install first
-                let status = std::process::Command::new("pacman")
-                    .arg("-Si")
-                    .arg(&task.pkg)
-                    .status();
-                if let Ok(s) = status {
-                    if s.success() {
-                        println!(
-                            "[reap] Installing {} from system repo via pacman...",
-                            task.pkg
-                        );
-                        pacman::install(&task.pkg);
-                        pacman_success = true;
-                        tried_pacman = true;
-                    }
-                }
+            if let Some(maintainer) = maintainer {
+                return Ok(
+                    handle_install_maintainer(maintainer, confirm, &install_options(cli)).await?,
+                );
             }
-            if !pacman_success && (backend == "aur" || backend == "auto") {
-                // Fallback to AUR install
-                println!("[reap] Installing {} from AUR...", task.pkg);
-                let opts = InstallOptions {
-                    insecure: false,
-                    gpg_keyserver: None,
-                    fast_mode: false,
-                    strict_signatures: false,
-                    max_parallel: 4,
-                };
-                install_aur_native(&task.pkg, &log_pane, &opts)
-                    .await
-                    .unwrap_or_else(|e| {
-                        println!("[reap] Failed to install {}: {:?}", task.pkg, e);
-                    });
-            } else if !pacman_success && !tried_pacman {
-                eprintln!("[reap] Package '{}' not found in repos or AUR.", task.pkg);
-            }
-        }
-        Commands::Upgrade { parallel: _ } => {
-            let config = crate::config::ReapConfig::load();
-            let installed = crate::pacman::list_installed_aur();
-            let mut to_upgrade: Vec<String> = Vec::new();
-            for pkg in installed {
-                if config.is_ignored(&pkg) {
-                    println!("[reap] Skipping ignored package: {}", pkg);
-                    continue;
-                }
-                if let Ok(remote) = crate::aur::fetch_package_info(&pkg) {
-                    let local_ver = crate::pacman::get_version(&pkg);
-                    if local_ver.as_deref() != Some(&remote.version) {
-                        to_upgrade.push(pkg.to_string());
-                    }
+            // clap requires a package unless --maintainer is given
+            let Some(pkg) = pkg else {
+                return Ok(());
+            };
+            if *diff {
+                // Show PKGBUILD diff before install
+                let name = pkg.clone();
+                let _ = tokio::task::spawn_blocking(move || show_pkgbuild_diff(&name)).await;
+                if !crate::interactive::InteractiveManager::confirm_action(
+                    "Continue with installation?",
+                    true,
+                ) {
+                    return Ok(());
                 }
             }
-            if to_upgrade.is_empty() {
-                println!("[reap] All AUR packages up to date.");
-                return Ok(());
+            // Backup package state before install
+            if !cli.dry_run
+                && let Err(e) = backup_package_state(pkg)
+            {
+                eprintln!("[backup] Warning: Failed to backup package state: {}", e);
             }
-            println!("[reap] Upgrading: {:?}", to_upgrade);
-            let log_pane = tui::LogPane::default();
-            let opts = InstallOptions {
-                insecure: false,
-                gpg_keyserver: None,
-                fast_mode: false,
-                strict_signatures: false,
-                max_parallel: 4,
-            };
-            for pkg in to_upgrade {
-                install_aur_native(&pkg, &log_pane, &opts)
+            if repo.is_none() && !*binary_only {
+                return Ok(
+                    handle_install(vec![pkg.clone()], confirm, &install_options(cli))
+                        .await
+                        .into_result()?,
+                );
+            }
+            // An explicit binary repo request bypasses normal source priority
+            let (name, repo_name, binary) = (pkg.clone(), repo.clone(), *binary_only);
+            let source = tokio::task::spawn_blocking(move || {
+                detect_source(&name, repo_name.as_deref(), binary)
+            })
+            .await
+            .unwrap_or(None);
+            match source {
+                Some(Source::BinaryRepo(repo_name)) => {
+                    println!("[reap] Installing {} from binary repo {}", pkg, repo_name);
+                    pacman::install(pkg);
+                }
+                Some(_) => handle_install(vec![pkg.clone()], confirm, &install_options(cli))
                     .await
-                    .unwrap_or_else(|e| {
-                        println!("[reap] Failed to upgrade {}: {:?}", pkg, e);
-                    });
+                    .into_result()?,
+                None => eprintln!("[reap] Package '{}' not found in a binary repo.", pkg),
+            }
+        }
+        Commands::Upgrade {
+            parallel,
+            repo,
+            aur,
+            flatpak,
+            devel,
+            json,
+        } => {
+            if cli.dry_run || *json {
+                handle_upgrade_plan(*repo, *aur, *flatpak, *devel, *json).await?;
+            } else {
+                handle_upgrade(*parallel, *repo, *aur, *flatpak, *devel).await;
             }
         }
-        Commands::Orphan { remove, all } => handle_orphan(*remove, *all),
-        Commands::Remove { pkgs } => {
-            for pkg in pkgs {
-                aur::uninstall(pkg);
+        Commands::Orphan { remove, all } => handle_orphan(*remove, *all, confirm),
+        Commands::List {
+            foreign,
+            explicit,
+            source,
+            reap,
+            installed,
+        } => {
+            if *installed {
+                handle_list_installed();
+            } else if *reap {
+                handle_list_reap();
+            } else {
+                handle_list(
+                    *foreign,
+                    *explicit,
+                    source.as_deref(),
+                    cli.format.as_deref(),
+                );
             }
         }
-        Commands::Local { pkgs } => {
-            for pkg in pkgs {
-                aur::install_local(pkg);
+        Commands::RepoAdd { dir, pkgs, name } => {
+            handle_repo_add(dir, pkgs, name.as_deref(), &install_options(cli)).await?;
+        }
+        Commands::Diff { pkg } => {
+            let name = pkg.clone();
+            let _ = tokio::task::spawn_blocking(move || show_pkgbuild_diff(&name)).await;
+        }
+        Commands::Remove {
+            pkgs,
+            recursive_check,
+            print,
+        } => handle_removal(pkgs, confirm, *recursive_check, *print),
+        Commands::Local { pkgs } => handle_local_install(pkgs),
+        Commands::Search { terms, maintainer } => {
+            if let Some(maintainer) = maintainer {
+                handle_search_maintainer(maintainer, cli.format.as_deref()).await?;
             }
+            handle_search(terms, cli.all_sources, cli.no_dedup, cli.format.as_deref()).await;
         }
-        Commands::Search { terms } => {
-            for term in terms {
-                match aur::search(term).await {
-                    Ok(results) => print_search_results(&results),
-                    Err(e) => eprintln!("[reap] Search failed for '{}': {}", term, e),
-                }
+        Commands::History { limit, undo } => match undo {
+            Some(id) => handle_undo(*id, confirm).await?,
+            None => handle_history(*limit),
+        },
+        Commands::Log { pkg, json } => handle_package_log(pkg, *json)?,
+        Commands::News { limit } => handle_news(*limit).await?,
+        Commands::Vote { pkg } => handle_vote(pkg, true).await?,
+        Commands::Unvote { pkg } => handle_vote(pkg, false).await?,
+        Commands::Info {
+            pkg,
+            comments,
+            limit,
+        } => handle_info(pkg, *comments, *limit).await?,
+        Commands::Sysupgrade {
+            parallel,
+            devel,
+            flatpak,
+        } => {
+            if cli.dry_run {
+                handle_upgrade_plan(true, true, *flatpak, *devel, false).await?;
+            } else if !handle_sysupgrade(true, *parallel, *devel, *flatpak).await {
+                return Err(ReapError::CommandFailed("system upgrade failed".to_string()).into());
             }
         }
         Commands::UpgradeAll => {
@@ -1125,6 +7928,9 @@ pub async fn handle_cli(cli: &Cli) -> Result<(), Box<dyn std::error::Error + Sen
             TapCmd::Update => crate::tap::sync_taps(),
             TapCmd::Sync => crate::tap::sync_taps(),
             TapCmd::List => crate::tap::list_taps(),
+            TapCmd::Clone { name, depth } => {
+                crate::tap::reclone_tap(name, *depth).map_err(ReapError::CommandFailed)?
+            }
         },
         Commands::Config { cmd } => match cmd {
             ConfigCmd::Set { key, value } => crate::config::set_config_key(key, value),
@@ -1145,252 +7951,566 @@ pub async fn handle_cli(cli: &Cli) -> Result<(), Box<dyn std::error::Error + Sen
             Ok(_) => println!("[reap] Config backup complete."),
             Err(e) => eprintln!("[reap] Config backup failed: {}", e),
         },
-        Commands::Doctor => {
-            let result = crate::utils::doctor_report();
-            match result {
-                Ok(report) => println!("[reap] Doctor report:\n{}", report),
-                Err(e) => eprintln!("[reap] Doctor error: {}", e),
+        Commands::Doctor { fix } => handle_doctor(*fix).await,
+        Commands::Mirrors {
+            rank,
+            country,
+            number,
+        } => handle_mirrors(*rank, country.as_deref(), *number)?,
+        Commands::FixBroken => handle_fix_broken(confirm, &install_options(cli)).await?,
+        Commands::Stats { json } => handle_stats(*json).await?,
+        Commands::Resolve { pkg, json } => handle_resolve(pkg, *json).await?,
+        Commands::Audit { pkg } => {
+            // Use the backend trait's audit method
+            let backend = crate::backend::AurBackend::new();
+            backend.audit(pkg).await;
+        }
+        Commands::Rollback { pkg } => handle_rollback(pkg),
+        Commands::SyncDb => {
+            handle_sync_db();
+        }
+        Commands::Mark { pkg, explicit, .. } => handle_mark(pkg, *explicit)?,
+        Commands::Pin { pkg } => {
+            if let Err(e) = crate::utils::pin_package(pkg) {
+                eprintln!("[reap] Pin failed: {}", e);
+            } else {
+                println!("[reap] Pinned {}", pkg);
+            }
+        }
+        Commands::Tui { dashboard } => {
+            if *dashboard {
+                crate::tui::launch_tui().await;
+            } else {
+                crate::tui::browse(!cli.no_dedup).await.into_result()?;
+            }
+        }
+        Commands::Profile { cmd } => {
+            let mut profile_manager = crate::profiles::ProfileManager::new();
+            match cmd {
+                crate::cli::ProfileCmd::Create { name, template } => {
+                    let base = match template.as_deref() {
+                        Some("developer") => crate::profiles::create_developer_profile(),
+                        Some("gaming") => crate::profiles::create_gaming_profile(),
+                        Some("minimal") => crate::profiles::create_minimal_profile(),
+                        _ => Default::default(),
+                    };
+                    let profile = crate::profiles::ProfileConfig {
+                        name: name.clone(),
+                        ..base
+                    };
+                    if let Err(e) = profile_manager.create_profile(&profile) {
+                        eprintln!("[profiles] Failed to create profile: {}", e);
+                    }
+                }
+                crate::cli::ProfileCmd::Switch { name } => {
+                    if let Err(e) = profile_manager.switch_profile(name) {
+                        eprintln!("[profiles] Failed to switch profile: {}", e);
+                    }
+                }
+                crate::cli::ProfileCmd::List => {
+                    if let Ok(profiles) = profile_manager.list_profiles() {
+                        println!("[profiles] Available profiles:");
+                        for profile in profiles {
+                            let marker = if profile == profile_manager.active_profile_name() {
+                                "*"
+                            } else {
+                                "-"
+                            };
+                            println!("  {} {}", marker, profile);
+                        }
+                    }
+                }
+                crate::cli::ProfileCmd::Show { name } => {
+                    let profile = match name {
+                        Some(name) => profile_manager.load_profile(name),
+                        None => profile_manager.get_active_profile(),
+                    };
+                    match profile {
+                        Ok(profile) => {
+                            println!("[profiles] Effective settings for '{}':", profile.name);
+                            let global = GlobalConfig::load();
+                            for (key, value) in
+                                crate::profiles::effective_settings(&profile, &global)
+                            {
+                                println!("  {:<18} {}", key, value);
+                            }
+                        }
+                        Err(e) => eprintln!("[profiles] Failed to load profile: {}", e),
+                    }
+                }
+                crate::cli::ProfileCmd::Delete { name } => {
+                    if let Err(e) = profile_manager.delete_profile(name) {
+                        eprintln!("[profiles] Failed to delete profile: {}", e);
+                    }
+                }
+                crate::cli::ProfileCmd::Edit { .. } => {
+                    println!("[profiles] Edit functionality coming soon");
+                }
+            }
+        }
+        Commands::Trust { refresh, cmd } => {
+            let trust_engine = crate::trust::TrustEngine::new();
+            let cmd = match (refresh, cmd) {
+                (_, Some(cmd)) => cmd,
+                (Some(pkg), None) => &crate::cli::TrustCmd::Score { pkg: pkg.clone() },
+                (None, None) => {
+                    eprintln!("[trust] Expected a subcommand or --refresh <pkg>");
+                    return Ok(());
+                }
+            };
+            match cmd {
+                crate::cli::TrustCmd::Score { pkg } => {
+                    // detect_source uses the blocking AUR client, keep it off the runtime threads
+                    let name = pkg.clone();
+                    let source =
+                        tokio::task::spawn_blocking(move || detect_source(&name, None, false))
+                            .await
+                            .ok()
+                            .flatten()
+                            .unwrap_or(Source::Aur);
+                    let trust_score = if refresh.as_deref() == Some(pkg.as_str()) {
+                        trust_engine.refresh_trust_score(pkg, &source).await
+                    } else {
+                        trust_engine.compute_trust_score(pkg, &source).await
+                    };
+                    let badge = trust_engine.trust_summary(&trust_score);
+                    println!(
+                        "[trust] {} {} (Score: {:.1}/10)",
+                        pkg, badge, trust_score.overall_score
+                    );
+                    for flag in trust_score.security_flags {
+                        println!("[trust] ⚠️ {:?}", flag);
+                    }
+                }
+                crate::cli::TrustCmd::Scan => {
+                    println!("[trust] Scanning all installed packages...");
+                    // TODO: Implement full system scan
+                }
+                crate::cli::TrustCmd::Stats => {
+                    println!("[trust] Trust statistics coming soon");
+                }
+                crate::cli::TrustCmd::Update => {
+                    println!("[trust] Updating trust database...");
+                }
+            }
+        }
+        Commands::Rate {
+            pkg,
+            rating,
+            comment,
+        } => {
+            let mut interactive = crate::interactive::InteractiveManager::new();
+
+            // Get and display current rating
+            if let Ok(pkg_rating) = interactive.get_package_rating(pkg).await {
+                println!(
+                    "Current rating: {}",
+                    interactive.display_rating(&pkg_rating)
+                );
+            }
+
+            if let Err(e) = interactive.submit_user_rating(pkg, *rating, comment.clone()) {
+                eprintln!("[rating] Failed to submit rating: {}", e);
+            }
+        }
+        Commands::Aur { cmd } => {
+            let mut aur_manager = crate::enhanced_aur::EnhancedAurManager::new();
+            match cmd {
+                crate::cli::AurCmd::Fetch { pkg } => match aur_manager.fetch_pkgbuild(pkg).await {
+                    Ok(pkgbuild) => println!("[aur] PKGBUILD fetched: {:?}", pkgbuild),
+                    Err(e) => eprintln!("[aur] Failed to fetch PKGBUILD: {}", e),
+                },
+                crate::cli::AurCmd::Edit { pkg } => {
+                    let interactive = crate::interactive::InteractiveManager::new();
+                    if interactive.confirm_pkgbuild_edit(pkg)
+                        && let Err(e) = aur_manager.edit_pkgbuild(pkg)
+                    {
+                        eprintln!("[aur] Failed to edit PKGBUILD: {}", e);
+                    }
+                }
+                crate::cli::AurCmd::Deps { pkg, conflicts: _ } => {
+                    match aur_manager
+                        .resolve_dependencies_advanced(std::slice::from_ref(pkg))
+                        .await
+                    {
+                        Ok(conflicts_found) => {
+                            if conflicts_found.is_empty() {
+                                println!("[aur] ✅ No conflicts detected for {}", pkg);
+                            } else {
+                                println!("[aur] ⚠️ {} conflicts detected:", conflicts_found.len());
+                                for conflict in conflicts_found {
+                                    println!("  • {:?}", conflict);
+                                }
+                            }
+                        }
+                        Err(e) => eprintln!("[aur] Failed to resolve dependencies: {}", e),
+                    }
+                }
+            }
+        }
+        Commands::BatchInstall { pkgs, parallel } => {
+            let config = std::sync::Arc::new(crate::config::ReapConfig::load());
+            let log = std::sync::Arc::new(StdoutReporter);
+
+            if *parallel {
+                let pkgs = &expand_groups(pkgs, confirm);
+                let msg = format!("Proceed with installing {} packages?", pkgs.len());
+                if !confirm_proceed(confirm, &msg) {
+                    return Ok(());
+                }
+                log.message(&format!(
+                    "[batch] Installing {} packages in parallel",
+                    pkgs.len()
+                ));
+                let report = parallel_install(pkgs, config, log, &install_options(cli)).await;
+                report.print("Install");
+                report.into_result()?;
+            } else {
+                let report = handle_install(pkgs.clone(), confirm, &install_options(cli)).await;
+                report.print("Install");
+                report.into_result()?;
+            }
+        }
+        Commands::Owns { path } => handle_owns(path)?,
+        Commands::RefreshKeys { taps, force } => {
+            handle_refresh_keys(taps, *force, cli.gpg_keyserver.as_deref())?
+        }
+        Commands::Keyring { init, refresh } => {
+            if *init {
+                crate::gpg::keyring_init().map_err(ReapError::CommandFailed)?;
+                println!("[reap] Keyring initialized");
+            }
+            if *refresh {
+                crate::gpg::keyring_refresh().map_err(ReapError::CommandFailed)?;
+                println!("[reap] Keyring refreshed");
+            }
+            if !*init && !*refresh {
+                let pacman = std::path::Path::new(crate::gpg::PACMAN_KEYRING_DIR);
+                let user = crate::gpg::user_keyring_dir();
+                for (label, dir) in [("pacman", pacman), ("user", user.as_path())] {
+                    let state = if crate::gpg::keyring_initialized(dir) {
+                        "initialized"
+                    } else {
+                        "missing"
+                    };
+                    println!("{:<8} {} ({})", label, state, dir.display());
+                }
+            }
+        }
+        Commands::Query {
+            pkg,
+            deps,
+            optdeps,
+            missing,
+        } => handle_query(pkg.as_deref(), *deps, *optdeps, *missing).await?,
+        Commands::Verify { pkg, all } => match pkg {
+            Some(pkg) if !*all => crate::verify::handle_verify(pkg)?,
+            _ => crate::verify::handle_verify_all()?,
+        },
+        Commands::Export { file } => crate::state::handle_export(file)?,
+        Commands::Import { file } => {
+            crate::state::handle_import(file, confirm, &install_options(cli)).await?
+        }
+        Commands::Update { notify } => {
+            handle_update(*notify).await;
+        }
+        Commands::GenerateTimer {
+            schedule,
+            aur_only,
+            notify_only,
+            dir,
+        } => handle_generate_timer(schedule, *aur_only, *notify_only, dir.clone())?,
+        Commands::ParallelUpgrade { pkgs } => {
+            let config = std::sync::Arc::new(crate::config::ReapConfig::load());
+            let log = std::sync::Arc::new(StdoutReporter);
+
+            let msg = format!("Proceed with upgrading {} packages?", pkgs.len());
+            if !confirm_proceed(confirm, &msg) {
+                return Ok(());
+            }
+            log.message(&format!(
+                "[parallel] Upgrading {} packages in parallel",
+                pkgs.len()
+            ));
+            let report = parallel_upgrade(pkgs, config, log).await;
+            report.print("Upgrade");
+            report.into_result()?;
+        }
+        Commands::Clean {
+            uninstalled_build_deps: true,
+            ..
+        } => handle_clean_build_deps(confirm)?,
+        Commands::Clean {
+            keep: Some(keep), ..
+        } => handle_clean_keep(*keep as usize)?,
+        Commands::Clean { .. } => {
+            handle_clean();
+            match crate::trust::clear_trust_cache() {
+                Ok(n) => println!("[clean] Removed {} cached trust scores", n),
+                Err(e) => eprintln!("[clean] Failed to clear trust cache: {}", e),
+            }
+            // Also clean cache using utils
+            match utils::clean_cache() {
+                Ok(msg) => println!("[clean] {}", msg),
+                Err(e) => eprintln!("[clean] Error: {}", e),
             }
         }
-        _ => return Err(anyhow!("Not yet implemented").into()),
+        Commands::Perf { cmd } => match cmd {
+            crate::cli::PerfCmd::WarmCache => {
+                println!("[perf] Warming cache with popular packages...");
+                if let Err(e) = aur::warm_cache().await {
+                    eprintln!("[perf] Cache warming failed: {}", e);
+                }
+            }
+            crate::cli::PerfCmd::ParallelSearch { queries } => {
+                println!(
+                    "[perf] Running parallel search for {} queries",
+                    queries.len()
+                );
+                match aur::parallel_search(queries).await {
+                    Ok(results) => println!("[perf] Found {} total results", results.len()),
+                    Err(e) => eprintln!("[perf] Parallel search failed: {}", e),
+                }
+            }
+            crate::cli::PerfCmd::ParallelFetch { packages } => {
+                println!(
+                    "[perf] Running parallel PKGBUILD fetch for {} packages",
+                    packages.len()
+                );
+                match aur::parallel_pkgbuild_fetch(packages).await {
+                    Ok(downloads) => println!(
+                        "[perf] Successfully downloaded {} PKGBUILDs",
+                        downloads.len()
+                    ),
+                    Err(e) => eprintln!("[perf] Parallel fetch failed: {}", e),
+                }
+            }
+            crate::cli::PerfCmd::CacheStats => {
+                println!("[perf] Cache statistics:");
+                #[cfg(feature = "cache")]
+                {
+                    println!("  Cache directory: {:?}", *utils::cache::PKGBUILD_CACHE_DIR);
+                    if let Ok(entries) = std::fs::read_dir(&*utils::cache::PKGBUILD_CACHE_DIR) {
+                        let count = entries.count();
+                        println!("  Cached PKGBUILDs: {}", count);
+                    }
+                }
+                #[cfg(not(feature = "cache"))]
+                println!("  Caching disabled (compile with --features cache)");
+            }
+            crate::cli::PerfCmd::ClearCache => match utils::clean_cache() {
+                Ok(msg) => println!("[perf] {}", msg),
+                Err(e) => eprintln!("[perf] Cache clear error: {}", e),
+            },
+        },
+        Commands::Security { cmd } => match cmd {
+            crate::cli::SecurityCmd::Audit { pkg } => {
+                println!("[security] Auditing package: {}", pkg);
+                let name = pkg.clone();
+                let pkgbuild =
+                    tokio::task::spawn_blocking(move || aur::get_pkgbuild_preview(&name))
+                        .await
+                        .unwrap_or_default();
+                let (warnings, risk_score) = utils::audit_pkgbuild(&pkgbuild);
+
+                if warnings.is_empty() {
+                    println!("✅ Package {} passed security audit", pkg);
+                } else {
+                    println!("⚠️ Package {} security audit findings:", pkg);
+                    for warning in warnings {
+                        println!("  {}", warning);
+                    }
+                }
+                println!("🛡️ Security risk score: {}", risk_score);
+            }
+            crate::cli::SecurityCmd::ScanAll => {
+                println!("[security] Scanning all installed packages...");
+                // get_pkgbuild_preview uses the blocking client, keep the scan off the runtime threads
+                let (total_risk, risky_packages) = tokio::task::spawn_blocking(|| {
+                    let mut total_risk = 0;
+                    let mut risky_packages = Vec::new();
+                    for (pkg, source) in get_installed_packages() {
+                        if matches!(source, Source::Aur) {
+                            let pkgbuild = aur::get_pkgbuild_preview(&pkg);
+                            let (warnings, risk_score) = utils::audit_pkgbuild(&pkgbuild);
+
+                            if risk_score > 15 {
+                                risky_packages.push((pkg, risk_score, warnings.len()));
+                            }
+                            total_risk += risk_score;
+                        }
+                    }
+                    (total_risk, risky_packages)
+                })
+                .await
+                .unwrap_or_default();
+
+                println!("🛡️ Security scan complete:");
+                println!("  Total risk score: {}", total_risk);
+                println!("  High-risk packages: {}", risky_packages.len());
+
+                for (pkg, score, warning_count) in risky_packages {
+                    println!("    {} (score: {}, {} warnings)", pkg, score, warning_count);
+                }
+            }
+            crate::cli::SecurityCmd::Stats => {
+                println!("[security] Security statistics:");
+                println!("  Security rules: 38 patterns");
+                println!("  Domain blacklist: 7 entries");
+                println!("  Credential patterns: 10 patterns");
+            }
+            crate::cli::SecurityCmd::UpdateRules => {
+                println!("[security] Security rules are built-in and updated with releases");
+            }
+        },
+        Commands::Gpg { cmd } => match cmd {
+            crate::cli::GpgCmd::Refresh => {
+                println!("Refreshing GPG keys...");
+                crate::gpg::refresh_keys();
+            }
+            crate::cli::GpgCmd::Import { keyid } => {
+                println!("Importing GPG key: {}", keyid);
+                if let Err(e) = crate::gpg::import_gpg_key_async(keyid).await {
+                    eprintln!("[reap] Failed to import GPG key: {}", e);
+                }
+            }
+            crate::cli::GpgCmd::Show { keyid } => {
+                println!("Showing GPG key: {}", keyid);
+                crate::gpg::show_key(keyid);
+            }
+            crate::cli::GpgCmd::Check { keyid } => {
+                println!("Checking GPG key: {}", keyid);
+                if crate::gpg::key_exists(keyid) {
+                    println!("[reap] GPG key {} exists in keyring", keyid);
+                } else {
+                    println!("[reap] GPG key {} not found in keyring", keyid);
+                }
+            }
+            crate::cli::GpgCmd::VerifyPkgbuild { path } => {
+                println!("Verifying PKGBUILD: {}", path);
+                match crate::gpg::gpg_check(std::path::Path::new(&path)) {
+                    Ok(()) => println!("[reap] PKGBUILD signature verified"),
+                    Err(e) => eprintln!("[reap] PKGBUILD verification failed: {}", e),
+                }
+            }
+            crate::cli::GpgCmd::SetKeyserver { url } => {
+                println!("Setting GPG keyserver: {}", url);
+                utils::cli_set_keyserver(url);
+            }
+            crate::cli::GpgCmd::CheckKeyserver { url } => {
+                println!("Checking GPG keyserver: {}", url);
+                utils::check_keyserver_async(url).await;
+            }
+        },
+        Commands::Flatpak { cmd } => match cmd {
+            crate::cli::FlatpakCmd::Install { pkg } => {
+                println!("Installing Flatpak package: {}", pkg);
+            }
+            crate::cli::FlatpakCmd::Remove { pkg } => {
+                println!("Removing Flatpak package: {}", pkg);
+            }
+            crate::cli::FlatpakCmd::Search { query } => {
+                println!("Searching Flatpak packages: {}", query);
+            }
+            crate::cli::FlatpakCmd::Update => {
+                println!("Updating Flatpak packages...");
+            }
+            crate::cli::FlatpakCmd::List => {
+                println!("Listing Flatpak packages...");
+            }
+            crate::cli::FlatpakCmd::Upgrade => {
+                println!("Upgrading Flatpak packages...");
+            }
+            crate::cli::FlatpakCmd::Audit { pkg } => {
+                println!("Auditing Flatpak package: {}", pkg);
+            }
+        },
     }
     Ok(())
 }
 
-/// Enhanced install function with profile and trust integration
-#[allow(dead_code)]
+/// Install `pkg` under the active profile: its backend order, keyserver,
+/// fast mode and dependency setting apply (CLI flags still win), the trust
+/// score is shown first, and strict profiles refuse packages without a valid
+/// signature unless `--insecure` is given.
 pub async fn install_with_priority_enhanced(
     pkg: &str,
     config: Arc<ReapConfig>,
-    _confirm: bool,
-    log: Arc<LogPane>,
+    confirm: bool,
+    log: Arc<dyn ProgressReporter>,
     opts: &InstallOptions,
     profile_manager: &ProfileManager,
     trust_engine: &TrustEngine,
-) {
-    use owo_colors::OwoColorize;
-    let start = Instant::now();
-
-    // Get active profile
+) -> Result<(), ReapError> {
+    use colored::Colorize;
     let profile = profile_manager.get_active_profile().unwrap_or_default();
-    log.push(&format!("[reap][profile] Using profile: {}", profile.name));
+    log.message(&format!("[reap][profile] Using profile: {}", profile.name));
 
-    // Compute trust score
-    let source = detect_source(pkg, None, false).unwrap_or(Source::Aur);
+    // detect_source uses the blocking AUR client, keep it off the runtime threads
+    let source = match &opts.source {
+        Some(source) => source.clone(),
+        None => {
+            let name = pkg.to_string();
+            tokio::task::spawn_blocking(move || detect_source(&name, None, false))
+                .await
+                .ok()
+                .flatten()
+                .unwrap_or(Source::Aur)
+        }
+    };
     let trust_score = trust_engine.compute_trust_score(pkg, &source).await;
-    let trust_badge = trust_engine.display_trust_badge(trust_score.overall_score);
+    let trust_summary = trust_engine.trust_summary(&trust_score);
+    log.message(&format!("[reap][trust] {} {}", pkg, trust_summary));
 
-    log.push(&format!("[reap][trust] {} {}", pkg, trust_badge));
-
-    // Check profile security settings
-    if profile.strict_signatures.unwrap_or(false) && !trust_score.signature_valid {
-        log.push("[reap][security] Aborting: strict mode requires valid signature");
-        return;
+    let strict = opts.strict_signatures || profile.strict_signatures.unwrap_or(false);
+    if strict && !trust_score.signature_valid && !opts.insecure {
+        log.message(&format!(
+            "[reap][security] {}",
+            "Aborting: strict mode requires a valid signature".red()
+        ));
+        return Err(ReapError::SignatureInvalid {
+            pkg: pkg.to_string(),
+            key: "none".to_string(),
+        });
     }
 
-    // Apply profile settings
-    let _effective_parallel = profile.parallel_jobs.unwrap_or(config.parallel);
-    let effective_fast = profile.fast_mode.unwrap_or(false);
-
-    if effective_fast {
-        log.push("[reap][profile] Fast mode enabled, skipping verification");
+    let global = GlobalConfig::load();
+    let issues = crate::trust::aur_policy_issues(
+        &trust_score,
+        global.block_orphaned_aur.unwrap_or(false),
+        global.warn_out_of_date.unwrap_or(false),
+    );
+    if !issues.is_empty() {
+        for issue in &issues {
+            log.message(&format!("[reap][trust] {} {}", "⚠️".yellow(), issue));
+        }
+        if strict {
+            return Err(ReapError::TrustPolicy {
+                pkg: pkg.to_string(),
+                reason: issues.join("; "),
+            });
+        }
+        if !confirm_proceed(confirm, &format!("Install {} anyway?", pkg)) {
+            return Err(ReapError::UserAborted);
+        }
     }
 
-    // Continue with existing install logic but with profile-aware settings
-    let ctx = HookContext {
-        pkg: pkg.to_string(),
-        version: None,
-        source: Some(source.label().to_string()),
-        install_path: None,
-        tap: None,
+    let opts = InstallOptions {
+        gpg_keyserver: opts
+            .gpg_keyserver
+            .clone()
+            .or_else(|| profile.gpg_keyserver.clone()),
+        fast_mode: opts.fast_mode || profile.fast_mode.unwrap_or(false),
+        strict_signatures: strict,
+        backend_order: opts
+            .backend_order
+            .clone()
+            .or_else(|| Some(profile.backend_order.clone()).filter(|o| !o.is_empty())),
+        auto_resolve_deps: opts.auto_resolve_deps.or(profile.auto_resolve_deps),
+        ..opts.clone()
     };
-
-    log.push(&format!("[reap][hook] pre_install executing for {}", pkg));
-    pre_install(&ctx);
-
-    let global_config = GlobalConfig::load();
-    if let Some((source, tap_name, prio, tap_obj)) =
-        resolve_package_source(pkg, None, &global_config)
-    {
-        // Prepare hook context
-        let ctx = HookContext {
-            pkg: pkg.to_string(),
-            version: None,
-            source: Some(format!("{:?}", source)),
-            install_path: None,
-            tap: tap_name.clone(),
-        };
-        log.push(&format!(
-            "[reap][priority] Resolved source for '{}': {}{} (priority {})",
-            pkg,
-            source.label(),
-            tap_name.as_deref().unwrap_or(""),
-            prio
-        ));
-        match source {
-            Source::Custom(ref _tap_repo) => {
-                if let Some(tap) = tap_obj {
-                    let tap_path = crate::tap::ensure_tap_cloned(&tap);
-                    let pkg_dir = tap_path.join(pkg);
-                    let pkgb_path = pkg_dir.join("PKGBUILD");
-                    let sig_path = pkg_dir.join("PKGBUILD.sig");
-                    let pubinfo = crate::tap::get_publisher_info(&tap);
-                    if let Some(pubinfo) = pubinfo {
-                        let keyid = pubinfo.gpg_key.split_whitespace().last().unwrap_or("");
-                        let verified_str = if pubinfo.verified {
-                            "[✓ Verified GPG]".green().to_string()
-                        } else {
-                            "[Unverified]".yellow().to_string()
-                        };
-                        log.push(&format!(
-                            "👤 {} from {} {}",
-                            tap.name.bold(),
-                            pubinfo.name,
-                            verified_str
-                        ));
-                        log.push(&format!("🔑 GPG Key: {}", keyid));
-                        // Check if key is in keyring
-                        let key_present = std::process::Command::new("gpg")
-                            .args(["--list-keys", keyid])
-                            .output()
-                            .map(|o| o.status.success())
-                            .unwrap_or(false);
-                        if !key_present {
-                            let keyserver = opts
-                                .gpg_keyserver
-                                .as_deref()
-                                .unwrap_or("hkps://keys.openpgp.org");
-                            log.push(&format!(
-                                "[reap][gpg] Importing publisher key {} from {}...",
-                                keyid, keyserver
-                            ));
-                            let fetch = std::process::Command::new("gpg")
-                                .args(["--keyserver", keyserver, "--recv-keys", keyid])
-                                .status();
-                            match fetch {
-                                Ok(s) if s.success() => log.push(&format!(
-                                    "[reap][gpg] {} Successfully imported {}",
-                                    "✓".green(),
-                                    keyid
-                                )),
-                                Ok(_) | Err(_) => log.push(&format!(
-                                    "[reap][gpg] {} Failed to import publisher key {}",
-                                    "❌".red(),
-                                    keyid
-                                )),
-                            }
-                        }
-                        // Verify PKGBUILD.sig
-                        if sig_path.exists() && pkgb_path.exists() {
-                            let verify = std::process::Command::new("gpg")
-                                .arg("--verify")
-                                .arg(&sig_path)
-                                .arg(&pkgb_path)
-                                .status();
-                            if let Ok(s) = verify {
-                                if s.success() {
-                                    log.push(&format!(
-                                        "{} PKGBUILD signature verified",
-                                        "✓".green()
-                                    ));
-                                } else {
-                                    log.push(&format!(
-                                        "{} Verification failed for PKGBUILD.sig (key: {})",
-                                        "❌".red(),
-                                        keyid
-                                    ));
-                                    if !opts.insecure {
-                                        log.push(&format!(
-                                            "{} Aborting install. Use --insecure to override.",
-                                            "✋".red()
-                                        ));
-                                        return;
-                                    } else {
-                                        log.push(&format!(
-                                            "{} Continuing install due to --insecure.",
-                                            "⚠️".yellow()
-                                        ));
-                                    }
-                                }
-                            } else {
-                                log.push(&format!(
-                                    "{} Verification failed for PKGBUILD.sig (key: {})",
-                                    "❌".red(),
-                                    keyid
-                                ));
-                                if !opts.insecure {
-                                    log.push(&format!(
-                                        "{} Aborting install. Use --insecure to override.",
-                                        "✋".red()
-                                    ));
-                                    return;
-                                } else {
-                                    log.push(&format!(
-                                        "{} Continuing install due to --insecure.",
-                                        "⚠️".yellow()
-                                    ));
-                                }
-                            }
-                        } else {
-                            log.push(&format!("{} PKGBUILD.sig missing. Aborting install. Use --insecure to override.", "❌".red()));
-                            if !opts.insecure {
-                                return;
-                            } else {
-                                log.push(&format!(
-                                    "{} Continuing install due to --insecure.",
-                                    "⚠️".yellow()
-                                ));
-                            }
-                        }
-                    } else {
-                        log.push(&format!(
-                            "{} Warning: Tap publisher not verified. Installing with --insecure.",
-                            "⚠️".yellow()
-                        ));
-                        if !opts.insecure {
-                            return;
-                        }
-                    }
-                }
-                // ...proceed with install if verified or --insecure...
-            }
-            Source::Pacman => {
-                log.push(&format!("[reap][pacman] Installing {} from repo", pkg));
-                pacman::install(pkg);
-                log.push(&format!("[✓] Installed {} from Pacman", pkg));
-            }
-            Source::Aur => {
-                println!("{} Building {} from AUR source...", "🔨".bright_yellow(), pkg.bright_white());
-                log.push(&format!("[reap][aur] Installing {} from AUR", pkg));
-                let opts = InstallOptions {
-                    insecure: false,
-                    gpg_keyserver: None,
-                    fast_mode: false,
-                    strict_signatures: false,
-                    max_parallel: 4,
-                };
-                let _ = install_aur_native(pkg, &log, &opts).await;
-                println!("{} Successfully installed {} from AUR!", "✅".bright_green(), pkg.bright_white().bold());
-                log.push(&format!("[✓] Installed {} from AUR", pkg));
-            }
-            Source::Flatpak => {
-                log.push(&format!("[reap][flatpak] Installing {} from Flatpak", pkg));
-                let _ = flatpak::install_flatpak(pkg).await;
-            }
-            _ => log.push(&format!("[!] Unknown source for {}", pkg)),
-        }
-        log.push(&format!("[reap][hook] post_install executing for {}", pkg));
-        post_install(&ctx);
-    } else {
-        log.push(&format!(
-            "[reap][error] Could not resolve source for {}",
-            pkg
-        ));
-        crate::utils::rollback(pkg);
+    if opts.fast_mode {
+        log.message("[reap][profile] Fast mode enabled, skipping PKGBUILD review");
     }
-    let elapsed = start.elapsed();
-    log.push(&format!(
-        "[reap][timing] Enhanced install for {} took: {:?}",
-        pkg, elapsed
-    ));
+    install_with_priority(pkg, config, confirm, log, &opts).await
 }
@@ -14,6 +14,7 @@ pub struct PkgbuildInfo {
     pub make_dependencies: Vec<String>,
     pub conflicts: Vec<String>,
     pub provides: Vec<String>,
+    pub arch: Vec<String>,
     pub source_files: Vec<String>,
     pub integrity_checks: Vec<String>,
 }
@@ -62,10 +63,7 @@ impl EnhancedAurManager {
         println!("[aur] Fetching PKGBUILD for {}", package);
 
         // Download PKGBUILD
-        let pkgbuild_url = format!(
-            "https://aur.archlinux.org/cgit/aur.git/plain/PKGBUILD?h={}",
-            package
-        );
+        let pkgbuild_url = crate::aur::pkgbuild_url(package);
         let pkgbuild_content = reqwest::get(&pkgbuild_url).await?.text().await?;
 
         // Parse PKGBUILD
@@ -92,6 +90,7 @@ impl EnhancedAurManager {
             make_dependencies: Vec::new(),
             conflicts: Vec::new(),
             provides: Vec::new(),
+            arch: Vec::new(),
             source_files: Vec::new(),
             integrity_checks: Vec::new(),
         };
@@ -123,6 +122,23 @@ impl EnhancedAurManager {
                     current_array = "makedepends".to_string();
                     array_content = trimmed.trim_start_matches("makedepends=(").to_string();
                 }
+            } else if let Some(array) = ["conflicts", "provides", "arch"]
+                .iter()
+                .find(|name| trimmed.starts_with(&format!("{}=(", name)))
+            {
+                let prefix = format!("{}=(", array);
+                if trimmed.ends_with(')') {
+                    let parsed = self.parse_array_line(trimmed, &prefix);
+                    match *array {
+                        "conflicts" => info.conflicts = parsed,
+                        "provides" => info.provides = parsed,
+                        _ => info.arch = parsed,
+                    }
+                } else {
+                    in_array = true;
+                    current_array = array.to_string();
+                    array_content = trimmed.trim_start_matches(prefix.as_str()).to_string();
+                }
             } else if in_array {
                 if trimmed.ends_with(')') {
                     array_content.push(' ');
@@ -131,6 +147,9 @@ impl EnhancedAurManager {
                     match current_array.as_str() {
                         "depends" => info.dependencies = parsed,
                         "makedepends" => info.make_dependencies = parsed,
+                        "conflicts" => info.conflicts = parsed,
+                        "provides" => info.provides = parsed,
+                        "arch" => info.arch = parsed,
                         _ => {}
                     }
                     in_array = false;
@@ -260,12 +279,11 @@ impl EnhancedAurManager {
             let mut conflicts = Vec::new();
 
             for line in files.lines() {
-                if let Some(file_path) = line.split_whitespace().nth(1) {
-                    if let Some(owner) = self.check_file_owner(file_path) {
-                        if owner != package {
-                            conflicts.push((file_path.to_string(), owner));
-                        }
-                    }
+                if let Some(file_path) = line.split_whitespace().nth(1)
+                    && let Some(owner) = self.check_file_owner(file_path)
+                    && owner != package
+                {
+                    conflicts.push((file_path.to_string(), owner));
                 }
             }
 
@@ -334,20 +352,16 @@ impl EnhancedAurManager {
 
         // Check if installed version conflicts with required versions
         for dep in &pkgbuild.dependencies {
-            if let Some((dep_name, version_req)) = self.parse_version_constraint(dep) {
-                if let Some(installed_version) = self.get_installed_version(&dep_name) {
-                    if !self.version_satisfies(&installed_version, &version_req) {
-                        conflicts.push(DependencyConflict {
-                            package: package.to_string(),
-                            conflicting_with: dep_name,
-                            conflict_type: ConflictType::VersionConflict(
-                                installed_version,
-                                version_req,
-                            ),
-                            resolution: Some("Upgrade or downgrade dependency".to_string()),
-                        });
-                    }
-                }
+            if let Some((dep_name, version_req)) = self.parse_version_constraint(dep)
+                && let Some(installed_version) = self.get_installed_version(&dep_name)
+                && !self.version_satisfies(&installed_version, &version_req)
+            {
+                conflicts.push(DependencyConflict {
+                    package: package.to_string(),
+                    conflicting_with: dep_name,
+                    conflict_type: ConflictType::VersionConflict(installed_version, version_req),
+                    resolution: Some("Upgrade or downgrade dependency".to_string()),
+                });
             }
         }
 
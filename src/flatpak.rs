@@ -10,51 +10,56 @@ pub fn search(query: &str) -> Vec<SearchResult> {
         eprintln!("[reap] Warning: Flatpak is not installed or not in PATH");
         return vec![];
     }
-    
+
     let output = Command::new("flatpak")
         .arg("search")
         .arg("--columns=name,application,version,branch,remotes,description")
         .arg(query)
         .output();
-        
+
     match output {
         Ok(out) if out.status.success() => {
             let stdout = String::from_utf8_lossy(&out.stdout);
             let mut results = Vec::new();
-            
+
             // Skip header line if present
             let lines: Vec<&str> = stdout.lines().collect();
-            let start_idx = if lines.first().map_or(false, |l| l.contains("Application ID")) {
+            let start_idx = if lines.first().is_some_and(|l| l.contains("Application ID")) {
                 1
             } else {
                 0
             };
-            
+
             for line in lines.iter().skip(start_idx) {
                 if line.trim().is_empty() {
                     continue;
                 }
-                
+
                 // Parse tab-separated output
                 let fields: Vec<&str> = line.split('\t').collect();
                 if fields.len() >= 2 {
                     // Format: Name<tab>App ID<tab>Version<tab>Branch<tab>Remotes<tab>Description
-                    let name = fields.get(0).unwrap_or(&"").trim().to_string();
+                    let name = fields.first().unwrap_or(&"").trim().to_string();
                     let app_id = fields.get(1).unwrap_or(&"").trim().to_string();
                     let version = fields.get(2).unwrap_or(&"").trim().to_string();
                     let description = fields.get(5).unwrap_or(&"").trim().to_string();
-                    
+
                     // Use app_id as the primary identifier for installation
                     if !app_id.is_empty() {
                         results.push(SearchResult {
                             name: app_id.clone(),
-                            version: if version.is_empty() { "latest".to_string() } else { version },
-                            description: if description.is_empty() { 
-                                name 
-                            } else { 
-                                format!("{} - {}", name, description) 
+                            version: if version.is_empty() {
+                                "latest".to_string()
+                            } else {
+                                version
+                            },
+                            description: if description.is_empty() {
+                                name
+                            } else {
+                                format!("{} - {}", name, description)
                             },
                             source: Source::Flatpak,
+                            votes: None,
                         });
                     }
                 }
@@ -79,6 +84,84 @@ pub fn search(query: &str) -> Vec<SearchResult> {
     }
 }
 
+/// System and per-user Flatpak installation dirs
+pub fn installation_dirs() -> Vec<std::path::PathBuf> {
+    let mut dirs = vec![std::path::PathBuf::from("/var/lib/flatpak")];
+    if let Some(data) = dirs::data_dir() {
+        dirs.push(data.join("flatpak"));
+    }
+    dirs
+}
+
+/// The Flatpak app or runtime a file under one of `installations` belongs
+/// to, e.g. `app/org.mozilla.firefox/...` or the exported
+/// `exports/bin/org.mozilla.firefox` launcher
+pub fn owner_of(path: &std::path::Path, installations: &[std::path::PathBuf]) -> Option<String> {
+    let rel = installations
+        .iter()
+        .find_map(|root| path.strip_prefix(root).ok())?;
+    let parts: Vec<&str> = rel.iter().filter_map(|c| c.to_str()).collect();
+    match parts.as_slice() {
+        ["app" | "runtime", id, ..] => Some(id.to_string()),
+        ["exports", "bin", id] => Some(id.to_string()),
+        ["exports", "share", .., file] => file
+            .strip_suffix(".desktop")
+            .or_else(|| file.strip_suffix(".svg"))
+            .or_else(|| file.strip_suffix(".png"))
+            .map(str::to_string),
+        _ => None,
+    }
+}
+
+/// Whether `app_id` is installed, and if so whether per-user (`--user`)
+pub fn installed_scope(app_id: &str) -> Option<bool> {
+    [false, true].into_iter().find(|&user| {
+        let mut cmd = Command::new("flatpak");
+        cmd.arg("info");
+        if user {
+            cmd.arg("--user");
+        }
+        cmd.arg(app_id)
+            .output()
+            .map(|o| o.status.success())
+            .unwrap_or(false)
+    })
+}
+
+/// Check the objects of the system or per-user installation without
+/// changing anything (`flatpak repair --dry-run`). Returns the problems found.
+pub fn check_installation(user: bool) -> Vec<String> {
+    let mut cmd = Command::new("flatpak");
+    cmd.args(["repair", "--dry-run"]);
+    if user {
+        cmd.arg("--user");
+    }
+    match cmd.output() {
+        Ok(o) if o.status.success() => Vec::new(),
+        Ok(o) => {
+            let text = format!(
+                "{}{}",
+                String::from_utf8_lossy(&o.stdout),
+                String::from_utf8_lossy(&o.stderr)
+            );
+            let problems: Vec<String> = text
+                .lines()
+                .map(str::trim)
+                .filter(|l| {
+                    !l.is_empty() && !l.starts_with("Checking") && !l.starts_with("Pruning")
+                })
+                .map(str::to_string)
+                .collect();
+            if problems.is_empty() {
+                vec!["flatpak repair --dry-run failed".to_string()]
+            } else {
+                problems
+            }
+        }
+        Err(e) => vec![format!("Failed to run flatpak: {}", e)],
+    }
+}
+
 /// Check if flatpak command is available
 pub fn is_flatpak_available() -> bool {
     Command::new("flatpak")
@@ -99,7 +182,7 @@ pub fn is_flatpak_available() -> bool {
 /// # Example
 ///
 /// ```no_run
-/// use crate::flatpak;
+/// use reap::flatpak;
 /// flatpak::install("com.example.App");
 /// ```
 pub fn install(pkg: &str) {
@@ -107,9 +190,9 @@ pub fn install(pkg: &str) {
         eprintln!("[reap] Error: Flatpak is not installed. Install with: sudo pacman -S flatpak");
         return;
     }
-    
+
     println!("[reap] Installing Flatpak package: {}", pkg);
-    
+
     // First try to install from flathub
     let status = Command::new("flatpak")
         .arg("install")
@@ -118,7 +201,7 @@ pub fn install(pkg: &str) {
         .arg("flathub")
         .arg(pkg)
         .status();
-        
+
     match status {
         Ok(s) if s.success() => {
             println!("[reap] Successfully installed: {}", pkg);
@@ -131,7 +214,7 @@ pub fn install(pkg: &str) {
                 .arg("-y")
                 .arg(pkg)
                 .status();
-                
+
             match retry {
                 Ok(rs) if rs.success() => {
                     println!("[reap] Successfully installed: {}", pkg);
@@ -161,7 +244,7 @@ pub fn install(pkg: &str) {
 /// # Example
 ///
 /// ```no_run
-/// use crate::flatpak;
+/// use reap::flatpak;
 ///
 /// #[tokio::main]
 /// async fn main() {
@@ -174,9 +257,9 @@ pub async fn install_flatpak(pkg: &str) -> Result<(), Box<dyn std::error::Error
     if !is_flatpak_available() {
         return Err("Flatpak is not installed. Install with: sudo pacman -S flatpak".into());
     }
-    
+
     println!("[reap][flatpak] Installing {}...", pkg);
-    
+
     // Try with flathub remote first
     let status = Command::new("flatpak")
         .arg("install")
@@ -185,7 +268,7 @@ pub async fn install_flatpak(pkg: &str) -> Result<(), Box<dyn std::error::Error
         .arg("flathub")
         .arg(pkg)
         .status()?;
-        
+
     if status.success() {
         println!("[reap][flatpak] Installed {}!", pkg);
         Ok(())
@@ -197,7 +280,7 @@ pub async fn install_flatpak(pkg: &str) -> Result<(), Box<dyn std::error::Error
             .arg("-y")
             .arg(pkg)
             .status()?;
-            
+
         if retry_status.success() {
             println!("[reap][flatpak] Installed {}!", pkg);
             Ok(())
@@ -212,7 +295,7 @@ pub async fn install_flatpak(pkg: &str) -> Result<(), Box<dyn std::error::Error
 /// # Example
 ///
 /// ```no_run
-/// use crate::flatpak;
+/// use reap::flatpak;
 /// flatpak::upgrade();
 /// ```
 #[allow(dead_code)]
@@ -221,17 +304,19 @@ pub fn upgrade() {
         eprintln!("[reap] Error: Flatpak is not installed. Install with: sudo pacman -S flatpak");
         return;
     }
-    
+
     println!("[reap] flatpak :: Upgrading all flatpak packages...");
     let status = Command::new("flatpak")
         .arg("update")
         .arg("--noninteractive")
         .arg("-y")
         .status();
-        
+
     match status {
         Ok(s) if s.success() => println!("[reap] flatpak :: All packages upgraded!"),
-        Ok(_) => eprintln!("[reap] flatpak :: upgrade failed. Check flatpak update output for details."),
+        Ok(_) => {
+            eprintln!("[reap] flatpak :: upgrade failed. Check flatpak update output for details.")
+        }
         Err(e) => eprintln!("[reap] flatpak :: upgrade error: {}", e),
     }
 }
@@ -245,7 +330,7 @@ pub fn upgrade() {
 /// # Example
 ///
 /// ```no_run
-/// use crate::flatpak;
+/// use reap::flatpak;
 ///
 /// #[tokio::main]
 /// async fn main() {
@@ -258,14 +343,16 @@ pub async fn upgrade_flatpak() -> Result<(), Box<dyn std::error::Error + Send +
     if !is_flatpak_available() {
         return Err("Flatpak is not installed. Install with: sudo pacman -S flatpak".into());
     }
-    
+
     println!("[reap][flatpak] Upgrading all flatpak packages...");
-    let status = Command::new("flatpak")
+    // Async so it can run alongside an AUR upgrade
+    let status = tokio::process::Command::new("flatpak")
         .arg("update")
         .arg("--noninteractive")
         .arg("-y")
-        .status()?;
-        
+        .status()
+        .await?;
+
     if status.success() {
         println!("[reap][flatpak] All packages upgraded!");
         Ok(())
@@ -281,10 +368,37 @@ pub async fn upgrade_flatpak() -> Result<(), Box<dyn std::error::Error + Send +
 ///
 /// * `pkg` - A string slice that holds the package name.
 ///
+/// Parse a size as flatpak prints it, e.g. `45.2 MB` or `812 bytes`
+pub fn parse_size(text: &str) -> Option<u64> {
+    let mut parts = text.split_whitespace();
+    let value: f64 = parts.next()?.replace(',', ".").parse().ok()?;
+    let unit = match parts.next().unwrap_or("bytes") {
+        "bytes" | "byte" | "B" => 1.0,
+        "kB" => 1e3,
+        "MB" => 1e6,
+        "GB" => 1e9,
+        _ => return None,
+    };
+    Some((value * unit) as u64)
+}
+
+/// Download size of a Flathub app from `flatpak remote-info`
+pub fn download_size(app_id: &str) -> Option<u64> {
+    let output = Command::new("flatpak")
+        .args(["remote-info", "flathub", app_id])
+        .output()
+        .ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout
+        .lines()
+        .find(|l| l.trim_start().starts_with("Download:"))?;
+    parse_size(line.split_once(':')?.1)
+}
+
 /// # Example
 ///
 /// ```
-/// flatpak::print_flatpak_sandbox_info("com.example.App");
+/// reap::flatpak::print_flatpak_sandbox_info("com.example.App");
 /// ```
 #[allow(dead_code)]
 pub fn print_flatpak_sandbox_info(pkg: &str) {
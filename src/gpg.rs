@@ -63,11 +63,11 @@ pub fn verify_pkgbuild(pkgdir: &Path) -> bool {
         .arg(sig_path)
         .arg(pkgb_path)
         .status();
-    if let Ok(s) = status {
-        if s.success() {
-            println!("[reap] gpg :: PKGBUILD signature verified");
-            return true;
-        }
+    if let Ok(s) = status
+        && s.success()
+    {
+        println!("[reap] gpg :: PKGBUILD signature verified");
+        return true;
     }
     eprintln!("[reap] gpg :: PKGBUILD signature verification failed");
     false
@@ -99,10 +99,9 @@ pub fn gpg_check(pkgdir: &Path) -> Result<(), String> {
                     }
                 }
                 None
-            }) {
-                if let Some(trust) = get_trust_level(&keyid) {
-                    println!("[reap] gpg :: Key {} trust level: {}", keyid, trust);
-                }
+            }) && let Some(trust) = get_trust_level(&keyid)
+            {
+                println!("[reap] gpg :: Key {} trust level: {}", keyid, trust);
             }
             Ok(())
         } else {
@@ -208,6 +207,176 @@ pub fn refresh_keys() {
     }
 }
 
+/// Pacman's keyring, created by `pacman-key --init`
+pub const PACMAN_KEYRING_DIR: &str = "/etc/pacman.d/gnupg";
+
+/// Suggestion shown when a signature can't be checked for lack of a keyring
+pub const KEYRING_HINT: &str = "run `reap keyring --init` to set up the GPG keyrings";
+
+/// Whether `dir` holds a gpg keyring (a public keybox, old or new format)
+pub fn keyring_initialized(dir: &Path) -> bool {
+    dir.join("pubring.kbx").exists() || dir.join("pubring.gpg").exists()
+}
+
+/// The user's gpg home, used for tap and PKGBUILD signature checks
+pub fn user_keyring_dir() -> std::path::PathBuf {
+    std::env::var_os("GNUPGHOME")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".gnupg"))
+}
+
+/// Why `gpg --verify` failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyFailure {
+    /// The signature doesn't match the file
+    BadSignature,
+    /// The signing key isn't in the keyring
+    MissingKey,
+    /// There is no keyring (or it can't be opened) to check against
+    NoKeyring,
+}
+
+/// Classify the stderr/status output of a failed `gpg --verify`. A bad
+/// signature wins over everything else so it is never explained away.
+pub fn classify_verify_failure(output: &str) -> VerifyFailure {
+    let lower = output.to_lowercase();
+    if output.contains("BADSIG") || lower.contains("bad signature") {
+        VerifyFailure::BadSignature
+    } else if lower.contains("keybox")
+        || lower.contains("no keyring")
+        || lower.contains("trustdb")
+        || (lower.contains("can't open") && lower.contains("pubring"))
+    {
+        VerifyFailure::NoKeyring
+    } else if output.contains("NO_PUBKEY") || lower.contains("no public key") {
+        VerifyFailure::MissingKey
+    } else {
+        VerifyFailure::BadSignature
+    }
+}
+
+/// Run `sudo pacman-key` with `args`
+fn pacman_key(args: &[&str]) -> Result<(), String> {
+    println!("[reap] keyring :: pacman-key {}", args.join(" "));
+    match crate::utils::escalate("pacman-key").args(args).status() {
+        Ok(s) if s.success() => Ok(()),
+        Ok(s) => Err(format!("pacman-key {} failed ({})", args.join(" "), s)),
+        Err(e) => Err(format!("failed to run pacman-key: {}", e)),
+    }
+}
+
+/// Initialize and populate pacman's keyring, and create the user's gpg
+/// keyring if it doesn't exist yet
+pub fn keyring_init() -> Result<(), String> {
+    pacman_key(&["--init"])?;
+    pacman_key(&["--populate"])?;
+    if !keyring_initialized(&user_keyring_dir()) {
+        // Listing keys makes gpg create its home and an empty keybox
+        let _ = Command::new("gpg")
+            .arg("--list-keys")
+            .stdout(std::process::Stdio::null())
+            .status();
+        if !keyring_initialized(&user_keyring_dir()) {
+            return Err(format!(
+                "could not create gpg keyring in {}",
+                user_keyring_dir().display()
+            ));
+        }
+        println!(
+            "[reap] keyring :: Created gpg keyring in {}",
+            user_keyring_dir().display()
+        );
+    }
+    Ok(())
+}
+
+/// Refresh the keys in pacman's keyring from the keyservers
+pub fn keyring_refresh() -> Result<(), String> {
+    if !keyring_initialized(Path::new(PACMAN_KEYRING_DIR)) {
+        return Err(format!(
+            "pacman keyring is not initialized, {}",
+            KEYRING_HINT
+        ));
+    }
+    pacman_key(&["--refresh-keys"])
+}
+
+/// Keyserver used when neither `--gpg-keyserver` nor the profile names one
+pub const DEFAULT_KEYSERVER: &str = "hkps://keys.openpgp.org";
+
+/// Days before its expiry that a publisher key is refreshed ahead of time
+pub const KEY_REFRESH_WINDOW_DAYS: i64 = 14;
+
+/// Expiry state of a key in the user's keyring
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyFreshness {
+    Valid,
+    /// Expires within `KEY_REFRESH_WINDOW_DAYS`, in this many days
+    Expiring(i64),
+    Expired,
+    /// Not in the keyring (or gpg failed)
+    Unknown,
+}
+
+impl KeyFreshness {
+    /// Whether the key should be refreshed from a keyserver
+    pub fn is_stale(self) -> bool {
+        matches!(self, KeyFreshness::Expiring(_) | KeyFreshness::Expired)
+    }
+}
+
+/// Freshness of the primary key in `gpg --list-keys --with-colons` output
+/// at unix time `now`
+pub fn key_freshness(colons: &str, now: i64) -> KeyFreshness {
+    let Some(fields) = colons
+        .lines()
+        .find(|l| l.starts_with("pub:"))
+        .map(|l| l.split(':').collect::<Vec<_>>())
+    else {
+        return KeyFreshness::Unknown;
+    };
+    if fields.get(1) == Some(&"e") {
+        return KeyFreshness::Expired;
+    }
+    match fields.get(6).and_then(|f| f.parse::<i64>().ok()) {
+        Some(expires) if expires <= now => KeyFreshness::Expired,
+        Some(expires) if expires - now < KEY_REFRESH_WINDOW_DAYS * 86400 => {
+            KeyFreshness::Expiring((expires - now) / 86400)
+        }
+        _ => KeyFreshness::Valid,
+    }
+}
+
+/// Freshness of `keyid` in the user's keyring
+pub fn check_key_freshness(keyid: &str) -> KeyFreshness {
+    match Command::new("gpg")
+        .args(["--list-keys", "--with-colons", keyid])
+        .output()
+    {
+        Ok(out) if out.status.success() => key_freshness(
+            &String::from_utf8_lossy(&out.stdout),
+            chrono::Utc::now().timestamp(),
+        ),
+        _ => KeyFreshness::Unknown,
+    }
+}
+
+/// `gpg --refresh-keys` for one key, picking up extended expiry dates
+pub fn refresh_key(keyid: &str, keyserver: &str) -> Result<(), String> {
+    match Command::new("gpg")
+        .args(["--keyserver", keyserver, "--refresh-keys", keyid])
+        .output()
+    {
+        Ok(out) if out.status.success() => Ok(()),
+        Ok(out) => Err(String::from_utf8_lossy(&out.stderr)
+            .lines()
+            .last()
+            .unwrap_or("gpg --refresh-keys failed")
+            .to_string()),
+        Err(e) => Err(format!("failed to run gpg: {}", e)),
+    }
+}
+
 /// Async GPG key import from multiple keyservers
 pub async fn import_gpg_key_async(keyid: &str) -> Result<(), String> {
     let keyservers = [
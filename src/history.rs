@@ -261,3 +261,683 @@ impl Default for HistoryManager {
         Self::new()
     }
 }
+
+/// Provenance of a package installed through reap
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallRecord {
+    pub source: crate::core::Source,
+    pub tap: Option<String>,
+    pub installed_at: DateTime<Utc>,
+    pub trust_score: Option<f32>,
+}
+
+/// `installed.json`: every package reap has installed, keyed by name
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InstallRegistry {
+    pub packages: std::collections::BTreeMap<String, InstallRecord>,
+}
+
+impl InstallRegistry {
+    pub fn path() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("/tmp"))
+            .join("reap/installed.json")
+    }
+
+    pub fn load() -> Self {
+        Self::load_from(&Self::path())
+    }
+
+    pub fn load_from(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Insert or replace the record for `pkg`
+    pub fn record(&mut self, pkg: &str, record: InstallRecord) {
+        self.packages.insert(pkg.to_string(), record);
+    }
+
+    pub fn get(&self, pkg: &str) -> Option<&InstallRecord> {
+        self.packages.get(pkg)
+    }
+}
+
+/// Record a successful install in the default registry
+pub fn record_install(pkg: &str, record: InstallRecord) -> Result<()> {
+    note(pkg, |n| n.trust_score = record.trust_score);
+    let path = InstallRegistry::path();
+    let mut registry = InstallRegistry::load_from(&path);
+    registry.record(pkg, record);
+    registry.save_to(&path)
+}
+
+/// `reviews.json`: the last AUR commit (or PKGBUILD hash, outside a git
+/// checkout) accepted for each package
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PkgbuildReviews {
+    pub accepted: std::collections::BTreeMap<String, String>,
+}
+
+impl PkgbuildReviews {
+    pub fn path() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("/tmp"))
+            .join("reap/reviews.json")
+    }
+
+    pub fn load() -> Self {
+        Self::load_from(&Self::path())
+    }
+
+    pub fn load_from(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn is_accepted(&self, pkg: &str, hash: &str) -> bool {
+        self.accepted.get(pkg).is_some_and(|h| h == hash)
+    }
+
+    pub fn accept(&mut self, pkg: &str, hash: &str) {
+        self.accepted.insert(pkg.to_string(), hash.to_string());
+    }
+}
+
+/// A package file added to a local repo by `reap repo-add`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoEntry {
+    pub file: String,
+    pub added_at: DateTime<Utc>,
+}
+
+/// `repos.json`: the packages reap added to each local repo, keyed by the
+/// repo database path and then by package name
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LocalRepos {
+    pub repos: std::collections::BTreeMap<String, std::collections::BTreeMap<String, RepoEntry>>,
+}
+
+impl LocalRepos {
+    pub fn path() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("/tmp"))
+            .join("reap/repos.json")
+    }
+
+    pub fn load() -> Self {
+        Self::load_from(&Self::path())
+    }
+
+    pub fn load_from(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Record that `file` now provides `pkg` in the repo at `db`
+    pub fn record(&mut self, db: &str, pkg: &str, file: &str) {
+        self.repos.entry(db.to_string()).or_default().insert(
+            pkg.to_string(),
+            RepoEntry {
+                file: file.to_string(),
+                added_at: Utc::now(),
+            },
+        );
+    }
+
+    pub fn packages(&self, db: &str) -> Option<&std::collections::BTreeMap<String, RepoEntry>> {
+        self.repos.get(db)
+    }
+}
+
+/// How many recent build durations are kept per package
+const KEPT_BUILD_TIMES: usize = 5;
+
+/// `build_times.json`: recent AUR build durations in seconds for each
+/// package, used to estimate how long an install will take
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BuildTimes {
+    pub builds: std::collections::BTreeMap<String, Vec<u64>>,
+}
+
+impl BuildTimes {
+    pub fn path() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("/tmp"))
+            .join("reap/build_times.json")
+    }
+
+    pub fn load() -> Self {
+        Self::load_from(&Self::path())
+    }
+
+    pub fn load_from(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Record a build of `pkg`, dropping the oldest beyond `KEPT_BUILD_TIMES`
+    pub fn record(&mut self, pkg: &str, duration: std::time::Duration) {
+        let times = self.builds.entry(pkg.to_string()).or_default();
+        times.push(duration.as_secs());
+        if times.len() > KEPT_BUILD_TIMES {
+            times.remove(0);
+        }
+    }
+
+    /// Average of the recent builds of `pkg`, if it was built before
+    pub fn estimate(&self, pkg: &str) -> Option<std::time::Duration> {
+        let times = self.builds.get(pkg).filter(|t| !t.is_empty())?;
+        let average = times.iter().sum::<u64>() / times.len() as u64;
+        Some(std::time::Duration::from_secs(average))
+    }
+}
+
+/// Build durations and trust scores noted during this run, attached to
+/// the package's change when the operation is logged
+static RUN_NOTES: once_cell::sync::Lazy<std::sync::Mutex<HashMap<String, RunNote>>> =
+    once_cell::sync::Lazy::new(Default::default);
+
+#[derive(Debug, Clone, Copy, Default)]
+struct RunNote {
+    build_secs: Option<u64>,
+    trust_score: Option<f32>,
+}
+
+fn note(pkg: &str, update: impl FnOnce(&mut RunNote)) {
+    let mut notes = RUN_NOTES.lock().unwrap_or_else(|e| e.into_inner());
+    update(notes.entry(pkg.to_string()).or_default());
+}
+
+/// Fill in the build duration and trust score noted for each change during
+/// this run, forgetting them
+pub fn annotate_changes(changes: &mut [PackageChange]) {
+    let mut notes = RUN_NOTES.lock().unwrap_or_else(|e| e.into_inner());
+    for change in changes {
+        if let Some(note) = notes.remove(&change.name) {
+            change.build_secs = note.build_secs;
+            change.trust_score = note.trust_score;
+        }
+    }
+}
+
+/// Record an AUR build duration in the default build time history
+pub fn record_build_time(pkg: &str, duration: std::time::Duration) -> Result<()> {
+    note(pkg, |n| n.build_secs = Some(duration.as_secs()));
+    let path = BuildTimes::path();
+    let mut times = BuildTimes::load_from(&path);
+    times.record(pkg, duration);
+    times.save_to(&path)
+}
+
+/// Upstream commits each VCS package was last built from, for `--devel`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct VcsCommits {
+    pub packages: std::collections::BTreeMap<String, Vec<String>>,
+}
+
+impl VcsCommits {
+    pub fn path() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("/tmp"))
+            .join("reap/vcs_commits.json")
+    }
+
+    pub fn load() -> Self {
+        Self::load_from(&Self::path())
+    }
+
+    pub fn load_from(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn get(&self, pkg: &str) -> Option<&[String]> {
+        self.packages.get(pkg).map(Vec::as_slice)
+    }
+
+    pub fn set(&mut self, pkg: &str, commits: Vec<String>) {
+        self.packages.insert(pkg.to_string(), commits);
+    }
+
+    /// Whether upstream moved past the commits `pkg` was last built from.
+    /// A package that was never recorded is not considered outdated.
+    pub fn is_outdated(&self, pkg: &str, upstream: &[String]) -> bool {
+        self.get(pkg).is_some_and(|built| built != upstream)
+    }
+}
+
+/// Record the upstream commits `pkg` was built from in the default store
+pub fn record_vcs_commits(pkg: &str, commits: Vec<String>) -> Result<()> {
+    let path = VcsCommits::path();
+    let mut store = VcsCommits::load_from(&path);
+    store.set(pkg, commits);
+    store.save_to(&path)
+}
+
+/// Dependencies pacman pulled in to build each AUR package, so they can be
+/// removed again once nothing needs them
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BuildDeps {
+    pub packages: std::collections::BTreeMap<String, Vec<String>>,
+}
+
+impl BuildDeps {
+    pub fn path() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("/tmp"))
+            .join("reap/build_deps.json")
+    }
+
+    pub fn load() -> Self {
+        Self::load_from(&Self::path())
+    }
+
+    pub fn load_from(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Add the dependencies installed while building `pkg`
+    pub fn record(&mut self, pkg: &str, deps: impl IntoIterator<Item = String>) {
+        let entry = self.packages.entry(pkg.to_string()).or_default();
+        for dep in deps {
+            if !entry.contains(&dep) {
+                entry.push(dep);
+            }
+        }
+        entry.sort();
+    }
+
+    /// Every tracked build dependency, across all packages
+    pub fn all(&self) -> std::collections::BTreeSet<String> {
+        self.packages.values().flatten().cloned().collect()
+    }
+
+    /// Stop tracking `deps`, e.g. once they were removed
+    pub fn forget(&mut self, deps: &[String]) {
+        for entry in self.packages.values_mut() {
+            entry.retain(|dep| !deps.contains(dep));
+        }
+        self.packages.retain(|_, entry| !entry.is_empty());
+    }
+}
+
+/// What kind of reap operation changed the system
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OperationKind {
+    Install,
+    Remove,
+    Upgrade,
+}
+
+impl OperationKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Install => "install",
+            Self::Remove => "remove",
+            Self::Upgrade => "upgrade",
+        }
+    }
+}
+
+/// A package whose installed version an operation changed; `None` on either
+/// side means it wasn't installed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackageChange {
+    pub name: String,
+    pub old_version: Option<String>,
+    pub new_version: Option<String>,
+    /// Where reap installed it from, if it knows
+    #[serde(default)]
+    pub source: Option<crate::core::Source>,
+    /// How long reap spent building it, for AUR builds
+    #[serde(default)]
+    pub build_secs: Option<u64>,
+    /// Its trust score when it was installed
+    #[serde(default)]
+    pub trust_score: Option<f32>,
+}
+
+impl PackageChange {
+    /// Packages whose version differs between two `pacman -Q` snapshots
+    pub fn between(
+        before: &std::collections::BTreeMap<String, String>,
+        after: &std::collections::BTreeMap<String, String>,
+    ) -> Vec<PackageChange> {
+        let names: std::collections::BTreeSet<&String> =
+            before.keys().chain(after.keys()).collect();
+        names
+            .into_iter()
+            .filter(|name| before.get(*name) != after.get(*name))
+            .map(|name| PackageChange {
+                name: name.clone(),
+                old_version: before.get(name).cloned(),
+                new_version: after.get(name).cloned(),
+                source: None,
+                build_secs: None,
+                trust_score: None,
+            })
+            .collect()
+    }
+}
+
+/// One entry in the operation log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Operation {
+    pub id: u64,
+    pub kind: OperationKind,
+    pub at: DateTime<Utc>,
+    /// The packages the user asked for
+    pub packages: Vec<String>,
+    /// Every package the operation changed, dependencies included
+    pub changes: Vec<PackageChange>,
+    /// `None` if the operation succeeded
+    pub error: Option<String>,
+    #[serde(default)]
+    pub undone: bool,
+}
+
+/// `history.json`: the install, remove and upgrade operations reap ran
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OperationLog {
+    pub operations: Vec<Operation>,
+}
+
+impl OperationLog {
+    pub fn path() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("/tmp"))
+            .join("reap/history.json")
+    }
+
+    pub fn load() -> Self {
+        Self::load_from(&Self::path())
+    }
+
+    pub fn load_from(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Append an operation, returning its id
+    pub fn record(
+        &mut self,
+        kind: OperationKind,
+        packages: Vec<String>,
+        changes: Vec<PackageChange>,
+        error: Option<String>,
+    ) -> u64 {
+        let id = self.operations.last().map_or(1, |op| op.id + 1);
+        self.operations.push(Operation {
+            id,
+            kind,
+            at: Utc::now(),
+            packages,
+            changes,
+            error,
+            undone: false,
+        });
+        id
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Operation> {
+        self.operations.iter().find(|op| op.id == id)
+    }
+
+    pub fn mark_undone(&mut self, id: u64) {
+        if let Some(op) = self.operations.iter_mut().find(|op| op.id == id) {
+            op.undone = true;
+        }
+    }
+
+    /// The newest `limit` operations, newest first
+    pub fn recent(&self, limit: usize) -> impl Iterator<Item = &Operation> {
+        self.operations.iter().rev().take(limit)
+    }
+
+    /// Packages that failed to install, newest first and without the ones
+    /// the user declined. Qualified targets (`aur/foo`) give their name.
+    pub fn failed_installs(&self) -> Vec<String> {
+        let mut failed: Vec<String> = Vec::new();
+        for op in self.operations.iter().rev() {
+            let Some(error) = op.error.as_deref() else {
+                continue;
+            };
+            if op.kind != OperationKind::Install || op.undone {
+                continue;
+            }
+            for target in &op.packages {
+                let name = target.rsplit(['/', ':']).next().unwrap_or(target);
+                let declined = crate::core::ReapError::UserAborted.to_string();
+                if error_for(error, name).is_some_and(|reason| reason != declined)
+                    && !failed.iter().any(|f| f == name)
+                {
+                    failed.push(name.to_string());
+                }
+            }
+        }
+        failed
+    }
+}
+
+/// Record an operation in the default operation log
+pub fn record_operation(
+    kind: OperationKind,
+    packages: Vec<String>,
+    changes: Vec<PackageChange>,
+    error: Option<String>,
+) -> Result<u64> {
+    let path = OperationLog::path();
+    let mut log = OperationLog::load_from(&path);
+    let id = log.record(kind, packages, changes, error);
+    log.save_to(&path)?;
+    Ok(id)
+}
+
+/// One recorded event in the life of a package, for `reap log <pkg>`
+#[derive(Debug, Clone, Serialize)]
+pub struct PackageEvent {
+    pub operation: u64,
+    pub at: DateTime<Utc>,
+    pub kind: &'static str,
+    pub old_version: Option<String>,
+    pub new_version: Option<String>,
+    pub source: Option<String>,
+    pub build_secs: Option<u64>,
+    pub trust_score: Option<f32>,
+    /// Why the operation failed for this package
+    pub error: Option<String>,
+    pub undone: bool,
+}
+
+/// The part of an operation's error about `pkg`: its `pkg: reason` entry,
+/// or the whole error when it names the package
+pub fn error_for(error: &str, pkg: &str) -> Option<String> {
+    let prefix = format!("{}: ", pkg);
+    if let Some(reason) = error
+        .split("; ")
+        .find_map(|entry| entry.strip_prefix(&prefix))
+    {
+        return Some(reason.to_string());
+    }
+    error
+        .split(|c: char| c.is_whitespace() || c == ',' || c == ':')
+        .any(|word| word == pkg)
+        .then(|| error.to_string())
+}
+
+/// Everything recorded about a package: where it is installed from now and
+/// each operation that touched or failed on it, oldest first
+#[derive(Debug, Clone, Serialize)]
+pub struct PackageLog {
+    pub package: String,
+    pub installed: Option<InstallRecord>,
+    pub events: Vec<PackageEvent>,
+}
+
+impl PackageLog {
+    pub fn collect(log: &OperationLog, registry: &InstallRegistry, pkg: &str) -> Self {
+        let events = log
+            .operations
+            .iter()
+            .filter_map(|op| {
+                let change = op.changes.iter().find(|c| c.name == pkg);
+                let error = op.error.as_deref().and_then(|e| {
+                    error_for(e, pkg).or_else(|| {
+                        (op.packages.len() == 1 && op.packages[0] == pkg).then(|| e.to_string())
+                    })
+                });
+                if change.is_none() && error.is_none() && !op.packages.iter().any(|p| p == pkg) {
+                    return None;
+                }
+                Some(PackageEvent {
+                    operation: op.id,
+                    at: op.at,
+                    kind: op.kind.label(),
+                    old_version: change.and_then(|c| c.old_version.clone()),
+                    new_version: change.and_then(|c| c.new_version.clone()),
+                    source: change
+                        .and_then(|c| c.source.as_ref())
+                        .map(|s| crate::core::source_key(s, None)),
+                    build_secs: change.and_then(|c| c.build_secs),
+                    trust_score: change.and_then(|c| c.trust_score),
+                    error,
+                    undone: op.undone,
+                })
+            })
+            .collect();
+        PackageLog {
+            package: pkg.to_string(),
+            installed: registry.get(pkg).cloned(),
+            events,
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = Vec::new();
+        if let Some(record) = &self.installed {
+            let mut line = format!(
+                "{}: installed from {} on {}",
+                self.package,
+                crate::core::source_key(&record.source, record.tap.as_deref()),
+                record.installed_at.format("%Y-%m-%d %H:%M")
+            );
+            if let Some(score) = record.trust_score {
+                line.push_str(&format!(", trust {:.1}/10", score));
+            }
+            out.push(line);
+        }
+        if self.events.is_empty() {
+            out.push(format!("No recorded operations for {}", self.package));
+        }
+        for event in &self.events {
+            let versions = match (&event.old_version, &event.new_version) {
+                (None, Some(new)) => format!("installed {}", new),
+                (Some(old), None) => format!("removed {}", old),
+                (Some(old), Some(new)) => format!("{} -> {}", old, new),
+                (None, None) => "no change".to_string(),
+            };
+            let mut line = format!(
+                "#{:<4} {}  {:<8} {}",
+                event.operation,
+                event
+                    .at
+                    .with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M"),
+                event.kind,
+                versions
+            );
+            if let Some(source) = &event.source {
+                line.push_str(&format!(" from {}", source));
+            }
+            if let Some(secs) = event.build_secs {
+                line.push_str(&format!(
+                    ", built in {}",
+                    crate::utils::format_duration(std::time::Duration::from_secs(secs))
+                ));
+            }
+            if let Some(score) = event.trust_score {
+                line.push_str(&format!(", trust {:.1}/10", score));
+            }
+            if let Some(error) = &event.error {
+                line.push_str(&format!("  failed: {}", error));
+            }
+            if event.undone {
+                line.push_str(" (undone)");
+            }
+            out.push(line);
+        }
+        out.join("\n")
+    }
+}
@@ -4,6 +4,125 @@ use std::collections::HashMap;
 use std::fs;
 use std::io::{self, Write};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Seconds a prompt waits for an answer, 0 for no limit
+static PROMPT_TIMEOUT_SECS: AtomicU64 = AtomicU64::new(0);
+/// What an unanswered prompt resolves to: 0 its own default, 1 yes, 2 no
+static TIMEOUT_ANSWER: AtomicU8 = AtomicU8::new(0);
+
+/// Whether prompts answer their own default without asking (`--noconfirm`)
+static NOCONFIRM: AtomicBool = AtomicBool::new(false);
+
+/// Timeout used by `--default-yes`/`--default-no` when `prompt_timeout_secs`
+/// isn't set
+pub const DEFAULT_PROMPT_TIMEOUT_SECS: u64 = 60;
+
+/// How long prompts wait: `prompt_timeout_secs` from reap.toml, or
+/// `DEFAULT_PROMPT_TIMEOUT_SECS` once an answer policy is given. `None`
+/// waits forever.
+pub fn prompt_timeout(configured: Option<u64>, answer: Option<bool>) -> Option<Duration> {
+    match (configured.filter(|&secs| secs > 0), answer) {
+        (Some(secs), _) => Some(Duration::from_secs(secs)),
+        (None, Some(_)) => Some(Duration::from_secs(DEFAULT_PROMPT_TIMEOUT_SECS)),
+        (None, None) => None,
+    }
+}
+
+/// Make prompts give up after `timeout` and answer `answer`, or their own
+/// default when `None`
+pub fn set_prompt_timeout(timeout: Option<Duration>, answer: Option<bool>) {
+    PROMPT_TIMEOUT_SECS.store(timeout.map_or(0, |t| t.as_secs()), Ordering::Relaxed);
+    TIMEOUT_ANSWER.store(
+        match answer {
+            None => 0,
+            Some(true) => 1,
+            Some(false) => 2,
+        },
+        Ordering::Relaxed,
+    );
+}
+
+/// Make every prompt answer its own default without reading stdin
+pub fn set_noconfirm(noconfirm: bool) {
+    NOCONFIRM.store(noconfirm, Ordering::Relaxed);
+}
+
+/// Whether prompts are answered with their defaults (`--noconfirm`)
+pub fn noconfirm() -> bool {
+    NOCONFIRM.load(Ordering::Relaxed)
+}
+
+/// How a yes/no prompt was answered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Answer {
+    /// Typed by the user, an empty line taking the default
+    User(bool),
+    /// Prompts are off (`--noconfirm`)
+    NoConfirm,
+    /// Nothing was typed within the prompt timeout
+    TimedOut,
+    /// stdin is closed or unreadable
+    Closed,
+}
+
+/// A line typed at a prompt
+pub enum PromptInput {
+    Line(String),
+    /// Nothing was typed within the prompt timeout
+    TimedOut,
+    /// stdin is closed or unreadable
+    Closed,
+}
+
+/// Whether stdin has something to read (a line, or EOF) within `timeout`.
+/// Polling leaves nothing reading stdin once the prompt is over, so
+/// commands run afterwards still get what the user types.
+fn stdin_ready(timeout: Duration) -> bool {
+    let mut fd = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let millis = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+    // SAFETY: `fd` is a single valid pollfd for the duration of the call
+    unsafe { libc::poll(&mut fd, 1, millis) > 0 }
+}
+
+/// Whether std's stdin buffer already holds input that polling the fd
+/// can't see, like the rest of piped answers an earlier `read_line` pulled
+/// in. Checked with stdin briefly non-blocking, so an empty buffer only
+/// takes what is already waiting on the fd.
+fn stdin_buffered() -> bool {
+    use std::io::BufRead;
+    let fd = libc::STDIN_FILENO;
+    // SAFETY: fcntl on stdin with valid commands, the flags are restored below
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 {
+        return false;
+    }
+    unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) };
+    let buffered = io::stdin()
+        .lock()
+        .fill_buf()
+        .is_ok_and(|buf| !buf.is_empty());
+    unsafe { libc::fcntl(fd, libc::F_SETFL, flags) };
+    buffered
+}
+
+/// Read one line from stdin, waiting at most the prompt timeout
+pub fn read_prompt_line() -> PromptInput {
+    let secs = PROMPT_TIMEOUT_SECS.load(Ordering::Relaxed);
+    if secs > 0 && !stdin_buffered() && !stdin_ready(Duration::from_secs(secs)) {
+        return PromptInput::TimedOut;
+    }
+    let mut input = String::new();
+    match io::stdin().read_line(&mut input) {
+        Ok(n) if n > 0 => PromptInput::Line(input),
+        _ => PromptInput::Closed,
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PackageRating {
@@ -43,25 +162,73 @@ impl InteractiveManager {
         }
     }
 
-    /// Interactive confirmation prompt
-    pub fn confirm_action(message: &str, default: bool) -> bool {
-        let _default_char = if default { 'Y' } else { 'N' };
-        let prompt = format!("{} [{}]: ", message, if default { "Y/n" } else { "y/N" });
-
+    /// Read a line from the terminal without echoing it
+    pub fn read_password(prompt: &str) -> io::Result<String> {
         print!("{}", prompt);
+        io::stdout().flush()?;
+        let stty = |arg: &str| {
+            std::process::Command::new("stty")
+                .arg(arg)
+                .stdin(std::process::Stdio::inherit())
+                .status()
+        };
+        let _ = stty("-echo");
+        let read = read_prompt_line();
+        let _ = stty("echo");
+        println!();
+        match read {
+            PromptInput::Line(input) => Ok(input.trim_end_matches(['\r', '\n']).to_string()),
+            PromptInput::TimedOut => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "no password entered before the prompt timeout",
+            )),
+            PromptInput::Closed => Err(io::ErrorKind::UnexpectedEof.into()),
+        }
+    }
+
+    /// Ask a yes/no question and report how it was answered, without
+    /// applying the timeout or `--noconfirm` policy
+    pub fn ask(message: &str, default: bool) -> Answer {
+        print!("{} [{}]: ", message, if default { "Y/n" } else { "y/N" });
+        if noconfirm() {
+            return Answer::NoConfirm;
+        }
         io::stdout().flush().unwrap();
 
-        let mut input = String::new();
-        if io::stdin().read_line(&mut input).is_ok() {
-            let trimmed = input.trim().to_lowercase();
-            match trimmed.as_str() {
-                "y" | "yes" => true,
-                "n" | "no" => false,
-                "" => default,
-                _ => Self::confirm_action(message, default), // Use Self instead of self
+        match read_prompt_line() {
+            PromptInput::Line(input) => match input.trim().to_lowercase().as_str() {
+                "y" | "yes" => Answer::User(true),
+                "n" | "no" => Answer::User(false),
+                "" => Answer::User(default),
+                _ => Self::ask(message, default),
+            },
+            PromptInput::TimedOut => Answer::TimedOut,
+            PromptInput::Closed => Answer::Closed,
+        }
+    }
+
+    /// Interactive confirmation prompt
+    pub fn confirm_action(message: &str, default: bool) -> bool {
+        match Self::ask(message, default) {
+            Answer::User(answer) => answer,
+            Answer::NoConfirm => {
+                println!("{}", if default { "y" } else { "n" });
+                default
             }
-        } else {
-            default
+            Answer::TimedOut => {
+                let answer = match TIMEOUT_ANSWER.load(Ordering::Relaxed) {
+                    1 => true,
+                    2 => false,
+                    _ => default,
+                };
+                println!(
+                    "\n[reap] No answer after {}s, answering {}",
+                    PROMPT_TIMEOUT_SECS.load(Ordering::Relaxed),
+                    if answer { "yes" } else { "no" }
+                );
+                answer
+            }
+            Answer::Closed => default,
         }
     }
 
@@ -116,12 +283,7 @@ impl InteractiveManager {
     }
 
     async fn fetch_aur_info(&self, package: &str) -> Result<()> {
-        let url = format!(
-            "https://aur.archlinux.org/rpc/?v=5&type=info&arg[]={}",
-            package
-        );
-        let resp = reqwest::get(&url).await?;
-        let _ = resp.text().await?;
+        crate::aur::rpc(&format!("type=info&arg[]={}", package)).await?;
         Ok(())
     }
 
@@ -209,13 +371,19 @@ impl InteractiveManager {
         print!("\nEnter your choice (1-{}): ", items.len());
         io::stdout().flush().unwrap();
 
-        let mut input = String::new();
-        if io::stdin().read_line(&mut input).is_ok() {
-            if let Ok(choice) = input.trim().parse::<usize>() {
-                if choice > 0 && choice <= items.len() {
-                    return Some(choice - 1);
-                }
+        let input = match read_prompt_line() {
+            PromptInput::Line(input) => input,
+            PromptInput::TimedOut => {
+                println!("\n[reap] No selection made before the prompt timeout");
+                return None;
             }
+            PromptInput::Closed => return None,
+        };
+        if let Ok(choice) = input.trim().parse::<usize>()
+            && choice > 0
+            && choice <= items.len()
+        {
+            return Some(choice - 1);
         }
 
         println!("Invalid selection.");
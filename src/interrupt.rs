@@ -0,0 +1,255 @@
+use crate::core::ReapError;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// reap's lockfile, next to the build dirs while any is in use. It holds
+/// reap's pid, so one left by a killed reap can be told apart.
+pub const LOCKFILE: &str = "reap.lock";
+
+/// Build dirs being worked in and the child processes running, so a Ctrl-C
+/// can clean up after them
+#[derive(Default)]
+struct Tracked {
+    build_dirs: HashSet<PathBuf>,
+    /// The lockfile and how many build dirs hold it
+    lockfile: Option<PathBuf>,
+    holders: usize,
+    /// Child pids, and whether each is a pacman transaction
+    children: HashMap<u32, bool>,
+}
+
+static TRACKED: once_cell::sync::Lazy<Mutex<Tracked>> =
+    once_cell::sync::Lazy::new(|| Mutex::new(Tracked::default()));
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// How often Ctrl-C handling checks on the children it waits for
+const POLL: Duration = Duration::from_millis(100);
+
+/// How long stopped children get to exit before they are killed
+const KILL_AFTER: Duration = Duration::from_secs(5);
+
+fn tracked() -> std::sync::MutexGuard<'static, Tracked> {
+    TRACKED.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Whether Ctrl-C was pressed
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+/// Keeps a build dir registered for removal on Ctrl-C while alive, and
+/// reap's lockfile in its parent dir while any build dir is
+pub struct BuildDirGuard(PathBuf);
+
+impl BuildDirGuard {
+    /// Register `path`, taking reap's lockfile next to it if this run doesn't
+    /// hold it yet. A lockfile left by a reap that is gone is taken over.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BuildLocked` if another running reap holds the lockfile.
+    pub fn register(path: &Path) -> Result<Self, ReapError> {
+        let mut tracked = tracked();
+        if tracked.lockfile.is_none()
+            && let Some(root) = path.parent()
+        {
+            let lockfile = root.join(LOCKFILE);
+            let _ = std::fs::create_dir_all(root);
+            let ours = std::process::id();
+            let mut created = create_lockfile(&lockfile);
+            if matches!(&created, Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists)
+                && is_stale_lockfile(&lockfile, |pid| pid != ours && is_running(pid))
+            {
+                let _ = std::fs::remove_file(&lockfile);
+                created = create_lockfile(&lockfile);
+            }
+            match created {
+                Ok(()) => tracked.lockfile = Some(lockfile),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    return Err(ReapError::BuildLocked(lockfile));
+                }
+                // Builds still work without a lockfile, it only guards cleanup
+                Err(_) => {}
+            }
+        }
+        tracked.build_dirs.insert(path.to_path_buf());
+        tracked.holders += 1;
+        Ok(Self(path.to_path_buf()))
+    }
+}
+
+/// Create the lockfile at `path` with reap's pid in it, failing if it exists
+fn create_lockfile(path: &Path) -> std::io::Result<()> {
+    use std::io::Write;
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)?
+        .write_all(std::process::id().to_string().as_bytes())
+}
+
+impl Drop for BuildDirGuard {
+    fn drop(&mut self) {
+        let mut tracked = tracked();
+        tracked.build_dirs.remove(&self.0);
+        tracked.holders = tracked.holders.saturating_sub(1);
+        if tracked.holders == 0 {
+            release_lockfile(&mut tracked);
+        }
+    }
+}
+
+/// Remove reap's lockfile, returning it if it was held
+fn release_lockfile(tracked: &mut Tracked) -> Option<PathBuf> {
+    let lockfile = tracked.lockfile.take()?;
+    std::fs::remove_file(&lockfile).ok().map(|()| lockfile)
+}
+
+/// Whether the lockfile at `path` was left by a reap that is gone: the pid
+/// in it isn't `alive`
+pub fn is_stale_lockfile(path: &Path, alive: impl Fn(u32) -> bool) -> bool {
+    match std::fs::read_to_string(path) {
+        Ok(pid) => pid.trim().parse::<u32>().is_ok_and(|pid| !alive(pid)),
+        Err(_) => false,
+    }
+}
+
+/// Whether a process with `pid` is running
+pub fn is_running(pid: u32) -> bool {
+    Path::new(&format!("/proc/{}", pid)).exists()
+}
+
+/// Keeps a running child registered while alive
+pub struct ChildGuard(u32);
+
+impl ChildGuard {
+    /// Track `child`, spawned from `cmd`
+    pub fn track(child: &std::process::Child, cmd: &Command) -> Self {
+        let args: Vec<String> = std::iter::once(cmd.get_program())
+            .chain(cmd.get_args())
+            .map(|a| a.to_string_lossy().into_owned())
+            .collect();
+        tracked()
+            .children
+            .insert(child.id(), is_pacman_transaction(&args));
+        Self(child.id())
+    }
+}
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        tracked().children.remove(&self.0);
+    }
+}
+
+/// Whether a command line (program first) runs a pacman operation that
+/// changes the system, which must never be cut short
+pub fn is_pacman_transaction(args: &[String]) -> bool {
+    let args = match args.first().map(String::as_str) {
+        Some("sudo" | "doas") => &args[1..],
+        Some(program) if program == crate::utils::privilege_program() => &args[1..],
+        _ => args,
+    };
+    if args.first().map(String::as_str) != Some("pacman") {
+        return false;
+    }
+    args[1..].iter().any(|arg| match arg.as_str() {
+        "--upgrade" | "--remove" => true,
+        "--sync" => !args.iter().any(|a| {
+            matches!(
+                a.as_str(),
+                "--search" | "--info" | "--print" | "--list" | "--groups"
+            )
+        }),
+        _ if arg.starts_with("--") => false,
+        _ => arg.strip_prefix('-').is_some_and(|flags| {
+            flags.starts_with(['U', 'R'])
+                || (flags.starts_with('S') && !flags.contains(['s', 'i', 'p', 'l', 'g']))
+        }),
+    })
+}
+
+/// Remove every registered build dir, returning the ones removed
+fn remove_build_dirs() -> Vec<PathBuf> {
+    let dirs: Vec<PathBuf> = tracked().build_dirs.drain().collect();
+    dirs.into_iter()
+        .filter(|dir| std::fs::remove_dir_all(dir).is_ok())
+        .collect()
+}
+
+/// Whether any child other than a pacman transaction is still running
+fn others_running() -> bool {
+    tracked().children.values().any(|pacman| !*pacman)
+}
+
+/// Send `signal` to every tracked child but pacman transactions
+fn signal_others(signal: &str) {
+    let others: Vec<u32> = tracked()
+        .children
+        .iter()
+        .filter(|(_, pacman)| !**pacman)
+        .map(|(pid, _)| *pid)
+        .collect();
+    for pid in others {
+        let _ = Command::new("kill")
+            .args([signal, &pid.to_string()])
+            .stderr(std::process::Stdio::null())
+            .status();
+    }
+}
+
+/// Handle Ctrl-C for the rest of the process: stop running builds and
+/// clones and wait for them to exit, wait for a pacman transaction to finish
+/// by itself, remove the build dirs in progress and reap's lockfile and exit
+/// with status 130. No new child or transaction starts once interrupted.
+pub fn install_handler() {
+    tokio::spawn(async {
+        loop {
+            if tokio::signal::ctrl_c().await.is_err() {
+                return;
+            }
+            if INTERRUPTED.swap(true, Ordering::Relaxed) {
+                // Already shutting down, most likely waiting for pacman
+                continue;
+            }
+            eprintln!("\n[reap] Interrupted, stopping...");
+            signal_others("-TERM");
+            // Their build dirs can only go once they stopped writing to them
+            let mut waited = Duration::ZERO;
+            while others_running() {
+                if waited == KILL_AFTER {
+                    signal_others("-KILL");
+                }
+                tokio::time::sleep(POLL).await;
+                waited += POLL;
+            }
+            // Transactions hold pacman's lock, including the ones reap runs
+            // without tracking them as children; holding it until exit keeps
+            // another from starting
+            let mut told = false;
+            let _transaction = loop {
+                if let Some(transaction) = crate::pacman::try_transaction() {
+                    break transaction;
+                }
+                if !told {
+                    eprintln!("[reap] Waiting for pacman to finish its transaction...");
+                    told = true;
+                }
+                tokio::time::sleep(POLL).await;
+            };
+            for dir in remove_build_dirs() {
+                eprintln!("[reap] Removed build dir {}", dir.display());
+            }
+            if let Some(lockfile) = release_lockfile(&mut tracked()) {
+                eprintln!("[reap] Released {}", lockfile.display());
+            }
+            eprintln!("[reap] Interrupted");
+            std::process::exit(130);
+        }
+    });
+}
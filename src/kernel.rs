@@ -0,0 +1,97 @@
+use std::fs;
+use std::path::Path;
+
+/// Where each installed kernel keeps its modules, one dir per release
+pub const MODULES_DIR: &str = "/usr/lib/modules";
+
+/// An installed kernel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Kernel {
+    /// Package it comes from: `linux`, `linux-lts`, `linux-zen`, ...
+    pub package: String,
+    /// Release as `uname -r` prints it
+    pub release: String,
+    /// Whether its headers are installed (`build` in its modules dir)
+    pub headers: bool,
+}
+
+impl Kernel {
+    /// The package with this kernel's headers
+    pub fn headers_package(&self) -> String {
+        format!("{}-headers", self.package)
+    }
+
+    /// Whether `--kernel <wanted>` picks this kernel, by package name or
+    /// (the start of) its release
+    pub fn matches(&self, wanted: &str) -> bool {
+        self.package == wanted
+            || self.release == wanted
+            || self.release.starts_with(&format!("{}-", wanted))
+    }
+}
+
+/// Kernels installed in `modules_dir`, sorted by package. Arch kernel
+/// packages leave a `pkgbase` file naming themselves in their modules dir.
+pub fn installed_kernels(modules_dir: &Path) -> Vec<Kernel> {
+    let mut kernels: Vec<Kernel> = fs::read_dir(modules_dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| {
+                    let dir = entry.path();
+                    let package = fs::read_to_string(dir.join("pkgbase")).ok()?;
+                    Some(Kernel {
+                        package: package.trim().to_string(),
+                        release: entry.file_name().to_string_lossy().into_owned(),
+                        headers: dir.join("build").is_dir(),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    kernels.sort_by(|a, b| a.package.cmp(&b.package));
+    kernels
+}
+
+/// Release of the running kernel
+pub fn running_release() -> Option<String> {
+    fs::read_to_string("/proc/sys/kernel/osrelease")
+        .ok()
+        .map(|release| release.trim().to_string())
+}
+
+/// Whether a package with these dependencies builds kernel modules: it
+/// uses DKMS or needs kernel headers
+pub fn builds_modules(deps: &[String]) -> bool {
+    deps.iter().any(|dep| {
+        let name = crate::core::dep_name(dep);
+        name == "dkms" || (name.starts_with("linux") && name.ends_with("-headers"))
+    })
+}
+
+/// Whether a package with these dependencies builds its modules with DKMS
+pub fn uses_dkms(deps: &[String]) -> bool {
+    deps.iter().any(|dep| crate::core::dep_name(dep) == "dkms")
+}
+
+/// The kernels to build modules for: the one `--kernel` picks, else every
+/// installed kernel
+pub fn select(kernels: &[Kernel], wanted: Option<&str>) -> Result<Vec<Kernel>, String> {
+    let Some(wanted) = wanted else {
+        return Ok(kernels.to_vec());
+    };
+    let selected: Vec<Kernel> = kernels
+        .iter()
+        .filter(|kernel| kernel.matches(wanted))
+        .cloned()
+        .collect();
+    if selected.is_empty() {
+        let installed: Vec<&str> = kernels.iter().map(|k| k.package.as_str()).collect();
+        return Err(format!(
+            "no installed kernel matches --kernel {} (installed: {})",
+            wanted,
+            installed.join(", ")
+        ));
+    }
+    Ok(selected)
+}
@@ -11,13 +11,26 @@ pub mod graph;
 pub mod history;
 pub mod hooks;
 pub mod interactive;
+pub mod interrupt;
+pub mod kernel;
+pub mod mirrors;
+pub mod news;
 pub mod pacman;
 pub mod profiles;
+pub mod srcinfo;
+pub mod state;
 pub mod tap;
 pub mod trust;
 pub mod tui;
 pub mod utils;
+pub mod verify;
 
 pub use crate::aur::SearchResult;
 pub use crate::aur::get_deps;
-pub use crate::core::{Source, install_with_priority, unified_search};
+pub use crate::config::{GlobalConfig, ReapConfig};
+pub use crate::core::{
+    InstallOptions, ProgressReporter, Source, StdoutReporter, install_with_priority,
+    resolve_package_source, unified_search,
+};
+pub use crate::profiles::{ProfileConfig, ProfileManager};
+pub use crate::trust::{TrustEngine, TrustScore};
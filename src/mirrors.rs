@@ -0,0 +1,197 @@
+use crate::core::ReapError;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// pacman's mirrorlist
+pub const MIRRORLIST: &str = "/etc/pacman.d/mirrorlist";
+
+/// Mirrors kept when ranking, unless `--number` says otherwise
+pub const DEFAULT_MIRROR_COUNT: usize = 20;
+
+/// How a mirrorlist gets ranked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ranker {
+    /// `reflector`, which also fetches the current mirror status
+    Reflector,
+    /// `rankmirrors` from pacman-contrib, timing the mirrors already listed
+    Rankmirrors,
+}
+
+impl Ranker {
+    /// The installed ranking tool, reflector first
+    pub fn detect() -> Option<Self> {
+        if which::which("reflector").is_ok() {
+            Some(Self::Reflector)
+        } else if which::which("rankmirrors").is_ok() {
+            Some(Self::Rankmirrors)
+        } else {
+            None
+        }
+    }
+}
+
+/// How long ago `path` was last written
+pub fn mirrorlist_age(path: &Path) -> Option<Duration> {
+    fs::metadata(path).ok()?.modified().ok()?.elapsed().ok()
+}
+
+/// Whether a mirrorlist of `age` is older than `max_age_days`; 0 never is
+pub fn is_stale(age: Duration, max_age_days: u64) -> bool {
+    max_age_days > 0 && age > Duration::from_secs(max_age_days * 24 * 60 * 60)
+}
+
+/// The `Server = ` URLs pacman uses from a mirrorlist
+pub fn active_servers(mirrorlist: &str) -> Vec<String> {
+    mirrorlist
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.trim().split_once('=')?;
+            (key.trim() == "Server").then(|| value.trim().to_string())
+        })
+        .collect()
+}
+
+/// A mirrorlist with every commented `#Server = ` line enabled, for
+/// `rankmirrors` to time them all
+pub fn uncomment_servers(mirrorlist: &str) -> String {
+    mirrorlist
+        .lines()
+        .map(|line| {
+            let uncommented = line.trim_start_matches(['#', ' ']);
+            if uncommented.starts_with("Server") && uncommented.contains('=') {
+                uncommented
+            } else {
+                line
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        + "\n"
+}
+
+/// `reflector` arguments writing the `number` fastest recently synced
+/// HTTPS mirrors, optionally of `country`, to `save`
+pub fn reflector_args(country: Option<&str>, number: usize, save: &Path) -> Vec<String> {
+    let mut args: Vec<String> = [
+        "--latest",
+        "50",
+        "--protocol",
+        "https",
+        "--sort",
+        "rate",
+        "--number",
+    ]
+    .map(String::from)
+    .to_vec();
+    args.push(number.to_string());
+    if let Some(country) = country {
+        args.extend(["--country".to_string(), country.to_string()]);
+    }
+    args.extend(["--save".to_string(), save.display().to_string()]);
+    args
+}
+
+/// Replace the mirrorlist with the `number` fastest mirrors, keeping the
+/// old one as `mirrorlist.bak`
+///
+/// # Errors
+///
+/// Returns `CommandFailed` if neither reflector nor rankmirrors is
+/// installed or the ranking fails.
+pub fn rank_mirrors(country: Option<&str>, number: usize) -> Result<(), ReapError> {
+    let Some(ranker) = Ranker::detect() else {
+        return Err(ReapError::CommandFailed(
+            "neither reflector nor rankmirrors is installed; install one with \
+             `reap -S reflector` (or `reap -S pacman-contrib` for rankmirrors)"
+                .to_string(),
+        ));
+    };
+    let mirrorlist = Path::new(MIRRORLIST);
+    let backup = mirrorlist.with_extension("bak");
+    run(
+        crate::utils::escalate("cp").arg(mirrorlist).arg(&backup),
+        "cp",
+    )?;
+    println!(
+        "[reap] Saved the current mirrorlist to {}",
+        backup.display()
+    );
+    match ranker {
+        Ranker::Reflector => {
+            println!("[reap] Ranking mirrors with reflector...");
+            run(
+                crate::utils::escalate("reflector")
+                    .args(reflector_args(country, number, mirrorlist)),
+                "reflector",
+            )?;
+        }
+        Ranker::Rankmirrors => {
+            if country.is_some() {
+                println!("[reap] rankmirrors can't filter by country, timing every listed mirror");
+            }
+            println!("[reap] Ranking mirrors with rankmirrors, this can take a while...");
+            // Created fresh and private, so nobody can plant or swap the
+            // mirrors being ranked
+            let candidates =
+                std::env::temp_dir().join(format!("reap-mirrorlist-{}", std::process::id()));
+            {
+                use std::io::Write;
+                use std::os::unix::fs::OpenOptionsExt;
+                let mut file = fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .mode(0o600)
+                    .open(&candidates)?;
+                file.write_all(uncomment_servers(&fs::read_to_string(mirrorlist)?).as_bytes())?;
+            }
+            let output = std::process::Command::new("rankmirrors")
+                .args(["-n", &number.to_string()])
+                .arg(&candidates)
+                .output();
+            let _ = fs::remove_file(&candidates);
+            let output = output?;
+            if !output.status.success() {
+                return Err(ReapError::CommandFailed(format!(
+                    "rankmirrors exited with {}",
+                    output.status
+                )));
+            }
+            // Piped straight into the mirrorlist, with no file in between
+            // for another user to tamper with
+            let mut tee = crate::utils::escalate("tee")
+                .arg(mirrorlist)
+                .stdin(std::process::Stdio::piped())
+                .stdout(std::process::Stdio::null())
+                .spawn()?;
+            let written = match tee.stdin.take() {
+                Some(mut stdin) => std::io::Write::write_all(&mut stdin, &output.stdout),
+                None => Ok(()),
+            };
+            let status = tee.wait()?;
+            written?;
+            if !status.success() {
+                return Err(ReapError::CommandFailed(format!(
+                    "tee exited with {}",
+                    status
+                )));
+            }
+        }
+    }
+    let servers = active_servers(&fs::read_to_string(mirrorlist)?);
+    println!("[reap] Mirrorlist now has {} mirrors", servers.len());
+    Ok(())
+}
+
+/// Run `cmd`, failing on a nonzero exit of `program`
+fn run(cmd: &mut std::process::Command, program: &str) -> Result<(), ReapError> {
+    let status = cmd.status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(ReapError::CommandFailed(format!(
+            "{} exited with {}",
+            program, status
+        )))
+    }
+}
@@ -0,0 +1,154 @@
+use crate::core::ReapError;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Arch Linux news feed
+pub const NEWS_FEED_URL: &str = "https://archlinux.org/feeds/news/";
+
+/// How far back news counts as unread before anything was ever marked seen
+const FIRST_RUN_WINDOW_DAYS: i64 = 30;
+
+/// One item of the Arch news feed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NewsItem {
+    pub title: String,
+    pub link: String,
+    pub published: DateTime<Utc>,
+    /// Body as plain text
+    pub text: String,
+}
+
+/// Contents of the first `<tag>` in `xml`, with CDATA unwrapped
+fn element<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let (_, rest) = xml.split_once(&format!("<{}>", tag))?;
+    let (inner, _) = rest.split_once(&format!("</{}>", tag))?;
+    let inner = inner.trim();
+    Some(
+        inner
+            .strip_prefix("<![CDATA[")
+            .and_then(|s| s.strip_suffix("]]>"))
+            .unwrap_or(inner),
+    )
+}
+
+/// Undo XML escaping of `text`
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Items of an RSS feed, newest first. Items without a parseable date are
+/// left out.
+pub fn parse_feed(xml: &str) -> Vec<NewsItem> {
+    let mut items: Vec<NewsItem> = xml
+        .split("<item>")
+        .skip(1)
+        .filter_map(|chunk| {
+            let item = chunk.split("</item>").next()?;
+            let published = DateTime::parse_from_rfc2822(element(item, "pubDate")?.trim())
+                .ok()?
+                .with_timezone(&Utc);
+            Some(NewsItem {
+                title: unescape(element(item, "title").unwrap_or_default()),
+                link: unescape(element(item, "link").unwrap_or_default()),
+                published,
+                // The description is escaped HTML
+                text: crate::aur::html_text(&unescape(
+                    element(item, "description").unwrap_or_default(),
+                )),
+            })
+        })
+        .collect();
+    items.sort_by_key(|item| std::cmp::Reverse(item.published));
+    items
+}
+
+/// Fetch the Arch news feed, newest first
+///
+/// # Errors
+///
+/// Returns `NetworkError` if the feed can't be downloaded.
+pub async fn fetch_news() -> Result<Vec<NewsItem>, ReapError> {
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(10))
+        .build()
+        .map_err(|e| ReapError::NetworkError(e.to_string()))?;
+    let xml = client
+        .get(NEWS_FEED_URL)
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .map_err(|e| ReapError::NetworkError(e.to_string()))?
+        .text()
+        .await
+        .map_err(|e| ReapError::NetworkError(e.to_string()))?;
+    Ok(parse_feed(&xml))
+}
+
+/// Where the date of the newest news item shown is kept
+pub fn seen_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("/tmp"))
+        .join("reap/news_seen")
+}
+
+/// Date of the newest news item shown so far
+pub fn last_seen(path: &Path) -> Option<DateTime<Utc>> {
+    let text = fs::read_to_string(path).ok()?;
+    DateTime::parse_from_rfc3339(text.trim())
+        .ok()
+        .map(|d| d.with_timezone(&Utc))
+}
+
+/// Remember `items` as read, unless newer news was already seen
+pub fn mark_seen(path: &Path, items: &[NewsItem]) -> std::io::Result<()> {
+    let Some(newest) = items.iter().map(|item| item.published).max() else {
+        return Ok(());
+    };
+    if last_seen(path).is_some_and(|seen| seen >= newest) {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, newest.to_rfc3339())
+}
+
+/// Items published after `seen`, or within the last 30 days when nothing
+/// was seen yet
+pub fn unread(
+    items: &[NewsItem],
+    seen: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Vec<NewsItem> {
+    let since = seen.unwrap_or(now - Duration::days(FIRST_RUN_WINDOW_DAYS));
+    items
+        .iter()
+        .filter(|item| item.published > since)
+        .cloned()
+        .collect()
+}
+
+/// Print `items` with their date, title, body and link
+pub fn print_items(items: &[NewsItem]) {
+    use colored::Colorize;
+    for item in items {
+        println!(
+            "{} {}",
+            item.published.format("%Y-%m-%d").to_string().dimmed(),
+            item.title.bold()
+        );
+        if !item.text.is_empty() {
+            println!("  {}", item.text);
+        }
+        if !item.link.is_empty() {
+            println!("  {}", item.link.blue());
+        }
+    }
+}
@@ -1,11 +1,29 @@
 // Pacman repo logic
 use std::process::Command;
 
+static TRANSACTION: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Held around every pacman run that changes the system. pacman allows one
+/// transaction at a time (`db.lck`), so parallel builds and installs take
+/// turns here instead of failing on the lock.
+pub fn transaction() -> std::sync::MutexGuard<'static, ()> {
+    TRANSACTION.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// [`transaction`] unless one is running
+pub fn try_transaction() -> Option<std::sync::MutexGuard<'static, ()>> {
+    match TRANSACTION.try_lock() {
+        Ok(guard) => Some(guard),
+        Err(std::sync::TryLockError::Poisoned(e)) => Some(e.into_inner()),
+        Err(std::sync::TryLockError::WouldBlock) => None,
+    }
+}
+
 /// Install a package from the official repositories using pacman
 pub fn install(package: &str) {
     println!("[pacman] Installing package: {}", package);
-    let status = Command::new("sudo")
-        .arg("pacman")
+    let _transaction = transaction();
+    let status = crate::utils::escalate("pacman")
         .arg("-S")
         .arg("--noconfirm")
         .arg(package)
@@ -21,6 +39,62 @@ pub fn install(package: &str) {
     }
 }
 
+/// Parse `pacman -Ss` output: `repo/name version [installed]` lines, each
+/// followed by an indented description
+pub fn parse_search(output: &str) -> Vec<crate::aur::SearchResult> {
+    let mut results: Vec<crate::aur::SearchResult> = Vec::new();
+    for line in output.lines() {
+        if line.starts_with(char::is_whitespace) {
+            if let Some(last) = results.last_mut() {
+                last.description = line.trim().to_string();
+            }
+        } else if let Some((repo_name, rest)) = line.split_once(' ')
+            && let Some((_, name)) = repo_name.split_once('/')
+        {
+            results.push(crate::aur::SearchResult {
+                name: name.to_string(),
+                version: rest.split_whitespace().next().unwrap_or("").to_string(),
+                description: String::new(),
+                source: crate::core::Source::Pacman,
+                votes: None,
+            });
+        }
+    }
+    results
+}
+
+/// Search the sync repos (`pacman -Ss`)
+pub fn search(query: &str) -> Vec<crate::aur::SearchResult> {
+    Command::new("pacman")
+        .arg("-Ss")
+        .arg(query)
+        .output()
+        .map(|o| parse_search(&String::from_utf8_lossy(&o.stdout)))
+        .unwrap_or_default()
+}
+
+/// Members of a repo package group (`pacman -Sgq`), empty if `name` is not a group
+pub fn group_members(name: &str) -> Vec<String> {
+    Command::new("pacman")
+        .arg("-Sgq")
+        .arg(name)
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| {
+            String::from_utf8_lossy(&o.stdout)
+                .lines()
+                .map(|l| l.trim().to_string())
+                .filter(|l| !l.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn is_group(name: &str) -> bool {
+    !group_members(name).is_empty()
+}
+
 pub fn is_installed(pkg: &str) -> bool {
     Command::new("pacman")
         .arg("-Q")
@@ -30,6 +104,45 @@ pub fn is_installed(pkg: &str) -> bool {
         .unwrap_or(false)
 }
 
+/// Names of the configured sync repos (`pacman-conf --repo-list`)
+pub fn sync_repos() -> Vec<String> {
+    Command::new("pacman-conf")
+        .arg("--repo-list")
+        .output()
+        .map(|o| {
+            String::from_utf8_lossy(&o.stdout)
+                .lines()
+                .map(|l| l.trim().to_string())
+                .filter(|l| !l.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Whether `pkg` is installed but in no sync repo (`pacman -Qm`), i.e.
+/// built from the AUR or a tap
+pub fn is_foreign(pkg: &str) -> bool {
+    Command::new("pacman")
+        .arg("-Qmq")
+        .arg(pkg)
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
+/// Change the install reason of `pkg` (`pacman -D --asexplicit/--asdeps`).
+/// Returns whether pacman succeeded.
+pub fn mark(pkg: &str, explicit: bool) -> bool {
+    let _transaction = transaction();
+    crate::utils::escalate("pacman")
+        .args(["-D"])
+        .arg(if explicit { "--asexplicit" } else { "--asdeps" })
+        .arg(pkg)
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
 pub fn get_version(pkg: &str) -> Option<String> {
     let output = Command::new("pacman").arg("-Qi").arg(pkg).output().ok()?;
     if !output.status.success() {
@@ -58,4 +171,521 @@ pub fn list_installed_aur() -> Vec<String> {
     }
 }
 
+/// Download `package` and its missing dependencies into `dir` without
+/// installing them (`pacman -Sw`). Returns whether pacman succeeded.
+pub fn download(package: &str, dir: &std::path::Path) -> bool {
+    println!("[pacman] Downloading package: {}", package);
+    let _transaction = transaction();
+    crate::utils::escalate("pacman")
+        .args(["-Sw", "--noconfirm", "--cachedir"])
+        .arg(dir)
+        .arg(package)
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// Names in an installed package's `Depends On`, without version constraints
+pub fn installed_depends(pkg: &str) -> Vec<String> {
+    let Ok(output) = Command::new("pacman").arg("-Qi").arg(pkg).output() else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find(|line| line.starts_with("Depends On"))
+        .and_then(|line| line.split_once(':'))
+        .map(|(_, deps)| {
+            deps.split_whitespace()
+                .filter(|d| *d != "None")
+                .map(|d| d.split(['<', '>', '=']).next().unwrap_or(d).to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// One entry of a package's optional dependencies
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptDep {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+impl OptDep {
+    /// Parse `name: description` as in `Optional Deps` or the AUR's
+    /// `OptDepends`, dropping pacman's ` [installed]` marker
+    pub fn parse(entry: &str) -> Self {
+        let entry = entry.trim();
+        let entry = entry.strip_suffix("[installed]").unwrap_or(entry).trim();
+        match entry.split_once(':') {
+            Some((name, description)) if !description.trim().is_empty() => Self {
+                name: name.trim().to_string(),
+                description: Some(description.trim().to_string()),
+            },
+            Some((name, _)) => Self {
+                name: name.trim().to_string(),
+                description: None,
+            },
+            None => Self {
+                name: entry.to_string(),
+                description: None,
+            },
+        }
+    }
+}
+
+/// Dependencies of an installed package, from `pacman -Qi`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackageDeps {
+    pub name: String,
+    pub version: String,
+    /// `Depends On`, with version constraints
+    pub depends: Vec<String>,
+    pub optdepends: Vec<OptDep>,
+    /// `Required By`: installed packages that depend on this one
+    pub required_by: Vec<String>,
+    /// `Provides`, with versions
+    pub provides: Vec<String>,
+    /// `Conflicts With`, with version constraints
+    pub conflicts: Vec<String>,
+}
+
+/// Parse `pacman -Qi` output for any number of packages. Values continue
+/// on indented lines, one optional dependency per line.
+pub fn parse_package_deps(output: &str) -> Vec<PackageDeps> {
+    let mut packages = Vec::new();
+    for block in output.split("\n\n") {
+        let mut pkg = PackageDeps::default();
+        let mut field = String::new();
+        for line in block.lines() {
+            let value = match line.split_once(" : ") {
+                Some((key, value)) if !line.starts_with(' ') => {
+                    field = key.trim().to_string();
+                    value
+                }
+                _ => line,
+            }
+            .trim();
+            if value.is_empty() || value == "None" {
+                continue;
+            }
+            match field.as_str() {
+                "Name" => pkg.name = value.to_string(),
+                "Version" => pkg.version = value.to_string(),
+                "Depends On" => pkg
+                    .depends
+                    .extend(value.split_whitespace().map(str::to_string)),
+                "Optional Deps" => pkg.optdepends.push(OptDep::parse(value)),
+                "Required By" => pkg
+                    .required_by
+                    .extend(value.split_whitespace().map(str::to_string)),
+                "Provides" => pkg
+                    .provides
+                    .extend(value.split_whitespace().map(str::to_string)),
+                "Conflicts With" => pkg
+                    .conflicts
+                    .extend(value.split_whitespace().map(str::to_string)),
+                _ => {}
+            }
+        }
+        if !pkg.name.is_empty() {
+            packages.push(pkg);
+        }
+    }
+    packages
+}
+
+/// Dependencies of the installed `pkgs`, or of every installed package
+/// when `pkgs` is empty
+pub fn package_deps(pkgs: &[String]) -> Vec<PackageDeps> {
+    match Command::new("pacman").arg("-Qi").args(pkgs).output() {
+        Ok(output) => parse_package_deps(&String::from_utf8_lossy(&output.stdout)),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// `Installed Size` of an installed package in bytes
+pub fn installed_size(pkg: &str) -> Option<u64> {
+    let output = Command::new("pacman").arg("-Qi").arg(pkg).output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout.lines().find(|l| l.starts_with("Installed Size"))?;
+    parse_size(line.split_once(':')?.1)
+}
+
+/// Bytes in a size as pacman prints it, e.g. `12.50 MiB`
+pub fn parse_size(value: &str) -> Option<u64> {
+    let mut parts = value.split_whitespace();
+    let value: f64 = parts.next()?.replace(',', ".").parse().ok()?;
+    let unit = match parts.next()? {
+        "B" => 1.0,
+        "KiB" => 1024.0,
+        "MiB" => 1024.0 * 1024.0,
+        "GiB" => 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some((value * unit) as u64)
+}
+
+/// Download and installed size of a sync repo package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageSize {
+    pub name: String,
+    pub download: u64,
+    pub installed: u64,
+}
+
+/// Parse `pacman -Si` output for any number of packages
+pub fn parse_sync_sizes(output: &str) -> Vec<PackageSize> {
+    output
+        .split("\n\n")
+        .filter_map(|block| {
+            let field = |key: &str| {
+                block.lines().find_map(|line| {
+                    let (k, v) = line.split_once(" : ")?;
+                    (k.trim() == key).then(|| v.trim())
+                })
+            };
+            Some(PackageSize {
+                name: field("Name")?.to_string(),
+                download: field("Download Size").and_then(parse_size).unwrap_or(0),
+                installed: field("Installed Size").and_then(parse_size).unwrap_or(0),
+            })
+        })
+        .collect()
+}
+
+/// Sizes of the sync repo packages `pkgs` (`pacman -Si`)
+pub fn sync_sizes(pkgs: &[String]) -> Vec<PackageSize> {
+    if pkgs.is_empty() {
+        return Vec::new();
+    }
+    match Command::new("pacman").arg("-Si").args(pkgs).output() {
+        Ok(output) => parse_sync_sizes(&String::from_utf8_lossy(&output.stdout)),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// The repo packages `pacman -S pkgs` would install, dependencies that
+/// aren't installed included (`pacman -Sp`)
+pub fn install_set(pkgs: &[String]) -> Vec<String> {
+    if pkgs.is_empty() {
+        return Vec::new();
+    }
+    let output = Command::new("pacman")
+        .args(["-Sp", "--noconfirm", "--print-format", "%n"])
+        .args(pkgs)
+        .output();
+    match output {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect(),
+        _ => pkgs.to_vec(),
+    }
+}
+
+/// Bytes pacman would download for `package` and its missing dependencies
+/// (`pacman -Sp`), `None` if it isn't in the sync repos
+pub fn download_size(package: &str) -> Option<u64> {
+    let output = Command::new("pacman")
+        .args(["-Sp", "--print-format", "%s"])
+        .arg(package)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|l| l.trim().parse::<u64>().ok())
+            .sum(),
+    )
+}
+
+/// Every package `pacman -Rsc` would remove along with `pkgs`: the
+/// packages themselves, everything depending on them and the dependencies
+/// left unneeded. Falls back to `pkgs` if pacman can't resolve them.
+pub fn removal_set(pkgs: &[String]) -> Vec<String> {
+    if pkgs.is_empty() {
+        return Vec::new();
+    }
+    let output = Command::new("pacman")
+        .args(["-Rsc", "--print", "--print-format", "%n"])
+        .args(pkgs)
+        .output();
+    match output {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect(),
+        _ => pkgs.to_vec(),
+    }
+}
+
+/// Every installed package with its version (`pacman -Q`)
+pub fn installed_versions() -> std::collections::BTreeMap<String, String> {
+    Command::new("pacman")
+        .arg("-Q")
+        .output()
+        .map(|o| {
+            String::from_utf8_lossy(&o.stdout)
+                .lines()
+                .filter_map(|l| l.split_once(' '))
+                .map(|(name, version)| (name.to_string(), version.trim().to_string()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Package name and version from `pacman -Qo` output such as
+/// `/usr/bin/ls is owned by coreutils 9.4-3`
+pub fn parse_owner(output: &str) -> Option<(String, String)> {
+    let (_, owner) = output.lines().next()?.split_once(" is owned by ")?;
+    let (name, version) = owner.trim().rsplit_once(' ')?;
+    Some((name.to_string(), version.to_string()))
+}
+
+/// The installed package owning `path` (`pacman -Qo`)
+pub fn owner(path: &std::path::Path) -> Option<(String, String)> {
+    let output = Command::new("pacman").arg("-Qo").arg(path).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_owner(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Files in a package archive (`pacman -Qlpq`)
+pub fn package_file_list(package: &std::path::Path) -> Vec<String> {
+    Command::new("pacman")
+        .arg("-Qlpq")
+        .arg(package)
+        .output()
+        .map(|o| {
+            String::from_utf8_lossy(&o.stdout)
+                .lines()
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Repository and version from `pacman -Si` output
+pub fn parse_sync_info(output: &str) -> Option<(String, String)> {
+    let field = |name: &str| {
+        output
+            .lines()
+            .find(|l| l.starts_with(name))
+            .and_then(|l| l.split_once(':'))
+            .map(|(_, v)| v.trim().to_string())
+    };
+    Some((field("Repository")?, field("Version")?))
+}
+
+/// The sync repository that has `pkg` and its version there (`pacman -Si`)
+pub fn sync_info(pkg: &str) -> Option<(String, String)> {
+    let output = Command::new("pacman").arg("-Si").arg(pkg).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_sync_info(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Files of an installed package (`pacman -Qlq`)
+pub fn file_list(pkg: &str) -> Vec<String> {
+    Command::new("pacman")
+        .arg("-Qlq")
+        .arg(pkg)
+        .output()
+        .map(|o| {
+            String::from_utf8_lossy(&o.stdout)
+                .lines()
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Compare installed files against the package database (`pacman -Qkk`),
+/// for `pkg` or every package. Returns the warnings and summary lines.
+pub fn check_files(pkg: Option<&str>) -> String {
+    let mut cmd = Command::new("pacman");
+    cmd.arg("-Qkk");
+    if let Some(pkg) = pkg {
+        cmd.arg(pkg);
+    }
+    cmd.output()
+        .map(|o| {
+            format!(
+                "{}{}",
+                String::from_utf8_lossy(&o.stderr),
+                String::from_utf8_lossy(&o.stdout)
+            )
+        })
+        .unwrap_or_default()
+}
+
+/// Packages installed as dependencies (`pacman -Qdq`)
+pub fn installed_as_deps() -> std::collections::BTreeSet<String> {
+    query_names(&["-Qdq"])
+}
+
+/// Dependencies nothing installed requires or optionally requires anymore
+/// (`pacman -Qdtq`)
+pub fn orphans() -> std::collections::BTreeSet<String> {
+    query_names(&["-Qdtq"])
+}
+
+fn query_names(args: &[&str]) -> std::collections::BTreeSet<String> {
+    Command::new("pacman")
+        .args(args)
+        .output()
+        .map(|o| {
+            String::from_utf8_lossy(&o.stdout)
+                .lines()
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// A package file for `name` at `version` in the pacman cache or `extra_dir`
+pub fn cached_package(
+    name: &str,
+    version: &str,
+    extra_dir: &std::path::Path,
+) -> Option<std::path::PathBuf> {
+    let prefix = format!("{}-{}-", name, version);
+    [std::path::Path::new("/var/cache/pacman/pkg"), extra_dir]
+        .iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| {
+            let file = path.file_name().and_then(|f| f.to_str()).unwrap_or("");
+            file.starts_with(&prefix)
+                && !file.ends_with(".sig")
+                && crate::core::package_file_name(file) == Some(name)
+        })
+}
+
+/// Files owned by an installed package (`pacman -Qlq`)
+pub fn owned_files(pkg: &str) -> Vec<String> {
+    Command::new("pacman")
+        .arg("-Qlq")
+        .arg(pkg)
+        .output()
+        .map(|o| {
+            String::from_utf8_lossy(&o.stdout)
+                .lines()
+                .map(|l| l.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 // No async/parallel flows in pacman.rs; nothing to change for prompt 2
+
+/// Compare two version segments like libalpm's `rpmvercmp`: runs of
+/// digits compare numerically, runs of letters alphabetically, a number
+/// beats letters, and leftover letters (`1.0beta`) lose to nothing at all
+fn rpmvercmp(a: &str, b: &str) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+    if a == b {
+        return Ordering::Equal;
+    }
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let (mut one, mut two) = (0, 0);
+    while one < a.len() || two < b.len() {
+        let (start1, start2) = (one, two);
+        while one < a.len() && !a[one].is_ascii_alphanumeric() {
+            one += 1;
+        }
+        while two < b.len() && !b[two].is_ascii_alphanumeric() {
+            two += 1;
+        }
+        if one >= a.len() || two >= b.len() {
+            break;
+        }
+        // More separators mean a newer version: 1.0.1 > 1.0a
+        if one - start1 != two - start2 {
+            return (one - start1).cmp(&(two - start2));
+        }
+        let numeric = a[one].is_ascii_digit();
+        let same_kind = |c: &u8| {
+            if numeric {
+                c.is_ascii_digit()
+            } else {
+                c.is_ascii_alphabetic()
+            }
+        };
+        let end1 = one + a[one..].iter().take_while(|c| same_kind(c)).count();
+        let end2 = two + b[two..].iter().take_while(|c| same_kind(c)).count();
+        if end2 == two {
+            // Different kinds of segment: numbers are newer
+            return if numeric {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            };
+        }
+        let (mut seg1, mut seg2) = (&a[one..end1], &b[two..end2]);
+        if numeric {
+            let trim = |s: &[u8]| -> usize { s.iter().take_while(|c| **c == b'0').count() };
+            seg1 = &seg1[trim(seg1)..];
+            seg2 = &seg2[trim(seg2)..];
+            if seg1.len() != seg2.len() {
+                return seg1.len().cmp(&seg2.len());
+            }
+        }
+        match seg1.cmp(seg2) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        one = end1;
+        two = end2;
+    }
+    match (a.get(one), b.get(two)) {
+        (None, None) => Ordering::Equal,
+        (None, Some(c)) if !c.is_ascii_alphabetic() => Ordering::Less,
+        (Some(c), _) if c.is_ascii_alphabetic() => Ordering::Less,
+        _ => Ordering::Greater,
+    }
+}
+
+/// Split `[epoch:]pkgver[-pkgrel]`, with a missing epoch being 0
+fn split_version(version: &str) -> (&str, &str, Option<&str>) {
+    let (epoch, rest) = match version.split_once(':') {
+        Some((epoch, rest)) if epoch.bytes().all(|c| c.is_ascii_digit()) => {
+            (if epoch.is_empty() { "0" } else { epoch }, rest)
+        }
+        _ => ("0", version),
+    };
+    match rest.rsplit_once('-') {
+        Some((pkgver, pkgrel)) => (epoch, pkgver, Some(pkgrel)),
+        None => (epoch, rest, None),
+    }
+}
+
+/// Compare two package versions the way `vercmp` does: epoch first, then
+/// pkgver, then pkgrel when both have one
+pub fn vercmp(a: &str, b: &str) -> std::cmp::Ordering {
+    if a == b {
+        return std::cmp::Ordering::Equal;
+    }
+    let (epoch1, ver1, rel1) = split_version(a);
+    let (epoch2, ver2, rel2) = split_version(b);
+    rpmvercmp(epoch1, epoch2)
+        .then_with(|| rpmvercmp(ver1, ver2))
+        .then_with(|| match (rel1, rel2) {
+            (Some(rel1), Some(rel2)) => rpmvercmp(rel1, rel2),
+            _ => std::cmp::Ordering::Equal,
+        })
+}
+
+/// Whether `remote` is a strictly newer version than the installed `local`
+pub fn is_newer(remote: &str, local: &str) -> bool {
+    vercmp(remote, local) == std::cmp::Ordering::Greater
+}
@@ -14,6 +14,13 @@ pub struct ProfileConfig {
     pub fast_mode: Option<bool>,
     pub strict_signatures: Option<bool>,
     pub auto_resolve_deps: Option<bool>,
+    /// Keyserver for fetching tap publisher keys, unless `--gpg-keyserver` is given
+    #[serde(default)]
+    pub gpg_keyserver: Option<String>,
+    /// makepkg.conf used for AUR builds instead of `/etc/makepkg.conf`
+    /// (`~` and `$VAR` are expanded)
+    #[serde(default)]
+    pub makepkg_conf: Option<PathBuf>,
 }
 
 impl Default for ProfileConfig {
@@ -28,10 +35,37 @@ impl Default for ProfileConfig {
             fast_mode: Some(false),
             strict_signatures: Some(false),
             auto_resolve_deps: Some(true),
+            gpg_keyserver: None,
+            makepkg_conf: None,
         }
     }
 }
 
+impl ProfileConfig {
+    /// The makepkg.conf this profile builds with, expanded, if it sets one
+    pub fn makepkg_conf_path(&self) -> Option<PathBuf> {
+        let raw = self.makepkg_conf.as_ref()?.to_string_lossy().into_owned();
+        let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("/tmp"));
+        Some(crate::config::expand_path(&raw, &home, |name| {
+            std::env::var(name).ok()
+        }))
+    }
+
+    /// Check the settings that point at files
+    pub fn validate(&self) -> Result<()> {
+        if let Some(path) = self.makepkg_conf_path()
+            && !path.is_file()
+        {
+            return Err(anyhow::anyhow!(
+                "profile {}: makepkg_conf {} does not exist",
+                self.name,
+                path.display()
+            ));
+        }
+        Ok(())
+    }
+}
+
 pub struct ProfileManager {
     profiles_dir: PathBuf,
     active_profile: String,
@@ -49,10 +83,15 @@ impl ProfileManager {
             .unwrap_or_else(|| PathBuf::from("/tmp"))
             .join("reap/profiles");
         let _ = fs::create_dir_all(&profiles_dir);
+        let active_profile = fs::read_to_string(profiles_dir.join(".active"))
+            .map(|name| name.trim().to_string())
+            .ok()
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "default".to_string());
 
         Self {
             profiles_dir,
-            active_profile: "default".to_string(),
+            active_profile,
         }
     }
 
@@ -72,9 +111,17 @@ impl ProfileManager {
 
         let content = fs::read_to_string(profile_path)?;
         let profile: ProfileConfig = toml::from_str(&content)?;
+        profile.validate()?;
         Ok(profile)
     }
     pub fn switch_profile(&mut self, name: &str) -> Result<()> {
+        if name != "default" && !self.profiles_dir.join(format!("{}.toml", name)).exists() {
+            return Err(anyhow::anyhow!(
+                "No profile named {} (create it with `reap profile create {}`)",
+                name,
+                name
+            ));
+        }
         let _profile = self.load_profile(name)?;
         self.active_profile = name.to_string();
 
@@ -86,6 +133,11 @@ impl ProfileManager {
         Ok(())
     }
 
+    /// Name of the profile selected with `reap profile use`
+    pub fn active_profile_name(&self) -> &str {
+        &self.active_profile
+    }
+
     pub fn get_active_profile(&self) -> Result<ProfileConfig> {
         self.load_profile(&self.active_profile)
     }
@@ -95,12 +147,11 @@ impl ProfileManager {
 
         if let Ok(entries) = fs::read_dir(&self.profiles_dir) {
             for entry in entries.flatten() {
-                if let Some(ext) = entry.path().extension() {
-                    if ext == "toml" {
-                        if let Some(name) = entry.path().file_stem() {
-                            profiles.push(name.to_string_lossy().to_string());
-                        }
-                    }
+                if let Some(ext) = entry.path().extension()
+                    && ext == "toml"
+                    && let Some(name) = entry.path().file_stem()
+                {
+                    profiles.push(name.to_string_lossy().to_string());
                 }
             }
         }
@@ -118,10 +169,62 @@ impl ProfileManager {
             fs::remove_file(profile_path)?;
             println!("[profiles] Deleted profile: {}", name);
         }
+        // Deleting the active profile falls back to the default one
+        if name == self.active_profile {
+            let _ = fs::remove_file(self.profiles_dir.join(".active"));
+        }
         Ok(())
     }
 }
 
+/// Settings in effect under `profile`, falling back to the global config and
+/// built-in defaults for anything the profile leaves unset
+pub fn effective_settings(
+    profile: &ProfileConfig,
+    global: &crate::config::GlobalConfig,
+) -> Vec<(&'static str, String)> {
+    let backend_order = if profile.backend_order.is_empty() {
+        &global.backend_order
+    } else {
+        &profile.backend_order
+    };
+    vec![
+        ("backend_order", backend_order.join(", ")),
+        (
+            "parallel_jobs",
+            profile.parallel_jobs.unwrap_or(4).to_string(),
+        ),
+        ("fast_mode", profile.fast_mode.unwrap_or(false).to_string()),
+        (
+            "strict_signatures",
+            profile.strict_signatures.unwrap_or(false).to_string(),
+        ),
+        (
+            "auto_resolve_deps",
+            profile
+                .auto_resolve_deps
+                .unwrap_or(global.auto_resolve_deps)
+                .to_string(),
+        ),
+        (
+            "gpg_keyserver",
+            profile
+                .gpg_keyserver
+                .clone()
+                .unwrap_or_else(|| "hkps://keys.openpgp.org".to_string()),
+        ),
+        (
+            "makepkg_conf",
+            profile
+                .makepkg_conf_path()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|| "/etc/makepkg.conf".to_string()),
+        ),
+        ("pinned_packages", profile.pinned_packages.join(", ")),
+        ("ignored_packages", profile.ignored_packages.join(", ")),
+    ]
+}
+
 // Predefined profiles for common use cases
 pub fn create_developer_profile() -> ProfileConfig {
     ProfileConfig {
@@ -0,0 +1,175 @@
+use crate::core::ReapError;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// One `pkgname` section of a .SRCINFO, with the keys it overrides
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SrcInfoPackage {
+    pub name: String,
+    pub fields: BTreeMap<String, Vec<String>>,
+}
+
+/// Parsed .SRCINFO: the `pkgbase` section and one section per package it
+/// builds. Arch-specific keys such as `depends_x86_64` are kept as is.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SrcInfo {
+    pub pkgbase: String,
+    pub base: BTreeMap<String, Vec<String>>,
+    pub packages: Vec<SrcInfoPackage>,
+}
+
+impl SrcInfo {
+    /// Parse .SRCINFO text (`makepkg --printsrcinfo` output)
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut info = SrcInfo::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("line {}: expected `key = value`", number + 1));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            match key {
+                "pkgbase" => info.pkgbase = value.to_string(),
+                "pkgname" => info.packages.push(SrcInfoPackage {
+                    name: value.to_string(),
+                    fields: BTreeMap::new(),
+                }),
+                _ if info.pkgbase.is_empty() => {
+                    return Err(format!("line {}: {} before pkgbase", number + 1, key));
+                }
+                _ => {
+                    let fields = match info.packages.last_mut() {
+                        Some(package) => &mut package.fields,
+                        None => &mut info.base,
+                    };
+                    let values = fields.entry(key.to_string()).or_default();
+                    // An empty value clears what the package inherits
+                    if !value.is_empty() {
+                        values.push(value.to_string());
+                    }
+                }
+            }
+        }
+        if info.pkgbase.is_empty() {
+            return Err("no pkgbase".to_string());
+        }
+        Ok(info)
+    }
+
+    /// First value of a `pkgbase` key, e.g. `pkgver`
+    pub fn value(&self, key: &str) -> Option<&str> {
+        self.base.get(key)?.first().map(String::as_str)
+    }
+
+    /// `[epoch:]pkgver-pkgrel`
+    pub fn version(&self) -> Option<String> {
+        let version = format!("{}-{}", self.value("pkgver")?, self.value("pkgrel")?);
+        Some(match self.value("epoch") {
+            Some(epoch) if epoch != "0" => format!("{}:{}", epoch, version),
+            _ => version,
+        })
+    }
+
+    /// Names of the packages this pkgbase builds
+    pub fn pkgnames(&self) -> Vec<&str> {
+        self.packages.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    /// Values of `key` for package `pkg` on `arch`: the package's own
+    /// values when it overrides the key, else the pkgbase's, plus the
+    /// `<key>_<arch>` values
+    pub fn get(&self, pkg: &str, key: &str, arch: &str) -> Vec<String> {
+        let package = self.packages.iter().find(|p| p.name == pkg);
+        let lookup = |key: &str| {
+            package
+                .and_then(|p| p.fields.get(key))
+                .or_else(|| self.base.get(key))
+                .cloned()
+                .unwrap_or_default()
+        };
+        let mut values = lookup(key);
+        values.extend(lookup(&format!("{}_{}", key, arch)));
+        values
+    }
+
+    /// Readable summary of the pkgbase and every package, for `arch`
+    pub fn render(&self, arch: &str) -> String {
+        use std::fmt::Write;
+        let mut out = String::new();
+        let field = |out: &mut String, label: &str, values: &[String]| {
+            if !values.is_empty() {
+                let _ = writeln!(out, "  {:<13} {}", format!("{}:", label), values.join("  "));
+            }
+        };
+        let _ = writeln!(
+            out,
+            "{} {}",
+            self.pkgbase,
+            self.version().unwrap_or_else(|| "?".to_string())
+        );
+        let first = self.packages.first().map(|p| p.name.as_str()).unwrap_or("");
+        for (label, key) in [
+            ("Description", "pkgdesc"),
+            ("URL", "url"),
+            ("Arch", "arch"),
+            ("License", "license"),
+        ] {
+            field(&mut out, label, &self.get(first, key, arch));
+        }
+        for (label, key) in [
+            ("Make deps", "makedepends"),
+            ("Check deps", "checkdepends"),
+            ("Sources", "source"),
+        ] {
+            field(&mut out, label, &self.get("", key, arch));
+        }
+        for package in &self.packages {
+            let _ = writeln!(out, "package {}", package.name);
+            if self.packages.len() > 1 {
+                field(
+                    &mut out,
+                    "Description",
+                    &self.get(&package.name, "pkgdesc", arch),
+                );
+            }
+            for (label, key) in [
+                ("Depends", "depends"),
+                ("Optional", "optdepends"),
+                ("Provides", "provides"),
+                ("Conflicts", "conflicts"),
+                ("Replaces", "replaces"),
+            ] {
+                field(&mut out, label, &self.get(&package.name, key, arch));
+            }
+        }
+        out
+    }
+}
+
+/// The .SRCINFO of the PKGBUILD in `build_dir`, generated with
+/// `makepkg --printsrcinfo` when the directory has none
+pub fn parse_srcinfo(build_dir: &Path) -> Result<SrcInfo, ReapError> {
+    let text = match fs::read_to_string(build_dir.join(".SRCINFO")) {
+        Ok(text) => text,
+        Err(_) => {
+            let output = Command::new("makepkg")
+                .arg("--printsrcinfo")
+                .current_dir(build_dir)
+                .output()?;
+            if !output.status.success() {
+                return Err(ReapError::CommandFailed(format!(
+                    "makepkg --printsrcinfo failed in {}",
+                    build_dir.display()
+                )));
+            }
+            String::from_utf8_lossy(&output.stdout).into_owned()
+        }
+    };
+    SrcInfo::parse(&text)
+        .map_err(|e| ReapError::CommandFailed(format!("{}: {}", build_dir.display(), e)))
+}
@@ -0,0 +1,271 @@
+use crate::core::{InstallOptions, PackageListEntry, ReapError, confirm_proceed};
+use crate::profiles::{ProfileConfig, ProfileManager};
+use crate::tap::Tap;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// A tap as recorded in an exported state file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TapState {
+    pub name: String,
+    pub url: String,
+    pub priority: u32,
+}
+
+impl From<&Tap> for TapState {
+    fn from(tap: &Tap) -> Self {
+        Self {
+            name: tap.name.clone(),
+            url: tap.url.clone(),
+            priority: tap.priority,
+        }
+    }
+}
+
+/// What importing a tap would do, given the tap already configured under
+/// the same name (if any)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TapImport {
+    Add,
+    Unchanged,
+    /// A tap with this name points somewhere else
+    Conflict {
+        existing_url: String,
+    },
+}
+
+pub fn classify_tap(tap: &TapState, existing: Option<&Tap>) -> TapImport {
+    match existing {
+        None => TapImport::Add,
+        Some(e) if e.url == tap.url && e.priority == tap.priority => TapImport::Unchanged,
+        Some(e) if e.url == tap.url => TapImport::Add,
+        Some(e) => TapImport::Conflict {
+            existing_url: e.url.clone(),
+        },
+    }
+}
+
+/// Everything `reap export` writes to move a setup to another machine
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReapState {
+    #[serde(default)]
+    pub exported_at: Option<String>,
+    #[serde(default)]
+    pub active_profile: Option<String>,
+    /// Explicitly installed AUR, tap and Flatpak packages as package list
+    /// lines (`aur:yay`, `tap:ghost:ghostctl`, ...)
+    #[serde(default)]
+    pub packages: Vec<String>,
+    /// Lines of `pinned.toml`
+    #[serde(default)]
+    pub pinned: Vec<String>,
+    #[serde(default)]
+    pub taps: Vec<TapState>,
+    #[serde(default)]
+    pub profiles: Vec<ProfileConfig>,
+    /// The contents of `reap.toml`
+    #[serde(default)]
+    pub config: Option<toml::Table>,
+}
+
+/// reap.toml keys holding credentials, which stay on this machine
+pub const SECRET_CONFIG_KEYS: &[&str] = &["aur_cookie"];
+
+/// `config` without the [`SECRET_CONFIG_KEYS`]
+pub fn without_secrets(mut config: toml::Table) -> toml::Table {
+    for key in SECRET_CONFIG_KEYS {
+        config.remove(*key);
+    }
+    config
+}
+
+/// Write `text` to `path`, readable only by the user
+fn write_private(path: &Path, text: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    // mode only applies to new files
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    file.write_all(text.as_bytes())
+}
+
+impl ReapState {
+    /// Collect the current machine's state. Secrets in reap.toml are left out.
+    pub fn collect() -> Self {
+        let manager = ProfileManager::new();
+        let profiles = manager
+            .list_profiles()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|name| manager.load_profile(name).ok())
+            .collect();
+        let config = fs::read_to_string(crate::config::config_path())
+            .ok()
+            .and_then(|s| s.parse::<toml::Table>().ok())
+            .map(without_secrets);
+        let packages = crate::core::installed_package_list()
+            .into_iter()
+            .filter(|e| e.source.as_deref() != Some("pacman"))
+            .map(|e| e.to_line())
+            .collect();
+        Self {
+            exported_at: Some(chrono::Local::now().to_rfc3339()),
+            active_profile: Some(manager.active_profile_name().to_string()),
+            packages,
+            pinned: crate::utils::pinned_lines(),
+            taps: crate::tap::discover_taps()
+                .iter()
+                .map(TapState::from)
+                .collect(),
+            profiles,
+            config,
+        }
+    }
+
+    pub fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string_pretty(self)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(text)
+    }
+}
+
+/// `reap export <file>`: write the current state to `path`
+pub fn handle_export(path: &Path) -> Result<(), ReapError> {
+    let state = ReapState::collect();
+    let text = state
+        .to_toml()
+        .map_err(|e| ReapError::CommandFailed(e.to_string()))?;
+    fs::write(path, text)?;
+    println!(
+        "[reap] Exported {} packages, {} taps, {} profiles and {} pins to {}",
+        state.packages.len(),
+        state.taps.len(),
+        state.profiles.len(),
+        state.pinned.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Ask before replacing something that already exists. Without prompts
+/// (`--noconfirm`) the existing setup is kept.
+fn confirm_replace(confirm: bool, message: &str) -> bool {
+    confirm && crate::interactive::InteractiveManager::confirm_action(message, false)
+}
+
+/// `reap import <file>`: add the taps, restore the config, profiles and
+/// pins, then install the packages from an exported state
+pub async fn handle_import(
+    path: &Path,
+    confirm: bool,
+    opts: &InstallOptions,
+) -> Result<(), ReapError> {
+    let state = ReapState::from_toml(&fs::read_to_string(path)?)
+        .map_err(|e| ReapError::CommandFailed(format!("{}: {}", path.display(), e)))?;
+
+    let existing = crate::tap::discover_taps();
+    for tap in &state.taps {
+        let current = existing.iter().find(|t| t.name == tap.name);
+        let add = match classify_tap(tap, current) {
+            TapImport::Add => true,
+            TapImport::Unchanged => false,
+            TapImport::Conflict { existing_url } => confirm_replace(
+                confirm,
+                &format!(
+                    "Tap {} already points to {}. Replace it with {}?",
+                    tap.name, existing_url, tap.url
+                ),
+            ),
+        };
+        if add {
+            crate::tap::add_or_update_tap(&tap.name, &tap.url, Some(tap.priority as u8), true);
+            println!("[reap] Added tap {} ({})", tap.name, tap.url);
+        }
+    }
+
+    if let Some(config) = &state.config {
+        let config_path = crate::config::config_path();
+        let current = fs::read_to_string(&config_path).ok();
+        let current_table = current.as_ref().and_then(|c| c.parse::<toml::Table>().ok());
+        // This machine's secrets are kept, the export never carries them
+        let mut config = config.clone();
+        for key in SECRET_CONFIG_KEYS {
+            match current_table.as_ref().and_then(|t| t.get(*key)) {
+                Some(value) => config.insert(key.to_string(), value.clone()),
+                None => config.remove(*key),
+            };
+        }
+        let text =
+            toml::to_string_pretty(&config).map_err(|e| ReapError::CommandFailed(e.to_string()))?;
+        let write = match &current {
+            None => true,
+            Some(_) if current_table.as_ref() == Some(&config) => false,
+            Some(_) => confirm_replace(
+                confirm,
+                &format!(
+                    "Replace {} with the imported config?",
+                    config_path.display()
+                ),
+            ),
+        };
+        if write {
+            if let Some(parent) = config_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            if current.is_some() {
+                fs::copy(&config_path, config_path.with_extension("toml.bak"))?;
+            }
+            write_private(&config_path, &text)?;
+            println!("[reap] Restored {}", config_path.display());
+        }
+    }
+
+    let mut manager = ProfileManager::new();
+    let known = manager.list_profiles().unwrap_or_default();
+    for profile in &state.profiles {
+        let replace = !known.contains(&profile.name)
+            || confirm_replace(
+                confirm,
+                &format!("Profile {} already exists. Replace it?", profile.name),
+            );
+        if replace && let Err(e) = manager.create_profile(profile) {
+            eprintln!("[reap] Failed to restore profile {}: {}", profile.name, e);
+        }
+    }
+    if let Some(active) = &state.active_profile
+        && active != manager.active_profile_name()
+        && let Err(e) = manager.switch_profile(active)
+    {
+        eprintln!("[reap] Failed to activate profile {}: {}", active, e);
+    }
+
+    let pinned = crate::utils::pinned_lines();
+    for pin in state.pinned.iter().filter(|p| !pinned.contains(p)) {
+        if let Err(e) = crate::utils::pin_package(pin) {
+            eprintln!("[reap] Failed to pin {}: {}", pin, e);
+        }
+    }
+
+    let mut entries = Vec::new();
+    for line in &state.packages {
+        match PackageListEntry::parse(line) {
+            Ok(Some(entry)) => entries.push(entry),
+            Ok(None) => {}
+            Err(e) => eprintln!("[reap] Skipping package '{}': {}", line, e),
+        }
+    }
+    if !confirm_proceed(confirm, "Install the imported packages?") {
+        return Err(ReapError::UserAborted);
+    }
+    let report = crate::core::install_package_list(entries, false, opts).await?;
+    report.print("Install");
+    report.into_result()
+}
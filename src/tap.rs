@@ -50,46 +50,45 @@ pub fn discover_taps() -> Vec<Tap> {
         if let Ok(entries) = fs::read_dir(&dir) {
             for entry in entries.flatten() {
                 let path = entry.path();
-                if path.extension().and_then(|e| e.to_str()) == Some("toml") {
-                    if let Ok(toml) = fs::read_to_string(&path) {
-                        if let Ok(val) = toml.parse::<Value>() {
-                            let name = val
-                                .as_table()
-                                .and_then(|t| t.get("name"))
-                                .and_then(|v| v.as_str())
-                                .unwrap_or("")
-                                .to_string();
-                            let url = val
-                                .as_table()
-                                .and_then(|t| t.get("url"))
-                                .and_then(|v| v.as_str())
-                                .unwrap_or("")
-                                .to_string();
-                            let priority = val
-                                .as_table()
-                                .and_then(|t| t.get("priority"))
-                                .and_then(|v| v.as_integer())
-                                .unwrap_or(50) as u32;
-                            let enabled = val
-                                .as_table()
-                                .and_then(|t| t.get("enabled"))
-                                .and_then(|v| v.as_bool())
-                                .unwrap_or(true);
-                            if !name.is_empty() && !url.is_empty() && enabled {
-                                taps.push(Tap {
-                                    name,
-                                    url,
-                                    priority,
-                                    enabled,
-                                });
-                            }
-                        }
+                if path.extension().and_then(|e| e.to_str()) == Some("toml")
+                    && let Ok(toml) = fs::read_to_string(&path)
+                    && let Ok(val) = toml.parse::<Value>()
+                {
+                    let name = val
+                        .as_table()
+                        .and_then(|t| t.get("name"))
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("")
+                        .to_string();
+                    let url = val
+                        .as_table()
+                        .and_then(|t| t.get("url"))
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("")
+                        .to_string();
+                    let priority = val
+                        .as_table()
+                        .and_then(|t| t.get("priority"))
+                        .and_then(|v| v.as_integer())
+                        .unwrap_or(50) as u32;
+                    let enabled = val
+                        .as_table()
+                        .and_then(|t| t.get("enabled"))
+                        .and_then(|v| v.as_bool())
+                        .unwrap_or(true);
+                    if !name.is_empty() && !url.is_empty() && enabled {
+                        taps.push(Tap {
+                            name,
+                            url,
+                            priority,
+                            enabled,
+                        });
                     }
                 }
             }
         }
     }
-    taps.sort_by(|a, b| b.priority.cmp(&a.priority));
+    taps.sort_by_key(|t| std::cmp::Reverse(t.priority));
     taps
 }
 
@@ -102,22 +101,124 @@ pub fn find_tap_for_pkg(pkg: &str, taps: &[Tap], forced: Option<&str>) -> Option
     }
 }
 
-/// Ensures that a tap is cloned to the local machine, pulling updates if it already exists.
-pub fn ensure_tap_cloned(tap: &Tap) -> PathBuf {
-    let cache_dir = dirs::cache_dir()
+/// Where the local copy of a tap is kept
+pub fn tap_clone_path(name: &str) -> PathBuf {
+    dirs::cache_dir()
         .unwrap_or_else(|| PathBuf::from("/tmp"))
-        .join("reap/taps");
-    let tap_path = cache_dir.join(&tap.name);
+        .join("reap/taps")
+        .join(name)
+}
+
+/// Clone depth for taps: shallow unless `tap_full_clone` is set
+fn configured_depth() -> Option<u32> {
+    let full = crate::config::GlobalConfig::load()
+        .tap_full_clone
+        .unwrap_or(false);
+    (!full).then_some(1)
+}
+
+/// `git clone` arguments for `depth` commits of history, or all of it
+pub fn clone_args(depth: Option<u32>) -> Vec<String> {
+    let mut args = vec!["clone".to_string()];
+    if let Some(depth) = depth {
+        args.extend([
+            "--depth".to_string(),
+            depth.to_string(),
+            "--single-branch".to_string(),
+        ]);
+    }
+    args
+}
+
+/// Size of everything under `path`, in bytes
+pub fn dir_size(path: &std::path::Path) -> u64 {
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| match entry.file_type() {
+                    Ok(t) if t.is_dir() => dir_size(&entry.path()),
+                    Ok(_) => entry.metadata().map(|m| m.len()).unwrap_or(0),
+                    Err(_) => 0,
+                })
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+fn clone_tap_to(tap: &Tap, path: &std::path::Path, depth: Option<u32>) -> bool {
+    let cloned = Command::new("git")
+        .args(clone_args(depth))
+        .arg(&tap.url)
+        .arg(path)
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false);
+    if cloned {
+        let history = match depth {
+            Some(depth) => format!("last {} commit(s)", depth),
+            None => "full history".to_string(),
+        };
+        println!(
+            "[tap] Cloned {} ({}, {:.1} MiB)",
+            tap.name,
+            history,
+            dir_size(path) as f64 / (1024.0 * 1024.0)
+        );
+    }
+    cloned
+}
+
+/// Ensures that a tap is cloned to the local machine. New clones are shallow
+/// unless `tap_full_clone` is set.
+pub fn ensure_tap_cloned(tap: &Tap) -> PathBuf {
+    let tap_path = tap_clone_path(&tap.name);
     if !tap_path.exists() {
-        let _ = std::process::Command::new("git")
-            .arg("clone")
-            .arg(&tap.url)
-            .arg(&tap_path)
-            .status();
+        clone_tap_to(tap, &tap_path, configured_depth());
     }
     tap_path
 }
 
+/// `reap tap clone <name> [--depth N]`: clone a tap again with `depth`
+/// commits of history (all of it for `None`), reporting the size difference
+pub fn reclone_tap(name: &str, depth: Option<u32>) -> Result<(), String> {
+    let tap = discover_taps()
+        .into_iter()
+        .find(|t| t.name == name)
+        .ok_or_else(|| format!("No enabled tap named {}", name))?;
+    let path = tap_clone_path(name);
+    let before = path.exists().then(|| dir_size(&path));
+    let fresh = path.with_extension("reclone");
+    let _ = fs::remove_dir_all(&fresh);
+    if !clone_tap_to(&tap, &fresh, depth) {
+        let _ = fs::remove_dir_all(&fresh);
+        return Err(format!("Failed to clone {}", tap.url));
+    }
+    let _ = fs::remove_dir_all(&path);
+    fs::rename(&fresh, &path).map_err(|e| e.to_string())?;
+    if let Some(before) = before {
+        let after = dir_size(&path);
+        let mib = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+        if after < before {
+            println!(
+                "[tap] {} now takes {:.1} MiB instead of {:.1} MiB (saved {:.1} MiB)",
+                name,
+                mib(after),
+                mib(before),
+                mib(before - after)
+            );
+        } else {
+            println!(
+                "[tap] {} now takes {:.1} MiB (was {:.1} MiB)",
+                name,
+                mib(after),
+                mib(before)
+            );
+        }
+    }
+    Ok(())
+}
+
 /// Gets the file path for a tap's configuration.
 pub fn tap_path(name: &str) -> PathBuf {
     let dir = dirs::config_dir()
@@ -149,23 +250,18 @@ pub fn add_or_update_tap(name: &str, url: &str, priority: Option<u8>, enabled: b
 pub fn remove_tap(name: &str) {
     let path = tap_path(name);
     let _ = fs::remove_file(&path);
-    let cache_dir = dirs::cache_dir()
-        .unwrap_or_else(|| PathBuf::from("/tmp"))
-        .join("reap/taps")
-        .join(name);
-    let _ = fs::remove_dir_all(&cache_dir);
+    let _ = fs::remove_dir_all(tap_clone_path(name));
 }
 
 /// Enables or disables a tap.
 pub fn set_tap_enabled(name: &str, enabled: bool) {
     let path = tap_path(name);
-    if path.exists() {
-        if let Ok(mut doc) = fs::read_to_string(&path)
+    if path.exists()
+        && let Ok(mut doc) = fs::read_to_string(&path)
             .and_then(|s| s.parse::<DocumentMut>().map_err(std::io::Error::other))
-        {
-            doc["enabled"] = value(enabled);
-            let _ = fs::write(&path, doc.to_string());
-        }
+    {
+        doc["enabled"] = value(enabled);
+        let _ = fs::write(&path, doc.to_string());
     }
 }
 
@@ -224,18 +320,8 @@ pub fn sync_enabled_taps() -> Result<(), String> {
         false
     };
     if should_sync {
-        for tap in taps.iter().filter(|t| t.enabled) {
-            let tap_path = ensure_tap_cloned(tap);
-            if tap_path.exists() {
-                // git pull
-                let _ = Command::new("git")
-                    .arg("-C")
-                    .arg(&tap_path)
-                    .arg("pull")
-                    .status();
-            } else {
-                // already cloned by ensure_tap_cloned
-            }
+        for name in pull_taps(&taps) {
+            eprintln!("[tap] Failed to sync {}", name);
         }
         state.last_sync = Some(now);
         let _ = fs::create_dir_all(state_path.parent().unwrap());
@@ -244,6 +330,71 @@ pub fn sync_enabled_taps() -> Result<(), String> {
     Ok(())
 }
 
+/// Pulls each enabled tap and returns the names of those that failed to sync.
+fn pull_taps(taps: &[Tap]) -> Vec<String> {
+    let depth = configured_depth();
+    let mut failed = Vec::new();
+    for tap in taps.iter().filter(|t| t.enabled) {
+        let tap_path = tap_clone_path(&tap.name);
+        if !tap_path.exists() {
+            if !clone_tap_to(tap, &tap_path, depth) {
+                failed.push(tap.name.clone());
+            }
+            continue;
+        }
+        let git = || {
+            let mut cmd = Command::new("git");
+            cmd.arg("-C").arg(&tap_path);
+            cmd
+        };
+        let ok = |cmd: &mut Command| cmd.status().map(|s| s.success()).unwrap_or(false);
+        let synced = match depth {
+            // Keep shallow clones shallow: fetch only the tip and move to it.
+            // Tap clones are read-only mirrors, so nothing local is lost.
+            Some(depth) => {
+                ok(git().args(["fetch", "--depth", &depth.to_string(), "origin", "HEAD"]))
+                    && ok(git().args(["reset", "--hard", "FETCH_HEAD"]))
+            }
+            None => {
+                // A clone made shallow earlier gets its full history back
+                if tap_path.join(".git/shallow").exists() {
+                    let _ = git().args(["fetch", "--unshallow"]).status();
+                }
+                ok(git().arg("pull"))
+            }
+        };
+        if !synced {
+            failed.push(tap.name.clone());
+        }
+    }
+    failed
+}
+
+/// Pulls all enabled taps now, regardless of the sync interval, and returns
+/// the names of the taps that failed to sync. The sync time is only recorded
+/// when every tap synced.
+pub fn force_sync_enabled_taps() -> Vec<String> {
+    let failed = pull_taps(&discover_taps());
+    if !failed.is_empty() {
+        return failed;
+    }
+    let state = SyncState {
+        last_sync: Some(Utc::now()),
+    };
+    let state_path = sync_state_path();
+    let _ = fs::create_dir_all(state_path.parent().unwrap());
+    let _ = fs::write(&state_path, serde_json::to_string(&state).unwrap());
+    failed
+}
+
+/// Returns when enabled taps were last synchronized, if ever.
+pub fn last_sync() -> Option<DateTime<Utc>> {
+    fs::read_to_string(sync_state_path())
+        .ok()
+        .and_then(|s| serde_json::from_str::<SyncState>(&s).ok())
+        .and_then(|s| s.last_sync)
+}
+
 fn sync_state_path() -> PathBuf {
     dirs::data_dir()
         .unwrap_or_else(|| PathBuf::from("/tmp"))
@@ -277,27 +428,26 @@ pub fn search_tap_indexes(query: &str) -> Vec<(String, String, String, String)>
     for tap in taps_sorted.iter().filter(|t| t.enabled) {
         let tap_path = ensure_tap_cloned(tap);
         let index_path = tap_path.join("index.json");
-        if let Ok(data) = fs::read_to_string(&index_path) {
-            if let Ok(json) = serde_json::from_str::<JsonValue>(&data) {
-                if let Some(obj) = json.as_object() {
-                    for (pkg, meta) in obj {
-                        if seen.contains(pkg) {
-                            continue;
-                        }
-                        let desc = meta.get("desc").and_then(|v| v.as_str()).unwrap_or("");
-                        let repo = meta
-                            .get("repo")
-                            .and_then(|v| v.as_str())
-                            .unwrap_or(&tap.name);
-                        results.push((
-                            pkg.clone(),
-                            desc.to_string(),
-                            repo.to_string(),
-                            format!("tap:{}", tap.name),
-                        ));
-                        seen.insert(pkg.clone());
-                    }
+        if let Ok(data) = fs::read_to_string(&index_path)
+            && let Ok(json) = serde_json::from_str::<JsonValue>(&data)
+            && let Some(obj) = json.as_object()
+        {
+            for (pkg, meta) in obj {
+                if seen.contains(pkg) {
+                    continue;
                 }
+                let desc = meta.get("desc").and_then(|v| v.as_str()).unwrap_or("");
+                let repo = meta
+                    .get("repo")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or(&tap.name);
+                results.push((
+                    pkg.clone(),
+                    desc.to_string(),
+                    repo.to_string(),
+                    format!("tap:{}", tap.name),
+                ));
+                seen.insert(pkg.clone());
             }
         }
     }
@@ -308,51 +458,85 @@ pub fn search_tap_indexes(query: &str) -> Vec<(String, String, String, String)>
         .collect()
 }
 
+/// Packages in a tap index whose `provides` metadata satisfies `name`.
+pub fn index_providers(index: &JsonValue, name: &str) -> Vec<String> {
+    let Some(obj) = index.as_object() else {
+        return Vec::new();
+    };
+    obj.iter()
+        .filter(|(_, meta)| {
+            meta.get("provides")
+                .and_then(|v| v.as_array())
+                .is_some_and(|list| {
+                    list.iter()
+                        .filter_map(|p| p.as_str())
+                        .any(|p| crate::core::provides_matches(p, name))
+                })
+        })
+        .map(|(pkg, _)| pkg.clone())
+        .collect()
+}
+
+/// Finds tap packages that provide `name`, as (package, tap) pairs.
+pub fn find_tap_providers(name: &str) -> Vec<(String, Tap)> {
+    let mut providers = Vec::new();
+    for tap in discover_taps().into_iter().filter(|t| t.enabled) {
+        let index_path = ensure_tap_cloned(&tap).join("index.json");
+        if let Ok(data) = fs::read_to_string(&index_path)
+            && let Ok(json) = serde_json::from_str::<JsonValue>(&data)
+        {
+            for pkg in index_providers(&json, name) {
+                providers.push((pkg, tap.clone()));
+            }
+        }
+    }
+    providers
+}
+
 /// Gets publisher information from a tap's publisher.toml file.
 pub fn get_publisher_info(tap: &Tap) -> Option<Publisher> {
     let tap_path = ensure_tap_cloned(tap);
     let pub_path = tap_path.join("publisher.toml");
-    if pub_path.exists() {
-        if let Ok(toml) = fs::read_to_string(&pub_path) {
-            if let Ok(val) = toml.parse::<toml::Value>() {
-                let name = val
-                    .as_table()
-                    .and_then(|t| t.get("name"))
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string();
-                let gpg_key = val
-                    .as_table()
-                    .and_then(|t| t.get("gpg_key"))
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string();
-                let email = val
-                    .as_table()
-                    .and_then(|t| t.get("email"))
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string();
-                let url = val
-                    .as_table()
-                    .and_then(|t| t.get("url"))
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string();
-                let verified = val
-                    .as_table()
-                    .and_then(|t| t.get("verified"))
-                    .and_then(|v| v.as_bool())
-                    .unwrap_or(false);
-                return Some(Publisher {
-                    name,
-                    gpg_key,
-                    email,
-                    url,
-                    verified,
-                });
-            }
-        }
+    if pub_path.exists()
+        && let Ok(toml) = fs::read_to_string(&pub_path)
+        && let Ok(val) = toml.parse::<toml::Value>()
+    {
+        let name = val
+            .as_table()
+            .and_then(|t| t.get("name"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string();
+        let gpg_key = val
+            .as_table()
+            .and_then(|t| t.get("gpg_key"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string();
+        let email = val
+            .as_table()
+            .and_then(|t| t.get("email"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string();
+        let url = val
+            .as_table()
+            .and_then(|t| t.get("url"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string();
+        let verified = val
+            .as_table()
+            .and_then(|t| t.get("verified"))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        return Some(Publisher {
+            name,
+            gpg_key,
+            email,
+            url,
+            verified,
+        });
     }
     None
 }
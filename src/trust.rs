@@ -5,6 +5,7 @@ use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 use std::process::Command;
+use std::time::{Duration, SystemTime};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TrustScore {
@@ -16,6 +17,15 @@ pub struct TrustScore {
     pub last_audit_date: Option<DateTime<Utc>>,
     pub security_flags: Vec<SecurityFlag>,
     pub overall_score: f32, // 0.0 - 10.0
+    /// AUR maintainer, `None` for orphaned or non-AUR packages
+    #[serde(default)]
+    pub maintainer: Option<String>,
+    /// AUR package without a maintainer
+    #[serde(default)]
+    pub orphaned: bool,
+    /// When the AUR package was flagged out-of-date
+    #[serde(default)]
+    pub out_of_date: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,8 +37,32 @@ pub enum SecurityFlag {
     NetworkAccess,
     SystemAccess,
     OutdatedDependencies,
+    Orphaned,
+    FlaggedOutOfDate,
 }
 
+/// Reasons the AUR policy (`block_orphaned_aur`, `warn_out_of_date`) objects
+/// to installing a package with this trust score
+pub fn aur_policy_issues(
+    score: &TrustScore,
+    block_orphaned: bool,
+    warn_out_of_date: bool,
+) -> Vec<String> {
+    let mut issues = Vec::new();
+    if block_orphaned && score.orphaned {
+        issues.push(format!("{} is orphaned (no AUR maintainer)", score.package));
+    }
+    if warn_out_of_date && let Some(flagged) = score.out_of_date {
+        issues.push(format!(
+            "{} was flagged out-of-date on {}",
+            score.package,
+            flagged.format("%Y-%m-%d")
+        ));
+    }
+    issues
+}
+
+#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PackageVerification {
     pub package: String,
@@ -49,6 +83,7 @@ pub struct PgpVerification {
     pub key_expired: bool,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DependencyScan {
     pub total_deps: u32,
@@ -58,30 +93,70 @@ pub struct DependencyScan {
 }
 
 pub struct TrustEngine {
-    #[allow(dead_code)]
     cache_dir: PathBuf,
+    cache_ttl: Duration,
     reputation_db: HashMap<String, f32>,
 }
 
+/// Where computed trust scores are cached
+pub fn trust_cache_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(|| PathBuf::from("/tmp"))
+        .join("reap/trust")
+}
+
+/// Remove all cached trust scores, returning how many were removed
+pub fn clear_trust_cache() -> std::io::Result<usize> {
+    let dir = trust_cache_dir();
+    if !dir.exists() {
+        return Ok(0);
+    }
+    let count = fs::read_dir(&dir)?.filter_map(|e| e.ok()).count();
+    fs::remove_dir_all(&dir)?;
+    Ok(count)
+}
+
 impl TrustEngine {
     pub fn new() -> Self {
-        let cache_dir = dirs::cache_dir()
-            .unwrap_or_else(|| PathBuf::from("/tmp"))
-            .join("reap/trust");
-        let _ = fs::create_dir_all(&cache_dir);
+        let ttl = crate::config::GlobalConfig::load()
+            .trust_cache_ttl
+            .unwrap_or(86400);
+        Self::with_cache_dir(trust_cache_dir(), Duration::from_secs(ttl))
+    }
 
+    /// Engine caching scores in `cache_dir`, reusing them for `cache_ttl`
+    pub fn with_cache_dir(cache_dir: PathBuf, cache_ttl: Duration) -> Self {
+        let _ = fs::create_dir_all(&cache_dir);
         Self {
             cache_dir,
+            cache_ttl,
             reputation_db: HashMap::new(),
         }
     }
 
+    /// Drop the cached score for `pkg` and compute it again
+    pub async fn refresh_trust_score(&self, pkg: &str, source: &crate::core::Source) -> TrustScore {
+        let _ = fs::remove_file(self.cache_file(pkg));
+        self.compute_trust_score(pkg, source).await
+    }
+
     pub async fn compute_trust_score(&self, pkg: &str, source: &crate::core::Source) -> TrustScore {
         // Check cache first
         if let Some(cached_score) = self.get_cached_trust_score(pkg) {
             return cached_score;
         }
+        let score = self.evaluate_trust_score(pkg, source).await;
+        let _ = self.cache_trust_score(&score);
+        score
+    }
 
+    /// Score `pkg` as provided by `source`, bypassing the cache (which keeps
+    /// one score per package, whatever its source)
+    pub async fn evaluate_trust_score(
+        &self,
+        pkg: &str,
+        source: &crate::core::Source,
+    ) -> TrustScore {
         let mut score = TrustScore {
             package: pkg.to_string(),
             signature_valid: false,
@@ -91,6 +166,9 @@ impl TrustEngine {
             last_audit_date: Some(Utc::now()),
             security_flags: Vec::new(),
             overall_score: 5.0,
+            maintainer: None,
+            orphaned: false,
+            out_of_date: None,
         };
 
         // Verify PGP signature
@@ -115,16 +193,25 @@ impl TrustEngine {
             score.security_flags.extend(security_analysis);
         }
 
-        // Get community reputation
-        score.community_votes = self.get_community_votes(pkg, source).await;
+        // Get community reputation and maintenance status
+        if let Some(info) = self.get_aur_info(pkg, source).await {
+            score.community_votes = info["NumVotes"].as_u64().unwrap_or(0) as u32;
+            score.maintainer = info["Maintainer"].as_str().map(String::from);
+            score.orphaned = score.maintainer.is_none();
+            if score.orphaned {
+                score.security_flags.push(SecurityFlag::Orphaned);
+            }
+            score.out_of_date = info["OutOfDate"]
+                .as_i64()
+                .and_then(|ts| DateTime::from_timestamp(ts, 0));
+            if score.out_of_date.is_some() {
+                score.security_flags.push(SecurityFlag::FlaggedOutOfDate);
+            }
+        }
         score.maintainer_reputation = self.get_maintainer_reputation(pkg, source).await;
 
         // Calculate overall score
         score.overall_score = self.calculate_overall_score(&score);
-
-        // Cache the result
-        let _ = self.cache_trust_score(&score);
-
         score
     }
 
@@ -135,11 +222,15 @@ impl TrustEngine {
     ) -> Option<PgpVerification> {
         match source {
             crate::core::Source::Aur => {
+                if crate::aur::is_offline() {
+                    return None;
+                }
                 // Check AUR package signature
                 let output = Command::new("curl")
                     .arg("-s")
                     .arg(format!(
-                        "https://aur.archlinux.org/cgit/aur.git/plain/PKGBUILD.sig?h={}",
+                        "{}/cgit/aur.git/plain/PKGBUILD.sig?h={}",
+                        crate::aur::aur_url(),
                         pkg
                     ))
                     .output()
@@ -222,7 +313,13 @@ impl TrustEngine {
 
     async fn get_pkgbuild(&self, pkg: &str, source: &crate::core::Source) -> Option<String> {
         match source {
-            crate::core::Source::Aur => Some(crate::aur::get_pkgbuild_preview(pkg)),
+            // get_pkgbuild_preview uses the blocking client, keep it off the runtime threads
+            crate::core::Source::Aur => {
+                let pkg = pkg.to_string();
+                tokio::task::spawn_blocking(move || crate::aur::get_pkgbuild_preview(&pkg))
+                    .await
+                    .ok()
+            }
             crate::core::Source::Custom(tap_name) => {
                 if let Some(tap) = self.find_tap_by_name(tap_name) {
                     let tap_path = crate::tap::ensure_tap_cloned(&tap);
@@ -267,23 +364,21 @@ impl TrustEngine {
         flags
     }
 
-    async fn get_community_votes(&self, _pkg: &str, _source: &crate::core::Source) -> u32 {
-        match _source {
-            crate::core::Source::Aur => {
-                // Query AUR API for vote count
-                let url = format!(
-                    "https://aur.archlinux.org/rpc/?v=5&type=info&arg[]={}",
-                    _pkg
-                );
-                if let Ok(resp) = reqwest::get(&url).await {
-                    if let Ok(json) = resp.json::<serde_json::Value>().await {
-                        return json["results"][0]["NumVotes"].as_u64().unwrap_or(0) as u32;
-                    }
-                }
-                0
-            }
-            _ => 0,
+    /// AUR RPC info entry for `pkg`, used for votes, maintainer and out-of-date flag
+    async fn get_aur_info(
+        &self,
+        pkg: &str,
+        source: &crate::core::Source,
+    ) -> Option<serde_json::Value> {
+        if *source != crate::core::Source::Aur {
+            return None;
         }
+        let body = crate::aur::rpc(&format!("type=info&arg[]={}", pkg))
+            .await
+            .ok()?;
+        let mut json = serde_json::from_str::<serde_json::Value>(&body).ok()?;
+        let info = json["results"][0].take();
+        (!info.is_null()).then_some(info)
     }
 
     async fn get_maintainer_reputation(&self, _pkg: &str, _source: &crate::core::Source) -> f32 {
@@ -311,27 +406,51 @@ impl TrustEngine {
         score.clamp(0.0, 10.0)
     }
 
-    #[allow(dead_code)]
+    fn cache_file(&self, pkg: &str) -> PathBuf {
+        self.cache_dir.join(format!("{}.json", pkg))
+    }
+
+    /// Cached score for `pkg`, unless it is older than the cache TTL
     pub fn get_cached_trust_score(&self, pkg: &str) -> Option<TrustScore> {
-        let cache_file = self.cache_dir.join(format!("{}.json", pkg));
-        if cache_file.exists() {
-            if let Ok(content) = fs::read_to_string(cache_file) {
-                return serde_json::from_str(&content).ok();
-            }
+        let cache_file = self.cache_file(pkg);
+        let age = fs::metadata(&cache_file)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())?;
+        if age > self.cache_ttl {
+            return None;
         }
-        None
+        let content = fs::read_to_string(cache_file).ok()?;
+        serde_json::from_str(&content).ok()
     }
 
-    #[allow(dead_code)]
     pub fn cache_trust_score(&self, trust_score: &TrustScore) -> Result<()> {
-        let cache_file = self.cache_dir.join(format!("{}.json", trust_score.package));
+        let cache_file = self.cache_file(&trust_score.package);
         let content = serde_json::to_string_pretty(trust_score)?;
         fs::write(cache_file, content)?;
         Ok(())
     }
 
+    /// Trust badge followed by the AUR maintainer and out-of-date flag, if known
+    pub fn trust_summary(&self, score: &TrustScore) -> String {
+        use colored::Colorize;
+        let mut summary = self.display_trust_badge(score.overall_score);
+        if let Some(maintainer) = &score.maintainer {
+            summary.push_str(&format!(" (maintainer: {})", maintainer));
+        } else if score.orphaned {
+            summary.push_str(&format!(" {}", "(orphaned)".red()));
+        }
+        if let Some(flagged) = score.out_of_date {
+            summary.push_str(&format!(
+                " {}",
+                format!("[flagged out-of-date {}]", flagged.format("%Y-%m-%d")).yellow()
+            ));
+        }
+        summary
+    }
+
     pub fn display_trust_badge(&self, score: f32) -> String {
-        use owo_colors::OwoColorize;
+        use colored::Colorize;
 
         match score {
             s if s >= 8.0 => "🛡️ TRUSTED".green().to_string(),
@@ -5,7 +5,7 @@ use crate::aur::SearchResult;
 use crate::core;
 use crate::profiles::ProfileManager;
 use crate::trust::{TrustEngine, TrustScore};
-use crossterm::event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode};
+use crossterm::event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent};
 use crossterm::execute;
 use crossterm::terminal::{
     EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
@@ -16,7 +16,9 @@ use ratatui::backend::CrosstermBackend;
 use ratatui::prelude::{Constraint, Direction, Layout};
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span};
-use ratatui::widgets::{BarChart, Block, Borders, Clear, Gauge, List, ListItem, Paragraph, Tabs};
+use ratatui::widgets::{
+    BarChart, Block, Borders, Clear, Gauge, List, ListItem, ListState, Paragraph, Tabs,
+};
 use std::collections::HashMap;
 use std::io;
 use std::sync::{Arc, Mutex};
@@ -169,6 +171,7 @@ fn search_pacman_like(query: &str, repo: Option<&str>) -> Vec<SearchResult> {
                     version: version.to_string(),
                     description: desc,
                     source: crate::core::Source::Pacman,
+                    votes: None,
                 });
             }
         }
@@ -247,29 +250,237 @@ impl InstallQueue {
     }
 }
 
+/// Lines kept in the log scrollback unless configured otherwise
+pub const DEFAULT_SCROLLBACK: usize = 1000;
+
 pub struct LogPane {
     lines: Arc<Mutex<Vec<String>>>,
+    scrollback: usize,
+    view: Mutex<LogView>,
+    /// A build prompt waiting for the user's answer
+    input: Mutex<Option<PendingInput>>,
+}
+
+/// A question from a running build and the answer typed so far
+struct PendingInput {
+    question: String,
+    answer: String,
+    reply: std::sync::mpsc::Sender<String>,
+}
+
+/// Navigation state for the log view
+struct LogView {
+    /// Index of the first visible line when not following
+    offset: usize,
+    /// Keep the newest output in view as lines arrive
+    follow: bool,
+    /// Number of lines the last render had room for
+    page: usize,
+    /// Query being typed after `/`, if search is active
+    search: Option<String>,
+    last_query: String,
 }
 
 impl LogPane {
     pub fn new() -> Self {
+        Self::with_scrollback(DEFAULT_SCROLLBACK)
+    }
+    /// Create a pane that keeps at most `scrollback` lines
+    pub fn with_scrollback(scrollback: usize) -> Self {
         Self {
             lines: Arc::new(Mutex::new(Vec::new())),
+            scrollback: scrollback.max(1),
+            view: Mutex::new(LogView {
+                offset: 0,
+                follow: true,
+                page: 10,
+                search: None,
+                last_query: String::new(),
+            }),
+            input: Mutex::new(None),
         }
     }
     pub fn push(&self, line: &str) {
         let mut lines = self.lines.lock().unwrap();
         lines.push(line.to_string());
-        if lines.len() > 1000 {
-            lines.remove(0);
+        if lines.len() > self.scrollback {
+            let excess = lines.len() - self.scrollback;
+            lines.drain(..excess);
+            // Keep the reader's place while they are scrolled back
+            let mut view = self.view.lock().unwrap();
+            view.offset = view.offset.saturating_sub(excess);
         }
     }
+    #[allow(dead_code)]
     pub fn get(&self) -> Vec<String> {
         self.lines.lock().unwrap().clone()
     }
     pub fn clear(&self) {
         let mut lines = self.lines.lock().unwrap();
         lines.clear();
+        let mut view = self.view.lock().unwrap();
+        view.offset = 0;
+        view.follow = true;
+    }
+    pub fn is_following(&self) -> bool {
+        self.view.lock().unwrap().follow
+    }
+    /// The search query being typed, if `/` search is active
+    pub fn search_prompt(&self) -> Option<String> {
+        self.view.lock().unwrap().search.clone()
+    }
+    /// Lines that fit in `height` rows at the current scroll position,
+    /// along with the index of the first one
+    pub fn visible(&self, height: usize) -> (usize, Vec<String>) {
+        let lines = self.lines.lock().unwrap();
+        let mut view = self.view.lock().unwrap();
+        view.page = height.max(1);
+        let max_offset = lines.len().saturating_sub(height);
+        if view.follow {
+            view.offset = max_offset;
+        } else {
+            view.offset = view.offset.min(max_offset);
+        }
+        let start = view.offset;
+        let end = (start + height).min(lines.len());
+        (start, lines[start..end].to_vec())
+    }
+    /// The question of a build waiting for input and the answer typed so far
+    pub fn input_prompt(&self) -> Option<(String, String)> {
+        self.input
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|input| (input.question.clone(), input.answer.clone()))
+    }
+    /// Type into a pending build prompt: Enter sends the answer, Esc gives
+    /// up on the build. Returns true if the key was consumed.
+    pub fn handle_input_key(&self, key: KeyEvent) -> bool {
+        let mut input = self.input.lock().unwrap();
+        let Some(pending) = input.as_mut() else {
+            return false;
+        };
+        match key.code {
+            KeyCode::Char(c) => pending.answer.push(c),
+            KeyCode::Backspace => {
+                pending.answer.pop();
+            }
+            KeyCode::Enter => {
+                if let Some(pending) = input.take() {
+                    let _ = pending.reply.send(pending.answer);
+                }
+            }
+            // Dropping the sender tells the build nobody will answer
+            KeyCode::Esc => *input = None,
+            _ => {}
+        }
+        true
+    }
+    /// Handle navigation keys. Returns true if the key was consumed.
+    ///
+    /// PageUp/PageDown/Up/Down/Home/End scroll, `/` starts a search
+    /// (Enter jumps to the next match, Esc cancels), `n` repeats the last
+    /// search and `f` toggles auto-scroll.
+    pub fn handle_key(&self, key: KeyEvent) -> bool {
+        let len = self.lines.lock().unwrap().len();
+        let mut view = self.view.lock().unwrap();
+        let max_offset = len.saturating_sub(view.page);
+
+        if let Some(query) = view.search.as_mut() {
+            match key.code {
+                KeyCode::Char(c) => query.push(c),
+                KeyCode::Backspace => {
+                    query.pop();
+                }
+                KeyCode::Enter => {
+                    let query = view.search.take().unwrap_or_default();
+                    if !query.is_empty() {
+                        view.last_query = query;
+                    }
+                    drop(view);
+                    self.jump_to_next_match();
+                }
+                KeyCode::Esc => view.search = None,
+                _ => {}
+            }
+            return true;
+        }
+
+        match key.code {
+            KeyCode::PageUp => {
+                view.follow = false;
+                view.offset = view.offset.saturating_sub(view.page);
+            }
+            KeyCode::PageDown => {
+                view.offset = (view.offset + view.page).min(max_offset);
+                view.follow = view.offset == max_offset;
+            }
+            KeyCode::Up => {
+                view.follow = false;
+                view.offset = view.offset.saturating_sub(1);
+            }
+            KeyCode::Down => {
+                view.offset = (view.offset + 1).min(max_offset);
+                view.follow = view.offset == max_offset;
+            }
+            KeyCode::Home => {
+                view.follow = false;
+                view.offset = 0;
+            }
+            KeyCode::End => {
+                view.follow = true;
+                view.offset = max_offset;
+            }
+            KeyCode::Char('/') => view.search = Some(String::new()),
+            KeyCode::Char('n') => {
+                drop(view);
+                self.jump_to_next_match();
+            }
+            KeyCode::Char('f') => {
+                view.follow = !view.follow;
+            }
+            _ => return false,
+        }
+        true
+    }
+    /// Scroll to the next line after the current position containing the
+    /// last search query, wrapping around to the top
+    fn jump_to_next_match(&self) {
+        let lines = self.lines.lock().unwrap();
+        let mut view = self.view.lock().unwrap();
+        if view.last_query.is_empty() || lines.is_empty() {
+            return;
+        }
+        let query = view.last_query.to_lowercase();
+        let start = view.offset + 1;
+        let found = (0..lines.len())
+            .map(|i| (start + i) % lines.len())
+            .find(|&i| lines[i].to_lowercase().contains(&query));
+        if let Some(idx) = found {
+            view.follow = false;
+            view.offset = idx;
+        }
+    }
+}
+
+impl core::ProgressReporter for LogPane {
+    fn step(&self, step: &str, msg: &str) {
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+        self.push(&format!("[{}][reap][{}] {}", now, step, msg));
+    }
+    fn message(&self, msg: &str) {
+        self.push(msg);
+    }
+    /// Shows the question in the log tab and waits for the answer typed there
+    fn prompt(&self, step: &str, question: &str) -> Option<String> {
+        let (reply, answer) = std::sync::mpsc::channel();
+        *self.input.lock().unwrap() = Some(PendingInput {
+            question: question.to_string(),
+            answer: String::new(),
+            reply,
+        });
+        self.step(step, &format!("⌨️ Waiting for your answer: {}", question));
+        answer.recv().ok()
     }
 }
 
@@ -351,8 +562,10 @@ pub async fn launch_tui() {
     let mut search_tab = SearchTab::new();
     let mut tab_idx = 0;
     let tab_titles = ["Search", "Queue", "Log", "Profiles", "System"];
-    let log_pane = Arc::new(LogPane::new());
-    let mut log_scroll = 0usize;
+    let scrollback = crate::config::GlobalConfig::load()
+        .log_scrollback
+        .unwrap_or(DEFAULT_SCROLLBACK);
+    let log_pane = Arc::new(LogPane::with_scrollback(scrollback));
     let install_queue = Arc::new(InstallQueue::new());
     let installed = core::get_installed_packages();
     let mut diff_viewer: Option<DiffViewer> = None;
@@ -408,7 +621,7 @@ pub async fn launch_tui() {
                     }
                     2 => {
                         // Log tab with filtering
-                        render_log_tab(f, chunks[1], &log_pane, log_scroll);
+                        render_log_tab(f, chunks[1], &log_pane);
                     }
                     3 => {
                         // Profiles management tab
@@ -434,88 +647,87 @@ pub async fn launch_tui() {
             })
             .unwrap();
 
-        if crossterm::event::poll(std::time::Duration::from_millis(100)).unwrap() {
-            if let Event::Key(key) = event::read().unwrap() {
-                match key.code {
-                    KeyCode::Char('q') | KeyCode::Esc => break,
-                    KeyCode::Char('/') => {
-                        // Enter search mode
-                        if tab_idx == 0 {
-                            log_pane.push("[tui] Search mode activated");
-                        }
-                    }
-                    KeyCode::Char('d') => {
-                        // Show diff for selected package
-                        if tab_idx == 0 && !search_tab.results.is_empty() {
-                            let selected_pkg = &search_tab.results[search_tab.selected];
-                            let old = "";
-                            let new = crate::aur::get_pkgbuild_preview(&selected_pkg.name);
-                            diff_viewer = Some(DiffViewer::new(old, &new));
-                        }
-                    }
-                    KeyCode::Char('t') => {
-                        // Show trust details for selected package
-                        if tab_idx == 0 && !search_tab.results.is_empty() {
-                            let selected_pkg = &search_tab.results[search_tab.selected];
-                            if let Some(trust) = search_tab.trust_scores.get(&selected_pkg.name) {
-                                log_pane.push(&format!(
-                                    "[trust] {}: Score {:.1}/10",
-                                    selected_pkg.name, trust.overall_score
-                                ));
-                                for flag in &trust.security_flags {
-                                    log_pane.push(&format!("[trust] ⚠️ {:?}", flag));
-                                }
-                            }
-                        }
-                    }
-                    KeyCode::Char('p') => {
-                        // Switch to profiles tab
-                        tab_idx = 3;
-                    }
-                    KeyCode::Char('c') | KeyCode::Char('C') => {
-                        log_pane.clear();
-                    }
-                    KeyCode::Char('\t') => {
-                        tab_idx = (tab_idx + 1) % tab_titles.len();
-                    }
-                    KeyCode::Char(c) => {
-                        if tab_idx == 0 {
-                            search_tab.query.push(c);
-                        }
-                    }
-                    KeyCode::Up => {
-                        if tab_idx == 0 && search_tab.selected > 0 {
-                            search_tab.selected -= 1;
-                        } else if tab_idx == 2 && log_scroll > 0 {
-                            log_scroll -= 1;
-                        }
-                    }
-                    KeyCode::Down => {
-                        if tab_idx == 0
-                            && search_tab.selected < search_tab.results.len().saturating_sub(1)
-                        {
-                            search_tab.selected += 1;
-                        } else if tab_idx == 2 {
-                            log_scroll += 1;
-                        }
-                    }
-                    KeyCode::Enter => {
-                        if tab_idx == 0 && !search_tab.results.is_empty() {
-                            let selected = &search_tab.results[search_tab.selected];
-                            let task = core::InstallTask::new(
-                                selected.name.clone(),
-                                selected.source.clone(),
-                            );
-                            install_queue.enqueue(task);
-                            log_pane
-                                .push(&format!("[queue] Added {} to install queue", selected.name));
+        if crossterm::event::poll(std::time::Duration::from_millis(100)).unwrap()
+            && let Event::Key(key) = event::read().unwrap()
+        {
+            // A build waiting for input takes every key until answered
+            if log_pane.handle_input_key(key) {
+                continue;
+            }
+            // The log tab gets first pick so scrolling and search work while it is open
+            if tab_idx == 2 && log_pane.handle_key(key) {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => break,
+                KeyCode::Char('/') if tab_idx == 0 => {
+                    // Enter search mode
+                    log_pane.push("[tui] Search mode activated");
+                }
+                KeyCode::Char('d') if tab_idx == 0 && !search_tab.results.is_empty() => {
+                    // Show diff for selected package
+                    let selected_pkg = &search_tab.results[search_tab.selected];
+                    let old = std::fs::read_to_string(crate::core::built_pkgbuild_path(
+                        &selected_pkg.name,
+                    ))
+                    .unwrap_or_default();
+                    let new = crate::aur::get_pkgbuild_preview(&selected_pkg.name);
+                    diff_viewer = Some(DiffViewer::new(&old, &new));
+                }
+                KeyCode::Char('t') if tab_idx == 0 && !search_tab.results.is_empty() => {
+                    // Show trust details for selected package
+                    let selected_pkg = &search_tab.results[search_tab.selected];
+                    if let Some(trust) = search_tab.trust_scores.get(&selected_pkg.name) {
+                        log_pane.push(&format!(
+                            "[trust] {}: Score {:.1}/10",
+                            selected_pkg.name, trust.overall_score
+                        ));
+                        for flag in &trust.security_flags {
+                            log_pane.push(&format!("[trust] ⚠️ {:?}", flag));
                         }
                     }
-                    _ => {}
                 }
+                // Commands even with nothing to show them for, never typed
+                // into the query
+                KeyCode::Char('d' | 't') => {}
+                KeyCode::Char('p') => {
+                    // Switch to profiles tab
+                    tab_idx = 3;
+                }
+                KeyCode::Char('c') | KeyCode::Char('C') => {
+                    log_pane.clear();
+                }
+                KeyCode::Char('\t') => {
+                    tab_idx = (tab_idx + 1) % tab_titles.len();
+                }
+                KeyCode::Char(c) if tab_idx == 0 => {
+                    search_tab.query.push(c);
+                }
+                KeyCode::Up if tab_idx == 0 && search_tab.selected > 0 => {
+                    search_tab.selected -= 1;
+                }
+                KeyCode::Down
+                    if tab_idx == 0
+                        && search_tab.selected < search_tab.results.len().saturating_sub(1) =>
+                {
+                    search_tab.selected += 1;
+                }
+                KeyCode::Enter if tab_idx == 0 && !search_tab.results.is_empty() => {
+                    let selected = &search_tab.results[search_tab.selected];
+                    let task =
+                        core::InstallTask::new(selected.name.clone(), selected.source.clone());
+                    install_queue.enqueue(task);
+                    log_pane.push(&format!("[queue] Added {} to install queue", selected.name));
+                }
+                _ => {}
             }
         }
 
+        // Show the log tab while a build asks a question
+        if log_pane.input_prompt().is_some() {
+            tab_idx = 2;
+        }
+
         // Update build progress periodically
         build_progress.update().await;
     }
@@ -524,6 +736,271 @@ pub async fn launch_tui() {
     restore_terminal(&mut terminal).expect("Failed to restore terminal");
 }
 
+/// State for the interactive package browser
+struct Browser {
+    query: String,
+    editing: bool,
+    results: Vec<SearchResult>,
+    selected: usize,
+    /// Marked results and the source each was marked from
+    marked: Vec<(String, core::Source)>,
+    preview: Option<String>,
+    status: String,
+    /// Merge repeats of a package with `core::dedup_search_results`
+    dedup: bool,
+    /// Keeps the selected result in view as the list scrolls
+    list_state: ListState,
+    /// First line of the details pane shown, moved with PageUp/PageDown
+    details_scroll: u16,
+}
+
+impl Browser {
+    fn new(dedup: bool) -> Self {
+        Self {
+            dedup,
+            query: String::new(),
+            editing: true,
+            results: Vec::new(),
+            selected: 0,
+            marked: Vec::new(),
+            preview: None,
+            status: "Type a query and press Enter to search".to_string(),
+            list_state: ListState::default(),
+            details_scroll: 0,
+        }
+    }
+
+    /// Select result `idx`, dropping the previous one's preview
+    fn select(&mut self, idx: usize) {
+        self.selected = idx;
+        self.preview = None;
+        self.details_scroll = 0;
+    }
+
+    fn current(&self) -> Option<&SearchResult> {
+        self.results.get(self.selected)
+    }
+
+    async fn search(&mut self) {
+        self.results = core::unified_search(&self.query, self.dedup).await;
+        self.select(0);
+        self.status = format!("{} results for '{}'", self.results.len(), self.query);
+    }
+
+    async fn load_preview(&mut self) {
+        let Some(result) = self.current() else {
+            return;
+        };
+        if result.source != core::Source::Aur {
+            self.preview = Some(format!(
+                "No PKGBUILD for {} packages",
+                result.source.label()
+            ));
+            return;
+        }
+        // get_pkgbuild_preview uses the blocking client, keep it off the runtime threads
+        let name = result.name.clone();
+        self.details_scroll = 0;
+        self.preview = tokio::task::spawn_blocking(move || aur::get_pkgbuild_preview(&name))
+            .await
+            .ok();
+    }
+
+    fn is_marked(&self, result: &SearchResult) -> bool {
+        self.marked
+            .iter()
+            .any(|(name, source)| *name == result.name && *source == result.source)
+    }
+
+    /// Mark or unmark the selected result. Marking a package from another
+    /// source replaces its earlier mark.
+    fn toggle_mark(&mut self) {
+        let Some(result) = self.current() else {
+            return;
+        };
+        let marked = self.is_marked(result);
+        let (name, source) = (result.name.clone(), result.source.clone());
+        self.marked.retain(|(m, _)| *m != name);
+        if !marked {
+            self.marked.push((name, source));
+        }
+        self.status = format!("{} package(s) marked for install", self.marked.len());
+    }
+
+    fn render(&mut self, f: &mut Frame<'_>) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3), // Search input
+                Constraint::Min(5),    // Results + details
+                Constraint::Length(1), // Status bar
+            ])
+            .split(f.size());
+
+        let input_style = if self.editing {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default()
+        };
+        let input = Paragraph::new(self.query.as_str())
+            .style(input_style)
+            .block(Block::default().borders(Borders::ALL).title("🔍 Search"));
+        f.render_widget(input, chunks[0]);
+
+        let body = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(45), Constraint::Percentage(55)])
+            .split(chunks[1]);
+
+        let items: Vec<ListItem> = self
+            .results
+            .iter()
+            .map(|result| {
+                let mark = if self.is_marked(result) { "[x]" } else { "[ ]" };
+                ListItem::new(format!(
+                    "{} {} {}",
+                    mark,
+                    result.name,
+                    result.source.label()
+                ))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title("Results"))
+            .highlight_style(Style::default().bg(Color::Blue).fg(Color::White));
+        self.list_state
+            .select((!self.results.is_empty()).then_some(self.selected));
+        f.render_stateful_widget(list, body[0], &mut self.list_state);
+
+        let details = match self.current() {
+            Some(result) => {
+                let mut text = format!(
+                    "Name: {}\nVersion: {}\nSource: {}\n\n{}\n",
+                    result.name,
+                    result.version,
+                    result.source.label(),
+                    result.description
+                );
+                if let Some(preview) = &self.preview {
+                    text.push_str("\n--- PKGBUILD ---\n");
+                    text.push_str(preview);
+                }
+                text
+            }
+            None => String::from("No package selected"),
+        };
+        // Don't scroll past the last line
+        let last_line = details.lines().count().saturating_sub(1);
+        self.details_scroll = self.details_scroll.min(last_line as u16);
+        let details_paragraph = Paragraph::new(details)
+            .block(Block::default().borders(Borders::ALL).title("Details"))
+            .scroll((self.details_scroll, 0));
+        f.render_widget(details_paragraph, body[1]);
+
+        let status = format!(
+            "{} | / search  ↑↓ move  Enter details  PgUp/PgDn scroll details  Space mark  i install marked  q quit",
+            self.status
+        );
+        let status_paragraph =
+            Paragraph::new(status).style(Style::default().bg(Color::Blue).fg(Color::White));
+        f.render_widget(status_paragraph, chunks[2]);
+    }
+}
+
+/// Interactive package browser backed by `core::unified_search`.
+///
+/// Results can be marked with Space and are batch-installed from the source
+/// they were marked from via `core::parallel_install_with` after leaving the
+/// TUI with `i`. Returns the install report, empty if nothing was installed.
+///
+/// Without `dedup`, every result from every source is listed.
+pub async fn browse(dedup: bool) -> core::BulkReport {
+    let mut terminal = setup_terminal().expect("Failed to setup terminal");
+    let mut browser = Browser::new(dedup);
+    let mut install = false;
+
+    loop {
+        terminal.draw(|f| browser.render(f)).unwrap();
+
+        if !crossterm::event::poll(std::time::Duration::from_millis(100)).unwrap() {
+            continue;
+        }
+        let Event::Key(key) = event::read().unwrap() else {
+            continue;
+        };
+        if browser.editing {
+            match key.code {
+                KeyCode::Enter => {
+                    browser.editing = false;
+                    browser.status = format!("Searching for '{}'...", browser.query);
+                    terminal.draw(|f| browser.render(f)).unwrap();
+                    browser.search().await;
+                }
+                KeyCode::Esc => browser.editing = false,
+                KeyCode::Backspace => {
+                    browser.query.pop();
+                }
+                KeyCode::Char(c) => browser.query.push(c),
+                _ => {}
+            }
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => break,
+            KeyCode::Char('/') => browser.editing = true,
+            KeyCode::Up if browser.selected > 0 => browser.select(browser.selected - 1),
+            KeyCode::Down if browser.selected < browser.results.len().saturating_sub(1) => {
+                browser.select(browser.selected + 1)
+            }
+            KeyCode::PageUp => browser.details_scroll = browser.details_scroll.saturating_sub(10),
+            KeyCode::PageDown => browser.details_scroll = browser.details_scroll.saturating_add(10),
+            KeyCode::Enter => browser.load_preview().await,
+            KeyCode::Char(' ') => browser.toggle_mark(),
+            KeyCode::Char('i') => {
+                if browser.marked.is_empty() {
+                    browser.status = "No packages marked".to_string();
+                } else {
+                    install = true;
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    restore_terminal(&mut terminal).expect("Failed to restore terminal");
+
+    if !install {
+        return core::BulkReport::default();
+    }
+    let labels: Vec<String> = browser
+        .marked
+        .iter()
+        .map(|(name, source)| format!("{} ({})", name, source.label()))
+        .collect();
+    println!(
+        "[tui] Installing {} marked package(s): {}",
+        labels.len(),
+        labels.join(", ")
+    );
+    let options: HashMap<String, core::InstallOptions> = browser
+        .marked
+        .iter()
+        .map(|(name, source)| {
+            let opts = core::InstallOptions {
+                source: Some(source.clone()),
+                ..Default::default()
+            };
+            (name.clone(), opts)
+        })
+        .collect();
+    let names: Vec<String> = browser.marked.into_iter().map(|(name, _)| name).collect();
+    let config = Arc::new(crate::config::ReapConfig::load());
+    let log = Arc::new(core::StdoutReporter);
+    let report = core::parallel_install_with(&names, config, log, options).await;
+    report.print("Install");
+    report
+}
+
 #[derive(Default)]
 struct BuildProgress {
     current_package: Option<String>,
@@ -607,17 +1084,10 @@ fn render_queue_tab(
     }
 }
 
-fn render_log_tab(
-    f: &mut Frame<'_>,
-    area: ratatui::layout::Rect,
-    log_pane: &LogPane,
-    scroll: usize,
-) {
-    let logs = log_pane.get();
+fn render_log_tab(f: &mut Frame<'_>, area: ratatui::layout::Rect, log_pane: &LogPane) {
+    let (_, logs) = log_pane.visible(area.height.saturating_sub(2) as usize);
     let visible_logs: Vec<ListItem> = logs
         .iter()
-        .skip(scroll)
-        .take(area.height as usize - 2)
         .map(|line| {
             let style = if line.contains("ERROR") || line.contains("❌") {
                 Style::default().fg(Color::Red)
@@ -628,12 +1098,24 @@ fn render_log_tab(
             } else {
                 Style::default()
             };
-            ListItem::new(line.as_str()).style(style)
+            ListItem::new(line.clone()).style(style)
         })
         .collect();
 
+    let title = if let Some((question, answer)) = log_pane.input_prompt() {
+        format!(
+            "Activity Log — {} {}_ (Enter to answer, Esc to abort)",
+            question, answer
+        )
+    } else {
+        match log_pane.search_prompt() {
+            Some(query) => format!("Activity Log — search: {}_", query),
+            None if log_pane.is_following() => "Activity Log [follow]".to_string(),
+            None => "Activity Log [paused — f to follow]".to_string(),
+        }
+    };
     let log_list =
-        List::new(visible_logs).block(Block::default().borders(Borders::ALL).title("Activity Log"));
+        List::new(visible_logs).block(Block::default().borders(Borders::ALL).title(title));
     f.render_widget(log_list, area);
 }
 
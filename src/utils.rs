@@ -7,6 +7,16 @@ use toml::Value;
 #[cfg(feature = "cache")]
 use crate::aur::SearchResult;
 
+/// Rough human readable duration, e.g. `1h 05m`, `12m 30s` or `45s`
+pub fn format_duration(duration: std::time::Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / 3600, secs % 3600 / 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {:02}s", m, s),
+        (h, m, _) => format!("{}h {:02}m", h, m),
+    }
+}
+
 /// Caches and returns AUR search results for a query
 #[cfg(feature = "cache")]
 pub fn get_cached_search(query: &str) -> Option<Vec<SearchResult>> {
@@ -267,15 +277,29 @@ pub fn pin_package(pkg: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// The entries of `pinned.toml`, one per line (`pkg` or `pkg = "version"`)
+pub fn pinned_lines() -> Vec<String> {
+    let path = dirs::home_dir()
+        .unwrap_or_else(|| std::path::PathBuf::from("/tmp"))
+        .join(".config/reap/pinned.toml");
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
 pub fn is_pinned(pkg: &str) -> bool {
     let config_path = dirs::home_dir()
         .unwrap_or_else(|| std::path::PathBuf::from("/tmp"))
         .join(".config/reap/pinned.toml");
     if let Ok(contents) = fs::read_to_string(&config_path) {
-        if let Ok(toml) = contents.parse::<Value>() {
-            if let Some(table) = toml.as_table() {
-                return table.contains_key(pkg);
-            }
+        if let Ok(toml) = contents.parse::<Value>()
+            && let Some(table) = toml.as_table()
+        {
+            return table.contains_key(pkg);
         }
         // Fallback: check for simple line pin
         contents.lines().any(|line| line.trim() == pkg)
@@ -341,24 +365,251 @@ pub fn clean_cache() -> Result<String, String> {
     }
 }
 
+/// `privilege_cmd` from reap.toml split into program and leading
+/// arguments. When unset or empty, `sudo` if `exists` finds it, else
+/// `doas` if it finds that, else `sudo`.
+pub fn parse_privilege_cmd(configured: Option<&str>, exists: impl Fn(&str) -> bool) -> Vec<String> {
+    let parts: Vec<String> = configured
+        .unwrap_or("")
+        .split_whitespace()
+        .map(String::from)
+        .collect();
+    if !parts.is_empty() {
+        return parts;
+    }
+    let program = ["sudo", "doas"]
+        .into_iter()
+        .find(|p| exists(p))
+        .unwrap_or("sudo");
+    vec![program.to_string()]
+}
+
+static PRIVILEGE_CMD: once_cell::sync::Lazy<Vec<String>> = once_cell::sync::Lazy::new(|| {
+    parse_privilege_cmd(
+        crate::config::GlobalConfig::load().privilege_cmd.as_deref(),
+        |p| which::which(p).is_ok(),
+    )
+});
+
+/// The privilege escalation program: `privilege_cmd`, or sudo/doas
+pub fn privilege_program() -> &'static str {
+    &PRIVILEGE_CMD[0]
+}
+
+/// A command that runs `program` as root through `privilege_cmd`
+pub fn escalate(program: &str) -> std::process::Command {
+    let mut cmd = std::process::Command::new(&PRIVILEGE_CMD[0]);
+    cmd.args(&PRIVILEGE_CMD[1..]).arg(program);
+    cmd
+}
+
+/// Check that the configured `privilege_cmd` can be found, returning a
+/// message for the user when it can't
+pub fn validate_privilege_cmd() -> Result<(), String> {
+    let program = privilege_program();
+    if which::which(program).is_ok() {
+        return Ok(());
+    }
+    let configured = crate::config::GlobalConfig::load().privilege_cmd.is_some();
+    Err(if configured {
+        format!("privilege_cmd `{}` was not found in PATH", program)
+    } else {
+        "neither sudo nor doas was found in PATH, set privilege_cmd in reap.toml".to_string()
+    })
+}
+
+/// Whether reap itself runs as root
+fn running_as_root() -> bool {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata("/proc/self").is_ok_and(|m| m.uid() == 0)
+}
+
+/// Seconds between refreshes of the sudo timestamp during long operations
+const SUDO_KEEPALIVE_SECS: u64 = 60;
+
+/// Ask for the sudo password once, up front, and keep the cached
+/// credentials fresh in the background for the rest of the run, so long
+/// builds don't stop at a password prompt for every pacman call. Skipped
+/// when running as root, when sudo needs no password, and for other
+/// `privilege_cmd`s (doas keeps its own `persist` timestamp).
+pub fn acquire_sudo() {
+    use std::process::{Command, Stdio};
+    let program = privilege_program();
+    if running_as_root()
+        || std::path::Path::new(program).file_name() != Some(std::ffi::OsStr::new("sudo"))
+    {
+        return;
+    }
+    let quiet = move |args: &[&str]| {
+        Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success())
+    };
+    // Passwordless or already cached: nothing to ask
+    if !quiet(&["-n", "true"]) {
+        println!("[reap] Administrator rights are needed, asking once for this run");
+        let ok = Command::new(program)
+            .arg("-v")
+            .status()
+            .is_ok_and(|s| s.success());
+        if !ok {
+            eprintln!("[reap] Warning: could not cache sudo credentials, you may be asked again");
+            return;
+        }
+    }
+    std::thread::spawn(move || {
+        loop {
+            std::thread::sleep(std::time::Duration::from_secs(SUDO_KEEPALIVE_SECS));
+            if !quiet(&["-n", "-v"]) {
+                break;
+            }
+        }
+    });
+}
+
+/// pacman's database lock, left behind when a transaction is killed
+pub const PACMAN_DB_LOCK: &str = "/var/lib/pacman/db.lck";
+
+/// Pids of the running processes named `name`, other than this one
+pub fn running_processes(name: &str) -> Vec<u32> {
+    let own = std::process::id();
+    fs::read_dir("/proc")
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|e| e.file_name().to_str()?.parse::<u32>().ok())
+                .filter(|pid| *pid != own)
+                .filter(|pid| {
+                    fs::read_to_string(format!("/proc/{}/comm", pid))
+                        .is_ok_and(|comm| comm.trim() == name)
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Pids of the processes that have `path` open, from `/proc/<pid>/fd`.
+/// Other users' descriptors are only readable as root; otherwise, when
+/// `escalated`, `fuser` runs through `privilege_cmd` to see them too.
+pub fn lock_holders(path: &std::path::Path, escalated: bool) -> Vec<u32> {
+    let mut holders = Vec::new();
+    let mut unreadable = false;
+    let pids = fs::read_dir("/proc")
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|e| e.file_name().to_str()?.parse::<u32>().ok())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    for pid in pids {
+        match fs::read_dir(format!("/proc/{}/fd", pid)) {
+            Ok(fds) => {
+                if fds
+                    .flatten()
+                    .any(|fd| fs::read_link(fd.path()).is_ok_and(|target| target == path))
+                {
+                    holders.push(pid);
+                }
+            }
+            Err(_) => unreadable = true,
+        }
+    }
+    if holders.is_empty() && unreadable && escalated && !running_as_root() {
+        // fuser prints the pids on stdout, e.g. ` 1234 5678c`
+        let output = escalate("fuser")
+            .arg(path)
+            .stderr(std::process::Stdio::null())
+            .output();
+        if let Ok(out) = output {
+            holders = String::from_utf8_lossy(&out.stdout)
+                .split_whitespace()
+                .filter_map(|pid| {
+                    pid.trim_end_matches(|c: char| !c.is_ascii_digit())
+                        .parse()
+                        .ok()
+                })
+                .collect();
+        }
+    }
+    holders
+}
+
+/// Whether `lock` is left over: it exists while no process holds it open
+pub fn is_stale_db_lock(lock: &std::path::Path, held_open: bool) -> bool {
+    lock.exists() && !held_open
+}
+
+/// What an interrupted reap run left behind: a stale pacman lock or reap
+/// lockfile, AUR build dirs and installs that failed. Build dirs are only counted while
+/// no other reap is running, since that one may be using them.
+pub fn broken_state_issues() -> Vec<String> {
+    let mut issues = Vec::new();
+    let lock = std::path::Path::new(PACMAN_DB_LOCK);
+    // Without escalating only this user's descriptors are visible, so a
+    // running pacman counts as holding it too
+    let held_open =
+        !lock_holders(lock, false).is_empty() || !running_processes("pacman").is_empty();
+    if is_stale_db_lock(lock, held_open) {
+        issues.push(format!(
+            "Stale pacman lock {} that nothing holds open; run `reap fix-broken`",
+            lock.display()
+        ));
+    }
+    let lockfile = crate::core::build_root().join(crate::interrupt::LOCKFILE);
+    if crate::interrupt::is_stale_lockfile(&lockfile, crate::interrupt::is_running) {
+        issues.push(format!(
+            "Stale reap lockfile {} left by a reap that is gone; run `reap fix-broken`",
+            lockfile.display()
+        ));
+    }
+    if running_processes("reap").is_empty() {
+        let root = crate::core::build_root();
+        let leftover = crate::core::leftover_build_dirs(&root);
+        if !leftover.is_empty() {
+            issues.push(format!(
+                "{} build dir(s) left in {} by an interrupted build; run `reap fix-broken`",
+                leftover.len(),
+                root.display()
+            ));
+        }
+    }
+    let installed = crate::pacman::installed_versions();
+    let failed: Vec<String> = crate::history::OperationLog::load()
+        .failed_installs()
+        .into_iter()
+        .filter(|pkg| !installed.contains_key(pkg))
+        .collect();
+    if !failed.is_empty() {
+        issues.push(format!(
+            "Failed installs not installed since: {}; run `reap fix-broken` to retry them",
+            failed.join(", ")
+        ));
+    }
+    issues
+}
+
 pub fn doctor_report() -> Result<String, String> {
     let mut issues = Vec::new();
     // Check for broken symlinks in /usr/bin/reap-*
     if let Ok(entries) = fs::read_dir("/usr/bin") {
         for entry in entries.flatten() {
             let path = entry.path();
-            if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                if name.starts_with("reap-") && path.is_symlink() {
-                    if let Ok(target) = fs::read_link(&path) {
-                        if !target.exists() {
-                            issues.push(format!(
-                                "Broken symlink: {} -> {}",
-                                path.display(),
-                                target.display()
-                            ));
-                        }
-                    }
-                }
+            if let Some(name) = path.file_name().and_then(|n| n.to_str())
+                && name.starts_with("reap-")
+                && path.is_symlink()
+                && let Ok(target) = fs::read_link(&path)
+                && !target.exists()
+            {
+                issues.push(format!(
+                    "Broken symlink: {} -> {}",
+                    path.display(),
+                    target.display()
+                ));
             }
         }
     }
@@ -374,6 +625,35 @@ pub fn doctor_report() -> Result<String, String> {
             issues.push(format!("Missing config file: {}", fpath.display()));
         }
     }
+    // Without keyrings pacman can't verify packages and reap can't verify PKGBUILDs
+    if std::path::Path::new("/etc/pacman.d").exists()
+        && !crate::gpg::keyring_initialized(std::path::Path::new(crate::gpg::PACMAN_KEYRING_DIR))
+    {
+        issues.push(format!(
+            "Pacman keyring not initialized in {}; {}",
+            crate::gpg::PACMAN_KEYRING_DIR,
+            crate::gpg::KEYRING_HINT
+        ));
+    }
+    let user_keyring = crate::gpg::user_keyring_dir();
+    if !crate::gpg::keyring_initialized(&user_keyring) {
+        issues.push(format!(
+            "No gpg keyring in {}, PKGBUILD signatures can't be checked; {}",
+            user_keyring.display(),
+            crate::gpg::KEYRING_HINT
+        ));
+    }
+    // Leftovers of a reap run that was killed
+    issues.extend(broken_state_issues());
+    // Pending repo updates mean AUR builds would link against a stale system
+    let pending = pending_repo_updates();
+    if !pending.is_empty() {
+        issues.push(format!(
+            "Partial upgrade risk: {} repo update(s) pending ({}). AUR packages built now may be linked against outdated dependencies; run `reap -Syu` first.",
+            pending.len(),
+            pending.iter().take(5).cloned().collect::<Vec<_>>().join(", ")
+        ));
+    }
     if issues.is_empty() {
         Ok("System appears healthy".to_string())
     } else {
@@ -381,6 +661,198 @@ pub fn doctor_report() -> Result<String, String> {
     }
 }
 
+/// Repo packages with updates available on the mirrors. Uses `checkupdates`
+/// (which syncs a temporary db) when installed, falling back to `pacman -Qu`.
+pub fn pending_repo_updates() -> Vec<String> {
+    pending_repo_update_versions()
+        .into_iter()
+        .map(|(name, _, _)| name)
+        .collect()
+}
+
+/// Like [`pending_repo_updates`], with the installed and new version of each
+pub fn pending_repo_update_versions() -> Vec<(String, String, String)> {
+    use std::process::Command;
+    let output = Command::new("checkupdates")
+        .output()
+        .or_else(|_| Command::new("pacman").arg("-Qu").output());
+    match output {
+        Ok(out) => parse_update_lines(&String::from_utf8_lossy(&out.stdout)),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Parse `name old -> new` lines as printed by `checkupdates` and `pacman -Qu`
+pub fn parse_update_lines(text: &str) -> Vec<(String, String, String)> {
+    text.lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let name = parts.next()?.to_string();
+            let old = parts.next().unwrap_or("").to_string();
+            let new = parts.find(|p| *p != "->").unwrap_or("").to_string();
+            Some((name, old, new))
+        })
+        .collect()
+}
+
+/// Outcome of `reap doctor --fix`
+#[derive(Debug, Default)]
+pub struct DoctorFixReport {
+    pub fixed: Vec<String>,
+    pub manual: Vec<String>,
+}
+
+/// Attempt to repair common problems, asking before each fix. Declined or
+/// failed fixes are reported as needing manual attention.
+pub async fn doctor_fix() -> DoctorFixReport {
+    use crate::interactive::InteractiveManager;
+    let mut report = DoctorFixReport::default();
+
+    // Missing GPG keys for tap publishers
+    for tap in crate::tap::discover_taps() {
+        let Some(publisher) = crate::tap::get_publisher_info(&tap) else {
+            continue;
+        };
+        if publisher.gpg_key.is_empty() || crate::gpg::key_exists(&publisher.gpg_key) {
+            continue;
+        }
+        let issue = format!("GPG key {} for tap '{}'", publisher.gpg_key, tap.name);
+        if InteractiveManager::confirm_action(&format!("Import missing {}?", issue), true) {
+            match crate::gpg::import_gpg_key_async(&publisher.gpg_key).await {
+                Ok(()) => report.fixed.push(format!("Imported {}", issue)),
+                Err(e) => report
+                    .manual
+                    .push(format!("Could not import {}: {}", issue, e)),
+            }
+        } else {
+            report.manual.push(format!("Missing {}", issue));
+        }
+    }
+
+    // Stale or missing pacman keyring
+    let keyring = std::path::Path::new("/etc/pacman.d/gnupg/pubring.gpg");
+    let keyring_age = fs::metadata(keyring)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.elapsed().ok());
+    let stale_keyring = match keyring_age {
+        Some(age) => age > std::time::Duration::from_secs(90 * 24 * 60 * 60),
+        None => std::path::Path::new("/etc/pacman.d").exists(),
+    };
+    let keyring_missing = std::path::Path::new("/etc/pacman.d").exists()
+        && !crate::gpg::keyring_initialized(std::path::Path::new(crate::gpg::PACMAN_KEYRING_DIR));
+    if keyring_missing {
+        if InteractiveManager::confirm_action("Initialize the pacman keyring?", true) {
+            match crate::gpg::keyring_init() {
+                Ok(()) => report.fixed.push("Initialized pacman keyring".to_string()),
+                Err(e) => report
+                    .manual
+                    .push(format!("Pacman keyring init failed: {}", e)),
+            }
+        } else {
+            report
+                .manual
+                .push("Pacman keyring is not initialized".to_string());
+        }
+    } else if stale_keyring {
+        if InteractiveManager::confirm_action(
+            "Refresh the pacman keyring (updates archlinux-keyring, then the system)?",
+            false,
+        ) {
+            let _transaction = crate::pacman::transaction();
+            // Syncing without upgrading the rest would leave a partial upgrade
+            let pacman = |args: &[&str]| {
+                escalate("pacman")
+                    .args(args)
+                    .status()
+                    .is_ok_and(|s| s.success())
+            };
+            let ok = pacman(&["-Sy", "--noconfirm", "archlinux-keyring"])
+                && pacman(&["-Su", "--noconfirm"]);
+            if ok {
+                report.fixed.push("Refreshed pacman keyring".to_string());
+            } else {
+                report
+                    .manual
+                    .push("Pacman keyring refresh failed, try `pacman-key --populate`".to_string());
+            }
+        } else {
+            report.manual.push("Pacman keyring is stale".to_string());
+        }
+    }
+
+    // Leftover build directories from failed AUR builds, unless another reap
+    // may still be building in them
+    let build_dirs = crate::core::leftover_build_dirs(&crate::core::build_root());
+    if !build_dirs.is_empty() && !running_processes("reap").is_empty() {
+        report.manual.push(format!(
+            "{} build dir(s) left alone while another reap is running",
+            build_dirs.len()
+        ));
+    } else if !build_dirs.is_empty() {
+        let msg = format!("Remove {} leftover build dir(s)?", build_dirs.len());
+        if InteractiveManager::confirm_action(&msg, true) {
+            for dir in build_dirs {
+                match fs::remove_dir_all(&dir) {
+                    Ok(()) => report.fixed.push(format!("Removed {}", dir.display())),
+                    Err(e) => {
+                        report
+                            .manual
+                            .push(format!("Could not remove {}: {}", dir.display(), e))
+                    }
+                }
+            }
+        } else {
+            report
+                .manual
+                .push(format!("{} leftover build dir(s)", build_dirs.len()));
+        }
+    }
+
+    // Taps that haven't been synced recently
+    let taps_stale = crate::tap::last_sync()
+        .is_none_or(|last| chrono::Utc::now() - last > chrono::Duration::hours(24));
+    if taps_stale && !crate::tap::discover_taps().is_empty() {
+        if InteractiveManager::confirm_action("Re-sync outdated taps?", true) {
+            let failed = crate::tap::force_sync_enabled_taps();
+            if failed.is_empty() {
+                report.fixed.push("Re-synced taps".to_string());
+            }
+            for name in failed {
+                report.manual.push(format!("Could not sync tap {}", name));
+            }
+        } else {
+            report.manual.push("Taps are out of date".to_string());
+        }
+    }
+
+    // Missing config directory
+    let config_dir = dirs::home_dir().unwrap_or_default().join(".config/reap");
+    if !config_dir.exists() {
+        if InteractiveManager::confirm_action(
+            &format!("Create config dir {}?", config_dir.display()),
+            true,
+        ) {
+            match fs::create_dir_all(&config_dir) {
+                Ok(()) => report
+                    .fixed
+                    .push(format!("Created {}", config_dir.display())),
+                Err(e) => {
+                    report
+                        .manual
+                        .push(format!("Could not create {}: {}", config_dir.display(), e))
+                }
+            }
+        } else {
+            report
+                .manual
+                .push(format!("Missing config dir: {}", config_dir.display()));
+        }
+    }
+
+    report
+}
+
 #[allow(dead_code)]
 pub fn build_pkg(pkgdir: &std::path::Path, edit: bool) -> Result<(), String> {
     use std::env;
@@ -422,6 +894,174 @@ pub fn build_pkg(pkgdir: &std::path::Path, edit: bool) -> Result<(), String> {
     }
 }
 
+static PAGER_ENABLED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(true);
+
+/// Turn paging of long output off, like `--no-pager`
+pub fn set_pager(enabled: bool) {
+    PAGER_ENABLED.store(enabled, std::sync::atomic::Ordering::Relaxed);
+}
+
+/// The pager to run: `pager` from reap.toml, then `$PAGER`, then `less -R`.
+/// An empty value or `cat` disables paging.
+pub fn pager_command(configured: Option<&str>, env: Option<&str>) -> Option<String> {
+    let pager = configured
+        .or(env)
+        .map(str::trim)
+        .unwrap_or("less -R")
+        .to_string();
+    (!pager.is_empty() && pager != "cat").then_some(pager)
+}
+
+/// Print `text`, through the pager when stdout is a terminal and the text
+/// is taller than it
+pub fn page(text: &str) {
+    use std::io::Write;
+    let height = crossterm::terminal::size().map_or(0, |(_, rows)| rows as usize);
+    let pager = if PAGER_ENABLED.load(std::sync::atomic::Ordering::Relaxed)
+        && std::io::IsTerminal::is_terminal(&std::io::stdout())
+        && text.lines().count() >= height
+    {
+        let global = crate::config::GlobalConfig::load();
+        pager_command(
+            global.pager.as_deref(),
+            std::env::var("PAGER").ok().as_deref(),
+        )
+    } else {
+        None
+    };
+    let Some(pager) = pager else {
+        print!("{}", text);
+        return;
+    };
+    // Like git: keep colors, and quit right away if it fits after all
+    let child = std::process::Command::new("sh")
+        .args(["-c", &pager])
+        .env(
+            "LESS",
+            std::env::var("LESS").unwrap_or_else(|_| "FRX".to_string()),
+        )
+        .stdin(std::process::Stdio::piped())
+        .spawn();
+    match child {
+        Ok(mut child) => {
+            if let Some(mut stdin) = child.stdin.take() {
+                // Quitting the pager early closes the pipe, which is fine
+                let _ = stdin.write_all(text.as_bytes());
+            }
+            let _ = child.wait();
+        }
+        Err(_) => print!("{}", text),
+    }
+}
+
+/// `diff_cmd` from reap.toml run through the shell on `old` and `new`, so
+/// it can carry its own flags (`diff --color`, `git diff --no-index`)
+pub fn diff_command(
+    diff_cmd: &str,
+    old: &std::path::Path,
+    new: &std::path::Path,
+) -> std::process::Command {
+    let mut cmd = std::process::Command::new("sh");
+    cmd.arg("-c")
+        .arg(format!("{} \"$1\" \"$2\"", diff_cmd))
+        .arg("reap")
+        .arg(old)
+        .arg(new);
+    cmd
+}
+
+/// Whether to colorize output for `--color <when>`. `auto` colors only a
+/// terminal, and never when `NO_COLOR` is set (https://no-color.org).
+pub fn use_color(when: &str, no_color: bool, is_tty: bool) -> bool {
+    match when {
+        "always" => true,
+        "never" => false,
+        _ => is_tty && !no_color,
+    }
+}
+
+/// Number of package names listed in an update notification
+const NOTIFY_TOP_PACKAGES: usize = 5;
+
+/// Summary and body of the desktop notification for available updates,
+/// naming the first few packages
+pub fn update_notification(pkgs: &[&str]) -> (String, String) {
+    let summary = match pkgs.len() {
+        1 => "1 package update available".to_string(),
+        n => format!("{} package updates available", n),
+    };
+    let mut body = pkgs
+        .iter()
+        .take(NOTIFY_TOP_PACKAGES)
+        .copied()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if pkgs.len() > NOTIFY_TOP_PACKAGES {
+        body.push_str(&format!(" and {} more", pkgs.len() - NOTIFY_TOP_PACKAGES));
+    }
+    (summary, body)
+}
+
+/// Send a desktop notification with `notify-send`. `false` if it isn't
+/// installed or no notification daemon is running.
+pub fn send_notification(summary: &str, body: &str) -> bool {
+    std::process::Command::new("notify-send")
+        .args(["--app-name=reap", "--icon=system-software-update"])
+        .arg(summary)
+        .arg(body)
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// Where systemd looks for the user's own units
+pub fn systemd_user_dir() -> std::path::PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| std::path::PathBuf::from("/tmp"))
+        .join("systemd/user")
+}
+
+/// `reap-upgrade.service`: upgrade unattended, or with `notify_only` just
+/// check and send a desktop notification when updates are available
+pub fn upgrade_service_unit(exe: &str, aur_only: bool, notify_only: bool) -> String {
+    let (description, command) = if notify_only {
+        (
+            "Check for reap package updates",
+            format!("{} update --notify", exe),
+        )
+    } else if aur_only {
+        (
+            "Upgrade AUR packages with reap",
+            format!("{} --noconfirm upgrade --aur", exe),
+        )
+    } else {
+        (
+            "Upgrade packages with reap",
+            format!("{} --noconfirm upgrade", exe),
+        )
+    };
+    let mut unit = format!("[Unit]\nDescription={}\n", description);
+    unit.push_str("Wants=network-online.target\nAfter=network-online.target\n\n");
+    unit.push_str("[Service]\nType=oneshot\n");
+    if !notify_only {
+        unit.push_str("# Installing runs sudo pacman, which needs a passwordless sudo rule\n");
+    }
+    unit.push_str(&format!("ExecStart={}\n", command));
+    unit
+}
+
+/// `reap-upgrade.timer` firing on an `OnCalendar=` schedule (e.g. `daily`);
+/// runs missed while the machine was off are caught up on the next boot
+pub fn upgrade_timer_unit(schedule: &str) -> String {
+    format!(
+        "[Unit]\nDescription=Scheduled reap upgrade ({})\n\n\
+         [Timer]\nOnCalendar={}\nPersistent=true\nRandomizedDelaySec=15min\n\n\
+         [Install]\nWantedBy=timers.target\n",
+        schedule, schedule
+    )
+}
+
 pub fn backup_config() -> Result<(), String> {
     use std::fs;
     use std::path::PathBuf;
@@ -671,11 +1311,11 @@ pub fn rollback(pkg: &str) {
     } else {
         eprintln!("[reap][rollback] No PKGBUILD backup found for {}.", pkg);
     }
-    // Optionally clean up failed build dirs
-    let tmp_dir = std::env::temp_dir().join(format!("reap-aur-{}", pkg));
-    if tmp_dir.exists() {
-        let _ = fs::remove_dir_all(&tmp_dir);
-        println!("[reap][rollback] Cleaned up temp dir for {}.", pkg);
+    // Clean up failed build dirs
+    for dir in crate::core::aur_build_dirs(pkg) {
+        if fs::remove_dir_all(&dir).is_ok() {
+            println!("[reap][rollback] Cleaned up {}.", dir.display());
+        }
     }
 }
 
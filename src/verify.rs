@@ -0,0 +1,279 @@
+use crate::core::ReapError;
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// Name of the checksum manifest `backup_package_state` keeps for packages
+/// reap built
+pub const MANIFEST_FILE: &str = "manifest";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileProblem {
+    Missing,
+    /// Differs from what was installed, with pacman's or reap's reason
+    Modified(String),
+}
+
+/// A file of an installed package that no longer matches what was installed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileIssue {
+    pub package: String,
+    pub path: String,
+    pub problem: FileProblem,
+}
+
+/// Issues from `pacman -Qkk` output, where each altered file is reported as
+/// `warning: <pkg>: <path> (<reason>)`
+pub fn parse_pacman_check(output: &str) -> Vec<FileIssue> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let rest = line.strip_prefix("warning: ")?;
+            let (package, rest) = rest.split_once(": ")?;
+            let (path, reason) = rest.trim_end().strip_suffix(')')?.rsplit_once(" (")?;
+            let problem = if reason == "No such file or directory" {
+                FileProblem::Missing
+            } else {
+                FileProblem::Modified(reason.to_string())
+            };
+            Some(FileIssue {
+                package: package.to_string(),
+                path: path.to_string(),
+                problem,
+            })
+        })
+        .collect()
+}
+
+/// SHA256 of a file's contents as lowercase hex
+pub fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut context = ring::digest::Context::new(&ring::digest::SHA256);
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        context.update(&buf[..n]);
+    }
+    Ok(context
+        .finish()
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// A manifest line, `<sha256> <mode> <path>`, for a regular file. Files that
+/// can't be read (e.g. root-only) are left out.
+pub fn manifest_line(path: &Path) -> Option<String> {
+    let meta = fs::symlink_metadata(path).ok()?;
+    if !meta.file_type().is_file() {
+        return None;
+    }
+    let hash = sha256_file(path).ok()?;
+    Some(format!(
+        "{} {:o} {}",
+        hash,
+        meta.permissions().mode() & 0o7777,
+        path.display()
+    ))
+}
+
+/// Write the manifest of `files` to `dest`
+pub fn write_manifest(dest: &Path, files: &[String]) -> std::io::Result<()> {
+    let lines: Vec<String> = files
+        .iter()
+        .filter_map(|f| manifest_line(Path::new(f)))
+        .collect();
+    fs::write(dest, lines.join("\n") + "\n")
+}
+
+/// Compare the files in a manifest with what is on disk now. Paths in
+/// `skip` (such as config files the user is meant to edit) are ignored.
+pub fn check_manifest(package: &str, manifest: &str, skip: &[String]) -> Vec<FileIssue> {
+    let mut issues = Vec::new();
+    for line in manifest.lines() {
+        let mut parts = line.splitn(3, ' ');
+        let (Some(hash), Some(mode), Some(path)) = (parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+        if skip.iter().any(|s| s == path) {
+            continue;
+        }
+        let issue = |problem| FileIssue {
+            package: package.to_string(),
+            path: path.to_string(),
+            problem,
+        };
+        let meta = match fs::symlink_metadata(path) {
+            Ok(meta) => meta,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                issues.push(issue(FileProblem::Missing));
+                continue;
+            }
+            Err(_) => continue,
+        };
+        if format!("{:o}", meta.permissions().mode() & 0o7777) != mode {
+            issues.push(issue(FileProblem::Modified(
+                "Permissions mismatch".to_string(),
+            )));
+        }
+        match sha256_file(Path::new(path)) {
+            Ok(current) if current != hash => issues.push(issue(FileProblem::Modified(
+                "SHA256 checksum mismatch".to_string(),
+            ))),
+            _ => {}
+        }
+    }
+    issues
+}
+
+/// Where `backup_package_state` keeps per-package backups
+pub fn backup_root() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("/tmp"))
+        .join("reap/backup")
+}
+
+/// The manifest from the newest backup of `pkg`, if reap built it
+pub fn latest_manifest(pkg: &str) -> Option<PathBuf> {
+    let mut backups: Vec<PathBuf> = fs::read_dir(backup_root().join(pkg))
+        .ok()?
+        .flatten()
+        .map(|e| e.path().join(MANIFEST_FILE))
+        .filter(|p| p.exists())
+        .collect();
+    // Backup dirs are named by timestamp
+    backups.sort();
+    backups.pop()
+}
+
+/// Config files pacman tracks separately and expects to be edited
+fn backup_files(pkg: &str) -> Vec<String> {
+    let Some(version) = crate::pacman::installed_versions().remove(pkg) else {
+        return Vec::new();
+    };
+    let files = format!("/var/lib/pacman/local/{}-{}/files", pkg, version);
+    fs::read_to_string(files)
+        .map(|text| {
+            text.split("%BACKUP%")
+                .nth(1)
+                .unwrap_or("")
+                .lines()
+                .take_while(|l| !l.starts_with('%'))
+                .filter_map(|l| l.split('\t').next())
+                .filter(|f| !f.is_empty())
+                .map(|f| format!("/{}", f))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn manifest_issues(pkg: &str) -> Vec<FileIssue> {
+    latest_manifest(pkg)
+        .and_then(|path| fs::read_to_string(path).ok())
+        .map(|text| check_manifest(pkg, &text, &backup_files(pkg)))
+        .unwrap_or_default()
+}
+
+/// Pacman and manifest issues for the same file are reported once
+fn merge(issues: &mut Vec<FileIssue>, more: Vec<FileIssue>) {
+    for issue in more {
+        if !issues.iter().any(|i| {
+            i.package == issue.package && i.path == issue.path && i.problem == issue.problem
+        }) {
+            issues.push(issue);
+        }
+    }
+}
+
+fn print_issues(issues: &[FileIssue]) {
+    let mut by_package: BTreeMap<&str, Vec<&FileIssue>> = BTreeMap::new();
+    for issue in issues {
+        by_package.entry(&issue.package).or_default().push(issue);
+    }
+    for (package, issues) in by_package {
+        println!("{} {}", "❌".red(), package.bold());
+        for issue in issues {
+            match &issue.problem {
+                FileProblem::Missing => {
+                    println!("   {} {}", "missing ".yellow(), issue.path)
+                }
+                FileProblem::Modified(reason) => println!(
+                    "   {} {} ({})",
+                    "modified".red(),
+                    issue.path,
+                    reason.dimmed()
+                ),
+            }
+        }
+    }
+}
+
+fn finish(
+    issues: Vec<FileIssue>,
+    flatpak_problems: Vec<String>,
+    checked: &str,
+) -> Result<(), ReapError> {
+    print_issues(&issues);
+    for problem in &flatpak_problems {
+        println!("{} {}", "❌".red(), problem);
+    }
+    let count = issues.len() + flatpak_problems.len();
+    if count == 0 {
+        println!("{} {}: no problems found", "✓".green(), checked);
+        Ok(())
+    } else {
+        Err(ReapError::CommandFailed(format!(
+            "{} problem(s) found in {}",
+            count, checked
+        )))
+    }
+}
+
+/// `reap verify <pkg>`: check an installed package's files against the
+/// pacman database (`pacman -Qkk`) and, for packages reap built, the
+/// checksum manifest kept at install time. Flatpak apps are checked with
+/// `flatpak repair --dry-run` on their installation.
+pub fn handle_verify(pkg: &str) -> Result<(), ReapError> {
+    if crate::pacman::is_installed(pkg) {
+        let mut issues = parse_pacman_check(&crate::pacman::check_files(Some(pkg)));
+        merge(&mut issues, manifest_issues(pkg));
+        return finish(issues, Vec::new(), pkg);
+    }
+    if let Some(user) = crate::flatpak::installed_scope(pkg) {
+        return finish(Vec::new(), crate::flatpak::check_installation(user), pkg);
+    }
+    Err(ReapError::SourceNotFound(format!(
+        "Installed package {}",
+        pkg
+    )))
+}
+
+/// `reap verify --all`: check every pacman package, every package reap
+/// built and every Flatpak installation
+pub fn handle_verify_all() -> Result<(), ReapError> {
+    println!("[reap] Checking installed files, this may take a while...");
+    let mut issues = parse_pacman_check(&crate::pacman::check_files(None));
+    if let Ok(entries) = fs::read_dir(backup_root()) {
+        for entry in entries.flatten() {
+            let pkg = entry.file_name().to_string_lossy().into_owned();
+            if crate::pacman::is_installed(&pkg) {
+                merge(&mut issues, manifest_issues(&pkg));
+            }
+        }
+    }
+    let mut flatpak_problems = Vec::new();
+    if crate::flatpak::is_flatpak_available() {
+        for user in [false, true] {
+            flatpak_problems.extend(crate::flatpak::check_installation(user));
+        }
+    }
+    finish(issues, flatpak_problems, "installed packages")
+}
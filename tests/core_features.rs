@@ -73,3 +73,3093 @@ async fn test_flatpak_install_failure() -> Result<()> {
     assert!(result.is_err());
     Ok(())
 }
+
+/// Test that a handler can be awaited inside a runtime; with no targets it touches nothing, not even the history.
+#[tokio::test]
+async fn test_handlers_run_inside_runtime() -> Result<()> {
+    let report =
+        reap::core::handle_install(Vec::new(), false, &reap::InstallOptions::default()).await;
+    assert!(report.succeeded.is_empty() && report.failed.is_empty());
+    Ok(())
+}
+
+/// Test that versioned and unversioned `provides` entries match the requested name.
+#[test]
+fn test_provides_matches() {
+    use reap::core::provides_matches;
+    assert!(provides_matches("ffmpeg", "ffmpeg"));
+    assert!(provides_matches("ffmpeg=6.1", "ffmpeg"));
+    assert!(provides_matches("ffmpeg>=6", "ffmpeg"));
+    assert!(!provides_matches("ffmpeg-full", "ffmpeg"));
+    assert!(!provides_matches("libffmpeg", "ffmpeg"));
+}
+
+/// Test that tap index `provides` metadata resolves virtual packages to their providers.
+#[test]
+fn test_tap_index_providers() {
+    let index = serde_json::json!({
+        "ffmpeg-full": { "desc": "FFmpeg with all options", "provides": ["ffmpeg=6.1", "libavcodec"] },
+        "ffmpeg-lite": { "desc": "Minimal FFmpeg", "provides": ["ffmpeg"] },
+        "mpv-git": { "desc": "Video player", "provides": ["mpv"] },
+        "htop": { "desc": "No provides field" }
+    });
+    let mut providers = reap::tap::index_providers(&index, "ffmpeg");
+    providers.sort();
+    assert_eq!(providers, vec!["ffmpeg-full", "ffmpeg-lite"]);
+    assert!(reap::tap::index_providers(&index, "vlc").is_empty());
+}
+
+/// Test that a single provider is chosen without prompting and no providers yields none.
+#[test]
+fn test_choose_single_provider() {
+    use reap::core::{Provider, Source, choose_provider};
+    let only = Provider {
+        name: "ffmpeg-full".to_string(),
+        source: Source::Aur,
+        tap: None,
+    };
+    let chosen = choose_provider("ffmpeg", vec![only]).expect("single provider");
+    assert_eq!(chosen.name, "ffmpeg-full");
+    assert_eq!(chosen.source, Source::Aur);
+    assert!(choose_provider("ffmpeg", Vec::new()).is_none());
+}
+
+/// Test that install records survive a save/load round trip and are keyed by package.
+#[test]
+fn test_install_registry_roundtrip() -> Result<()> {
+    use reap::history::{InstallRecord, InstallRegistry};
+    let path = std::env::temp_dir().join(format!("reap-installed-{}.json", std::process::id()));
+    let mut registry = InstallRegistry::default();
+    registry.record(
+        "foo",
+        InstallRecord {
+            source: reap::Source::Custom("mytap".to_string()),
+            tap: Some("mytap".to_string()),
+            installed_at: chrono::Utc::now(),
+            trust_score: Some(7.5),
+        },
+    );
+    registry.save_to(&path)?;
+    let loaded = InstallRegistry::load_from(&path);
+    fs::remove_file(&path).context("Failed to remove registry file")?;
+    let record = loaded.get("foo").context("record missing")?;
+    assert_eq!(record.tap.as_deref(), Some("mytap"));
+    assert_eq!(record.trust_score, Some(7.5));
+    assert!(loaded.get("bar").is_none());
+    Ok(())
+}
+
+/// Test that only added and removed PKGBUILD lines count as changes.
+#[test]
+fn test_count_changed_lines() {
+    use reap::core::count_changed_lines;
+    let old = "pkgname=foo\npkgver=1.0\npkgrel=1\n";
+    let new = "pkgname=foo\npkgver=1.1\npkgrel=1\n";
+    assert_eq!(count_changed_lines(old, old), 0);
+    assert_eq!(count_changed_lines(old, new), 2);
+    assert_eq!(count_changed_lines("", "a\nb"), 2);
+}
+
+/// Test that a PKGBUILD review is only remembered for the exact accepted revision.
+#[test]
+fn test_pkgbuild_review_memory() -> Result<()> {
+    use reap::core::pkgbuild_hash;
+    use reap::history::PkgbuildReviews;
+    let path = std::env::temp_dir().join(format!("reap-reviews-{}.json", std::process::id()));
+    let v1 = pkgbuild_hash("pkgname=foo\npkgver=1.0\n");
+    let v2 = pkgbuild_hash("pkgname=foo\npkgver=1.1\n");
+    assert_ne!(v1, v2);
+    let mut reviews = PkgbuildReviews::default();
+    reviews.accept("foo", &v1);
+    reviews.save_to(&path)?;
+    let loaded = PkgbuildReviews::load_from(&path);
+    fs::remove_file(&path).context("Failed to remove reviews file")?;
+    assert!(loaded.is_accepted("foo", &v1));
+    assert!(!loaded.is_accepted("foo", &v2));
+    assert!(!loaded.is_accepted("bar", &v1));
+    Ok(())
+}
+
+/// Test that reviews are keyed on the AUR clone's commit, and on the
+/// PKGBUILD hash outside a git checkout.
+#[test]
+fn test_review_revision() -> Result<()> {
+    use reap::core::{pkgbuild_hash, review_revision};
+    let dir = std::env::temp_dir().join(format!("reap-review-rev-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir)?;
+    let pkgbuild = "pkgname=foo\npkgver=1.0\n";
+    fs::write(dir.join("PKGBUILD"), pkgbuild)?;
+    assert_eq!(review_revision(&dir, pkgbuild), pkgbuild_hash(pkgbuild));
+    let git = |args: &[&str]| {
+        std::process::Command::new("git")
+            .arg("-C")
+            .arg(&dir)
+            .args(["-c", "user.name=reap", "-c", "user.email=reap@localhost"])
+            .args(args)
+            .output()
+    };
+    git(&["init", "--quiet"])?;
+    git(&["add", "PKGBUILD"])?;
+    git(&["commit", "--quiet", "-m", "1.0"])?;
+    let head = String::from_utf8_lossy(&git(&["rev-parse", "HEAD"])?.stdout)
+        .trim()
+        .to_string();
+    let revision = review_revision(&dir, pkgbuild);
+    fs::remove_dir_all(&dir).context("Failed to remove test repo")?;
+    assert_eq!(revision, head);
+    Ok(())
+}
+
+/// Test parsing of the build_isolation config value.
+#[test]
+fn test_build_isolation_parse() {
+    use reap::core::BuildIsolation;
+    assert_eq!(BuildIsolation::parse("none"), Some(BuildIsolation::None));
+    assert_eq!(
+        BuildIsolation::parse("Sandbox"),
+        Some(BuildIsolation::Sandbox)
+    );
+    assert_eq!(
+        BuildIsolation::parse("bwrap"),
+        Some(BuildIsolation::Sandbox)
+    );
+    assert_eq!(
+        BuildIsolation::parse("nspawn"),
+        Some(BuildIsolation::Nspawn)
+    );
+    assert_eq!(BuildIsolation::parse("docker"), None);
+    let config = GlobalConfig {
+        build_isolation: Some("docker".to_string()),
+        ..GlobalConfig::default()
+    };
+    assert!(BuildIsolation::from_config(&config).is_err());
+    assert_eq!(
+        BuildIsolation::from_config(&GlobalConfig::default()),
+        Ok(BuildIsolation::None)
+    );
+}
+
+/// Test that the dependency scheduler orders builds level by level and rejects cycles.
+#[test]
+fn test_build_levels() {
+    use reap::core::build_levels;
+    use std::collections::HashMap;
+    let graph: HashMap<String, Vec<String>> = [
+        ("app", vec!["lib-a", "lib-b"]),
+        ("lib-a", vec!["base"]),
+        ("lib-b", vec!["base", "glibc"]),
+        ("base", vec![]),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v.into_iter().map(String::from).collect()))
+    .collect();
+    let levels = build_levels(&graph).unwrap();
+    assert_eq!(
+        levels,
+        vec![
+            vec!["base".to_string()],
+            vec!["lib-a".to_string(), "lib-b".to_string()],
+            vec!["app".to_string()],
+        ]
+    );
+    let cycle: HashMap<String, Vec<String>> = [("a", vec!["b"]), ("b", vec!["a"])]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.into_iter().map(String::from).collect()))
+        .collect();
+    assert!(matches!(
+        build_levels(&cycle),
+        Err(reap::core::ReapError::DependencyCycle(pkgs)) if pkgs == ["a", "b"]
+    ));
+}
+
+/// Test PKGBUILD arch parsing and matching against the host architecture.
+#[test]
+fn test_supports_arch() -> Result<()> {
+    use reap::core::supports_arch;
+    let info = reap::enhanced_aur::EnhancedAurManager::new()
+        .parse_pkgbuild("foo", "pkgname=foo\narch=('x86_64' 'i686')\n")?;
+    assert_eq!(info.arch, vec!["x86_64", "i686"]);
+    assert!(supports_arch(&info.arch, "x86_64"));
+    assert!(!supports_arch(&info.arch, "aarch64"));
+    assert!(supports_arch(&["any".to_string()], "aarch64"));
+    assert!(supports_arch(&[], "riscv64"));
+    Ok(())
+}
+
+/// makepkg's `CARCH` comes from makepkg.conf, or `uname -m` with makepkg's names
+#[test]
+fn test_host_arch() {
+    use reap::core::{makepkg_arch, parse_carch};
+    let conf = "#CARCH=\"i686\"\nCARCH=\"armv7h\"\nCHOST=\"armv7l-unknown-linux-gnueabihf\"\n";
+    assert_eq!(parse_carch(conf).as_deref(), Some("armv7h"));
+    assert_eq!(parse_carch("CARCH='aarch64'").as_deref(), Some("aarch64"));
+    assert_eq!(parse_carch("MAKEFLAGS=\"-j4\""), None);
+    assert_eq!(makepkg_arch("armv7l"), "armv7h");
+    assert_eq!(makepkg_arch("i686"), "i686");
+    assert_eq!(makepkg_arch("x86"), "i686");
+    assert_eq!(makepkg_arch("aarch64"), "aarch64");
+    assert_eq!(makepkg_arch("x86_64"), "x86_64");
+    assert!(!reap::core::host_arch().is_empty());
+}
+
+/// Test that a bulk report with any failure turns into an error naming it.
+#[test]
+fn test_bulk_report_result() {
+    use reap::core::{BulkReport, ReapError};
+    let mut report = BulkReport::default();
+    report.record("ok-pkg", Ok(()));
+    assert!(report.is_success());
+    report.record("bad-pkg", Err(ReapError::SourceNotFound("bad-pkg".into())));
+    assert_eq!(report.failed[0].1, "bad-pkg was not found in any source");
+    let err = report.into_result().unwrap_err().to_string();
+    assert!(err.contains("1 of 2 packages failed: bad-pkg"));
+}
+
+/// Test that run_parallel reports every failed or panicked task instead of stopping at the first.
+#[tokio::test]
+async fn test_run_parallel_surfaces_failures() {
+    use reap::core::{ReapError, run_parallel};
+    let pkgs = vec!["good".to_string(), "bad".to_string(), "boom".to_string()];
+    let report = run_parallel(&pkgs, 2, |pkg| async move {
+        match pkg.as_str() {
+            "bad" => Err(ReapError::BuildFailed {
+                pkg,
+                stage: "build",
+            }),
+            "boom" => panic!("deliberate failure"),
+            _ => Ok(()),
+        }
+    })
+    .await;
+    assert_eq!(report.succeeded, vec!["good".to_string()]);
+    assert_eq!(report.failed.len(), 2);
+    assert!(report.failed[1].1.contains("panicked: deliberate failure"));
+    match report.into_result() {
+        Err(ReapError::PackagesFailed { failed, total }) => {
+            assert_eq!(failed, vec!["bad".to_string(), "boom".to_string()]);
+            assert_eq!(total, 3);
+        }
+        other => panic!("expected PackagesFailed, got {:?}", other),
+    }
+}
+
+/// Parallel tasks overlap, but never inside a pacman transaction.
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_parallel_pacman_transactions_take_turns() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    let active = Arc::new(AtomicUsize::new(0));
+    let most = Arc::new(AtomicUsize::new(0));
+    let pkgs: Vec<String> = (0..6).map(|i| format!("pkg{}", i)).collect();
+    let report = reap::core::run_parallel(&pkgs, 4, |_| {
+        let active = Arc::clone(&active);
+        let most = Arc::clone(&most);
+        async move {
+            let _transaction = reap::pacman::transaction();
+            let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+            most.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            active.fetch_sub(1, Ordering::SeqCst);
+            Ok(())
+        }
+    })
+    .await;
+    assert!(report.is_success());
+    assert_eq!(most.load(Ordering::SeqCst), 1);
+}
+
+/// Test AUR endpoint validation and the fallback to upstream.
+#[test]
+fn test_aur_url_config() {
+    use reap::config::{DEFAULT_AUR_RPC_URL, DEFAULT_AUR_URL, validate_url};
+    assert_eq!(
+        validate_url("https://aur.example.org/").unwrap(),
+        "https://aur.example.org"
+    );
+    assert_eq!(
+        validate_url("http://127.0.0.1:8080/aur/rpc").unwrap(),
+        "http://127.0.0.1:8080/aur/rpc"
+    );
+    assert!(validate_url("aur.example.org").is_err());
+    assert!(validate_url("ftp://aur.example.org").is_err());
+    assert!(validate_url("https://aur.example.org/rpc?v=5").is_err());
+    let config = GlobalConfig {
+        aur_url: Some("not a url".into()),
+        aur_rpc_url: None,
+        ..GlobalConfig::default()
+    };
+    assert_eq!(config.aur_url(), DEFAULT_AUR_URL);
+    assert_eq!(config.aur_rpc_url(), DEFAULT_AUR_RPC_URL);
+}
+
+/// Test offline source cache checks against makepkg's source naming.
+#[test]
+fn test_missing_sources() -> Result<()> {
+    use reap::core::{missing_sources, source_filename};
+    assert_eq!(
+        source_filename("https://example.org/foo-1.0.tar.gz"),
+        "foo-1.0.tar.gz"
+    );
+    assert_eq!(
+        source_filename("foo.tar.gz::https://example.org/v1.0.tar.gz"),
+        "foo.tar.gz"
+    );
+    assert_eq!(
+        source_filename("git+https://example.org/foo.git#tag=v1"),
+        "foo"
+    );
+    assert_eq!(source_filename("foo.service"), "foo.service");
+    let dir = std::env::temp_dir().join(format!("reap-test-sources-{}", std::process::id()));
+    fs::create_dir_all(dir.join("foo"))?;
+    fs::write(dir.join("foo.service"), "")?;
+    let srcinfo = "pkgbase = foo\n\tsource = git+https://example.org/foo.git\n\tsource = foo.service\n\tsource_x86_64 = https://example.org/foo-x86_64.bin\n\tsource_aarch64 = https://example.org/foo-aarch64.bin\n";
+    assert_eq!(
+        missing_sources(srcinfo, &dir, "x86_64"),
+        vec!["foo-x86_64.bin"]
+    );
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+/// Test package file name parsing and local repo tracking.
+#[test]
+fn test_local_repo_tracking() -> Result<()> {
+    use reap::core::package_file_name;
+    use reap::history::LocalRepos;
+    assert_eq!(
+        package_file_name("foo-bar-1.0-1-x86_64.pkg.tar.zst"),
+        Some("foo-bar")
+    );
+    assert_eq!(
+        package_file_name("foo-1:2.0.r3-2-any.pkg.tar.xz"),
+        Some("foo")
+    );
+    assert_eq!(package_file_name("custom.db.tar.gz"), None);
+    assert_eq!(package_file_name("1.0-1-x86_64.pkg.tar.zst"), None);
+
+    let path = std::env::temp_dir().join(format!("reap-test-repos-{}.json", std::process::id()));
+    let mut repos = LocalRepos::default();
+    repos.record(
+        "/srv/repo/custom.db.tar.gz",
+        "foo",
+        "foo-1.0-1-x86_64.pkg.tar.zst",
+    );
+    repos.record(
+        "/srv/repo/custom.db.tar.gz",
+        "foo",
+        "foo-1.1-1-x86_64.pkg.tar.zst",
+    );
+    repos.save_to(&path)?;
+    let loaded = LocalRepos::load_from(&path);
+    let packages = loaded
+        .packages("/srv/repo/custom.db.tar.gz")
+        .context("repo not recorded")?;
+    assert_eq!(packages.len(), 1);
+    assert_eq!(packages["foo"].file, "foo-1.1-1-x86_64.pkg.tar.zst");
+    fs::remove_file(&path)?;
+    Ok(())
+}
+
+/// Test signing key checks on gpg's colon-delimited output.
+#[test]
+fn test_secret_key_status() {
+    use reap::core::secret_key_status;
+    let now = 1_700_000_000;
+    let usable = "sec:u:255:22:ABCDEF0123456789:1600000000:1800000000::u:::scESC:::+:::23::0:\nfpr:::::::::0123:\n";
+    assert!(secret_key_status(usable, now).is_ok());
+    let lapsed = usable.replace(":1800000000:", ":1650000000:");
+    assert_eq!(
+        secret_key_status(&lapsed, now).unwrap_err(),
+        "key has expired"
+    );
+    let revoked = usable.replacen("sec:u:", "sec:r:", 1);
+    assert!(secret_key_status(&revoked, now).is_err());
+    let encrypt_only = usable.replace(":scESC:", ":e:");
+    assert_eq!(
+        secret_key_status(&encrypt_only, now).unwrap_err(),
+        "key cannot sign"
+    );
+    assert_eq!(secret_key_status("", now).unwrap_err(), "no secret key");
+}
+
+/// Test that profile settings override the global config where set.
+#[test]
+fn test_profile_effective_settings() {
+    use reap::profiles::{ProfileConfig, effective_settings};
+    let global = GlobalConfig::default();
+    let secure = ProfileConfig {
+        name: "secure".into(),
+        backend_order: vec!["aur".into(), "pacman".into()],
+        strict_signatures: Some(true),
+        gpg_keyserver: Some("hkps://keyserver.ubuntu.com".into()),
+        ..ProfileConfig::default()
+    };
+    let settings: std::collections::HashMap<_, _> =
+        effective_settings(&secure, &global).into_iter().collect();
+    assert_eq!(settings["backend_order"], "aur, pacman");
+    assert_eq!(settings["strict_signatures"], "true");
+    assert_eq!(settings["gpg_keyserver"], "hkps://keyserver.ubuntu.com");
+
+    let inherit = ProfileConfig {
+        backend_order: vec![],
+        ..ProfileConfig::default()
+    };
+    let settings: std::collections::HashMap<_, _> =
+        effective_settings(&inherit, &global).into_iter().collect();
+    assert_eq!(settings["backend_order"], global.backend_order.join(", "));
+    assert_eq!(settings["gpg_keyserver"], "hkps://keys.openpgp.org");
+}
+
+/// Test that cached trust scores are reused only within the cache TTL.
+#[test]
+fn test_trust_score_cache_ttl() -> Result<()> {
+    use reap::trust::{TrustEngine, TrustScore};
+    use std::time::Duration;
+    let dir = std::env::temp_dir().join(format!("reap-test-trust-{}", std::process::id()));
+    let score = TrustScore {
+        package: "foo".into(),
+        signature_valid: true,
+        publisher_verified: false,
+        community_votes: 12,
+        maintainer_reputation: 5.0,
+        last_audit_date: None,
+        security_flags: vec![],
+        overall_score: 7.0,
+        maintainer: Some("alice".into()),
+        orphaned: false,
+        out_of_date: None,
+    };
+    let engine = TrustEngine::with_cache_dir(dir.clone(), Duration::from_secs(3600));
+    engine.cache_trust_score(&score)?;
+    let cached = engine
+        .get_cached_trust_score("foo")
+        .context("score not cached")?;
+    assert_eq!(cached.community_votes, 12);
+    assert!(engine.get_cached_trust_score("bar").is_none());
+
+    std::thread::sleep(Duration::from_millis(20));
+    let expired = TrustEngine::with_cache_dir(dir.clone(), Duration::from_millis(1));
+    assert!(expired.get_cached_trust_score("foo").is_none());
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+/// Test that the AUR policy reports orphaned and out-of-date packages only when enabled.
+#[test]
+fn test_aur_policy_issues() {
+    use reap::trust::{TrustScore, aur_policy_issues};
+    let score = TrustScore {
+        package: "abandoned".into(),
+        signature_valid: false,
+        publisher_verified: false,
+        community_votes: 0,
+        maintainer_reputation: 5.0,
+        last_audit_date: None,
+        security_flags: vec![],
+        overall_score: 4.0,
+        maintainer: None,
+        orphaned: true,
+        out_of_date: chrono::DateTime::from_timestamp(1_700_000_000, 0),
+    };
+    assert!(aur_policy_issues(&score, false, false).is_empty());
+    let issues = aur_policy_issues(&score, true, true);
+    assert_eq!(issues.len(), 2);
+    assert!(issues[0].contains("orphaned"));
+    assert!(issues[1].contains("2023-11-14"));
+    assert_eq!(aur_policy_issues(&score, false, true).len(), 1);
+}
+
+/// Test parsing comments from an AUR package page, newest first and pinned ones once.
+#[test]
+fn test_parse_aur_comments() {
+    use reap::aur::parse_comments;
+    let html = r##"
+<div class="comments package-comments"><h3>Pinned Comments</h3>
+<h4 id="comment-200" class="comment-header">
+    <a href="/account/bob" title="View account information for bob">bob</a> commented on <a href="#comment-200" class="date">2024-03-01 12:00 (UTC)</a>
+</h4>
+<div id="comment-200-content" class="article-content">
+    <div><p>Import the key first: gpg --recv-keys ABCD</p></div>
+</div>
+</div>
+<div class="comments package-comments"><h3>Latest Comments</h3>
+<h4 id="comment-300" class="comment-header">
+    <a href="/account/alice" title="View account information for alice">alice</a> commented on <a href="#comment-300" class="date">2024-05-02 08:30 (UTC)</a>
+</h4>
+<div id="comment-300-content" class="article-content">
+    <div><p>Build fails with gcc 14 &amp; needs <code>-fpermissive</code></p></div>
+</div>
+<h4 id="comment-200" class="comment-header">
+    <a href="/account/bob" title="View account information for bob">bob</a> commented on <a href="#comment-200" class="date">2024-03-01 12:00 (UTC)</a>
+</h4>
+<div id="comment-200-content" class="article-content">
+    <div><p>Import the key first: gpg --recv-keys ABCD</p></div>
+</div>
+</div>
+<footer>Copyright</footer>
+"##;
+    let comments = parse_comments(html);
+    assert_eq!(comments.len(), 2);
+    assert_eq!(comments[0].id, 300);
+    assert_eq!(comments[0].author, "alice");
+    assert_eq!(comments[0].date, "2024-05-02 08:30 (UTC)");
+    assert_eq!(
+        comments[0].text,
+        "Build fails with gcc 14 & needs -fpermissive"
+    );
+    assert_eq!(comments[1].author, "bob");
+    assert!(parse_comments("<html></html>").is_empty());
+}
+
+/// Test extracting the AUR session id from Set-Cookie headers.
+#[test]
+fn test_aur_session_cookie() {
+    use reap::aur::session_cookie;
+    assert_eq!(
+        session_cookie("AURSID=abc123; Path=/; HttpOnly; Secure; SameSite=strict").as_deref(),
+        Some("abc123")
+    );
+    assert_eq!(session_cookie("AURTZ=UTC; Path=/"), None);
+    assert_eq!(session_cookie("AURSID=; Max-Age=0"), None);
+}
+
+/// Test build time estimates from recorded builds and the size/duration helpers.
+#[test]
+fn test_build_time_estimates() -> Result<()> {
+    use reap::history::BuildTimes;
+    use std::time::Duration;
+    let path =
+        std::env::temp_dir().join(format!("reap-test-build-times-{}.json", std::process::id()));
+    let mut times = BuildTimes::default();
+    assert_eq!(times.estimate("electron-app"), None);
+    for mins in [10, 20, 30, 40, 50, 60] {
+        times.record("electron-app", Duration::from_secs(mins * 60));
+    }
+    times.save_to(&path)?;
+    let loaded = BuildTimes::load_from(&path);
+    // Only the last five builds count: 20..=60 minutes
+    assert_eq!(loaded.builds["electron-app"].len(), 5);
+    assert_eq!(
+        loaded.estimate("electron-app"),
+        Some(Duration::from_secs(40 * 60))
+    );
+    fs::remove_file(&path)?;
+
+    assert_eq!(utils::format_duration(Duration::from_secs(45)), "45s");
+    assert_eq!(utils::format_duration(Duration::from_secs(750)), "12m 30s");
+    assert_eq!(utils::format_duration(Duration::from_secs(3900)), "1h 05m");
+    assert_eq!(reap::flatpak::parse_size(" 45.2 MB"), Some(45_200_000));
+    assert_eq!(reap::flatpak::parse_size("812 bytes"), Some(812));
+    assert_eq!(reap::flatpak::parse_size("lots"), None);
+    Ok(())
+}
+
+/// Test the operation log: ids, lookup, undo marking and version diffs.
+#[test]
+fn test_operation_log() -> Result<()> {
+    use reap::history::{OperationKind, OperationLog, PackageChange};
+    use std::collections::BTreeMap;
+    let before: BTreeMap<String, String> =
+        [("htop", "3.3.0-1"), ("vim", "9.1-1"), ("nano", "8.0-1")]
+            .into_iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect();
+    let mut after = before.clone();
+    after.insert("vim".into(), "9.1-2".into());
+    after.remove("nano");
+    after.insert("ncurses-extra".into(), "1.0-1".into());
+    let changes = PackageChange::between(&before, &after);
+    let names: Vec<&str> = changes.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["nano", "ncurses-extra", "vim"]);
+    assert_eq!(changes[0].new_version, None);
+    assert_eq!(changes[1].old_version, None);
+    assert_eq!(changes[2].old_version.as_deref(), Some("9.1-1"));
+
+    let path = std::env::temp_dir().join(format!("reap-test-history-{}.json", std::process::id()));
+    let mut log = OperationLog::default();
+    let first = log.record(OperationKind::Install, vec!["htop".into()], vec![], None);
+    let second = log.record(
+        OperationKind::Upgrade,
+        vec![],
+        changes,
+        Some("failed: foo".into()),
+    );
+    assert_eq!((first, second), (1, 2));
+    log.mark_undone(first);
+    log.save_to(&path)?;
+    let loaded = OperationLog::load_from(&path);
+    assert!(loaded.get(1).context("missing #1")?.undone);
+    assert_eq!(loaded.get(2).context("missing #2")?.changes.len(), 3);
+    let recent: Vec<u64> = loaded.recent(1).map(|op| op.id).collect();
+    assert_eq!(recent, [2]);
+    fs::remove_file(&path)?;
+    Ok(())
+}
+
+/// Test source choice keys, their priorities and reading remembered choices.
+#[test]
+fn test_source_choices() -> Result<()> {
+    use reap::core::{Source, SourceCandidate};
+    use reap::tap::Tap;
+    let tap = SourceCandidate {
+        source: Source::Custom("ghost".into()),
+        tap: Some(Tap {
+            name: "ghost".into(),
+            url: "https://example.com/ghost.git".into(),
+            priority: 90,
+            enabled: true,
+        }),
+        version: Some("1.2.0".into()),
+    };
+    let aur = SourceCandidate {
+        source: Source::Aur,
+        tap: None,
+        version: Some("1.3.0-1".into()),
+    };
+    assert_eq!(tap.key(), "tap:ghost");
+    assert_eq!(aur.key(), "aur");
+    let (source, tap_name, prio, _) = tap.resolved();
+    assert_eq!(
+        (source, tap_name.as_deref(), prio),
+        (Source::Custom("ghost".into()), Some("ghost"), 90)
+    );
+    assert_eq!(aur.resolved().2, 10);
+
+    let config: GlobalConfig = toml::from_str(
+        r#"backend_order = ['tap', 'aur']
+auto_resolve_deps = true
+noconfirm = false
+log_verbose = false
+
+[source_choices]
+ghostty = "aur"
+"#,
+    )?;
+    let choices = config.source_choices.context("no source_choices")?;
+    assert_eq!(choices["ghostty"], "aur");
+    Ok(())
+}
+
+/// Test that search results from different sources group under one package name.
+#[test]
+fn test_group_search_results() {
+    use reap::core::{Source, group_search_results};
+    let output = "extra/firefox 131.0-1 [installed]\n    Fast, Private & Safe Web Browser\nextra/firefox-i18n-de 131.0-1\n    German language pack for Firefox\n";
+    let mut results = reap::pacman::parse_search(output);
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].name, "firefox");
+    assert_eq!(results[0].version, "131.0-1");
+    assert_eq!(results[0].description, "Fast, Private & Safe Web Browser");
+    results.push(reap::SearchResult {
+        name: "firefox".into(),
+        version: "132.0a1-1".into(),
+        description: String::new(),
+        source: Source::Aur,
+        votes: None,
+    });
+    results.push(reap::SearchResult {
+        name: "org.mozilla.firefox".into(),
+        version: "131.0".into(),
+        description: "Firefox - Web browser".into(),
+        source: Source::Flatpak,
+        votes: None,
+    });
+    let groups = group_search_results(results);
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups[0].name, "firefox");
+    let sources: Vec<&Source> = groups[0].results.iter().map(|r| &r.source).collect();
+    assert_eq!(sources, [&Source::Pacman, &Source::Aur, &Source::Flatpak]);
+    assert_eq!(groups[1].name, "firefox-i18n-de");
+}
+
+/// Test that an unchanged PKGBUILD diffs empty and that build dirs are matched per package.
+#[test]
+fn test_pkgbuild_diff_and_build_dirs() -> Result<()> {
+    use reap::core::{is_aur_build_dir, pkgbuild_changes};
+    let pkgbuild = "pkgname=foo\npkgver=1.0\npkgrel=1\nsource=(foo.tar.gz)\n";
+    let stored =
+        std::env::temp_dir().join(format!("reap-test-built-PKGBUILD-{}", std::process::id()));
+    fs::write(&stored, pkgbuild)?;
+    let local = fs::read_to_string(&stored)?;
+    assert!(pkgbuild_changes(&local, pkgbuild).is_empty());
+    assert_eq!(
+        pkgbuild_changes(&local, &pkgbuild.replace("1.0", "1.1")),
+        ["- pkgver=1.0", "+ pkgver=1.1"]
+    );
+    fs::remove_file(&stored)?;
+
+    assert!(is_aur_build_dir("foo", "reap-aur-foo-2026-10-16 12:00:00"));
+    assert!(!is_aur_build_dir(
+        "foo",
+        "reap-aur-foo-bar-2026-10-16 12:00:00"
+    ));
+    assert!(is_aur_build_dir(
+        "foo-bar",
+        "reap-aur-foo-bar-2026-10-16 12:00:00"
+    ));
+    assert!(!is_aur_build_dir("foo", "reap-aur-foo"));
+    Ok(())
+}
+
+/// Test VCS package detection, .SRCINFO source parsing and commit tracking.
+#[test]
+fn test_vcs_devel_tracking() -> Result<()> {
+    use reap::aur::{VcsSource, is_vcs_package, vcs_sources};
+    use reap::history::VcsCommits;
+    assert!(is_vcs_package("neovim-git", None));
+    assert!(is_vcs_package(
+        "foo",
+        Some("pkgname=foo\npkgver() {\n  git describe\n}\n")
+    ));
+    assert!(!is_vcs_package("yay-bin", Some("pkgname=yay-bin\n")));
+
+    let srcinfo = "pkgbase = neovim-git\n\
+        \tsource = neovim::git+https://github.com/neovim/neovim.git\n\
+        \tsource = git+https://example.org/lib.git#branch=stable\n\
+        \tsource = git+https://example.org/pinned.git#tag=v1.0\n\
+        \tsource = svn+https://svn.example.org/trunk\n\
+        \tsource = neovim.desktop\n\
+        \tsource_x86_64 = https://example.org/blob.tar.gz\n";
+    let sources = vcs_sources(srcinfo);
+    assert_eq!(
+        sources,
+        vec![
+            VcsSource {
+                vcs: "git".into(),
+                url: "https://github.com/neovim/neovim.git".into(),
+                branch: None,
+            },
+            VcsSource {
+                vcs: "git".into(),
+                url: "https://example.org/lib.git".into(),
+                branch: Some("stable".into()),
+            },
+            VcsSource {
+                vcs: "svn".into(),
+                url: "https://svn.example.org/trunk".into(),
+                branch: None,
+            },
+        ]
+    );
+
+    let path = std::env::temp_dir().join(format!("reap-test-vcs-{}.json", std::process::id()));
+    let mut store = VcsCommits::default();
+    let upstream = vec!["abc123".to_string()];
+    // Untracked packages are never reported as outdated
+    assert!(!store.is_outdated("neovim-git", &upstream));
+    store.set("neovim-git", upstream.clone());
+    store.save_to(&path)?;
+    let loaded = VcsCommits::load_from(&path);
+    assert!(!loaded.is_outdated("neovim-git", &upstream));
+    assert!(loaded.is_outdated("neovim-git", &["def456".to_string()]));
+    fs::remove_file(&path)?;
+    Ok(())
+}
+
+/// Test build dependency tracking and which deps are safe to remove.
+#[test]
+fn test_build_deps_tracking() -> Result<()> {
+    use reap::history::BuildDeps;
+    use std::collections::BTreeSet;
+    let path =
+        std::env::temp_dir().join(format!("reap-test-build-deps-{}.json", std::process::id()));
+    let mut deps = BuildDeps::default();
+    deps.record("neovim-git", ["cmake".to_string(), "ninja".to_string()]);
+    deps.record("alacritty", ["rust".to_string(), "cmake".to_string()]);
+    deps.save_to(&path)?;
+    let mut loaded = BuildDeps::load_from(&path);
+    assert_eq!(loaded.all().len(), 3);
+
+    // cmake is still required by something else, so only the orphans go
+    let orphans: BTreeSet<String> = ["ninja", "rust", "unrelated"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let candidates: Vec<String> = loaded.all().into_iter().collect();
+    assert_eq!(
+        reap::core::removable_build_deps(&candidates, &orphans),
+        vec!["ninja".to_string(), "rust".to_string()]
+    );
+
+    loaded.forget(&["ninja".to_string(), "rust".to_string()]);
+    assert_eq!(loaded.packages["neovim-git"], vec!["cmake".to_string()]);
+    loaded.forget(&["cmake".to_string()]);
+    assert!(loaded.packages.is_empty());
+    fs::remove_file(&path)?;
+    Ok(())
+}
+
+/// Test the generated systemd units for scheduled upgrades.
+#[test]
+fn test_upgrade_timer_units() {
+    let service = utils::upgrade_service_unit("/usr/bin/reap", false, false);
+    assert!(service.contains("Type=oneshot"));
+    assert!(service.contains("ExecStart=/usr/bin/reap --noconfirm upgrade\n"));
+    let aur = utils::upgrade_service_unit("/usr/bin/reap", true, false);
+    assert!(aur.contains("ExecStart=/usr/bin/reap --noconfirm upgrade --aur\n"));
+    let notify = utils::upgrade_service_unit("/usr/bin/reap", false, true);
+    assert!(notify.contains("ExecStart=/usr/bin/reap update --notify\n"));
+    assert!(!notify.contains("sudo"));
+
+    let timer = utils::upgrade_timer_unit("weekly");
+    assert!(timer.contains("OnCalendar=weekly\n"));
+    assert!(timer.contains("Persistent=true"));
+    assert!(timer.contains("WantedBy=timers.target"));
+}
+
+/// Test the update notification text.
+#[test]
+fn test_update_notification() {
+    let (summary, body) = utils::update_notification(&["yay"]);
+    assert_eq!(summary, "1 package update available");
+    assert_eq!(body, "yay");
+    let pkgs = ["a", "b", "c", "d", "e", "f", "g"];
+    let (summary, body) = utils::update_notification(&pkgs);
+    assert_eq!(summary, "7 package updates available");
+    assert_eq!(body, "a, b, c, d, e and 2 more");
+}
+
+/// Test when `--color` colorizes output.
+#[test]
+fn test_use_color() {
+    assert!(utils::use_color("auto", false, true));
+    assert!(!utils::use_color("auto", false, false));
+    assert!(!utils::use_color("auto", true, true));
+    assert!(utils::use_color("always", true, false));
+    assert!(!utils::use_color("never", false, true));
+}
+
+/// Test which pager long output goes through.
+#[test]
+fn test_pager_command() {
+    assert_eq!(
+        utils::pager_command(None, None),
+        Some("less -R".to_string())
+    );
+    assert_eq!(
+        utils::pager_command(None, Some("most")),
+        Some("most".to_string())
+    );
+    assert_eq!(
+        utils::pager_command(Some("bat -p"), Some("most")),
+        Some("bat -p".to_string())
+    );
+    assert_eq!(utils::pager_command(Some(""), Some("most")), None);
+    assert_eq!(utils::pager_command(None, Some("cat")), None);
+}
+
+/// Test file owner parsing for pacman and Flatpak paths.
+#[test]
+fn test_file_owners() {
+    use std::path::{Path, PathBuf};
+    assert_eq!(
+        reap::pacman::parse_owner("/usr/bin/ls is owned by coreutils 9.4-3\n"),
+        Some(("coreutils".to_string(), "9.4-3".to_string()))
+    );
+    assert_eq!(
+        reap::pacman::parse_owner("error: No package owns /tmp/x"),
+        None
+    );
+
+    let roots = vec![
+        PathBuf::from("/var/lib/flatpak"),
+        PathBuf::from("/home/u/.local/share/flatpak"),
+    ];
+    let owner = |p: &str| reap::flatpak::owner_of(Path::new(p), &roots);
+    assert_eq!(
+        owner("/var/lib/flatpak/app/org.mozilla.firefox/x86_64/stable/active/files/bin/firefox"),
+        Some("org.mozilla.firefox".to_string())
+    );
+    assert_eq!(
+        owner("/home/u/.local/share/flatpak/exports/bin/com.spotify.Client"),
+        Some("com.spotify.Client".to_string())
+    );
+    assert_eq!(
+        owner("/var/lib/flatpak/exports/share/applications/org.gimp.GIMP.desktop"),
+        Some("org.gimp.GIMP".to_string())
+    );
+    assert_eq!(owner("/usr/bin/firefox"), None);
+}
+
+/// Test that pacman-style flag clusters parse like pacman's.
+#[test]
+fn test_expand_pacman_flags() {
+    use clap::Parser;
+    use reap::cli::{Cli, expand_pacman_flags, hoist_operation_flags};
+    let parse = |args: &[&str]| {
+        Cli::try_parse_from(expand_pacman_flags(args.iter().map(|a| a.to_string())))
+            .expect("parses")
+    };
+    let cli = parse(&["reap", "-Syu"]);
+    assert!(cli.refresh && cli.upgrade);
+    assert_eq!(cli.sync, Some(vec![]));
+    let cli = parse(&["reap", "-Ss", "editor"]);
+    assert!(cli.sync_search);
+    assert_eq!(cli.sync, Some(vec!["editor".to_string()]));
+    let cli = parse(&["reap", "-Qo", "/usr/bin/ls"]);
+    assert!(cli.owns);
+    assert_eq!(cli.search, Some(vec!["/usr/bin/ls".to_string()]));
+    // Plain operations and long flags are left alone
+    assert_eq!(
+        expand_pacman_flags(["-S", "foo", "--sync-search"].map(String::from)),
+        vec!["-S", "foo", "--sync-search"]
+    );
+    assert_eq!(expand_pacman_flags(["-Sfoo".to_string()]), vec!["-Sfoo"]);
+    // Flags after the operation move in front of it
+    let parse = |args: &[&str]| {
+        Cli::try_parse_from(hoist_operation_flags(expand_pacman_flags(
+            args.iter().map(|a| a.to_string()),
+        )))
+        .expect("parses")
+    };
+    let cli = parse(&["reap", "-S", "--print-srcinfo", "yay"]);
+    assert!(cli.print_srcinfo);
+    assert_eq!(cli.sync, Some(vec!["yay".to_string()]));
+    let cli = parse(&["reap", "-S", "foo", "--overwrite", "/usr/lib/*", "bar"]);
+    assert_eq!(cli.overwrite, vec!["/usr/lib/*".to_string()]);
+    assert_eq!(cli.sync, Some(vec!["foo".to_string(), "bar".to_string()]));
+    let cli = parse(&["reap", "-Syu", "--noconfirm"]);
+    assert!(cli.noconfirm && cli.refresh && cli.upgrade);
+}
+
+/// Test package list parsing for `-S --from-file`.
+#[test]
+fn test_parse_package_list() {
+    use reap::core::{InstallOptions, PackageListEntry, parse_package_list};
+    let list = "# my laptop\n\
+        neovim\n\
+        \n\
+        aur:yay-bin   # helper\n\
+        repo:firefox\n\
+        flatpak:org.gimp.GIMP\n\
+        tap:ghost:ghostctl\n";
+    let entries = parse_package_list(list).expect("valid list");
+    let lines: Vec<String> = entries.iter().map(PackageListEntry::to_line).collect();
+    assert_eq!(
+        lines,
+        vec![
+            "neovim",
+            "aur:yay-bin",
+            "pacman:firefox",
+            "flatpak:org.gimp.GIMP",
+            "tap:ghost:ghostctl"
+        ]
+    );
+    let opts = entries[4].install_options(&InstallOptions::default());
+    assert_eq!(opts.backend_order, Some(vec!["tap".to_string()]));
+    assert_eq!(opts.tap.as_deref(), Some("ghost"));
+    assert_eq!(
+        entries[0]
+            .install_options(&InstallOptions::default())
+            .backend_order,
+        None
+    );
+
+    let err = parse_package_list("vim\nsnap:code\n").unwrap_err();
+    assert_eq!(err, "line 2: unknown source 'snap'");
+}
+
+/// Test the exported state file and tap conflict handling on import.
+#[test]
+fn test_state_roundtrip() {
+    use reap::state::{ReapState, TapImport, TapState, classify_tap};
+    let config: toml::Table = "backend_order = [\"aur\", \"pacman\"]\nparallel = 8\n"
+        .parse()
+        .expect("valid toml");
+    let state = ReapState {
+        exported_at: Some("2026-10-16T12:00:00+00:00".into()),
+        active_profile: Some("developer".into()),
+        packages: vec!["aur:yay-bin".into(), "flatpak:org.gimp.GIMP".into()],
+        pinned: vec!["linux-lts".into()],
+        taps: vec![TapState {
+            name: "ghost".into(),
+            url: "https://github.com/ghostkellz/tap.git".into(),
+            priority: 80,
+        }],
+        profiles: vec![reap::profiles::create_developer_profile()],
+        config: Some(config.clone()),
+    };
+    let text = state.to_toml().expect("serializes");
+    let loaded = ReapState::from_toml(&text).expect("parses");
+    assert_eq!(loaded.packages, state.packages);
+    assert_eq!(loaded.taps, state.taps);
+    assert_eq!(loaded.pinned, state.pinned);
+    assert_eq!(loaded.active_profile.as_deref(), Some("developer"));
+    assert_eq!(loaded.profiles[0].name, "developer");
+    assert_eq!(loaded.config.as_ref(), Some(&config));
+    // Older or hand-written files may leave sections out
+    assert!(ReapState::from_toml("packages = [\"vim\"]").is_ok());
+    // The AUR session cookie never goes into an export
+    let mut with_cookie = config.clone();
+    with_cookie.insert("aur_cookie".into(), "secret".into());
+    assert_eq!(reap::state::without_secrets(with_cookie), config);
+
+    let tap = &state.taps[0];
+    let existing = |url: &str, priority: u32| reap::tap::Tap {
+        name: "ghost".into(),
+        url: url.into(),
+        priority,
+        enabled: true,
+    };
+    assert_eq!(classify_tap(tap, None), TapImport::Add);
+    assert_eq!(
+        classify_tap(tap, Some(&existing(&tap.url, 80))),
+        TapImport::Unchanged
+    );
+    assert_eq!(
+        classify_tap(tap, Some(&existing(&tap.url, 50))),
+        TapImport::Add
+    );
+    assert_eq!(
+        classify_tap(tap, Some(&existing("https://example.org/fork.git", 80))),
+        TapImport::Conflict {
+            existing_url: "https://example.org/fork.git".into()
+        }
+    );
+}
+
+/// Test the git clone arguments for shallow and full tap clones, and tap size accounting.
+#[test]
+fn test_tap_clone_args() {
+    use reap::tap::{clone_args, dir_size};
+    assert_eq!(clone_args(None), vec!["clone"]);
+    assert_eq!(
+        clone_args(Some(1)),
+        vec!["clone", "--depth", "1", "--single-branch"]
+    );
+
+    let dir = std::env::temp_dir().join(format!("reap-test-tapsize-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("a"), [0u8; 100]).unwrap();
+    std::fs::write(dir.join("sub/b"), [0u8; 28]).unwrap();
+    assert_eq!(dir_size(&dir), 128);
+    assert_eq!(dir_size(&dir.join("missing")), 0);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Test that per-package source overrides are validated and take precedence in source resolution.
+#[test]
+fn test_source_overrides() {
+    use reap::config::{GlobalConfig, validate_source_override};
+    use reap::core::{Source, resolve_package_source};
+    for ok in ["aur", "pacman", "flatpak"] {
+        assert!(validate_source_override(ok).is_ok(), "{ok}");
+    }
+    // Taps can't be built from yet
+    for bad in ["", "tap:", "tap:ghost", "snap", "AUR"] {
+        assert!(validate_source_override(bad).is_err(), "{bad}");
+    }
+
+    let mut config = GlobalConfig::default();
+    assert_eq!(config.source_override("yay"), None);
+    config.source_overrides = Some(
+        [
+            ("yay".to_string(), "aur".to_string()),
+            ("firefox".to_string(), "flatpak".to_string()),
+        ]
+        .into(),
+    );
+    assert_eq!(config.source_override("yay"), Some("aur"));
+    let resolved = resolve_package_source("yay", None, &config).unwrap();
+    assert_eq!(resolved.0, Source::Aur);
+    let resolved = resolve_package_source("firefox", None, &config).unwrap();
+    assert_eq!(resolved.0, Source::Flatpak);
+}
+
+/// Test parsing of pacman -Qkk output and checking files against a package manifest.
+#[test]
+fn test_verify_files() {
+    use reap::verify::{FileIssue, FileProblem, check_manifest, manifest_line, parse_pacman_check};
+    let output = "\
+warning: foo: /usr/bin/foo (Modification time mismatch)
+warning: foo: /usr/share/foo/a b.txt (No such file or directory)
+foo: 12 total files, 2 altered files
+bar: 3 total files, 0 altered files
+";
+    assert_eq!(
+        parse_pacman_check(output),
+        vec![
+            FileIssue {
+                package: "foo".into(),
+                path: "/usr/bin/foo".into(),
+                problem: FileProblem::Modified("Modification time mismatch".into()),
+            },
+            FileIssue {
+                package: "foo".into(),
+                path: "/usr/share/foo/a b.txt".into(),
+                problem: FileProblem::Missing,
+            },
+        ]
+    );
+
+    let dir = std::env::temp_dir().join(format!("reap-test-verify-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (kept, changed, gone, config) = (
+        dir.join("kept"),
+        dir.join("changed file"),
+        dir.join("gone"),
+        dir.join("config"),
+    );
+    for f in [&kept, &changed, &gone, &config] {
+        std::fs::write(f, "original").unwrap();
+    }
+    assert!(manifest_line(&dir).is_none());
+    let manifest: Vec<String> = [&kept, &changed, &gone, &config]
+        .iter()
+        .filter_map(|f| manifest_line(f))
+        .collect();
+    assert!(
+        manifest[0]
+            .starts_with("0682c5f2076f099c34cfdd15a9e063849ed437a49677e6fcc5b4198c76575be5 ")
+    );
+    std::fs::write(&changed, "tampered").unwrap();
+    std::fs::write(&config, "edited").unwrap();
+    std::fs::remove_file(&gone).unwrap();
+
+    let skip = vec![config.display().to_string()];
+    let issues = check_manifest("foo", &manifest.join("\n"), &skip);
+    assert_eq!(
+        issues,
+        vec![
+            FileIssue {
+                package: "foo".into(),
+                path: changed.display().to_string(),
+                problem: FileProblem::Modified("SHA256 checksum mismatch".into()),
+            },
+            FileIssue {
+                package: "foo".into(),
+                path: gone.display().to_string(),
+                problem: FileProblem::Missing,
+            },
+        ]
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Test that search drops a result with the same name and version as one from an earlier source.
+#[test]
+fn test_dedup_search_results() {
+    use reap::aur::SearchResult;
+    use reap::core::{Source, dedup_search_results};
+    let result = |name: &str, version: &str, source: Source| SearchResult {
+        name: name.into(),
+        version: version.into(),
+        description: String::new(),
+        votes: None,
+        source,
+    };
+    let results = vec![
+        result("foo", "", Source::Custom("ghost".into())),
+        result("foo", "1.0-1", Source::Pacman),
+        result("foo", "1.1-1", Source::Aur),
+        result("bar", "2.0-1", Source::Pacman),
+        result("bar", "2.0-1", Source::Aur),
+        result("bar", "2.0-1", Source::Aur),
+    ];
+    let kept: Vec<(String, Source)> = dedup_search_results(results.clone())
+        .into_iter()
+        .map(|r| (r.name, r.source))
+        .collect();
+    assert_eq!(
+        kept,
+        vec![
+            ("foo".to_string(), Source::Custom("ghost".into())),
+            ("foo".to_string(), Source::Pacman),
+            ("foo".to_string(), Source::Aur),
+            ("bar".to_string(), Source::Pacman),
+        ]
+    );
+}
+
+/// Test that `reap list --source` splits foreign packages into AUR and tap ones.
+#[test]
+fn test_installed_from_source() {
+    use reap::core::{InstalledPackage, Source, installed_from_source};
+    use reap::history::{InstallRecord, InstallRegistry};
+    let pkg = |name: &str, source: Source| InstalledPackage {
+        name: name.into(),
+        version: "1.0-1".into(),
+        source,
+        explicit: true,
+    };
+    let mut registry = InstallRegistry::default();
+    registry.record(
+        "ghostty-git",
+        InstallRecord {
+            source: Source::Aur,
+            tap: Some("ghost".into()),
+            installed_at: chrono::Utc::now(),
+            trust_score: None,
+        },
+    );
+    let tapped = pkg("ghostty-git", Source::Aur);
+    let aur = pkg("yay", Source::Aur);
+    let repo = pkg("bash", Source::Pacman);
+    let app = pkg("org.gnome.Maps", Source::Flatpak);
+    assert!(installed_from_source(&tapped, "tap", &registry));
+    assert!(!installed_from_source(&tapped, "aur", &registry));
+    assert!(installed_from_source(&aur, "aur", &registry));
+    assert!(!installed_from_source(&aur, "tap", &registry));
+    assert!(installed_from_source(&repo, "pacman", &registry));
+    assert!(installed_from_source(&app, "flatpak", &registry));
+    assert!(!installed_from_source(&repo, "bogus", &registry));
+}
+
+/// Test that prompt timeouts come from the config value or the default when enabled.
+#[test]
+fn test_prompt_timeout() {
+    use reap::interactive::{DEFAULT_PROMPT_TIMEOUT_SECS, prompt_timeout};
+    use std::time::Duration;
+    assert_eq!(prompt_timeout(None, None), None);
+    assert_eq!(prompt_timeout(Some(0), None), None);
+    assert_eq!(
+        prompt_timeout(Some(15), None),
+        Some(Duration::from_secs(15))
+    );
+    assert_eq!(
+        prompt_timeout(Some(15), Some(false)),
+        Some(Duration::from_secs(15))
+    );
+    assert_eq!(
+        prompt_timeout(None, Some(true)),
+        Some(Duration::from_secs(DEFAULT_PROMPT_TIMEOUT_SECS))
+    );
+}
+
+/// Test that confirmed conflicts are replaced by the install transaction itself.
+#[test]
+fn test_replace_conflicts_args() {
+    use reap::core::replace_conflicts_args;
+    assert!(replace_conflicts_args(&[]).is_empty());
+    assert_eq!(
+        replace_conflicts_args(&["foo".to_string()]),
+        vec!["--ask=4"]
+    );
+}
+
+/// Test that conflicts are found through provides and installed packages' own conflicts.
+#[test]
+fn test_conflicting_packages() {
+    use reap::core::conflicting_packages;
+    use reap::pacman::PackageDeps;
+    let installed = |name: &str, provides: &[&str], conflicts: &[&str]| PackageDeps {
+        name: name.to_string(),
+        provides: provides.iter().map(|s| s.to_string()).collect(),
+        conflicts: conflicts.iter().map(|s| s.to_string()).collect(),
+        ..Default::default()
+    };
+    let strings = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    let system = vec![
+        installed("foo", &[], &[]),
+        installed("libbar-git", &["libbar=2.0"], &["libbar"]),
+        installed("baz", &[], &["qux-bin"]),
+        installed("unrelated", &[], &[]),
+        installed("qux", &[], &[]),
+    ];
+    // By name, with a version constraint
+    assert_eq!(
+        conflicting_packages("foo-git", &strings(&["foo<2"]), &[], &system),
+        vec!["foo"]
+    );
+    // Through what the installed package provides
+    assert_eq!(
+        conflicting_packages("libbar", &strings(&["libbar-git"]), &[], &system),
+        vec!["libbar-git"]
+    );
+    assert_eq!(
+        conflicting_packages("libbar2", &strings(&["libbar"]), &[], &system),
+        vec!["libbar-git"]
+    );
+    // Declared by the installed package, against the name or a provide
+    assert_eq!(
+        conflicting_packages("qux-bin", &[], &[], &system),
+        vec!["baz"]
+    );
+    assert_eq!(
+        conflicting_packages("libbar-ng", &[], &strings(&["libbar=3"]), &system),
+        vec!["libbar-git"]
+    );
+    // The installed package itself is an upgrade
+    assert!(conflicting_packages("qux", &strings(&["qux"]), &[], &system).is_empty());
+}
+
+/// Test that overwrite globs become repeated --overwrite pacman arguments.
+#[test]
+fn test_overwrite_args() {
+    use reap::core::overwrite_args;
+    assert!(overwrite_args(&[]).is_empty());
+    assert_eq!(
+        overwrite_args(&["/usr/lib/foo/*".to_string(), "/etc/foo.conf".to_string()]),
+        vec![
+            "--overwrite",
+            "/usr/lib/foo/*",
+            "--overwrite",
+            "/etc/foo.conf"
+        ]
+    );
+}
+
+/// Test the guidance given for AUR packages that moved to a repo, were replaced or are gone.
+#[test]
+fn test_moved_package_guidance() {
+    use reap::core::MovedPackage;
+    use reap::pacman::parse_sync_info;
+    let info = "Repository      : extra\nName            : yay\nVersion         : 12.3.5-1\n";
+    assert_eq!(
+        parse_sync_info(info),
+        Some(("extra".to_string(), "12.3.5-1".to_string()))
+    );
+    assert_eq!(parse_sync_info("error: package 'x' was not found"), None);
+
+    let moved = MovedPackage::Repo {
+        repo: "extra".into(),
+        version: "1.0-1".into(),
+    };
+    assert!(moved.guidance("foo").contains("moved to [extra] (1.0-1)"));
+    let merged = MovedPackage::Replaced(vec!["foo-ng".into()]);
+    assert!(merged.guidance("foo").contains("merged into foo-ng"));
+    let several = MovedPackage::Replaced(vec!["a".into(), "b".into()]);
+    assert!(several.guidance("foo").ends_with("a, b"));
+    assert!(
+        MovedPackage::Gone
+            .guidance("foo")
+            .contains("reap search foo")
+    );
+}
+
+/// Test that comparing two builds ignores build metadata and lists changed, added and removed files.
+#[test]
+fn test_reproducible_build_diff() {
+    use reap::core::{compare_file_hashes, file_hashes};
+    let dir = std::env::temp_dir().join(format!("reap-test-repro-{}", std::process::id()));
+    let (first, second) = (dir.join("first"), dir.join("second"));
+    for root in [&first, &second] {
+        std::fs::create_dir_all(root.join("usr/bin")).unwrap();
+        std::fs::write(root.join("usr/bin/foo"), "same").unwrap();
+    }
+    assert!(compare_file_hashes(&file_hashes(&first), &file_hashes(&second)).is_empty());
+
+    std::fs::write(first.join(".BUILDINFO"), "builddate = 1").unwrap();
+    std::fs::write(second.join(".BUILDINFO"), "builddate = 2").unwrap();
+    std::fs::write(first.join("usr/bin/old"), "").unwrap();
+    std::fs::write(second.join("usr/bin/new"), "").unwrap();
+    assert_eq!(
+        compare_file_hashes(&file_hashes(&first), &file_hashes(&second)),
+        vec![
+            "differs: .BUILDINFO",
+            "only in the first build: usr/bin/old",
+            "only in the second build: usr/bin/new",
+        ]
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Test parsing of .SRCINFO fields, including epoch and architecture-specific dependencies.
+#[test]
+fn test_parse_srcinfo() {
+    use reap::srcinfo::SrcInfo;
+    let text = "\
+pkgbase = foo
+\tpkgdesc = Foo tools
+\tpkgver = 1.2
+\tpkgrel = 3
+\tepoch = 1
+\tarch = x86_64
+\tmakedepends = cmake
+\tdepends = glibc
+\tdepends_x86_64 = lib32-glibc
+\tsource = https://example.org/foo-1.2.tar.gz
+\tconflicts = foo-git
+
+pkgname = foo
+\tdepends = glibc
+\tdepends = zlib
+
+pkgname = libfoo
+\tpkgdesc = Foo library
+\tconflicts =
+";
+    let info = SrcInfo::parse(text).unwrap();
+    assert_eq!(info.pkgbase, "foo");
+    assert_eq!(info.version().as_deref(), Some("1:1.2-3"));
+    assert_eq!(info.pkgnames(), vec!["foo", "libfoo"]);
+    // foo overrides depends, libfoo inherits them
+    assert_eq!(
+        info.get("foo", "depends", "x86_64"),
+        vec!["glibc", "zlib", "lib32-glibc"]
+    );
+    assert_eq!(info.get("libfoo", "depends", "aarch64"), vec!["glibc"]);
+    assert_eq!(info.get("foo", "conflicts", "x86_64"), vec!["foo-git"]);
+    // An empty value clears the inherited list
+    assert!(info.get("libfoo", "conflicts", "x86_64").is_empty());
+
+    let rendered = info.render("x86_64");
+    assert!(rendered.starts_with("foo 1:1.2-3\n"));
+    assert!(rendered.contains("package libfoo\n  Description:  Foo library"));
+    assert!(rendered.contains("Make deps:    cmake"));
+
+    assert!(SrcInfo::parse("pkgver = 1").is_err());
+    assert!(SrcInfo::parse("").is_err());
+}
+
+/// Split packages: pipewire-git builds several packages from one pkgbase
+#[test]
+fn test_split_packages() {
+    use reap::core::{parse_split_selection, split_package_files};
+    use reap::srcinfo::SrcInfo;
+    use std::path::PathBuf;
+    let srcinfo = "\
+pkgbase = pipewire-git
+\tpkgver = 1.2.0.r12.gabcdef
+\tpkgrel = 1
+\tarch = x86_64
+\tmakedepends = git
+\tmakedepends = meson
+
+pkgname = pipewire-git
+\tdepends = libpipewire-git
+
+pkgname = libpipewire-git
+
+pkgname = pipewire-pulse-git
+\tdepends = pipewire-git
+";
+    let info = SrcInfo::parse(srcinfo).unwrap();
+    let names: Vec<String> = info.pkgnames().iter().map(|n| n.to_string()).collect();
+    assert_eq!(
+        names,
+        vec!["pipewire-git", "libpipewire-git", "pipewire-pulse-git"]
+    );
+
+    assert_eq!(parse_split_selection("", &names), Ok(vec![]));
+    assert_eq!(
+        parse_split_selection("1 3", &names),
+        Ok(vec![
+            "pipewire-git".to_string(),
+            "pipewire-pulse-git".to_string()
+        ])
+    );
+    assert_eq!(
+        parse_split_selection("libpipewire-git,1,1", &names),
+        Ok(vec![
+            "libpipewire-git".to_string(),
+            "pipewire-git".to_string()
+        ])
+    );
+    assert_eq!(parse_split_selection("all", &names).unwrap().len(), 3);
+    assert!(parse_split_selection("4", &names).is_err());
+    assert!(parse_split_selection("0", &names).is_err());
+    assert!(parse_split_selection("pipewire", &names).is_err());
+
+    let version = info.version().unwrap();
+    let built: Vec<PathBuf> = names
+        .iter()
+        .map(|n| PathBuf::from(format!("/tmp/{}-{}-x86_64.pkg.tar.zst", n, version)))
+        .collect();
+    let picked = split_package_files(&built, &["pipewire-git".to_string()], &version);
+    assert_eq!(picked, vec![built[0].clone()]);
+    let picked = split_package_files(&built, &names[1..], &version);
+    assert_eq!(picked, built[1..].to_vec());
+}
+
+/// Keyring helper: telling a missing keyring apart from a bad signature
+#[test]
+fn test_keyring_failures() {
+    use reap::gpg::{VerifyFailure, classify_verify_failure, keyring_initialized};
+    assert_eq!(
+        classify_verify_failure(
+            "[GNUPG:] BADSIG 1234ABCD Someone\ngpg: BAD signature from \"Someone\""
+        ),
+        VerifyFailure::BadSignature
+    );
+    assert_eq!(
+        classify_verify_failure(
+            "[GNUPG:] ERRSIG 1234ABCD 1 10 00 1700000000 9\n[GNUPG:] NO_PUBKEY 1234ABCD\ngpg: Can't check signature: No public key"
+        ),
+        VerifyFailure::MissingKey
+    );
+    assert_eq!(
+        classify_verify_failure(
+            "gpg: keybox '/root/.gnupg/pubring.kbx' created\ngpg: Can't check signature: No public key"
+        ),
+        VerifyFailure::NoKeyring
+    );
+    assert_eq!(
+        classify_verify_failure(
+            "gpg: failed to create temporary file '/etc/pacman.d/gnupg/.#lk0x': No such file or directory\ngpg: fatal: can't open '/etc/pacman.d/gnupg/trustdb.gpg'"
+        ),
+        VerifyFailure::NoKeyring
+    );
+    assert_eq!(
+        classify_verify_failure("gpg: something odd"),
+        VerifyFailure::BadSignature
+    );
+
+    let dir = std::env::temp_dir().join(format!("reap-test-keyring-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    assert!(!keyring_initialized(&dir));
+    std::fs::write(dir.join("pubring.kbx"), b"").unwrap();
+    assert!(keyring_initialized(&dir));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// --ignore-dependency: ignored deps are matched by name and passed to pacman
+#[test]
+fn test_ignore_dependency() {
+    use reap::core::{assume_installed_args, dep_name, without_ignored};
+    assert_eq!(dep_name("python>=3.12"), "python");
+    assert_eq!(dep_name("libfoo.so=2-64"), "libfoo.so");
+    assert_eq!(dep_name("qt6-base"), "qt6-base");
+
+    let ignored = vec!["python".to_string(), "nvidia-utils=550".to_string()];
+    assert_eq!(
+        assume_installed_args(&ignored),
+        vec![
+            "--assume-installed",
+            "python",
+            "--assume-installed",
+            "nvidia-utils=550"
+        ]
+    );
+    assert!(assume_installed_args(&[]).is_empty());
+
+    let deps: Vec<String> = ["python>=3.12", "python-requests", "nvidia-utils", "git"]
+        .iter()
+        .map(|d| d.to_string())
+        .collect();
+    assert_eq!(
+        without_ignored(&deps, &ignored),
+        vec!["python-requests".to_string(), "git".to_string()]
+    );
+    assert_eq!(without_ignored(&deps, &[]), deps);
+}
+
+/// AUR RPC rate limiting: token bucket pacing and 429 backoff
+#[test]
+fn test_rpc_rate_limit() {
+    use reap::aur::{TokenBucket, backoff_delay};
+    use std::time::{Duration, Instant};
+    let start = Instant::now();
+    let mut bucket = TokenBucket::new(2.0, 2.0, start);
+    // The burst goes through at once
+    assert_eq!(bucket.reserve(start), Duration::ZERO);
+    assert_eq!(bucket.reserve(start), Duration::ZERO);
+    // Concurrent callers queue up half a second apart
+    assert_eq!(bucket.reserve(start), Duration::from_millis(500));
+    assert_eq!(bucket.reserve(start), Duration::from_millis(1000));
+    // Once the queue has drained and refilled, the burst is available again
+    let later = start + Duration::from_secs(10);
+    assert_eq!(bucket.reserve(later), Duration::ZERO);
+    assert_eq!(bucket.reserve(later), Duration::ZERO);
+    assert!(bucket.reserve(later) > Duration::ZERO);
+
+    assert_eq!(backoff_delay(0, None, 0.0), Duration::from_secs(1));
+    assert_eq!(backoff_delay(3, None, 0.0), Duration::from_secs(8));
+    assert_eq!(backoff_delay(1, None, 0.5), Duration::from_secs(3));
+    assert_eq!(backoff_delay(4, Some(7), 0.0), Duration::from_secs(7));
+    assert_eq!(backoff_delay(0, Some(2), 2.0), Duration::from_secs(4));
+    assert!(backoff_delay(60, None, 0.0) <= Duration::from_secs(64));
+}
+
+/// reap stats: update counting and the rendered/JSON dashboard
+#[test]
+fn test_reap_stats() {
+    use reap::core::{ReapStats, count_aur_updates};
+    use std::collections::{BTreeMap, HashMap};
+    let installed: BTreeMap<String, String> = [("yay", "12.3.0-1"), ("paru", "2.0.3-1")]
+        .iter()
+        .map(|(n, v)| (n.to_string(), v.to_string()))
+        .collect();
+    let remote: HashMap<String, String> =
+        [("yay", "12.4.1-1"), ("paru", "2.0.3-1"), ("other", "1-1")]
+            .iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect();
+    assert_eq!(count_aur_updates(&installed, &remote), 1);
+
+    let stats = ReapStats {
+        installed_packages: 900,
+        foreign_packages: 12,
+        flatpak_apps: 3,
+        enabled_taps: 2,
+        repo_updates: 5,
+        aur_updates: None,
+        build_cache_bytes: 3 * 1024 * 1024 / 2,
+        backup_bytes: 0,
+        pacman_cache_bytes: 2048 * 1024 * 1024,
+    };
+    let text = stats.render();
+    assert!(text.contains("900 (12 foreign, 3 flatpak)"));
+    assert!(text.contains("5 repo, unknown AUR"));
+    assert!(text.contains("1.5 MiB"));
+    assert!(text.contains("2048.0 MiB"));
+    let json: serde_json::Value = serde_json::to_value(&stats).unwrap();
+    assert_eq!(json["foreign_packages"], 12);
+    assert!(json["aur_updates"].is_null());
+    assert_eq!(json["pacman_cache_bytes"], 2048u64 * 1024 * 1024);
+}
+
+/// build_dir: path expansion, writability check and the build root
+#[test]
+fn test_build_dir_config() {
+    use reap::config::{GlobalConfig, expand_path, validate_build_dir};
+    use std::path::{Path, PathBuf};
+    let home = Path::new("/home/alex");
+    let var = |name: &str| match name {
+        "XDG_RUNTIME_DIR" => Some("/run/user/1000".to_string()),
+        "FAST" => Some("/mnt/nvme".to_string()),
+        _ => None,
+    };
+    assert_eq!(
+        expand_path("~/builds", home, var),
+        PathBuf::from("/home/alex/builds")
+    );
+    assert_eq!(expand_path("~", home, var), PathBuf::from("/home/alex"));
+    assert_eq!(
+        expand_path("~other/x", home, var),
+        PathBuf::from("~other/x")
+    );
+    assert_eq!(
+        expand_path("$XDG_RUNTIME_DIR/reap", home, var),
+        PathBuf::from("/run/user/1000/reap")
+    );
+    assert_eq!(
+        expand_path("${FAST}_reap", home, var),
+        PathBuf::from("/mnt/nvme_reap")
+    );
+    assert_eq!(
+        expand_path("/x/$UNSET/y", home, var),
+        PathBuf::from("/x//y")
+    );
+    assert_eq!(expand_path("/x/$/y", home, var), PathBuf::from("/x/$/y"));
+    assert_eq!(
+        expand_path("/x/${FAST", home, var),
+        PathBuf::from("/x/${FAST")
+    );
+
+    let dir = std::env::temp_dir().join(format!("reap-test-build-dir-{}", std::process::id()));
+    let nested = dir.join("a/b");
+    assert!(validate_build_dir(&nested).is_ok());
+    assert!(nested.is_dir());
+    assert_eq!(std::fs::read_dir(&nested).unwrap().count(), 0);
+    std::fs::write(dir.join("file"), b"").unwrap();
+    assert!(validate_build_dir(&dir.join("file")).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let config = GlobalConfig {
+        build_dir: Some("/srv/reap-builds".to_string()),
+        ..GlobalConfig::default()
+    };
+    assert_eq!(config.build_root(), PathBuf::from("/srv/reap-builds"));
+}
+
+/// Ctrl-C handling: pacman transactions are recognised so they aren't killed, and reap's lockfile is held while building
+#[test]
+fn test_interrupt_pacman_transactions() {
+    use reap::interrupt::{
+        BuildDirGuard, LOCKFILE, interrupted, is_pacman_transaction, is_running, is_stale_lockfile,
+    };
+    let args = |line: &str| {
+        line.split_whitespace()
+            .map(String::from)
+            .collect::<Vec<_>>()
+    };
+    assert!(is_pacman_transaction(&args(
+        "sudo pacman -U --noconfirm --needed a.pkg.tar.zst"
+    )));
+    assert!(is_pacman_transaction(&args(
+        "sudo pacman -S --asdeps --needed --noconfirm zlib"
+    )));
+    assert!(is_pacman_transaction(&args("pacman -Syu --noconfirm")));
+    assert!(is_pacman_transaction(&args("sudo pacman -Rns foo")));
+    assert!(is_pacman_transaction(&args("sudo pacman --sync foo")));
+    assert!(!is_pacman_transaction(&args("pacman -Ss foo")));
+    assert!(!is_pacman_transaction(&args("pacman -Si foo")));
+    assert!(!is_pacman_transaction(&args(
+        "pacman -Sddp --print-format %n foo"
+    )));
+    assert!(!is_pacman_transaction(&args("pacman --sync --search foo")));
+    assert!(!is_pacman_transaction(&args("pacman -Qi foo")));
+    assert!(!is_pacman_transaction(&args("makepkg -si --noconfirm")));
+    assert!(!is_pacman_transaction(&args(
+        "git clone https://aur.archlinux.org/foo.git"
+    )));
+    assert!(!is_pacman_transaction(&[]));
+
+    // Registering build dirs never removes anything by itself; the lockfile
+    // next to them is held until the last one is done
+    let root = std::env::temp_dir().join(format!("reap-test-interrupt-{}", std::process::id()));
+    let dir = root.join("reap-aur-foo");
+    std::fs::create_dir_all(&dir).unwrap();
+    let lockfile = root.join(LOCKFILE);
+    let first = BuildDirGuard::register(&dir).unwrap();
+    let second = BuildDirGuard::register(&root.join("reap-aur-bar")).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&lockfile).unwrap(),
+        std::process::id().to_string()
+    );
+    assert!(!is_stale_lockfile(&lockfile, is_running));
+    assert!(is_stale_lockfile(&lockfile, |_| false));
+    drop(first);
+    assert!(lockfile.exists());
+    drop(second);
+    assert!(!lockfile.exists());
+    assert!(!is_stale_lockfile(&lockfile, |_| false));
+    assert!(dir.exists());
+
+    // A lockfile held by another running reap is refused, one left by a
+    // reap that is gone is taken over
+    std::fs::write(&lockfile, "1").unwrap();
+    assert!(BuildDirGuard::register(&dir).is_err());
+    assert_eq!(std::fs::read_to_string(&lockfile).unwrap(), "1");
+    std::fs::write(&lockfile, u32::MAX.to_string()).unwrap();
+    let taken = BuildDirGuard::register(&dir).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&lockfile).unwrap(),
+        std::process::id().to_string()
+    );
+    drop(taken);
+    assert!(!lockfile.exists());
+    assert!(!interrupted());
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+/// Publisher key freshness from gpg's colon listing
+#[test]
+fn test_key_freshness() {
+    use reap::gpg::{KeyFreshness, key_freshness};
+    let now = 1_700_000_000;
+    let listing = |validity: &str, expires: &str| {
+        format!(
+            "tru::1:1699999999:0:3:1:5\npub:{}:4096:1:ABCDEF0123456789:1600000000:{}::-:::scESC::::::23::0:\nfpr:::::::::0123456789ABCDEF0123456789ABCDEF01234567:\n",
+            validity, expires
+        )
+    };
+    assert_eq!(key_freshness(&listing("u", ""), now), KeyFreshness::Valid);
+    assert_eq!(
+        key_freshness(&listing("f", &(now + 90 * 86400).to_string()), now),
+        KeyFreshness::Valid
+    );
+    assert_eq!(
+        key_freshness(&listing("f", &(now + 3 * 86400 + 60).to_string()), now),
+        KeyFreshness::Expiring(3)
+    );
+    assert_eq!(
+        key_freshness(&listing("f", &(now - 1).to_string()), now),
+        KeyFreshness::Expired
+    );
+    assert_eq!(key_freshness(&listing("e", ""), now), KeyFreshness::Expired);
+    assert_eq!(key_freshness("", now), KeyFreshness::Unknown);
+    assert!(KeyFreshness::Expired.is_stale());
+    assert!(KeyFreshness::Expiring(0).is_stale());
+    assert!(!KeyFreshness::Valid.is_stale());
+    assert!(!KeyFreshness::Unknown.is_stale());
+}
+
+/// Sysupgrade: AUR upgrades that depend on each other go dependencies first
+#[test]
+fn test_sysupgrade_order() {
+    use reap::core::{build_levels, upgrade_graph};
+    use std::collections::HashMap;
+    let pkgs: Vec<String> = ["app", "libfoo", "libbar", "tool"]
+        .iter()
+        .map(|p| p.to_string())
+        .collect();
+    let deps: HashMap<String, Vec<String>> = [
+        ("app", vec!["libfoo>=2.0", "glibc", "libbar"]),
+        ("libfoo", vec!["libbar=1.1", "cmake"]),
+        ("libbar", vec!["zlib"]),
+        ("tool", vec!["tool", "python"]),
+    ]
+    .iter()
+    .map(|(p, d)| (p.to_string(), d.iter().map(|d| d.to_string()).collect()))
+    .collect();
+    let graph = upgrade_graph(deps, &pkgs);
+    assert_eq!(graph["app"], vec!["libbar", "libfoo"]);
+    assert_eq!(graph["libfoo"], vec!["libbar"]);
+    assert!(graph["libbar"].is_empty());
+    // Self-dependencies don't count
+    assert!(graph["tool"].is_empty());
+    let levels = build_levels(&graph).unwrap();
+    assert_eq!(
+        levels,
+        vec![
+            vec!["libbar".to_string(), "tool".to_string()],
+            vec!["libfoo".to_string()],
+            vec!["app".to_string()],
+        ]
+    );
+}
+
+/// `--rebuild-all` reinstalls at the same version instead of pacman skipping it with `--needed`
+#[test]
+fn test_rebuild_all_reinstalls() {
+    use reap::core::{InstallOptions, pacman_install_args, rebuild_options};
+    let opts = InstallOptions {
+        overwrite: vec!["/usr/lib/foo/*".to_string()],
+        ..InstallOptions::default()
+    };
+    assert!(pacman_install_args(&opts).contains(&"--needed".to_string()));
+    let rebuild = rebuild_options(&opts);
+    assert!(rebuild.reinstall);
+    let args = pacman_install_args(&rebuild);
+    assert!(!args.contains(&"--needed".to_string()));
+    assert_eq!(
+        args,
+        vec!["-U", "--noconfirm", "--overwrite", "/usr/lib/foo/*"]
+    );
+}
+
+/// sudo handling: configurable command and which invocations need root
+#[test]
+fn test_sudo_handling() {
+    use clap::Parser;
+    use reap::cli::Cli;
+    use reap::core::needs_root;
+    use reap::utils::parse_privilege_cmd;
+    let all = |_: &str| true;
+    assert_eq!(parse_privilege_cmd(None, all), vec!["sudo"]);
+    assert_eq!(parse_privilege_cmd(Some("  "), all), vec!["sudo"]);
+    assert_eq!(parse_privilege_cmd(Some("doas"), all), vec!["doas"]);
+    assert_eq!(
+        parse_privilege_cmd(Some("sudo -E"), all),
+        vec!["sudo", "-E"]
+    );
+    // Autodetection prefers sudo and falls back to doas
+    assert_eq!(parse_privilege_cmd(None, |p| p == "doas"), vec!["doas"]);
+    assert_eq!(parse_privilege_cmd(None, |_| false), vec!["sudo"]);
+    let config: reap::config::GlobalConfig = toml::from_str(
+        "backend_order = []\nauto_resolve_deps = true\nnoconfirm = false\nlog_verbose = false\nsudo_cmd = \"doas\"",
+    )
+    .unwrap();
+    assert_eq!(config.privilege_cmd.as_deref(), Some("doas"));
+
+    let parse = |line: &str| {
+        Cli::parse_from(reap::cli::hoist_operation_flags(
+            reap::cli::expand_pacman_flags(line.split_whitespace().map(String::from)),
+        ))
+    };
+    assert!(needs_root(&parse("reap -S foo")));
+    assert!(needs_root(&parse("reap -Syu")));
+    assert!(needs_root(&parse("reap -R foo")));
+    assert!(needs_root(&parse("reap sysupgrade")));
+    assert!(needs_root(&parse("reap keyring --init")));
+    assert!(needs_root(&parse("reap orphan --remove")));
+    assert!(!needs_root(&parse("reap -Ss foo")));
+    assert!(!needs_root(&parse("reap -S --print-srcinfo foo")));
+    assert!(!needs_root(&parse("reap --dry-run -S foo")));
+    assert!(!needs_root(&parse("reap keyring")));
+    assert!(!needs_root(&parse("reap orphan")));
+    assert!(!needs_root(&parse("reap stats")));
+
+    assert!(reap::interrupt::is_pacman_transaction(
+        &"doas pacman -U a.pkg.tar.zst"
+            .split_whitespace()
+            .map(String::from)
+            .collect::<Vec<_>>()
+    ));
+}
+
+/// makepkg phases are recognised so slow check() runs can be reported
+#[test]
+fn test_makepkg_phases() {
+    use reap::core::makepkg_phase;
+    assert_eq!(makepkg_phase("==> Starting check()..."), Some("check"));
+    assert_eq!(makepkg_phase("==> Starting build()..."), Some("build"));
+    assert_eq!(makepkg_phase("==> Starting prepare()..."), Some("prepare"));
+    assert_eq!(
+        makepkg_phase("==> Entering fakeroot environment..."),
+        Some("package")
+    );
+    assert_eq!(makepkg_phase("  -> Found foo.tar.gz"), None);
+    assert_eq!(makepkg_phase("==> Making package: foo 1.0-1"), None);
+    assert_eq!(makepkg_phase("Starting check() in a test log"), None);
+
+    use clap::Parser;
+    let cli = reap::cli::Cli::parse_from(["reap", "--nocheck", "-S", "foo"]);
+    assert!(reap::core::install_options(&cli).nocheck);
+    assert_eq!(reap::config::GlobalConfig::default().nocheck, Some(false));
+}
+
+/// `reap resolve` reports the chosen source and the candidates considered
+#[test]
+fn test_resolve_report() {
+    use reap::core::{Resolution, ResolveCandidate, Source, source_key};
+    assert_eq!(source_key(&Source::Aur, None), "aur");
+    assert_eq!(source_key(&Source::Pacman, None), "pacman");
+    assert_eq!(source_key(&Source::Flatpak, None), "flatpak");
+    assert_eq!(
+        source_key(&Source::Custom("ghost".into()), Some("ghost")),
+        "tap:ghost"
+    );
+
+    let resolution = Resolution {
+        package: "foo".into(),
+        source: Some("tap:ghost".into()),
+        priority: Some(90),
+        tap: Some("ghost".into()),
+        provider: None,
+        candidates: vec![
+            ResolveCandidate {
+                source: "tap:ghost".into(),
+                tap: Some("ghost".into()),
+                priority: 90,
+                version: Some("1.2-1".into()),
+            },
+            ResolveCandidate {
+                source: "aur".into(),
+                tap: None,
+                priority: 10,
+                version: Some("1.1-1".into()),
+            },
+        ],
+    };
+    let json: serde_json::Value = serde_json::to_value(&resolution).unwrap();
+    assert_eq!(json["source"], "tap:ghost");
+    assert_eq!(json["priority"], 90);
+    assert_eq!(json["candidates"][1]["source"], "aur");
+    assert_eq!(json["candidates"][1]["version"], "1.1-1");
+    let text = resolution.render();
+    assert!(text.starts_with("foo: tap:ghost (priority 90)"));
+    assert!(text.contains("1.1-1"));
+
+    let missing = Resolution {
+        package: "nope".into(),
+        source: None,
+        priority: None,
+        tap: None,
+        provider: None,
+        candidates: vec![],
+    };
+    assert_eq!(missing.render(), "nope: not found in any source");
+}
+
+/// AUR git mirrors live under the cache, one bare repository per pkgbase
+#[test]
+fn test_aur_mirror_dir() {
+    let dir = reap::core::aur_mirror_dir("foo-git");
+    assert!(dir.ends_with("reap/git/foo-git.git"));
+    assert_ne!(dir, reap::core::aur_mirror_dir("foo"));
+}
+
+/// `reap upgrade --dry-run` lists versions and new dependencies
+#[test]
+fn test_upgrade_plan() {
+    use reap::core::{PlannedUpgrade, UpgradePlan};
+    use reap::utils::parse_update_lines;
+    assert_eq!(
+        parse_update_lines("linux 6.9.1-1 -> 6.9.2-1\nzstd 1.5.5-1 -> 1.5.6-1\n"),
+        vec![
+            (
+                "linux".to_string(),
+                "6.9.1-1".to_string(),
+                "6.9.2-1".to_string()
+            ),
+            (
+                "zstd".to_string(),
+                "1.5.5-1".to_string(),
+                "1.5.6-1".to_string()
+            ),
+        ]
+    );
+    assert_eq!(
+        parse_update_lines("foo\n"),
+        vec![("foo".to_string(), String::new(), String::new())]
+    );
+
+    let empty = UpgradePlan::default();
+    assert!(empty.is_empty());
+    assert_eq!(empty.render(), "Nothing to upgrade.");
+
+    let plan = UpgradePlan {
+        repo: vec![PlannedUpgrade {
+            name: "linux".into(),
+            from: Some("6.9.1-1".into()),
+            to: Some("6.9.2-1".into()),
+            devel: false,
+            new_dependencies: vec![],
+        }],
+        repo_new_dependencies: vec!["linux-firmware-intel".into()],
+        aur: vec![
+            PlannedUpgrade {
+                name: "foo".into(),
+                from: Some("1.0-1".into()),
+                to: Some("2.0-1".into()),
+                devel: false,
+                new_dependencies: vec!["libbar".into()],
+            },
+            PlannedUpgrade {
+                name: "baz-git".into(),
+                from: Some("r10.abc-1".into()),
+                to: Some("r10.abc-1".into()),
+                devel: true,
+                new_dependencies: vec![],
+            },
+        ],
+        flatpak: vec![],
+    };
+    let text = plan.render();
+    assert!(text.contains("Repo packages to upgrade (1):"));
+    assert!(text.contains("6.9.1-1 -> 6.9.2-1"));
+    assert!(text.contains("New dependencies: linux-firmware-intel"));
+    assert!(text.contains("AUR packages to rebuild (2):"));
+    assert!(text.contains("(new dependencies: libbar)"));
+    assert!(text.contains("(new upstream commits)"));
+    assert!(!text.contains("Flatpak"));
+    let json = serde_json::to_value(&plan).unwrap();
+    assert_eq!(json["aur"][0]["to"], "2.0-1");
+    assert_eq!(json["aur"][1]["devel"], true);
+    assert_eq!(json["repo_new_dependencies"][0], "linux-firmware-intel");
+}
+
+/// Build output is split into lines, leaving an unanswered prompt pending,
+/// and the TUI log pane collects answers to build prompts
+#[test]
+fn test_build_prompts() {
+    use reap::core::{ProgressReporter, take_lines};
+    let mut pending =
+        b"==> Starting build()...\nDownloading 10%\rDownloading 100%\r\nAccept the license? [y/N] "
+            .to_vec();
+    assert_eq!(
+        take_lines(&mut pending),
+        vec![
+            "==> Starting build()...",
+            "Downloading 10%",
+            "Downloading 100%"
+        ]
+    );
+    assert_eq!(pending, b"Accept the license? [y/N] ");
+    assert!(take_lines(&mut pending).is_empty());
+    pending.extend_from_slice(b"y\n");
+    assert_eq!(
+        take_lines(&mut pending),
+        vec!["Accept the license? [y/N] y"]
+    );
+    assert!(pending.is_empty());
+
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use std::sync::Arc;
+    let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+    let pane = Arc::new(reap::tui::LogPane::new());
+    assert!(!pane.handle_input_key(key(KeyCode::Char('x'))));
+    let asking = Arc::clone(&pane);
+    let answer = std::thread::spawn(move || asking.prompt("build", "Accept? [y/N]"));
+    while pane.input_prompt().is_none() {
+        std::thread::sleep(std::time::Duration::from_millis(5));
+    }
+    assert!(pane.handle_input_key(key(KeyCode::Char('y'))));
+    assert!(pane.handle_input_key(key(KeyCode::Char('e'))));
+    assert!(pane.handle_input_key(key(KeyCode::Backspace)));
+    assert_eq!(
+        pane.input_prompt(),
+        Some(("Accept? [y/N]".to_string(), "y".to_string()))
+    );
+    assert!(pane.handle_input_key(key(KeyCode::Enter)));
+    assert_eq!(answer.join().unwrap().as_deref(), Some("y"));
+    assert!(pane.input_prompt().is_none());
+
+    let asking = Arc::clone(&pane);
+    let answer = std::thread::spawn(move || asking.prompt("build", "Continue?"));
+    while pane.input_prompt().is_none() {
+        std::thread::sleep(std::time::Duration::from_millis(5));
+    }
+    assert!(pane.handle_input_key(key(KeyCode::Esc)));
+    assert_eq!(answer.join().unwrap(), None);
+}
+
+/// `reap log <pkg>` collects a package's events from the operation history
+#[test]
+fn test_package_log() {
+    use reap::core::Source;
+    use reap::history::{
+        InstallRecord, InstallRegistry, OperationKind, OperationLog, PackageChange, PackageLog,
+        error_for,
+    };
+    assert_eq!(
+        error_for("foo: build failed; bar: not found", "bar").as_deref(),
+        Some("not found")
+    );
+    assert_eq!(
+        error_for("failed: foo, bar", "bar").as_deref(),
+        Some("failed: foo, bar")
+    );
+    assert_eq!(error_for("failed: foobar", "foo"), None);
+
+    let change = |old: Option<&str>, new: Option<&str>| PackageChange {
+        name: "foo".into(),
+        old_version: old.map(String::from),
+        new_version: new.map(String::from),
+        source: Some(Source::Aur),
+        build_secs: Some(95),
+        trust_score: Some(7.5),
+    };
+    let mut log = OperationLog::default();
+    log.record(
+        OperationKind::Install,
+        vec!["foo".into()],
+        vec![change(None, Some("1.0-1"))],
+        None,
+    );
+    log.record(
+        OperationKind::Install,
+        vec!["other".into()],
+        vec![],
+        Some("other: not found".into()),
+    );
+    log.record(
+        OperationKind::Upgrade,
+        vec![],
+        vec![],
+        Some("failed: foo".into()),
+    );
+    log.record(
+        OperationKind::Upgrade,
+        vec![],
+        vec![change(Some("1.0-1"), Some("1.1-1"))],
+        None,
+    );
+    let mut registry = InstallRegistry::default();
+    registry.record(
+        "foo",
+        InstallRecord {
+            source: Source::Custom("ghost".into()),
+            tap: Some("ghost".into()),
+            installed_at: chrono::Utc::now(),
+            trust_score: Some(8.0),
+        },
+    );
+    let pkg_log = PackageLog::collect(&log, &registry, "foo");
+    assert_eq!(
+        pkg_log
+            .events
+            .iter()
+            .map(|e| e.operation)
+            .collect::<Vec<_>>(),
+        vec![1, 3, 4]
+    );
+    assert_eq!(pkg_log.events[0].source.as_deref(), Some("aur"));
+    assert_eq!(pkg_log.events[1].error.as_deref(), Some("failed: foo"));
+    let text = pkg_log.render();
+    assert!(text.starts_with("foo: installed from tap:ghost"));
+    assert!(text.contains("installed 1.0-1 from aur, built in"));
+    assert!(text.contains("trust 7.5/10"));
+    assert!(text.contains("1.0-1 -> 1.1-1"));
+    assert!(text.contains("failed: failed: foo"));
+    let json = serde_json::to_value(&pkg_log).unwrap();
+    assert_eq!(json["events"][0]["build_secs"], 95);
+    assert_eq!(json["installed"]["tap"], "ghost");
+
+    let empty = PackageLog::collect(&log, &registry, "nothing");
+    assert!(empty.events.is_empty());
+    assert_eq!(empty.render(), "No recorded operations for nothing");
+}
+
+/// Profiles can build with their own makepkg.conf, which must exist
+#[test]
+fn test_profile_makepkg_conf() {
+    use reap::profiles::{ProfileConfig, effective_settings};
+    let conf = std::env::temp_dir().join(format!("reap-test-makepkg-{}.conf", std::process::id()));
+    let mut profile = ProfileConfig {
+        name: "native".into(),
+        makepkg_conf: Some(conf.clone()),
+        ..Default::default()
+    };
+    assert!(profile.validate().is_err());
+    std::fs::write(&conf, "CFLAGS=\"-march=native -O2\"\n").unwrap();
+    assert!(profile.validate().is_ok());
+    assert_eq!(profile.makepkg_conf_path(), Some(conf.clone()));
+    let global = reap::config::GlobalConfig::default();
+    let settings = effective_settings(&profile, &global);
+    assert!(settings.contains(&("makepkg_conf", conf.display().to_string())));
+
+    profile.makepkg_conf = None;
+    assert!(profile.validate().is_ok());
+    let settings = effective_settings(&profile, &global);
+    assert!(settings.contains(&("makepkg_conf", "/etc/makepkg.conf".to_string())));
+
+    // Older profile files without the key still load
+    let old: ProfileConfig = toml::from_str(
+        "name = \"old\"\nbackend_order = []\nauto_install_deps = []\npinned_packages = []\nignored_packages = []\n",
+    )
+    .unwrap();
+    assert!(old.makepkg_conf.is_none());
+    let _ = std::fs::remove_file(&conf);
+}
+
+/// Packages flagged out-of-date are caught from the RPC and the flag page
+#[test]
+fn test_out_of_date_flag() {
+    use reap::aur::{AurResult, OutOfDateFlag, parse_flag_comment};
+    let flagged: AurResult = serde_json::from_str(
+        r#"{"Name":"foo","Version":"1.0-1","Description":null,"OutOfDate":1700000000}"#,
+    )
+    .unwrap();
+    assert_eq!(flagged.out_of_date, Some(1700000000));
+    let fresh: AurResult = serde_json::from_str(
+        r#"{"Name":"foo","Version":"1.0-1","Description":null,"OutOfDate":null}"#,
+    )
+    .unwrap();
+    assert_eq!(fresh.out_of_date, None);
+
+    let html = r#"<div class="box"><h2>Flagged Out-of-Date Comment: foo</h2>
+<p><strong>alice</strong> flagged <strong>foo</strong> out-of-date on <strong>2023-11-14 22:13 (UTC)</strong> for the following reason:</p>
+<p><blockquote>2.0 is out, see &lt;https://example.org&gt;</blockquote></p></div>"#;
+    assert_eq!(
+        parse_flag_comment(html),
+        OutOfDateFlag {
+            flagged_by: Some("alice".into()),
+            reason: Some("2.0 is out, see <https://example.org>".into()),
+        }
+    );
+    assert_eq!(
+        parse_flag_comment("<p>Not found</p>"),
+        OutOfDateFlag::default()
+    );
+
+    use clap::Parser;
+    let cli = reap::cli::Cli::parse_from(["reap", "--ignore-ood", "-S", "foo"]);
+    assert!(reap::core::install_options(&cli).ignore_ood);
+    let cli = reap::cli::Cli::parse_from(["reap", "-S", "foo"]);
+    assert!(!reap::core::install_options(&cli).ignore_ood);
+
+    // A strict install keeps its strictness into the AUR build, which
+    // refuses a flagged package instead of asking
+    use reap::core::{InstallOptions, ReapError, aur_build_options, refuse_out_of_date};
+    let strict = InstallOptions {
+        strict_signatures: true,
+        insecure: true,
+        gpg_keyserver: Some("hkps://keyserver.ubuntu.com".into()),
+        ..Default::default()
+    };
+    let build = aur_build_options(&strict);
+    assert!(build.strict_signatures && build.insecure);
+    assert_eq!(build.gpg_keyserver, strict.gpg_keyserver);
+    assert!(matches!(
+        refuse_out_of_date("foo", &build),
+        Err(ReapError::TrustPolicy { .. })
+    ));
+    assert!(refuse_out_of_date("foo", &aur_build_options(&InstallOptions::default())).is_ok());
+}
+
+/// `reap clean --keep N` keeps the newest N versions of each cached package
+#[test]
+fn test_cache_prune_candidates() {
+    use reap::core::cache_prune_candidates;
+    use std::time::{Duration, UNIX_EPOCH};
+    let at = |secs: u64| UNIX_EPOCH + Duration::from_secs(secs);
+    let files = vec![
+        ("foo-1.0-1-x86_64.pkg.tar.zst".to_string(), at(1)),
+        ("foo-1.0-1-x86_64.pkg.tar.zst.sig".to_string(), at(1)),
+        ("foo-1.1-1-x86_64.pkg.tar.zst".to_string(), at(2)),
+        ("foo-2:1.2-1-x86_64.pkg.tar.zst".to_string(), at(3)),
+        ("foo-1.0-1-i686.pkg.tar.xz".to_string(), at(1)),
+        ("lib32-foo-bar-0.9-3-any.pkg.tar.zst".to_string(), at(1)),
+        ("lib32-foo-bar-1.0-1-any.pkg.tar.zst".to_string(), at(5)),
+        ("download-abc.part".to_string(), at(9)),
+        ("notes.txt".to_string(), at(9)),
+    ];
+    assert_eq!(
+        cache_prune_candidates(&files, 2),
+        vec![
+            "foo-1.0-1-x86_64.pkg.tar.zst".to_string(),
+            "foo-1.0-1-x86_64.pkg.tar.zst.sig".to_string(),
+        ]
+    );
+    assert_eq!(
+        cache_prune_candidates(&files, 1),
+        vec![
+            "foo-1.0-1-x86_64.pkg.tar.zst".to_string(),
+            "foo-1.0-1-x86_64.pkg.tar.zst.sig".to_string(),
+            "foo-1.1-1-x86_64.pkg.tar.zst".to_string(),
+            "lib32-foo-bar-0.9-3-any.pkg.tar.zst".to_string(),
+        ]
+    );
+    assert!(cache_prune_candidates(&files, 3).is_empty());
+    // An old version copied back into the cache is still the oldest
+    let files = vec![
+        ("bar-1.10-1-x86_64.pkg.tar.zst".to_string(), at(1)),
+        ("bar-1.9-1-x86_64.pkg.tar.zst".to_string(), at(7)),
+        ("bar-1.10-2-x86_64.pkg.tar.zst".to_string(), at(2)),
+    ];
+    assert_eq!(
+        cache_prune_candidates(&files, 2),
+        vec!["bar-1.9-1-x86_64.pkg.tar.zst".to_string()]
+    );
+
+    use clap::Parser;
+    use reap::cli::{Cli, Commands};
+    let keep = |args: &[&str]| match Cli::parse_from(args).command {
+        Some(Commands::Clean { keep, .. }) => keep,
+        _ => panic!("not a clean command"),
+    };
+    assert_eq!(keep(&["reap", "clean", "--keep"]), Some(2));
+    assert_eq!(keep(&["reap", "clean-cache", "--keep", "3"]), Some(3));
+    assert_eq!(keep(&["reap", "clean"]), None);
+    // Keeping nothing would empty the cache
+    assert!(Cli::try_parse_from(["reap", "clean", "--keep", "0"]).is_err());
+}
+
+/// `reap search/install --maintainer` parse and need a package otherwise
+#[test]
+fn test_maintainer_args() {
+    use clap::Parser;
+    use reap::cli::{Cli, Commands};
+    match Cli::parse_from(["reap", "install", "--maintainer", "alice"]).command {
+        Some(Commands::Install {
+            pkg, maintainer, ..
+        }) => {
+            assert_eq!(pkg, None);
+            assert_eq!(maintainer.as_deref(), Some("alice"));
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    match Cli::parse_from(["reap", "install", "foo"]).command {
+        Some(Commands::Install {
+            pkg, maintainer, ..
+        }) => {
+            assert_eq!(pkg.as_deref(), Some("foo"));
+            assert_eq!(maintainer, None);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(Cli::try_parse_from(["reap", "install"]).is_err());
+    assert!(Cli::try_parse_from(["reap", "install", "foo", "--maintainer", "alice"]).is_err());
+    match Cli::parse_from(["reap", "search", "--maintainer", "alice"]).command {
+        Some(Commands::Search { terms, maintainer }) => {
+            assert!(terms.is_empty());
+            assert_eq!(maintainer.as_deref(), Some("alice"));
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+/// Arch news is parsed from RSS and only news newer than the last seen is unread
+#[test]
+fn test_arch_news() {
+    use chrono::{TimeZone, Utc};
+    use reap::news::{last_seen, mark_seen, parse_feed, unread};
+    let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<rss version="2.0"><channel><title>Arch Linux: Recent news updates</title>
+<item><title>Older &amp; fine</title><link>https://archlinux.org/news/older/</link>
+<description>&lt;p&gt;Nothing to do.&lt;/p&gt;</description>
+<pubDate>Mon, 01 Jan 2024 10:00:00 +0000</pubDate></item>
+<item><title>Manual intervention needed</title><link>https://archlinux.org/news/manual/</link>
+<description>&lt;p&gt;Run &lt;code&gt;pacman -Syu --overwrite '*'&lt;/code&gt; first.&lt;/p&gt;</description>
+<pubDate>Tue, 02 Jul 2024 12:30:00 +0000</pubDate></item>
+<item><title>No date</title></item>
+</channel></rss>"#;
+    let items = parse_feed(xml);
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0].title, "Manual intervention needed");
+    assert_eq!(items[0].link, "https://archlinux.org/news/manual/");
+    assert_eq!(items[0].text, "Run pacman -Syu --overwrite '*' first.");
+    assert_eq!(
+        items[0].published,
+        Utc.with_ymd_and_hms(2024, 7, 2, 12, 30, 0).unwrap()
+    );
+    assert_eq!(items[1].title, "Older & fine");
+
+    let now = Utc.with_ymd_and_hms(2024, 7, 10, 0, 0, 0).unwrap();
+    // Nothing seen yet: only the last 30 days count
+    assert_eq!(unread(&items, None, now).len(), 1);
+    let path =
+        std::env::temp_dir().join(format!("reap-test-news-{}/news_seen", std::process::id()));
+    assert_eq!(last_seen(&path), None);
+    mark_seen(&path, &items[1..]).unwrap();
+    assert_eq!(last_seen(&path), Some(items[1].published));
+    assert_eq!(
+        unread(&items, last_seen(&path), now),
+        vec![items[0].clone()]
+    );
+    mark_seen(&path, &items).unwrap();
+    assert!(unread(&items, last_seen(&path), now).is_empty());
+    // Older items never move the mark back
+    mark_seen(&path, &items[1..]).unwrap();
+    assert_eq!(last_seen(&path), Some(items[0].published));
+    let _ = std::fs::remove_dir_all(path.parent().unwrap());
+}
+
+/// Resolving AUR dependencies fetches each tree level in one batched RPC
+#[tokio::test]
+async fn test_batched_dependency_resolution() {
+    use reap::aur::fetch_info_batch_with;
+    use reap::core::resolve_aur_deps;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // app -> lib0..lib9 -> lib<i>-a..c: 40 AUR dependencies over two levels,
+    // plus a repo package and a dependency the AUR doesn't have
+    let mut tree: HashMap<String, Vec<String>> = HashMap::new();
+    let libs: Vec<String> = (0..10).map(|i| format!("lib{}", i)).collect();
+    let mut app_deps = libs.clone();
+    app_deps.extend(["glibc".to_string(), "ghost".to_string()]);
+    tree.insert("app".into(), app_deps);
+    for lib in &libs {
+        let leaves: Vec<String> = ["a", "b", "c"]
+            .iter()
+            .map(|s| format!("{}-{}>=1.0", lib, s))
+            .collect();
+        for leaf in &leaves {
+            tree.insert(reap::core::dep_name(leaf).to_string(), vec!["glibc".into()]);
+        }
+        // Shared by every lib, fetched once
+        let mut deps = leaves;
+        deps.push("lib0-a".into());
+        tree.insert(lib.clone(), deps);
+    }
+    let requests = AtomicUsize::new(0);
+    let rpc = |query: String| {
+        requests.fetch_add(1, Ordering::Relaxed);
+        let results: Vec<serde_json::Value> = query
+            .split("&arg[]=")
+            .skip(1)
+            .filter_map(|name| {
+                let deps = tree.get(name)?;
+                Some(serde_json::json!({
+                    "Name": name, "Version": "1.0-1", "Description": null, "Depends": deps,
+                }))
+            })
+            .collect();
+        async move { Ok(serde_json::json!({ "results": results }).to_string()) }
+    };
+    let fetch_info = |level: Vec<String>| {
+        let rpc = &rpc;
+        async move { fetch_info_batch_with(&level, rpc).await }
+    };
+    let needs_build = |deps: &[String]| -> Vec<String> {
+        deps.iter()
+            .map(|dep| reap::core::dep_name(dep).to_string())
+            .filter(|dep| dep != "glibc")
+            .collect()
+    };
+    let graph = resolve_aur_deps(&["app".to_string()], &[], fetch_info, needs_build).await;
+    // app, its 10 libs, their 30 leaves: one request per level
+    assert_eq!(graph.len(), 41);
+    assert_eq!(requests.load(Ordering::Relaxed), 3);
+    assert_eq!(graph["app"], libs);
+    assert!(graph["lib1"].contains(&"lib0-a".to_string()));
+    assert!(graph["lib0-a"].is_empty());
+    assert!(!graph.contains_key("ghost"));
+    assert_eq!(reap::core::build_levels(&graph).unwrap().len(), 3);
+
+    // Ignored dependencies aren't followed
+    requests.store(0, Ordering::Relaxed);
+    let graph = resolve_aur_deps(
+        &["lib3".to_string()],
+        &["lib3-b".to_string()],
+        fetch_info,
+        needs_build,
+    )
+    .await;
+    assert_eq!(graph.len(), 4);
+    assert!(!graph.contains_key("lib3-b"));
+    assert_eq!(requests.load(Ordering::Relaxed), 2);
+
+    // Wide levels are split into batches of at most 150 names
+    requests.store(0, Ordering::Relaxed);
+    let many: Vec<String> = (0..160).map(|i| format!("pkg{}", i)).collect();
+    assert!(fetch_info_batch_with(&many, &rpc).await.unwrap().is_empty());
+    assert_eq!(requests.load(Ordering::Relaxed), 2);
+}
+
+/// `--format` templates expand per package like pacman's `--print-format`
+#[test]
+fn test_format_template() {
+    use reap::aur::SearchResult;
+    use reap::core::{
+        InstalledPackage, Source, expand_format, format_installed_package, format_search_result,
+    };
+    let aur = SearchResult {
+        name: "yay".into(),
+        version: "12.3.5-1".into(),
+        description: "Yet another yogurt".into(),
+        source: Source::Aur,
+        votes: Some(2500),
+    };
+    assert_eq!(
+        format_search_result(&aur, "%n %v %s %w: %d"),
+        "yay 12.3.5-1 aur 2500: Yet another yogurt"
+    );
+    assert_eq!(format_search_result(&aur, "%n\t100%% %x%"), "yay\t100% %x%");
+    let tap = SearchResult {
+        source: Source::Custom("ghost".into()),
+        votes: None,
+        ..aur.clone()
+    };
+    assert_eq!(format_search_result(&tap, "%s|%w|"), "tap:ghost||");
+
+    let installed = InstalledPackage {
+        name: "htop".into(),
+        version: "3.3.0-1".into(),
+        source: Source::Pacman,
+        explicit: true,
+    };
+    assert_eq!(
+        format_installed_package(&installed, "%n=%v (%s)%d%w"),
+        "htop=3.3.0-1 (pacman)"
+    );
+    assert_eq!(
+        expand_format("%a%b", |c| (c == 'a').then(|| "A".to_string())),
+        "A%b"
+    );
+
+    use clap::Parser;
+    let cli = reap::cli::Cli::parse_from(["reap", "--format", "%n", "search", "foo"]);
+    assert_eq!(cli.format.as_deref(), Some("%n"));
+    let cli = reap::cli::Cli::parse_from(["reap", "list", "--format", "%n %v"]);
+    assert_eq!(cli.format.as_deref(), Some("%n %v"));
+}
+
+/// AUR packages are matched to the same app on Flatpak
+#[test]
+fn test_flatpak_alternative() {
+    use reap::aur::SearchResult;
+    use reap::core::{Source, flatpak_alternative};
+    let app = |id: &str, description: &str| SearchResult {
+        name: id.into(),
+        version: "1.0".into(),
+        description: description.into(),
+        source: Source::Flatpak,
+        votes: None,
+    };
+    let results = vec![
+        app("com.discordapp.DiscordCanary", "Discord Canary - Chat"),
+        app(
+            "com.discordapp.Discord",
+            "Discord - Messaging, voice and video",
+        ),
+        app("com.visualstudio.code", "Visual Studio Code - Code editor"),
+        SearchResult {
+            source: Source::Aur,
+            ..app("org.example.Foo", "Foo - not a flatpak")
+        },
+    ];
+    assert_eq!(
+        flatpak_alternative("discord", &results).as_deref(),
+        Some("com.discordapp.Discord")
+    );
+    assert_eq!(
+        flatpak_alternative("visual-studio-code-bin", &results).as_deref(),
+        Some("com.visualstudio.code")
+    );
+    assert_eq!(
+        flatpak_alternative("discord-canary-git", &results).as_deref(),
+        Some("com.discordapp.DiscordCanary")
+    );
+    assert_eq!(flatpak_alternative("foo", &results), None);
+    assert_eq!(flatpak_alternative("slack-desktop", &results), None);
+    assert_eq!(
+        reap::config::GlobalConfig::default().suggest_flatpak,
+        Some(false)
+    );
+}
+
+/// Version comparison follows pacman's `vercmp`, epoch and pkgrel included
+#[test]
+fn test_vercmp() {
+    use reap::pacman::{is_newer, vercmp};
+    use std::cmp::Ordering::{Equal, Greater, Less};
+    let cases = [
+        ("1.5.0", "1.5.0", Equal),
+        ("1.5.1", "1.5.0", Greater),
+        ("1.5", "1.5.1", Less),
+        ("1.10", "1.9", Greater),
+        ("1.010", "1.10", Equal),
+        // Letters sort before the release they lead up to
+        ("1.5b", "1.5", Less),
+        ("1.0a", "1.0alpha", Less),
+        ("1.0alpha", "1.0b", Less),
+        ("1.0beta", "1.0rc", Less),
+        ("1.0rc", "1.0", Less),
+        ("1.5.a", "1.5", Less),
+        ("1.5.1", "1.5.b", Greater),
+        // Separators
+        ("2.0", "2_0", Equal),
+        ("2.0a", "2.0.a", Less),
+        ("2___a", "2_a", Greater),
+        // Epochs win over everything else
+        ("0:1.0", "1.0", Equal),
+        ("1:1.0", "2.0", Greater),
+        ("1:1.0-1", "0:1.1-1", Greater),
+        ("1:1.0", "2:0.1", Less),
+        // pkgrel only counts when both have one
+        ("1.5.0-1", "1.5.0-2", Less),
+        ("1.5.0-2", "1.5.1-1", Less),
+        ("1.5.0-1", "1.5.0", Equal),
+        ("1.0-1.1", "1.0-1", Greater),
+        ("1.5.b-1", "1.5.b", Equal),
+        // VCS versions
+        ("r123.abc1234-1", "r99.fff0000-1", Greater),
+        ("1.2.r10.g1234567-1", "1.2.r9.gabcdef0-1", Greater),
+    ];
+    for (a, b, expected) in cases {
+        assert_eq!(vercmp(a, b), expected, "vercmp {} {}", a, b);
+        assert_eq!(vercmp(b, a), expected.reverse(), "vercmp {} {}", b, a);
+    }
+    assert!(is_newer("12.4.1-1", "12.3.0-1"));
+    assert!(!is_newer("12.3.0-1", "12.3.0-1"));
+    // An older or equal AUR version is not an update
+    assert!(!is_newer("1.0-1", "1:0.9-1"));
+    assert!(!is_newer("2.0-1", "2.0"));
+
+    use reap::core::count_aur_updates;
+    use std::collections::{BTreeMap, HashMap};
+    let installed: BTreeMap<String, String> = [
+        ("foo-git", "r200.abc-1"),
+        ("bar", "1:1.0-1"),
+        ("baz", "1.0-1"),
+    ]
+    .iter()
+    .map(|(n, v)| (n.to_string(), v.to_string()))
+    .collect();
+    let remote: HashMap<String, String> = [
+        ("foo-git", "r100.def-1"),
+        ("bar", "2.0-1"),
+        ("baz", "1.0-2"),
+    ]
+    .iter()
+    .map(|(n, v)| (n.to_string(), v.to_string()))
+    .collect();
+    assert_eq!(count_aur_updates(&installed, &remote), 1);
+}
+
+/// `--reinstall` and `--asexplicit` are threaded into the install options
+#[test]
+fn test_reinstall_options() {
+    use clap::Parser;
+    let cli = reap::cli::Cli::parse_from(["reap", "--reinstall", "--asexplicit", "-S", "foo"]);
+    let opts = reap::core::install_options(&cli);
+    assert!(opts.reinstall);
+    assert!(opts.as_explicit);
+    let cli = reap::cli::Cli::parse_from(["reap", "-S", "foo"]);
+    let opts = reap::core::install_options(&cli);
+    assert!(!opts.reinstall);
+    assert!(!opts.as_explicit);
+}
+
+/// `--backend` limits source resolution to that backend, unless a qualified target names another
+#[test]
+fn test_backend_flag() {
+    use clap::Parser;
+    use reap::core::PackageListEntry;
+    let cli = reap::cli::Cli::parse_from(["reap", "--backend", "pacman", "-S", "foo"]);
+    let opts = reap::core::install_options(&cli);
+    assert_eq!(opts.backend_order, Some(vec!["pacman".to_string()]));
+    let entry = PackageListEntry::parse_target("foo", &[]).unwrap();
+    assert_eq!(
+        entry.install_options(&opts).backend_order,
+        Some(vec!["pacman".to_string()])
+    );
+    let entry = PackageListEntry::parse_target("aur/foo", &[]).unwrap();
+    assert_eq!(
+        entry.install_options(&opts).backend_order,
+        Some(vec!["aur".to_string()])
+    );
+    let cli = reap::cli::Cli::parse_from(["reap", "-S", "foo"]);
+    assert_eq!(reap::core::install_options(&cli).backend_order, None);
+    assert!(reap::cli::Cli::try_parse_from(["reap", "--backend", "snap", "-S", "foo"]).is_err());
+}
+
+/// `aur/foo`, `<repo>/foo` and `flatpak/foo` targets force their source
+#[test]
+fn test_qualified_install_targets() {
+    use reap::core::{InstallOptions, PackageListEntry, Source};
+    let repos = vec!["core".to_string(), "extra".to_string()];
+    let parse = |target: &str| PackageListEntry::parse_target(target, &repos);
+
+    let entry = parse("aur/firefox").expect("aur target");
+    assert_eq!(entry.name, "firefox");
+    assert_eq!(entry.forced_source(), Some(Source::Aur));
+
+    let entry = parse("extra/firefox").expect("repo target");
+    assert_eq!(entry.to_line(), "extra/firefox");
+    let opts = entry.install_options(&InstallOptions::default());
+    assert_eq!(opts.source, Some(Source::Pacman));
+    assert_eq!(opts.repo.as_deref(), Some("extra"));
+    assert_eq!(opts.backend_order, Some(vec!["pacman".to_string()]));
+
+    let entry = parse("flatpak/org.mozilla.firefox").expect("flatpak target");
+    assert_eq!(entry.name, "org.mozilla.firefox");
+    assert_eq!(entry.forced_source(), Some(Source::Flatpak));
+
+    let entry = parse("firefox").expect("plain target");
+    assert_eq!(entry.source, None);
+    assert_eq!(entry.forced_source(), None);
+    assert!(
+        parse("tap:ghost:ghostctl")
+            .unwrap()
+            .forced_source()
+            .is_none()
+    );
+
+    assert_eq!(
+        parse("testing/firefox").unwrap_err(),
+        "unknown source or repo 'testing'"
+    );
+}
+
+/// `diff_cmd` runs through the shell with the two PKGBUILDs as arguments
+#[test]
+fn test_diff_command() {
+    let dir = std::env::temp_dir().join(format!("reap-test-diff-cmd-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (old, new) = (dir.join("PKGBUILD.built"), dir.join("PKGBUILD.aur"));
+    std::fs::write(&old, "pkgver=1.0\n").unwrap();
+    std::fs::write(&new, "pkgver=1.1\n").unwrap();
+
+    let out = reap::utils::diff_command("diff -u", &old, &new)
+        .output()
+        .expect("run diff");
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(stdout.contains("-pkgver=1.0"));
+    assert!(stdout.contains("+pkgver=1.1"));
+
+    let out = reap::utils::diff_command("reap-no-such-diff-tool", &old, &new)
+        .output()
+        .expect("run sh");
+    assert_eq!(out.status.code(), Some(127));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// `reap query` reads dependencies and optional dependencies from `pacman -Qi`
+#[test]
+fn test_query_package_deps() {
+    use reap::pacman::{OptDep, parse_package_deps};
+    let output = "Name            : mpv\n\
+Version         : 1:0.38.0-1\n\
+Depends On      : ffmpeg  libass>=0.17\n\
+Optional Deps   : yt-dlp: for video-sharing websites playback [installed]\n\
+                  youtube-dl\n\
+Required By     : None\n\
+\n\
+Name            : foo-git\n\
+Version         : r12.abc-1\n\
+Depends On      : None\n\
+Optional Deps   : None\n";
+    let packages = parse_package_deps(output);
+    assert_eq!(packages.len(), 2);
+    assert_eq!(packages[0].name, "mpv");
+    assert_eq!(packages[0].version, "1:0.38.0-1");
+    assert_eq!(packages[0].depends, vec!["ffmpeg", "libass>=0.17"]);
+    assert_eq!(
+        packages[0].optdepends,
+        vec![
+            OptDep {
+                name: "yt-dlp".into(),
+                description: Some("for video-sharing websites playback".into()),
+            },
+            OptDep {
+                name: "youtube-dl".into(),
+                description: None,
+            },
+        ]
+    );
+    assert!(packages[1].depends.is_empty());
+    assert!(packages[1].optdepends.is_empty());
+
+    // Descriptions the package lacks come from the AUR's OptDepends
+    let mut optdepends = packages[0].optdepends.clone();
+    reap::core::fill_optdep_descriptions(
+        &mut optdepends,
+        &["youtube-dl: older downloader".to_string()],
+    );
+    assert_eq!(
+        optdepends[1].description.as_deref(),
+        Some("older downloader")
+    );
+
+    use clap::Parser;
+    assert!(reap::cli::Cli::try_parse_from(["reap", "query", "--optdeps", "--missing"]).is_ok());
+    assert!(reap::cli::Cli::try_parse_from(["reap", "query", "--optdeps"]).is_err());
+}
+
+/// `install_optdepends` from reap.toml, with `--with-optdepends` turning on the prompt
+#[test]
+fn test_optdepends_mode() {
+    use reap::core::OptDependsMode;
+    assert_eq!(
+        OptDependsMode::from_config(false, None),
+        OptDependsMode::None
+    );
+    assert_eq!(
+        OptDependsMode::from_config(false, Some("prompt")),
+        OptDependsMode::Prompt
+    );
+    assert_eq!(
+        OptDependsMode::from_config(false, Some("All")),
+        OptDependsMode::All
+    );
+    assert_eq!(
+        OptDependsMode::from_config(true, Some("none")),
+        OptDependsMode::Prompt
+    );
+    assert_eq!(
+        OptDependsMode::from_config(true, Some("all")),
+        OptDependsMode::All
+    );
+    assert_eq!(
+        OptDependsMode::from_config(false, Some("sometimes")),
+        OptDependsMode::None
+    );
+
+    use clap::Parser;
+    let cli = reap::cli::Cli::parse_from(["reap", "--with-optdepends", "-S", "foo"]);
+    assert!(reap::core::install_options(&cli).with_optdepends);
+}
+
+/// `reap fix-broken` finds leftover build dirs, stale locks and failed installs
+#[test]
+fn test_fix_broken_detection() {
+    let root = std::env::temp_dir().join(format!("reap-test-fix-broken-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    for dir in [
+        "reap-aur-foo-2024-01-02 03:04:05",
+        "reap-aur-foo-bar-git-2024-01-02 03:04:05",
+        "reap-aur-notastamp",
+        "other",
+    ] {
+        std::fs::create_dir_all(root.join(dir)).unwrap();
+    }
+    let mut leftover: Vec<String> = reap::core::leftover_build_dirs(&root)
+        .iter()
+        .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+    leftover.sort();
+    assert_eq!(
+        leftover,
+        vec![
+            "reap-aur-foo-2024-01-02 03:04:05",
+            "reap-aur-foo-bar-git-2024-01-02 03:04:05"
+        ]
+    );
+
+    let lock = root.join("db.lck");
+    assert!(!reap::utils::is_stale_db_lock(&lock, false));
+    std::fs::write(&lock, "").unwrap();
+    assert!(reap::utils::is_stale_db_lock(&lock, false));
+    assert!(!reap::utils::is_stale_db_lock(&lock, true));
+    let held = std::fs::File::open(&lock).unwrap();
+    assert!(reap::utils::lock_holders(&lock, false).contains(&std::process::id()));
+    drop(held);
+    assert!(!reap::utils::lock_holders(&lock, false).contains(&std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+
+    use reap::history::{OperationKind, OperationLog};
+    let mut log = OperationLog::default();
+    log.record(
+        OperationKind::Install,
+        vec!["aur/foo".into(), "bar".into(), "baz".into()],
+        Vec::new(),
+        Some("foo: Building foo failed at the build step; baz: Aborted by user".into()),
+    );
+    log.record(
+        OperationKind::Install,
+        vec!["qux".into()],
+        Vec::new(),
+        Some("qux was not found in any source".into()),
+    );
+    log.record(OperationKind::Install, vec!["ok".into()], Vec::new(), None);
+    assert_eq!(log.failed_installs(), vec!["qux", "foo"]);
+}
+
+/// `reap mirrors --rank` helpers: mirrorlist parsing, reflector arguments and staleness
+#[test]
+fn test_mirrors() {
+    use reap::mirrors::{active_servers, is_stale, reflector_args, uncomment_servers};
+    use std::time::Duration;
+    let mirrorlist = "## Germany\n\
+#Server = https://a.example/$repo/os/$arch\n\
+Server = https://b.example/$repo/os/$arch\n\
+# Server = http://c.example/$repo/os/$arch\n";
+    assert_eq!(
+        active_servers(mirrorlist),
+        vec!["https://b.example/$repo/os/$arch"]
+    );
+    assert_eq!(active_servers(&uncomment_servers(mirrorlist)).len(), 3);
+    assert!(uncomment_servers(mirrorlist).starts_with("## Germany\n"));
+
+    let args = reflector_args(
+        Some("Germany"),
+        10,
+        std::path::Path::new("/etc/pacman.d/mirrorlist"),
+    );
+    assert_eq!(
+        args.join(" "),
+        "--latest 50 --protocol https --sort rate --number 10 --country Germany --save /etc/pacman.d/mirrorlist"
+    );
+
+    let day = Duration::from_secs(24 * 60 * 60);
+    assert!(is_stale(day * 31, 30));
+    assert!(!is_stale(day * 29, 30));
+    assert!(!is_stale(day * 365, 0));
+
+    use clap::Parser;
+    assert!(reap::cli::Cli::try_parse_from(["reap", "mirrors", "--rank", "-n", "5"]).is_ok());
+    assert!(reap::cli::Cli::try_parse_from(["reap", "mirrors", "--country", "Germany"]).is_err());
+}
+
+/// `--print-deps-size` sums `pacman -Si` sizes and AUR estimates
+#[test]
+fn test_install_footprint() {
+    use reap::core::InstallFootprint;
+    use reap::pacman::{PackageSize, parse_size, parse_sync_sizes};
+    assert_eq!(parse_size("1.50 KiB"), Some(1536));
+    assert_eq!(parse_size("2,00 MiB"), Some(2 * 1024 * 1024));
+    assert_eq!(parse_size("lots"), None);
+
+    let output = "Repository      : extra\n\
+Name            : ffmpeg\n\
+Version         : 2:7.0-1\n\
+Download Size   : 10.00 MiB\n\
+Installed Size  : 40.00 MiB\n\
+\n\
+Repository      : extra\n\
+Name            : x264\n\
+Download Size   : 512.00 KiB\n\
+Installed Size  : 2.00 MiB\n";
+    let repo = parse_sync_sizes(output);
+    assert_eq!(
+        repo[0],
+        PackageSize {
+            name: "ffmpeg".into(),
+            download: 10 * 1024 * 1024,
+            installed: 40 * 1024 * 1024,
+        }
+    );
+    assert_eq!(repo.len(), 2);
+
+    let footprint = InstallFootprint {
+        repo,
+        aur: vec![
+            ("mpv-git".into(), Some(8 * 1024 * 1024)),
+            ("new-aur-pkg".into(), None),
+        ],
+        flatpak: vec![("org.gimp.GIMP".into(), Some(100 * 1024 * 1024))],
+        not_found: Vec::new(),
+    };
+    let mib = 1024 * 1024;
+    assert_eq!(
+        footprint.totals(),
+        (
+            10 * mib + 512 * 1024 + 100 * mib,
+            40 * mib + 2 * mib + 8 * mib,
+            1
+        )
+    );
+    let text = footprint.render();
+    assert!(text.contains("Repo packages (2): ffmpeg  x264"));
+    assert!(text.contains("AUR packages (2): mpv-git  new-aur-pkg"));
+    let installed = text
+        .lines()
+        .find(|l| l.starts_with("Total Installed Size:"))
+        .unwrap();
+    assert!(installed.ends_with(" 50.00 MiB"));
+    assert!(text.contains("1 package(s) without a size estimate"));
+
+    use clap::Parser;
+    let cli = reap::cli::Cli::parse_from(["reap", "--print-deps-size", "-S", "mpv"]);
+    assert!(reap::core::install_options(&cli).print_deps_size);
+}
+
+/// Kernel detection for AUR packages that build kernel modules
+#[test]
+fn test_kernel_modules() {
+    use reap::kernel::{builds_modules, installed_kernels, select, uses_dkms};
+    let dir = std::env::temp_dir().join(format!("reap-test-kernels-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    for (release, package, headers) in [
+        ("6.9.3-arch1-1", "linux", true),
+        ("6.6.32-1-lts", "linux-lts", false),
+    ] {
+        std::fs::create_dir_all(dir.join(release)).unwrap();
+        std::fs::write(dir.join(release).join("pkgbase"), format!("{}\n", package)).unwrap();
+        if headers {
+            std::fs::create_dir_all(dir.join(release).join("build")).unwrap();
+        }
+    }
+    // Leftover modules of a removed kernel have no pkgbase
+    std::fs::create_dir_all(dir.join("6.8.0-arch1-1/extramodules")).unwrap();
+
+    let kernels = installed_kernels(&dir);
+    let _ = std::fs::remove_dir_all(&dir);
+    assert_eq!(kernels.len(), 2);
+    assert_eq!(kernels[0].package, "linux");
+    assert!(kernels[0].headers);
+    assert_eq!(kernels[1].release, "6.6.32-1-lts");
+    assert!(!kernels[1].headers);
+    assert_eq!(kernels[1].headers_package(), "linux-lts-headers");
+
+    assert_eq!(select(&kernels, None).unwrap().len(), 2);
+    assert_eq!(
+        select(&kernels, Some("linux-lts")).unwrap(),
+        vec![kernels[1].clone()]
+    );
+    assert_eq!(
+        select(&kernels, Some("6.9.3")).unwrap(),
+        vec![kernels[0].clone()]
+    );
+    assert!(select(&kernels, Some("linux-zen")).is_err());
+
+    let dkms = vec!["dkms".to_string(), "nvidia-utils>=550".to_string()];
+    assert!(builds_modules(&dkms) && uses_dkms(&dkms));
+    let headers = vec!["linux-lts-headers".to_string()];
+    assert!(builds_modules(&headers) && !uses_dkms(&headers));
+    assert!(!builds_modules(&["linux-firmware".to_string()]));
+
+    use clap::Parser;
+    let cli = reap::cli::Cli::parse_from(["reap", "--kernel", "linux-lts", "-S", "zfs-dkms"]);
+    assert_eq!(
+        reap::core::install_options(&cli).kernel.as_deref(),
+        Some("linux-lts")
+    );
+}
+
+/// Test that the removal preview lists the installed dependents each removal would break.
+#[test]
+fn test_removal_preview() {
+    use reap::core::RemovalPreview;
+    use reap::pacman::parse_package_deps;
+    let output = "Name            : qt5-base\n\
+Version         : 5.15.14-1\n\
+Depends On      : libjpeg-turbo\n\
+Required By     : qt5-svg  qt5-tools\n\
+                  vlc\n\
+\n\
+Name            : qt5-svg\n\
+Version         : 5.15.14-1\n\
+Required By     : vlc\n";
+    let deps = parse_package_deps(output);
+    assert_eq!(deps[0].required_by, vec!["qt5-svg", "qt5-tools", "vlc"]);
+
+    // Dependents that are being removed too don't break
+    let targets = vec!["qt5-base".to_string(), "qt5-svg".to_string()];
+    let preview = RemovalPreview::new(&targets, &deps, &[]);
+    assert_eq!(
+        preview.breaks,
+        vec![
+            ("qt5-tools".to_string(), vec!["qt5-base".to_string()]),
+            (
+                "vlc".to_string(),
+                vec!["qt5-base".to_string(), "qt5-svg".to_string()]
+            ),
+        ]
+    );
+    assert!(preview.cascade.is_empty());
+    let rendered = preview.render();
+    assert!(rendered.contains("2 installed package(s)"));
+    assert!(rendered.contains("vlc (needs qt5-base, qt5-svg)"));
+
+    // The -Rsc removal set adds everything besides the targets
+    let removal_set: Vec<String> = ["qt5-base", "qt5-svg", "qt5-tools", "vlc", "libjpeg-turbo"]
+        .map(String::from)
+        .to_vec();
+    let preview = RemovalPreview::new(&targets, &deps, &removal_set);
+    assert_eq!(preview.cascade, vec!["qt5-tools", "vlc", "libjpeg-turbo"]);
+    assert!(preview.render().contains("would also remove 3 package(s)"));
+
+    // Nothing to show for a leaf package
+    let leaf = RemovalPreview::new(&["vlc".to_string()], &[], &[]);
+    assert!(leaf.render().is_empty());
+}